@@ -0,0 +1,404 @@
+use core::ffi::c_void;
+use core::ptr;
+use std::rc::Rc;
+
+use tinywasm::types::{ExternType, ExternVal, WasmValue};
+use tinywasm::{Error, Extern, Result, Store};
+
+use crate::store::StoreCell;
+use crate::types::{WASM_EXTERN_FUNC, WASM_EXTERN_GLOBAL, WASM_EXTERN_MEMORY, WASM_EXTERN_TABLE};
+use crate::vec::{delete_vec, type_functions, VecItem};
+use crate::{
+    wasm_externtype_t, wasm_functype_t, wasm_globaltype_t, wasm_memorytype_t, wasm_ref_t, wasm_store_t,
+    wasm_tabletype_t, wasm_trap_t, wasm_val_t, wasm_val_vec_t, WasmVec,
+};
+
+/// `wasm_extern_t`, also used as `wasm_func_t`, `wasm_global_t`, `wasm_table_t` and `wasm_memory_t`
+///
+/// A handle to an item in a store.
+#[derive(Debug, Clone)]
+pub struct wasm_extern_t {
+    pub(crate) store: Rc<StoreCell>,
+    pub(crate) item: ExternVal,
+}
+
+/// `wasm_func_t`
+pub type wasm_func_t = wasm_extern_t;
+/// `wasm_global_t`
+pub type wasm_global_t = wasm_extern_t;
+/// `wasm_table_t`
+pub type wasm_table_t = wasm_extern_t;
+/// `wasm_memory_t`
+pub type wasm_memory_t = wasm_extern_t;
+
+/// `wasm_extern_vec_t`
+pub type wasm_extern_vec_t = WasmVec<*mut wasm_extern_t>;
+
+/// `wasm_func_callback_t`
+pub type wasm_func_callback_t =
+    unsafe extern "C" fn(args: *const wasm_val_vec_t, results: *mut wasm_val_vec_t) -> *mut wasm_trap_t;
+
+/// `wasm_func_callback_with_env_t`
+pub type wasm_func_callback_with_env_t = unsafe extern "C" fn(
+    env: *mut c_void,
+    args: *const wasm_val_vec_t,
+    results: *mut wasm_val_vec_t,
+) -> *mut wasm_trap_t;
+
+impl wasm_extern_t {
+    pub(crate) fn boxed(store: &Rc<StoreCell>, item: ExternVal) -> *mut Self {
+        Box::into_raw(Box::new(Self { store: store.clone(), item }))
+    }
+
+    fn add(store: *mut wasm_store_t, value: Extern) -> *mut Self {
+        let store = unsafe { &(*store).inner };
+        match store.with(|s| s.add_extern(value)) {
+            Ok(item) => Self::boxed(store, item),
+            Err(_) => ptr::null_mut(),
+        }
+    }
+
+    fn ty(&self) -> ExternType {
+        self.store.with(|s| s.extern_type(&self.item)).expect("items are always in their store")
+    }
+
+    fn kind(&self) -> u8 {
+        match self.item {
+            ExternVal::Func(_) => WASM_EXTERN_FUNC,
+            ExternVal::Global(_) => WASM_EXTERN_GLOBAL,
+            ExternVal::Table(_) => WASM_EXTERN_TABLE,
+            ExternVal::Memory(_) => WASM_EXTERN_MEMORY,
+        }
+    }
+
+    // the same item if it is of the given kind
+    fn cast(item: *const Self, kind: u8) -> *const Self {
+        match unsafe { (*item).kind() } == kind {
+            true => item,
+            false => ptr::null(),
+        }
+    }
+
+    fn with<R>(&self, f: impl FnOnce(&mut Store, u32) -> Result<R>) -> Result<R> {
+        let addr = match self.item {
+            ExternVal::Func(addr) | ExternVal::Global(addr) | ExternVal::Table(addr) | ExternVal::Memory(addr) => addr,
+        };
+        self.store.with(|store| f(store, addr))
+    }
+
+    fn content_type(&self) -> tinywasm::types::ValType {
+        match self.ty() {
+            ExternType::Global(ty) => ty.ty,
+            ExternType::Table(ty) => ty.element_type,
+            _ => unreachable!("only globals and tables have a content type"),
+        }
+    }
+}
+
+// the environment of a host function, finalized when the store is dropped
+struct Env {
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(*mut c_void)>,
+}
+
+impl Drop for Env {
+    fn drop(&mut self) {
+        if let Some(finalizer) = self.finalizer {
+            unsafe { finalizer(self.env) }
+        }
+    }
+}
+
+unsafe fn new_func(
+    store: *mut wasm_store_t,
+    ty: *const wasm_functype_t,
+    callback: impl Fn(*const wasm_val_vec_t, *mut wasm_val_vec_t) -> *mut wasm_trap_t + 'static,
+) -> *mut wasm_func_t {
+    let Some(ty) = (*ty).func().cloned() else { return ptr::null_mut() };
+    let cell = Rc::downgrade(&(*store).inner);
+    let result_types = ty.results.clone();
+
+    let func = Extern::func(&ty, move |mut ctx, args| {
+        let cell = cell.upgrade().ok_or_else(|| Error::Other("the store was deleted".to_string()))?;
+        let mut args = WasmVec::from_vec(
+            args.iter().map(|&arg| wasm_val_t::from_wasm(arg).unwrap_or_else(wasm_val_t::empty)).collect(),
+        );
+        let mut results = WasmVec::from_vec(result_types.iter().map(|_| wasm_val_t::empty()).collect());
+        let trap = cell.lend(ctx.store_mut(), || callback(&args, &mut results));
+
+        let res = match trap.is_null() {
+            true => unsafe { results.as_slice() }
+                .iter()
+                .zip(result_types.iter())
+                .map(|(val, &ty)| {
+                    unsafe { val.value(Some(ty)) }.ok_or_else(|| Error::Other(format!("invalid result: {val:?}")))
+                })
+                .collect(),
+            false => Err(Error::host(unsafe { Box::from_raw(trap) }.message)),
+        };
+
+        unsafe {
+            delete_vec(&mut args);
+            delete_vec(&mut results);
+        }
+        res
+    });
+
+    wasm_extern_t::add(store, func)
+}
+
+unsafe fn call(func: &wasm_func_t, args: &[wasm_val_t], results: &mut [wasm_val_t]) -> Result<()> {
+    let ExternType::Func(ty) = func.ty() else {
+        return Err(Error::Other("not a function".to_string()));
+    };
+
+    if args.len() != ty.params.len() || results.len() < ty.results.len() {
+        return Err(Error::Other("wrong number of arguments or results".to_string()));
+    }
+
+    let args = args
+        .iter()
+        .zip(ty.params.iter())
+        .map(|(arg, &ty)| arg.value(Some(ty)).ok_or_else(|| Error::Other(format!("invalid argument: {arg:?}"))))
+        .collect::<Result<Vec<_>>>()?;
+
+    let values = func.with(|store, addr| store.func(addr)?.call(store, &args))?;
+    for (slot, val) in results.iter_mut().zip(values) {
+        *slot = wasm_val_t::from_wasm(val).ok_or_else(|| Error::Other(format!("unsupported result: {val:?}")))?;
+    }
+    Ok(())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_new(
+    store: *mut wasm_store_t,
+    ty: *const wasm_functype_t,
+    callback: wasm_func_callback_t,
+) -> *mut wasm_func_t {
+    new_func(store, ty, move |args, results| unsafe { callback(args, results) })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_new_with_env(
+    store: *mut wasm_store_t,
+    ty: *const wasm_functype_t,
+    callback: wasm_func_callback_with_env_t,
+    env: *mut c_void,
+    finalizer: Option<unsafe extern "C" fn(*mut c_void)>,
+) -> *mut wasm_func_t {
+    let env = Env { env, finalizer };
+    new_func(store, ty, move |args, results| {
+        // the whole environment is moved into the function, so it's finalized together with it
+        let env = &env;
+        unsafe { callback(env.env, args, results) }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_type(func: *const wasm_func_t) -> *mut wasm_functype_t {
+    wasm_externtype_t::boxed((*func).ty())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_param_arity(func: *const wasm_func_t) -> usize {
+    match (*func).ty() {
+        ExternType::Func(ty) => ty.params.len(),
+        _ => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_result_arity(func: *const wasm_func_t) -> usize {
+    match (*func).ty() {
+        ExternType::Func(ty) => ty.results.len(),
+        _ => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_func_call(
+    func: *const wasm_func_t,
+    args: *const wasm_val_vec_t,
+    results: *mut wasm_val_vec_t,
+) -> *mut wasm_trap_t {
+    let results = match (*results).data.is_null() {
+        true => &mut [],
+        false => core::slice::from_raw_parts_mut((*results).data, (*results).size),
+    };
+
+    match call(&*func, (*args).as_slice(), results) {
+        Ok(()) => ptr::null_mut(),
+        Err(err) => wasm_trap_t::from_error(err),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_global_new(
+    store: *mut wasm_store_t,
+    ty: *const wasm_globaltype_t,
+    val: *const wasm_val_t,
+) -> *mut wasm_global_t {
+    let ExternType::Global(ty) = (*ty).ty else { return ptr::null_mut() };
+    match (*val).value(Some(ty.ty)) {
+        Some(val) => wasm_extern_t::add(store, Extern::global(val, ty.mutable)),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_global_type(global: *const wasm_global_t) -> *mut wasm_globaltype_t {
+    wasm_externtype_t::boxed((*global).ty())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_global_get(global: *const wasm_global_t, out: *mut wasm_val_t) {
+    let val = (*global).with(|store, addr| Ok(store.global(addr)?.get()));
+    out.write(val.ok().and_then(wasm_val_t::from_wasm).unwrap_or_else(wasm_val_t::empty));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_global_set(global: *mut wasm_global_t, val: *const wasm_val_t) {
+    let Some(val) = (*val).value(Some((*global).content_type())) else { return };
+    // setting an immutable global is not allowed by the standard, so it's ignored
+    let _ = (*global).with(|store, addr| store.global(addr)?.set(val));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_new(
+    store: *mut wasm_store_t,
+    ty: *const wasm_tabletype_t,
+    init: *mut wasm_ref_t,
+) -> *mut wasm_table_t {
+    let ExternType::Table(ty) = &(*ty).ty else { return ptr::null_mut() };
+    let init = wasm_ref_t::to_wasm(init, ty.element_type);
+    let table = wasm_extern_t::add(store, Extern::table(ty.clone(), init));
+    if !table.is_null() && !matches!(init, WasmValue::RefNull(_)) {
+        let _ = (*table).with(|store, addr| store.table_mut(addr)?.fill(0, ty.size_initial, init));
+    }
+    table
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_type(table: *const wasm_table_t) -> *mut wasm_tabletype_t {
+    wasm_externtype_t::boxed((*table).ty())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_get(table: *const wasm_table_t, index: u32) -> *mut wasm_ref_t {
+    let val = (*table).with(|store, addr| store.table(addr)?.get(index));
+    val.map_or(ptr::null_mut(), wasm_ref_t::from_wasm)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_set(table: *mut wasm_table_t, index: u32, reference: *mut wasm_ref_t) -> bool {
+    let val = wasm_ref_t::to_wasm(reference, (*table).content_type());
+    (*table).with(|store, addr| store.table_mut(addr)?.set(index, val)).is_ok()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_size(table: *const wasm_table_t) -> u32 {
+    (*table).with(|store, addr| Ok(store.table(addr)?.size())).unwrap_or(0)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_table_grow(table: *mut wasm_table_t, delta: u32, init: *mut wasm_ref_t) -> bool {
+    let init = wasm_ref_t::to_wasm(init, (*table).content_type());
+    (*table).with(|store, addr| store.table_mut(addr)?.grow(delta, init)).is_ok()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_new(store: *mut wasm_store_t, ty: *const wasm_memorytype_t) -> *mut wasm_memory_t {
+    match (*ty).ty {
+        ExternType::Memory(ty) => wasm_extern_t::add(store, Extern::memory(ty)),
+        _ => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_type(memory: *const wasm_memory_t) -> *mut wasm_memorytype_t {
+    wasm_externtype_t::boxed((*memory).ty())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_data(memory: *mut wasm_memory_t) -> *mut u8 {
+    let data = (*memory).with(|store, addr| Ok(store.memory_mut(addr)?.data_mut().as_mut_ptr()));
+    data.unwrap_or(ptr::null_mut())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_data_size(memory: *const wasm_memory_t) -> usize {
+    (*memory).with(|store, addr| Ok(store.memory_mut(addr)?.data_mut().len())).unwrap_or(0)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_size(memory: *const wasm_memory_t) -> u32 {
+    (*memory).with(|store, addr| Ok(store.memory_mut(addr)?.page_count() as u32)).unwrap_or(0)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memory_grow(memory: *mut wasm_memory_t, delta: u32) -> bool {
+    let Ok(delta) = i32::try_from(delta) else { return false };
+    (*memory).with(|store, addr| Ok(store.memory_mut(addr)?.grow(delta).is_some())).unwrap_or(false)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_extern_kind(item: *const wasm_extern_t) -> u8 {
+    (*item).kind()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_extern_type(item: *const wasm_extern_t) -> *mut wasm_externtype_t {
+    wasm_externtype_t::boxed((*item).ty())
+}
+
+type_functions!(
+    wasm_extern_t,
+    wasm_extern_delete,
+    wasm_extern_copy,
+    wasm_extern_vec_new_empty,
+    wasm_extern_vec_new_uninitialized,
+    wasm_extern_vec_new,
+    wasm_extern_vec_copy,
+    wasm_extern_vec_delete
+);
+
+// the specific items are the same as `wasm_extern_t`, so the conversions only check the kind
+macro_rules! extern_functions {
+    ($($kind:ident: $delete:ident, $as_extern:ident, $as_extern_const:ident, $from_extern:ident, $from_extern_const:ident;)*) => {$(
+        #[no_mangle]
+        pub unsafe extern "C" fn $delete(item: *mut wasm_extern_t) {
+            <*mut wasm_extern_t as VecItem>::delete(item);
+        }
+
+        #[no_mangle]
+        pub extern "C" fn $as_extern(item: *mut wasm_extern_t) -> *mut wasm_extern_t {
+            item
+        }
+
+        #[no_mangle]
+        pub extern "C" fn $as_extern_const(item: *const wasm_extern_t) -> *const wasm_extern_t {
+            item
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn $from_extern(item: *mut wasm_extern_t) -> *mut wasm_extern_t {
+            wasm_extern_t::cast(item, $kind).cast_mut()
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn $from_extern_const(item: *const wasm_extern_t) -> *const wasm_extern_t {
+            wasm_extern_t::cast(item, $kind)
+        }
+    )*};
+}
+
+extern_functions!(
+    WASM_EXTERN_FUNC: wasm_func_delete, wasm_func_as_extern, wasm_func_as_extern_const,
+        wasm_extern_as_func, wasm_extern_as_func_const;
+    WASM_EXTERN_GLOBAL: wasm_global_delete, wasm_global_as_extern, wasm_global_as_extern_const,
+        wasm_extern_as_global, wasm_extern_as_global_const;
+    WASM_EXTERN_TABLE: wasm_table_delete, wasm_table_as_extern, wasm_table_as_extern_const,
+        wasm_extern_as_table, wasm_extern_as_table_const;
+    WASM_EXTERN_MEMORY: wasm_memory_delete, wasm_memory_as_extern, wasm_memory_as_extern_const,
+        wasm_extern_as_memory, wasm_extern_as_memory_const;
+);
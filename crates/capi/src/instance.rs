@@ -0,0 +1,78 @@
+use core::ptr;
+use std::rc::Rc;
+
+use tinywasm::{Error, Imports, ModuleInstance, Result};
+
+use crate::store::StoreCell;
+use crate::{wasm_extern_t, wasm_extern_vec_t, wasm_module_t, wasm_store_t, wasm_trap_t, WasmVec};
+
+/// `wasm_instance_t`
+#[derive(Debug, Clone)]
+pub struct wasm_instance_t {
+    store: Rc<StoreCell>,
+    instance: ModuleInstance,
+}
+
+unsafe fn instantiate(
+    store: &Rc<StoreCell>,
+    module: &wasm_module_t,
+    externs: &[*mut wasm_extern_t],
+) -> Result<ModuleInstance> {
+    store.with(|s| {
+        // the imports are given in the order of the module's imports
+        let mut imports = Imports::new();
+        for (import, &item) in module.module.imports().zip(externs) {
+            if !Rc::ptr_eq(&(*item).store, store) {
+                return Err(Error::Other(format!(
+                    "import {}.{} is from a different store",
+                    import.module, import.name
+                )));
+            }
+            imports.define_extern_val(s, import.module, import.name, (*item).item.clone())?;
+        }
+
+        module.module.clone().instantiate(s, Some(imports))
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_instance_new(
+    store: *mut wasm_store_t,
+    module: *const wasm_module_t,
+    imports: *const wasm_extern_vec_t,
+    trap: *mut *mut wasm_trap_t,
+) -> *mut wasm_instance_t {
+    let store = &(*store).inner;
+    let externs = match imports.is_null() {
+        true => &[],
+        false => (*imports).as_slice(),
+    };
+
+    match instantiate(store, &*module, externs) {
+        Ok(instance) => Box::into_raw(Box::new(wasm_instance_t { store: store.clone(), instance })),
+        Err(err) => {
+            if !trap.is_null() {
+                trap.write(wasm_trap_t::from_error(err));
+            }
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_instance_exports(instance: *const wasm_instance_t, out: *mut wasm_extern_vec_t) {
+    let wasm_instance_t { store, instance } = &*instance;
+    let names = store.with(|s| instance.exports(s).map(|exports| exports.map(|e| e.name.to_string()).collect()));
+    let exports = names
+        .unwrap_or_else(|_| Vec::new())
+        .into_iter()
+        .filter_map(|name: String| instance.export_addr(&name))
+        .map(|item| wasm_extern_t::boxed(store, item))
+        .collect();
+    out.write(WasmVec::from_vec(exports));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_instance_delete(instance: *mut wasm_instance_t) {
+    drop(Box::from_raw(instance));
+}
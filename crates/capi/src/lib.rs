@@ -0,0 +1,35 @@
+#![warn(rust_2018_idioms, unreachable_pub)]
+#![allow(non_camel_case_types, clippy::missing_safety_doc)]
+
+//! A C API for [`tinywasm`], compatible with the standard
+//! [`wasm.h`](https://github.com/WebAssembly/wasm-c-api/blob/main/include/wasm.h) interface.
+//!
+//! The crate builds a shared (`cdylib`) and a static (`staticlib`) library. The functions it implements are
+//! declared in `include/wasm.h`, a subset of the standard header covering engines, stores, modules, instances,
+//! functions, globals, tables, memories, traps and the vectors used to pass them around.
+//!
+//! All functions follow the ownership rules of the standard: arguments and results marked `own` are owned by
+//! the receiver and have to be deleted, everything else is borrowed. Pointers have to be valid, as in the
+//! standard no checks are performed.
+//!
+//! Items created in a store stay alive as long as the store or any item referencing it exists, deleting an
+//! item only deletes the handle to it. Host functions can call back into the API, e.g. to read the memory
+//! of the calling instance.
+
+mod externs;
+mod instance;
+mod module;
+mod store;
+mod trap;
+mod types;
+mod values;
+mod vec;
+
+pub use externs::*;
+pub use instance::*;
+pub use module::*;
+pub use store::*;
+pub use trap::*;
+pub use types::*;
+pub use values::*;
+pub use vec::*;
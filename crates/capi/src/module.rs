@@ -0,0 +1,48 @@
+use core::ptr;
+
+use tinywasm::Module;
+
+use crate::types::{wasm_exporttype_vec_t, wasm_importtype_vec_t};
+use crate::{wasm_byte_vec_t, wasm_exporttype_t, wasm_importtype_t, wasm_store_t, WasmVec};
+
+/// `wasm_module_t`
+#[derive(Debug, Clone)]
+pub struct wasm_module_t {
+    pub(crate) module: Module,
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_new(
+    _store: *mut wasm_store_t,
+    binary: *const wasm_byte_vec_t,
+) -> *mut wasm_module_t {
+    match Module::parse_bytes((*binary).as_slice()) {
+        Ok(module) => Box::into_raw(Box::new(wasm_module_t { module })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_validate(_store: *mut wasm_store_t, binary: *const wasm_byte_vec_t) -> bool {
+    Module::parse_bytes((*binary).as_slice()).is_ok()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_imports(module: *const wasm_module_t, out: *mut wasm_importtype_vec_t) {
+    let imports = (*module).module.imports();
+    let imports =
+        imports.map(|import| Box::into_raw(Box::new(wasm_importtype_t::new(import.module, import.name, import.ty))));
+    out.write(WasmVec::from_vec(imports.collect()));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_exports(module: *const wasm_module_t, out: *mut wasm_exporttype_vec_t) {
+    let exports = (*module).module.exports();
+    let exports = exports.map(|export| Box::into_raw(Box::new(wasm_exporttype_t::new(export.name, export.ty))));
+    out.write(WasmVec::from_vec(exports.collect()));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_module_delete(module: *mut wasm_module_t) {
+    drop(Box::from_raw(module));
+}
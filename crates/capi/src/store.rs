@@ -0,0 +1,88 @@
+use std::cell::{Cell, UnsafeCell};
+use std::rc::Rc;
+
+use tinywasm::Store;
+
+/// `wasm_config_t`, there are no options yet
+#[derive(Debug, Default)]
+pub struct wasm_config_t {}
+
+/// `wasm_engine_t`, the runtime configuration is per store
+#[derive(Debug, Default)]
+pub struct wasm_engine_t {}
+
+/// `wasm_store_t`
+#[derive(Debug)]
+pub struct wasm_store_t {
+    pub(crate) inner: Rc<StoreCell>,
+}
+
+/// The store shared by all items created in it
+#[derive(Debug)]
+pub(crate) struct StoreCell {
+    store: UnsafeCell<Store>,
+    // the store borrowed by the host function that is currently running
+    active: Cell<*mut Store>,
+}
+
+impl StoreCell {
+    /// Run `f` with the store
+    ///
+    /// While a host function runs, the store is borrowed by the interpreter and lent to the host function,
+    /// so calls from the host function into the API use that borrow instead.
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut Store) -> R) -> R {
+        let store = match self.active.get() {
+            active if active.is_null() => self.store.get(),
+            active => active,
+        };
+
+        // SAFETY: the API is single threaded and the only other borrow is the one of the running host
+        // function, which is not used until the callback returns
+        f(unsafe { &mut *store })
+    }
+
+    /// Run a host function with the store lent to it by the interpreter
+    pub(crate) fn lend<R>(&self, store: &mut Store, f: impl FnOnce() -> R) -> R {
+        let prev = self.active.replace(store);
+        let res = f();
+        self.active.set(prev);
+        res
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn wasm_config_new() -> *mut wasm_config_t {
+    Box::into_raw(Box::default())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_config_delete(config: *mut wasm_config_t) {
+    drop(Box::from_raw(config));
+}
+
+#[no_mangle]
+pub extern "C" fn wasm_engine_new() -> *mut wasm_engine_t {
+    Box::into_raw(Box::default())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_engine_new_with_config(config: *mut wasm_config_t) -> *mut wasm_engine_t {
+    wasm_config_delete(config);
+    wasm_engine_new()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_engine_delete(engine: *mut wasm_engine_t) {
+    drop(Box::from_raw(engine));
+}
+
+#[no_mangle]
+pub extern "C" fn wasm_store_new(_engine: *mut wasm_engine_t) -> *mut wasm_store_t {
+    let inner = StoreCell { store: UnsafeCell::new(Store::default()), active: Cell::new(std::ptr::null_mut()) };
+    Box::into_raw(Box::new(wasm_store_t { inner: Rc::new(inner) }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_store_delete(store: *mut wasm_store_t) {
+    drop(Box::from_raw(store));
+}
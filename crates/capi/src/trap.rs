@@ -0,0 +1,47 @@
+use tinywasm::Error;
+
+use crate::{wasm_byte_vec_t, wasm_store_t, WasmVec};
+
+/// `wasm_message_t`, a null terminated message
+pub type wasm_message_t = wasm_byte_vec_t;
+
+/// `wasm_trap_t`
+#[derive(Debug, Clone)]
+pub struct wasm_trap_t {
+    pub(crate) message: String,
+}
+
+impl wasm_trap_t {
+    pub(crate) fn boxed(message: String) -> *mut Self {
+        Box::into_raw(Box::new(Self { message }))
+    }
+
+    /// A trap for an error returned by tinywasm
+    pub(crate) fn from_error(err: Error) -> *mut Self {
+        Self::boxed(match err {
+            // e.g. a trap returned by a host function
+            Error::Host(err) => err.to_string(),
+            Error::Trap(trap) => trap.to_string(),
+            err => err.to_string(),
+        })
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_trap_new(_store: *mut wasm_store_t, message: *const wasm_message_t) -> *mut wasm_trap_t {
+    let message = (*message).as_slice();
+    let message = message.strip_suffix(&[0]).unwrap_or(message);
+    wasm_trap_t::boxed(String::from_utf8_lossy(message).into_owned())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_trap_message(trap: *const wasm_trap_t, out: *mut wasm_message_t) {
+    let mut message = (*trap).message.as_bytes().to_vec();
+    message.push(0);
+    out.write(WasmVec::from_vec(message));
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_trap_delete(trap: *mut wasm_trap_t) {
+    drop(Box::from_raw(trap));
+}
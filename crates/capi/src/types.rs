@@ -0,0 +1,483 @@
+use core::ptr;
+
+use tinywasm::types::{ExternType, FuncType, GlobalType, MemoryType, TableType, ValType};
+
+use crate::vec::{copy_vec, delete_vec, type_functions};
+use crate::{wasm_byte_vec_t, WasmVec};
+
+pub const WASM_I32: u8 = 0;
+pub const WASM_I64: u8 = 1;
+pub const WASM_F32: u8 = 2;
+pub const WASM_F64: u8 = 3;
+// not part of the standard, only used to report the type of `v128` values
+const WASM_V128: u8 = 4;
+pub const WASM_ANYREF: u8 = 128;
+pub const WASM_FUNCREF: u8 = 129;
+
+pub const WASM_EXTERN_FUNC: u8 = 0;
+pub const WASM_EXTERN_GLOBAL: u8 = 1;
+pub const WASM_EXTERN_TABLE: u8 = 2;
+pub const WASM_EXTERN_MEMORY: u8 = 3;
+
+/// `wasm_name_t`
+pub type wasm_name_t = wasm_byte_vec_t;
+
+/// `wasm_limits_t`, a `max` of `u32::MAX` means there is no maximum
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct wasm_limits_t {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl wasm_limits_t {
+    fn new(min: u64, max: Option<u64>) -> Self {
+        Self { min: min as u32, max: max.map_or(u32::MAX, |max| max as u32) }
+    }
+
+    fn max(&self) -> Option<u32> {
+        (self.max != u32::MAX).then_some(self.max)
+    }
+}
+
+/// `wasm_valtype_t`
+#[derive(Debug, Clone)]
+pub struct wasm_valtype_t {
+    pub(crate) ty: ValType,
+}
+
+/// `wasm_valtype_vec_t`
+pub type wasm_valtype_vec_t = WasmVec<*mut wasm_valtype_t>;
+
+pub(crate) fn valkind(ty: ValType) -> u8 {
+    match ty {
+        ValType::I32 => WASM_I32,
+        ValType::I64 => WASM_I64,
+        ValType::F32 => WASM_F32,
+        ValType::F64 => WASM_F64,
+        ValType::V128 => WASM_V128,
+        ValType::RefExtern => WASM_ANYREF,
+        ValType::RefFunc => WASM_FUNCREF,
+    }
+}
+
+pub(crate) fn valtype(kind: u8) -> Option<ValType> {
+    Some(match kind {
+        WASM_I32 => ValType::I32,
+        WASM_I64 => ValType::I64,
+        WASM_F32 => ValType::F32,
+        WASM_F64 => ValType::F64,
+        WASM_ANYREF => ValType::RefExtern,
+        WASM_FUNCREF => ValType::RefFunc,
+        _ => return None,
+    })
+}
+
+fn valtypes(types: &[ValType]) -> wasm_valtype_vec_t {
+    WasmVec::from_vec(types.iter().map(|&ty| Box::into_raw(Box::new(wasm_valtype_t { ty }))).collect())
+}
+
+// takes the types out of an owned vector
+unsafe fn take_valtypes(types: *mut wasm_valtype_vec_t) -> Box<[ValType]> {
+    (*types).take().into_iter().map(|ty| Box::from_raw(ty).ty).collect()
+}
+
+/// `wasm_externtype_t`, also used as `wasm_functype_t`, `wasm_globaltype_t`, `wasm_tabletype_t` and
+/// `wasm_memorytype_t`
+#[derive(Debug)]
+pub struct wasm_externtype_t {
+    pub(crate) ty: ExternType,
+
+    // the parts of the type that are returned as borrowed pointers
+    params: wasm_valtype_vec_t,
+    results: wasm_valtype_vec_t,
+    content: wasm_valtype_t,
+    limits: wasm_limits_t,
+}
+
+/// `wasm_functype_t`
+pub type wasm_functype_t = wasm_externtype_t;
+/// `wasm_globaltype_t`
+pub type wasm_globaltype_t = wasm_externtype_t;
+/// `wasm_tabletype_t`
+pub type wasm_tabletype_t = wasm_externtype_t;
+/// `wasm_memorytype_t`
+pub type wasm_memorytype_t = wasm_externtype_t;
+
+impl wasm_externtype_t {
+    pub(crate) fn new(ty: ExternType) -> Self {
+        let (mut params, mut results) = (WasmVec::from_vec(Vec::new()), WasmVec::from_vec(Vec::new()));
+        let (mut content, mut limits) = (ValType::I32, wasm_limits_t::default());
+        match &ty {
+            ExternType::Func(ty) => (params, results) = (valtypes(&ty.params), valtypes(&ty.results)),
+            ExternType::Global(ty) => content = ty.ty,
+            ExternType::Table(ty) => {
+                content = ty.element_type;
+                limits = wasm_limits_t::new(ty.size_initial.into(), ty.size_max.map(Into::into));
+            }
+            ExternType::Memory(ty) => limits = wasm_limits_t::new(ty.page_count_initial, ty.page_count_max),
+        }
+
+        Self { ty, params, results, content: wasm_valtype_t { ty: content }, limits }
+    }
+
+    pub(crate) fn boxed(ty: ExternType) -> *mut Self {
+        Box::into_raw(Box::new(Self::new(ty)))
+    }
+
+    pub(crate) fn kind(&self) -> u8 {
+        match self.ty {
+            ExternType::Func(_) => WASM_EXTERN_FUNC,
+            ExternType::Global(_) => WASM_EXTERN_GLOBAL,
+            ExternType::Table(_) => WASM_EXTERN_TABLE,
+            ExternType::Memory(_) => WASM_EXTERN_MEMORY,
+        }
+    }
+
+    pub(crate) fn func(&self) -> Option<&FuncType> {
+        match &self.ty {
+            ExternType::Func(ty) => Some(ty),
+            _ => None,
+        }
+    }
+
+    // the same type if it is of the given kind
+    fn cast(ty: *const Self, kind: u8) -> *const Self {
+        match unsafe { (*ty).kind() } == kind {
+            true => ty,
+            false => ptr::null(),
+        }
+    }
+}
+
+impl Clone for wasm_externtype_t {
+    fn clone(&self) -> Self {
+        Self::new(self.ty.clone())
+    }
+}
+
+impl Drop for wasm_externtype_t {
+    fn drop(&mut self) {
+        unsafe {
+            delete_vec(&mut self.params);
+            delete_vec(&mut self.results);
+        }
+    }
+}
+
+/// `wasm_importtype_t`
+#[derive(Debug)]
+pub struct wasm_importtype_t {
+    module: wasm_name_t,
+    name: wasm_name_t,
+    ty: Box<wasm_externtype_t>,
+}
+
+impl wasm_importtype_t {
+    pub(crate) fn new(module: &str, name: &str, ty: ExternType) -> Self {
+        let (module, name) = (WasmVec::from_str(module), WasmVec::from_str(name));
+        Self { module, name, ty: Box::new(wasm_externtype_t::new(ty)) }
+    }
+}
+
+impl Clone for wasm_importtype_t {
+    fn clone(&self) -> Self {
+        unsafe { Self { module: copy_vec(&self.module), name: copy_vec(&self.name), ty: self.ty.clone() } }
+    }
+}
+
+impl Drop for wasm_importtype_t {
+    fn drop(&mut self) {
+        unsafe {
+            delete_vec(&mut self.module);
+            delete_vec(&mut self.name);
+        }
+    }
+}
+
+/// `wasm_exporttype_t`
+#[derive(Debug)]
+pub struct wasm_exporttype_t {
+    name: wasm_name_t,
+    ty: Box<wasm_externtype_t>,
+}
+
+impl wasm_exporttype_t {
+    pub(crate) fn new(name: &str, ty: ExternType) -> Self {
+        Self { name: WasmVec::from_str(name), ty: Box::new(wasm_externtype_t::new(ty)) }
+    }
+}
+
+impl Clone for wasm_exporttype_t {
+    fn clone(&self) -> Self {
+        unsafe { Self { name: copy_vec(&self.name), ty: self.ty.clone() } }
+    }
+}
+
+impl Drop for wasm_exporttype_t {
+    fn drop(&mut self) {
+        unsafe { delete_vec(&mut self.name) }
+    }
+}
+
+type_functions!(
+    wasm_valtype_t,
+    wasm_valtype_delete,
+    wasm_valtype_copy,
+    wasm_valtype_vec_new_empty,
+    wasm_valtype_vec_new_uninitialized,
+    wasm_valtype_vec_new,
+    wasm_valtype_vec_copy,
+    wasm_valtype_vec_delete
+);
+
+type_functions!(
+    wasm_functype_t,
+    wasm_functype_delete,
+    wasm_functype_copy,
+    wasm_functype_vec_new_empty,
+    wasm_functype_vec_new_uninitialized,
+    wasm_functype_vec_new,
+    wasm_functype_vec_copy,
+    wasm_functype_vec_delete
+);
+
+type_functions!(
+    wasm_globaltype_t,
+    wasm_globaltype_delete,
+    wasm_globaltype_copy,
+    wasm_globaltype_vec_new_empty,
+    wasm_globaltype_vec_new_uninitialized,
+    wasm_globaltype_vec_new,
+    wasm_globaltype_vec_copy,
+    wasm_globaltype_vec_delete
+);
+
+type_functions!(
+    wasm_tabletype_t,
+    wasm_tabletype_delete,
+    wasm_tabletype_copy,
+    wasm_tabletype_vec_new_empty,
+    wasm_tabletype_vec_new_uninitialized,
+    wasm_tabletype_vec_new,
+    wasm_tabletype_vec_copy,
+    wasm_tabletype_vec_delete
+);
+
+type_functions!(
+    wasm_memorytype_t,
+    wasm_memorytype_delete,
+    wasm_memorytype_copy,
+    wasm_memorytype_vec_new_empty,
+    wasm_memorytype_vec_new_uninitialized,
+    wasm_memorytype_vec_new,
+    wasm_memorytype_vec_copy,
+    wasm_memorytype_vec_delete
+);
+
+type_functions!(
+    wasm_externtype_t,
+    wasm_externtype_delete,
+    wasm_externtype_copy,
+    wasm_externtype_vec_new_empty,
+    wasm_externtype_vec_new_uninitialized,
+    wasm_externtype_vec_new,
+    wasm_externtype_vec_copy,
+    wasm_externtype_vec_delete
+);
+
+type_functions!(
+    wasm_importtype_t,
+    wasm_importtype_delete,
+    wasm_importtype_copy,
+    wasm_importtype_vec_new_empty,
+    wasm_importtype_vec_new_uninitialized,
+    wasm_importtype_vec_new,
+    wasm_importtype_vec_copy,
+    wasm_importtype_vec_delete
+);
+
+type_functions!(
+    wasm_exporttype_t,
+    wasm_exporttype_delete,
+    wasm_exporttype_copy,
+    wasm_exporttype_vec_new_empty,
+    wasm_exporttype_vec_new_uninitialized,
+    wasm_exporttype_vec_new,
+    wasm_exporttype_vec_copy,
+    wasm_exporttype_vec_delete
+);
+
+/// `wasm_importtype_vec_t`
+pub type wasm_importtype_vec_t = WasmVec<*mut wasm_importtype_t>;
+/// `wasm_exporttype_vec_t`
+pub type wasm_exporttype_vec_t = WasmVec<*mut wasm_exporttype_t>;
+
+#[no_mangle]
+pub extern "C" fn wasm_valtype_new(kind: u8) -> *mut wasm_valtype_t {
+    match valtype(kind) {
+        Some(ty) => Box::into_raw(Box::new(wasm_valtype_t { ty })),
+        None => ptr::null_mut(),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_valtype_kind(ty: *const wasm_valtype_t) -> u8 {
+    valkind((*ty).ty)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_functype_new(
+    params: *mut wasm_valtype_vec_t,
+    results: *mut wasm_valtype_vec_t,
+) -> *mut wasm_functype_t {
+    wasm_externtype_t::boxed(ExternType::Func(FuncType {
+        params: take_valtypes(params),
+        results: take_valtypes(results),
+    }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_functype_params(ty: *const wasm_functype_t) -> *const wasm_valtype_vec_t {
+    &(*ty).params
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_functype_results(ty: *const wasm_functype_t) -> *const wasm_valtype_vec_t {
+    &(*ty).results
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_globaltype_new(content: *mut wasm_valtype_t, mutability: u8) -> *mut wasm_globaltype_t {
+    let ty = Box::from_raw(content).ty;
+    wasm_externtype_t::boxed(ExternType::Global(GlobalType { ty, mutable: mutability != 0 }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_globaltype_content(ty: *const wasm_globaltype_t) -> *const wasm_valtype_t {
+    &(*ty).content
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_globaltype_mutability(ty: *const wasm_globaltype_t) -> u8 {
+    match &(*ty).ty {
+        ExternType::Global(ty) => ty.mutable.into(),
+        _ => 0,
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_tabletype_new(
+    element: *mut wasm_valtype_t,
+    limits: *const wasm_limits_t,
+) -> *mut wasm_tabletype_t {
+    let element_type = Box::from_raw(element).ty;
+    let ty = TableType { element_type, size_initial: (*limits).min, size_max: (*limits).max() };
+    wasm_externtype_t::boxed(ExternType::Table(ty))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_tabletype_element(ty: *const wasm_tabletype_t) -> *const wasm_valtype_t {
+    &(*ty).content
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_tabletype_limits(ty: *const wasm_tabletype_t) -> *const wasm_limits_t {
+    &(*ty).limits
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memorytype_new(limits: *const wasm_limits_t) -> *mut wasm_memorytype_t {
+    let ty = MemoryType::new_32((*limits).min.into(), (*limits).max().map(Into::into));
+    wasm_externtype_t::boxed(ExternType::Memory(ty))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_memorytype_limits(ty: *const wasm_memorytype_t) -> *const wasm_limits_t {
+    &(*ty).limits
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_externtype_kind(ty: *const wasm_externtype_t) -> u8 {
+    (*ty).kind()
+}
+
+// the specific types are the same as `wasm_externtype_t`, so the conversions only check the kind
+macro_rules! externtype_casts {
+    ($($kind:ident: $as_extern:ident, $as_extern_const:ident, $from_extern:ident, $from_extern_const:ident;)*) => {$(
+        #[no_mangle]
+        pub extern "C" fn $as_extern(ty: *mut wasm_externtype_t) -> *mut wasm_externtype_t {
+            ty
+        }
+
+        #[no_mangle]
+        pub extern "C" fn $as_extern_const(ty: *const wasm_externtype_t) -> *const wasm_externtype_t {
+            ty
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn $from_extern(ty: *mut wasm_externtype_t) -> *mut wasm_externtype_t {
+            wasm_externtype_t::cast(ty, $kind).cast_mut()
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn $from_extern_const(ty: *const wasm_externtype_t) -> *const wasm_externtype_t {
+            wasm_externtype_t::cast(ty, $kind)
+        }
+    )*};
+}
+
+externtype_casts!(
+    WASM_EXTERN_FUNC: wasm_functype_as_externtype, wasm_functype_as_externtype_const,
+        wasm_externtype_as_functype, wasm_externtype_as_functype_const;
+    WASM_EXTERN_GLOBAL: wasm_globaltype_as_externtype, wasm_globaltype_as_externtype_const,
+        wasm_externtype_as_globaltype, wasm_externtype_as_globaltype_const;
+    WASM_EXTERN_TABLE: wasm_tabletype_as_externtype, wasm_tabletype_as_externtype_const,
+        wasm_externtype_as_tabletype, wasm_externtype_as_tabletype_const;
+    WASM_EXTERN_MEMORY: wasm_memorytype_as_externtype, wasm_memorytype_as_externtype_const,
+        wasm_externtype_as_memorytype, wasm_externtype_as_memorytype_const;
+);
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_importtype_new(
+    module: *mut wasm_name_t,
+    name: *mut wasm_name_t,
+    ty: *mut wasm_externtype_t,
+) -> *mut wasm_importtype_t {
+    let (module, name) = (WasmVec::from_vec((*module).take()), WasmVec::from_vec((*name).take()));
+    Box::into_raw(Box::new(wasm_importtype_t { module, name, ty: Box::from_raw(ty) }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_importtype_module(ty: *const wasm_importtype_t) -> *const wasm_name_t {
+    &(*ty).module
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_importtype_name(ty: *const wasm_importtype_t) -> *const wasm_name_t {
+    &(*ty).name
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_importtype_type(ty: *const wasm_importtype_t) -> *const wasm_externtype_t {
+    &*(*ty).ty
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_exporttype_new(
+    name: *mut wasm_name_t,
+    ty: *mut wasm_externtype_t,
+) -> *mut wasm_exporttype_t {
+    let name = WasmVec::from_vec((*name).take());
+    Box::into_raw(Box::new(wasm_exporttype_t { name, ty: Box::from_raw(ty) }))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_exporttype_name(ty: *const wasm_exporttype_t) -> *const wasm_name_t {
+    &(*ty).name
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_exporttype_type(ty: *const wasm_exporttype_t) -> *const wasm_externtype_t {
+    &*(*ty).ty
+}
@@ -0,0 +1,153 @@
+use core::ptr;
+
+use tinywasm::types::{ValType, WasmValue};
+
+use crate::types::{valkind, valtype, WASM_ANYREF, WASM_FUNCREF, WASM_I32};
+use crate::vec::{vec_functions, VecItem};
+use crate::WasmVec;
+
+/// `wasm_ref_t`, a non-null `funcref` or `externref`
+#[derive(Debug, Clone, PartialEq)]
+pub struct wasm_ref_t {
+    pub(crate) val: WasmValue,
+}
+
+/// `wasm_val_t`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct wasm_val_t {
+    pub kind: u8,
+    pub of: wasm_val_union,
+}
+
+/// The value of a `wasm_val_t`
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union wasm_val_union {
+    pub i32: i32,
+    pub i64: i64,
+    pub f32: f32,
+    pub f64: f64,
+    pub r#ref: *mut wasm_ref_t,
+}
+
+impl core::fmt::Debug for wasm_val_t {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match unsafe { self.value(valtype(self.kind)) } {
+            Some(val) => write!(f, "wasm_val_t({val:?})"),
+            None => write!(f, "wasm_val_t(kind: {})", self.kind),
+        }
+    }
+}
+
+/// `wasm_val_vec_t`
+pub type wasm_val_vec_t = WasmVec<wasm_val_t>;
+
+impl wasm_val_t {
+    /// Convert a value, references become new owned `wasm_ref_t`s
+    pub(crate) fn from_wasm(val: WasmValue) -> Option<Self> {
+        let of = match val {
+            WasmValue::I32(i32) => wasm_val_union { i32 },
+            WasmValue::I64(i64) => wasm_val_union { i64 },
+            WasmValue::F32(f32) => wasm_val_union { f32 },
+            WasmValue::F64(f64) => wasm_val_union { f64 },
+            WasmValue::V128(_) => return None,
+            WasmValue::RefNull(_) => wasm_val_union { r#ref: ptr::null_mut() },
+            WasmValue::RefFunc(_) | WasmValue::RefExtern(_) => {
+                wasm_val_union { r#ref: Box::into_raw(Box::new(wasm_ref_t { val })) }
+            }
+        };
+        Some(Self { kind: valkind(val.val_type()), of })
+    }
+
+    /// Convert a value of the given type, `None` if the kind doesn't match
+    pub(crate) unsafe fn value(&self, ty: Option<ValType>) -> Option<WasmValue> {
+        let ty = ty.filter(|&ty| valkind(ty) == self.kind)?;
+        Some(match ty {
+            ValType::I32 => WasmValue::I32(self.of.i32),
+            ValType::I64 => WasmValue::I64(self.of.i64),
+            ValType::F32 => WasmValue::F32(self.of.f32),
+            ValType::F64 => WasmValue::F64(self.of.f64),
+            ValType::V128 => return None,
+            ValType::RefFunc | ValType::RefExtern => wasm_ref_t::to_wasm(self.of.r#ref, ty),
+        })
+    }
+}
+
+impl wasm_ref_t {
+    /// Convert a nullable reference of the given type
+    pub(crate) unsafe fn to_wasm(reference: *const Self, ty: ValType) -> WasmValue {
+        match reference.is_null() {
+            true => WasmValue::RefNull(ty),
+            false => (*reference).val,
+        }
+    }
+
+    /// Convert a reference to a new owned `wasm_ref_t`, `NULL` for null references
+    pub(crate) fn from_wasm(val: WasmValue) -> *mut Self {
+        match val {
+            WasmValue::RefFunc(_) | WasmValue::RefExtern(_) => Box::into_raw(Box::new(Self { val })),
+            _ => ptr::null_mut(),
+        }
+    }
+}
+
+impl VecItem for wasm_val_t {
+    fn empty() -> Self {
+        Self { kind: WASM_I32, of: wasm_val_union { i64: 0 } }
+    }
+
+    unsafe fn copy(&self) -> Self {
+        let mut copy = *self;
+        wasm_val_copy(&mut copy, self);
+        copy
+    }
+
+    unsafe fn delete(mut self) {
+        wasm_val_delete(&mut self);
+    }
+}
+
+vec_functions!(
+    wasm_val_t,
+    wasm_val_vec_new_empty,
+    wasm_val_vec_new_uninitialized,
+    wasm_val_vec_new,
+    wasm_val_vec_copy,
+    wasm_val_vec_delete
+);
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_val_delete(val: *mut wasm_val_t) {
+    if matches!((*val).kind, WASM_ANYREF | WASM_FUNCREF) {
+        <*mut wasm_ref_t as VecItem>::delete((*val).of.r#ref);
+        (*val).of.r#ref = ptr::null_mut();
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_val_copy(out: *mut wasm_val_t, val: *const wasm_val_t) {
+    let mut copy = *val;
+    if matches!(copy.kind, WASM_ANYREF | WASM_FUNCREF) {
+        copy.of.r#ref = VecItem::copy(&copy.of.r#ref);
+    }
+    out.write(copy);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_ref_delete(reference: *mut wasm_ref_t) {
+    <*mut wasm_ref_t as VecItem>::delete(reference);
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_ref_copy(reference: *const wasm_ref_t) -> *mut wasm_ref_t {
+    VecItem::copy(&reference.cast_mut())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasm_ref_same(a: *const wasm_ref_t, b: *const wasm_ref_t) -> bool {
+    match (a.is_null(), b.is_null()) {
+        (false, false) => *a == *b,
+        (a, b) => a && b,
+    }
+}
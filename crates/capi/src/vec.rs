@@ -0,0 +1,165 @@
+use core::{ptr, slice};
+
+/// The layout of all `wasm_*_vec_t` types
+///
+/// The elements are either values or owned pointers, deleting a vector also deletes its elements.
+#[repr(C)]
+#[derive(Debug)]
+pub struct WasmVec<T> {
+    /// The number of elements
+    pub size: usize,
+    /// The elements, `NULL` if the vector is empty
+    pub data: *mut T,
+}
+
+/// `wasm_byte_vec_t`, also used for names and messages
+pub type wasm_byte_vec_t = WasmVec<u8>;
+
+impl<T> WasmVec<T> {
+    pub(crate) fn from_vec(items: Vec<T>) -> Self {
+        if items.is_empty() {
+            return Self { size: 0, data: ptr::null_mut() };
+        }
+
+        let items = Box::into_raw(items.into_boxed_slice());
+        Self { size: items.len(), data: items.cast() }
+    }
+
+    pub(crate) unsafe fn as_slice(&self) -> &[T] {
+        match self.data.is_null() {
+            true => &[],
+            false => slice::from_raw_parts(self.data, self.size),
+        }
+    }
+
+    // takes back the elements, leaving the vector empty
+    pub(crate) unsafe fn take(&mut self) -> Vec<T> {
+        let (size, data) = (self.size, self.data);
+        (self.size, self.data) = (0, ptr::null_mut());
+        match data.is_null() || size == 0 {
+            true => Vec::new(),
+            false => Box::from_raw(ptr::slice_from_raw_parts_mut(data, size)).into_vec(),
+        }
+    }
+}
+
+impl WasmVec<u8> {
+    pub(crate) fn from_str(s: &str) -> Self {
+        Self::from_vec(s.as_bytes().to_vec())
+    }
+}
+
+/// An element of a vector
+pub(crate) trait VecItem: Sized {
+    // the value of an uninitialized element
+    fn empty() -> Self;
+    unsafe fn copy(&self) -> Self;
+    unsafe fn delete(self);
+}
+
+impl VecItem for u8 {
+    fn empty() -> Self {
+        0
+    }
+
+    unsafe fn copy(&self) -> Self {
+        *self
+    }
+
+    unsafe fn delete(self) {}
+}
+
+impl<T: Clone> VecItem for *mut T {
+    fn empty() -> Self {
+        ptr::null_mut()
+    }
+
+    unsafe fn copy(&self) -> Self {
+        match self.is_null() {
+            true => ptr::null_mut(),
+            false => Box::into_raw(Box::new((**self).clone())),
+        }
+    }
+
+    unsafe fn delete(self) {
+        if !self.is_null() {
+            drop(Box::from_raw(self));
+        }
+    }
+}
+
+/// Delete a vector and its elements
+pub(crate) unsafe fn delete_vec<T: VecItem>(vec: &mut WasmVec<T>) {
+    vec.take().into_iter().for_each(|item| item.delete());
+}
+
+/// Copy a vector and its elements
+pub(crate) unsafe fn copy_vec<T: VecItem>(vec: &WasmVec<T>) -> WasmVec<T> {
+    WasmVec::from_vec(vec.as_slice().iter().map(|item| item.copy()).collect())
+}
+
+// the functions of a `wasm_*_vec_t` type
+macro_rules! vec_functions {
+    ($item:ty, $new_empty:ident, $new_uninitialized:ident, $new:ident, $copy:ident, $delete:ident) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $new_empty(out: *mut $crate::WasmVec<$item>) {
+            out.write($crate::WasmVec::from_vec(Vec::new()));
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn $new_uninitialized(out: *mut $crate::WasmVec<$item>, size: usize) {
+            let items = (0..size).map(|_| <$item as $crate::vec::VecItem>::empty()).collect();
+            out.write($crate::WasmVec::from_vec(items));
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn $new(out: *mut $crate::WasmVec<$item>, size: usize, data: *const $item) {
+            // the elements are moved into the vector
+            let items = match size {
+                0 => Vec::new(),
+                _ => core::slice::from_raw_parts(data, size).iter().map(|item| core::ptr::read(item)).collect(),
+            };
+            out.write($crate::WasmVec::from_vec(items));
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn $copy(out: *mut $crate::WasmVec<$item>, src: *const $crate::WasmVec<$item>) {
+            out.write($crate::vec::copy_vec(&*src));
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn $delete(vec: *mut $crate::WasmVec<$item>) {
+            $crate::vec::delete_vec(&mut *vec);
+        }
+    };
+}
+
+pub(crate) use vec_functions;
+
+vec_functions!(
+    u8,
+    wasm_byte_vec_new_empty,
+    wasm_byte_vec_new_uninitialized,
+    wasm_byte_vec_new,
+    wasm_byte_vec_copy,
+    wasm_byte_vec_delete
+);
+
+// the functions of a type that is passed around as an owned pointer
+macro_rules! type_functions {
+    ($ty:ty, $delete:ident, $copy:ident, $($vec:ident),*) => {
+        #[no_mangle]
+        pub unsafe extern "C" fn $delete(item: *mut $ty) {
+            <*mut $ty as $crate::vec::VecItem>::delete(item);
+        }
+
+        #[no_mangle]
+        pub unsafe extern "C" fn $copy(item: *const $ty) -> *mut $ty {
+            <*mut $ty as $crate::vec::VecItem>::copy(&item.cast_mut())
+        }
+
+        $crate::vec::vec_functions!(*mut $ty, $($vec),*);
+    };
+}
+
+pub(crate) use type_functions;
@@ -0,0 +1,225 @@
+// Runs tests/c/api.wat using the C API, the path of the compiled module is passed as the first argument
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "wasm.h"
+
+#define CHECK(cond) \
+  if (!(cond)) { \
+    fprintf(stderr, "check failed at line %d: %s\n", __LINE__, #cond); \
+    exit(1); \
+  }
+
+static wasm_memory_t* memory = NULL;
+static char logged[16] = {0};
+static int finalized = 0;
+
+// reads the message from the memory of the calling instance
+static wasm_trap_t* log_callback(const wasm_val_vec_t* args, wasm_val_vec_t* results) {
+  int32_t ptr = args->data[0].of.i32, len = args->data[1].of.i32;
+  CHECK(memory != NULL && len < (int32_t)sizeof(logged));
+  memcpy(logged, wasm_memory_data(memory) + ptr, len);
+  return NULL;
+}
+
+static wasm_trap_t* double_callback(void* env, const wasm_val_vec_t* args, wasm_val_vec_t* results) {
+  int32_t arg = args->data[0].of.i32;
+  if (arg < 0) {
+    wasm_message_t message;
+    wasm_name_new_from_string_nt(&message, "negative argument");
+    wasm_trap_t* trap = wasm_trap_new(NULL, &message);
+    wasm_name_delete(&message);
+    return trap;
+  }
+
+  results->data[0].kind = WASM_I32;
+  results->data[0].of.i32 = arg * *(int32_t*)env;
+  return NULL;
+}
+
+static void finalizer(void* env) {
+  finalized = 1;
+}
+
+static wasm_extern_t* export(const wasm_extern_vec_t* exports, const wasm_module_t* module, const char* name) {
+  wasm_exporttype_vec_t types;
+  wasm_module_exports(module, &types);
+  CHECK(types.size == exports->size);
+
+  wasm_extern_t* found = NULL;
+  for (size_t i = 0; i < types.size; i++) {
+    const wasm_name_t* export_name = wasm_exporttype_name(types.data[i]);
+    if (export_name->size == strlen(name) && memcmp(export_name->data, name, export_name->size) == 0) {
+      found = exports->data[i];
+    }
+  }
+
+  wasm_exporttype_vec_delete(&types);
+  CHECK(found != NULL);
+  return found;
+}
+
+static void check_trap(wasm_trap_t* trap, const char* expected) {
+  CHECK(trap != NULL);
+  wasm_message_t message;
+  wasm_trap_message(trap, &message);
+  CHECK(strstr(message.data, expected) != NULL);
+  wasm_name_delete(&message);
+  wasm_trap_delete(trap);
+}
+
+int main(int argc, const char* argv[]) {
+  CHECK(argc == 2);
+  FILE* file = fopen(argv[1], "rb");
+  CHECK(file != NULL);
+  fseek(file, 0L, SEEK_END);
+  size_t size = ftell(file);
+  fseek(file, 0L, SEEK_SET);
+  wasm_byte_vec_t binary;
+  wasm_byte_vec_new_uninitialized(&binary, size);
+  CHECK(fread(binary.data, size, 1, file) == 1);
+  fclose(file);
+
+  wasm_engine_t* engine = wasm_engine_new_with_config(wasm_config_new());
+  wasm_store_t* store = wasm_store_new(engine);
+
+  // parse the module and inspect its imports
+  CHECK(wasm_module_validate(store, &binary));
+  wasm_module_t* module = wasm_module_new(store, &binary);
+  CHECK(module != NULL);
+  wasm_byte_vec_delete(&binary);
+
+  wasm_importtype_vec_t import_types;
+  wasm_module_imports(module, &import_types);
+  CHECK(import_types.size == 4);
+  const wasm_name_t* name = wasm_importtype_name(import_types.data[1]);
+  CHECK(name->size == 6 && memcmp(name->data, "double", 6) == 0);
+  CHECK(wasm_externtype_kind(wasm_importtype_type(import_types.data[2])) == WASM_EXTERN_GLOBAL);
+  wasm_importtype_vec_delete(&import_types);
+
+  // create the imports
+  wasm_valtype_t* log_params[] = {wasm_valtype_new(WASM_I32), wasm_valtype_new(WASM_I32)};
+  wasm_valtype_vec_t params, results;
+  wasm_valtype_vec_new(&params, 2, log_params);
+  wasm_valtype_vec_new_empty(&results);
+  wasm_functype_t* log_type = wasm_functype_new(&params, &results);
+  wasm_func_t* log_func = wasm_func_new(store, log_type, log_callback);
+  CHECK(wasm_func_param_arity(log_func) == 2 && wasm_func_result_arity(log_func) == 0);
+  wasm_functype_delete(log_type);
+
+  static int32_t factor = 2;
+  wasm_valtype_t* double_params[] = {wasm_valtype_new(WASM_I32)};
+  wasm_valtype_t* double_results[] = {wasm_valtype_new(WASM_I32)};
+  wasm_valtype_vec_new(&params, 1, double_params);
+  wasm_valtype_vec_new(&results, 1, double_results);
+  wasm_functype_t* double_type = wasm_functype_new(&params, &results);
+  wasm_func_t* double_func = wasm_func_new_with_env(store, double_type, double_callback, &factor, finalizer);
+  wasm_functype_delete(double_type);
+
+  wasm_globaltype_t* counter_type = wasm_globaltype_new(wasm_valtype_new(WASM_I32), WASM_VAR);
+  wasm_val_t zero = WASM_I32_VAL(0);
+  wasm_global_t* counter = wasm_global_new(store, counter_type, &zero);
+  CHECK(wasm_globaltype_mutability(counter_type) == WASM_VAR);
+  wasm_globaltype_delete(counter_type);
+
+  wasm_limits_t limits = {2, 10};
+  wasm_tabletype_t* table_type = wasm_tabletype_new(wasm_valtype_new(WASM_FUNCREF), &limits);
+  wasm_table_t* table = wasm_table_new(store, table_type, NULL);
+  CHECK(wasm_table_size(table) == 2);
+  wasm_tabletype_delete(table_type);
+
+  wasm_extern_t* imports[] = {
+    wasm_func_as_extern(log_func),
+    wasm_func_as_extern(double_func),
+    wasm_global_as_extern(counter),
+    wasm_table_as_extern(table),
+  };
+  wasm_extern_vec_t import_vec = WASM_ARRAY_VEC(imports);
+
+  // instantiate the module
+  wasm_trap_t* trap = NULL;
+  wasm_instance_t* instance = wasm_instance_new(store, module, &import_vec, &trap);
+  CHECK(instance != NULL && trap == NULL);
+
+  wasm_extern_vec_t exports;
+  wasm_instance_exports(instance, &exports);
+  CHECK(exports.size == 6);
+  memory = wasm_extern_as_memory(export(&exports, module, "memory"));
+  CHECK(memory != NULL);
+  CHECK(wasm_extern_as_func(export(&exports, module, "memory")) == NULL);
+
+  // call functions
+  wasm_func_t* add = wasm_extern_as_func(export(&exports, module, "add"));
+  wasm_val_t add_args_data[] = {WASM_I32_VAL(1), WASM_I32_VAL(2)};
+  wasm_val_t add_results_data[1] = {WASM_INIT_VAL};
+  wasm_val_vec_t add_args = WASM_ARRAY_VEC(add_args_data);
+  wasm_val_vec_t add_results = WASM_ARRAY_VEC(add_results_data);
+  CHECK(wasm_func_call(add, &add_args, &add_results) == NULL);
+  CHECK(add_results_data[0].kind == WASM_I32 && add_results_data[0].of.i32 == 3);
+
+  wasm_val_vec_t empty = WASM_EMPTY_VEC;
+  CHECK(wasm_func_call(wasm_extern_as_func(export(&exports, module, "greet")), &empty, &empty) == NULL);
+  CHECK(strcmp(logged, "hello") == 0);
+  wasm_val_t counter_value;
+  wasm_global_get(counter, &counter_value);
+  CHECK(counter_value.of.i32 == 1);
+  wasm_val_t ten = WASM_I32_VAL(10);
+  wasm_global_set(counter, &ten);
+  wasm_global_get(counter, &counter_value);
+  CHECK(counter_value.of.i32 == 10);
+
+  // host functions with an environment and traps
+  wasm_func_t* quadruple = wasm_extern_as_func(export(&exports, module, "quadruple"));
+  wasm_val_t quadruple_args_data[] = {WASM_I32_VAL(3)};
+  wasm_val_vec_t quadruple_args = WASM_ARRAY_VEC(quadruple_args_data);
+  CHECK(wasm_func_call(quadruple, &quadruple_args, &add_results) == NULL);
+  CHECK(add_results_data[0].of.i32 == 12);
+  quadruple_args_data[0].of.i32 = -1;
+  check_trap(wasm_func_call(quadruple, &quadruple_args, &add_results), "negative argument");
+  check_trap(wasm_func_call(wasm_extern_as_func(export(&exports, module, "fail")), &empty, &empty), "unreachable");
+
+  // memories
+  CHECK(wasm_memory_size(memory) == 1 && wasm_memory_data_size(memory) == MEMORY_PAGE_SIZE);
+  CHECK(wasm_memory_grow(memory, 1));
+  CHECK(!wasm_memory_grow(memory, 10));
+  CHECK(wasm_memory_size(memory) == 2 && wasm_memory_data_size(memory) == 2 * MEMORY_PAGE_SIZE);
+  wasm_memorytype_t* memory_type = wasm_memory_type(memory);
+  CHECK(wasm_memorytype_limits(memory_type)->min == 2 && wasm_memorytype_limits(memory_type)->max == 4);
+  wasm_memorytype_delete(memory_type);
+
+  // tables
+  wasm_func_t* apply = wasm_extern_as_func(export(&exports, module, "apply"));
+  wasm_val_t apply_args_data[] = {WASM_I32_VAL(4), WASM_I32_VAL(5), WASM_I32_VAL(1)};
+  wasm_val_vec_t apply_args = WASM_ARRAY_VEC(apply_args_data);
+  check_trap(wasm_func_call(apply, &apply_args, &add_results), "uninitialized");
+
+  wasm_ref_t* add_ref = wasm_table_get(table, 0);
+  CHECK(add_ref != NULL && wasm_table_get(table, 1) == NULL);
+  CHECK(wasm_table_set(table, 1, add_ref));
+  CHECK(!wasm_table_set(table, 2, add_ref));
+  CHECK(wasm_func_call(apply, &apply_args, &add_results) == NULL);
+  CHECK(add_results_data[0].of.i32 == 9);
+  CHECK(wasm_table_grow(table, 1, add_ref) && wasm_table_size(table) == 3);
+  wasm_ref_t* grown = wasm_table_get(table, 2);
+  CHECK(wasm_ref_same(grown, add_ref));
+  wasm_ref_delete(grown);
+  wasm_ref_delete(add_ref);
+
+  // clean up, the host function environment is finalized when the store is deleted
+  wasm_extern_vec_delete(&exports);
+  wasm_instance_delete(instance);
+  wasm_func_delete(log_func);
+  wasm_func_delete(double_func);
+  wasm_global_delete(counter);
+  wasm_table_delete(table);
+  wasm_module_delete(module);
+  CHECK(!finalized);
+  wasm_store_delete(store);
+  wasm_engine_delete(engine);
+  CHECK(finalized);
+
+  printf("ok\n");
+  return 0;
+}
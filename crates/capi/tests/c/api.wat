@@ -0,0 +1,24 @@
+(module
+  (import "host" "log" (func $log (param i32 i32)))
+  (import "host" "double" (func $double (param i32) (result i32)))
+  (import "host" "counter" (global $counter (mut i32)))
+  (import "host" "table" (table 2 funcref))
+
+  (memory (export "memory") 1 4)
+  (data (i32.const 16) "hello")
+  (elem (i32.const 0) $add)
+
+  (func $add (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1)))
+
+  (func (export "greet")
+    (call $log (i32.const 16) (i32.const 5))
+    (global.set $counter (i32.add (global.get $counter) (i32.const 1))))
+
+  (func (export "quadruple") (param i32) (result i32)
+    (call $double (call $double (local.get 0))))
+
+  (func (export "apply") (param i32 i32 i32) (result i32)
+    (call_indirect (param i32 i32) (result i32) (local.get 0) (local.get 1) (local.get 2)))
+
+  (func (export "fail") unreachable))
@@ -0,0 +1,56 @@
+//! Compiles the C programs in `tests/c` against the shared library and runs them
+#![cfg(unix)]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn out_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("capi")
+}
+
+// the shared library is built next to the test binary
+fn library_dir() -> PathBuf {
+    std::env::current_exe().unwrap().parent().unwrap().to_path_buf()
+}
+
+fn run_c_test(name: &str) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let out = out_dir();
+    std::fs::create_dir_all(&out).unwrap();
+
+    let wasm = wat::parse_file(root.join(format!("tests/c/{name}.wat"))).unwrap();
+    let wasm_path = out.join(format!("{name}.wasm"));
+    std::fs::write(&wasm_path, wasm).unwrap();
+
+    let cc = std::env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let exe = out.join(name);
+    let lib = library_dir();
+    let status = Command::new(&cc)
+        .arg(root.join(format!("tests/c/{name}.c")))
+        .arg("-I")
+        .arg(root.join("include"))
+        .args(["-Wall", "-Werror", "-Wno-unused-parameter", "-o"])
+        .arg(&exe)
+        .arg("-L")
+        .arg(&lib)
+        .arg("-ltinywasm_capi")
+        .status()
+        .unwrap_or_else(|err| panic!("failed to run the C compiler `{cc}`: {err}"));
+    assert!(status.success(), "failed to compile {name}.c");
+
+    // the search path set by cargo can contain an outdated copy of the library in the target directory
+    let output = Command::new(&exe)
+        .arg(&wasm_path)
+        .env("LD_LIBRARY_PATH", &lib)
+        .env("DYLD_LIBRARY_PATH", &lib)
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(output.status.success(), "{name} failed: {stderr}");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\n");
+}
+
+#[test]
+fn c_api() {
+    run_c_test("api");
+}
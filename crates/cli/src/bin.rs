@@ -1,3 +1,4 @@
+use std::path::Path;
 use std::str::FromStr;
 
 use argh::FromArgs;
@@ -7,6 +8,7 @@ use log::{debug, info};
 use tinywasm::{types::WasmValue, Module};
 
 use crate::args::to_wasm_args;
+use crate::util::format_extern_type;
 mod args;
 mod util;
 
@@ -28,6 +30,7 @@ struct TinyWasmCli {
 #[argh(subcommand)]
 enum TinyWasmSubcommand {
     Run(Run),
+    Inspect(Inspect),
 }
 
 enum Engine {
@@ -49,7 +52,7 @@ impl FromStr for Engine {
 /// run a wasm file
 #[argh(subcommand, name = "run")]
 struct Run {
-    /// wasm file to run
+    /// wasm module or component to run
     #[argh(positional)]
     wasm_file: String,
 
@@ -64,6 +67,27 @@ struct Run {
     /// engine to use
     #[argh(option, short = 'e', default = "Engine::Main")]
     engine: Engine,
+
+    /// give a WASI program access to a host directory, as `host` or `host::guest`
+    #[argh(option)]
+    dir: Vec<String>,
+
+    /// set an environment variable for a WASI program, as `KEY=VALUE`
+    #[argh(option)]
+    env: Vec<String>,
+
+    /// arguments passed to a WASI program
+    #[argh(positional)]
+    wasi_args: Vec<String>,
+}
+
+#[derive(FromArgs)]
+/// list the imports and exports of a wasm file
+#[argh(subcommand, name = "inspect")]
+struct Inspect {
+    /// wasm file to inspect
+    #[argh(positional)]
+    wasm_file: String,
 }
 
 fn main() -> Result<()> {
@@ -81,26 +105,60 @@ fn main() -> Result<()> {
     let cwd = std::env::current_dir()?;
 
     match args.nested {
-        TinyWasmSubcommand::Run(Run { wasm_file, engine, args, func }) => {
-            debug!("args: {:?}", args);
-
-            let path = cwd.join(wasm_file.clone());
-            let module = match wasm_file.ends_with(".wat") {
-                #[cfg(feature = "wat")]
-                true => {
-                    let wat = std::fs::read_to_string(path)?;
-                    let wasm = wat::wat2wasm(&wat);
-                    tinywasm::Module::parse_bytes(&wasm)?
-                }
-                #[cfg(not(feature = "wat"))]
-                true => return Err(eyre::eyre!("wat support is not enabled in this build")),
-                false => tinywasm::Module::parse_file(path)?,
-            };
-
-            match engine {
-                Engine::Main => run(module, func, &to_wasm_args(args)),
+        TinyWasmSubcommand::Run(run_args) => {
+            debug!("args: {:?}", run_args.args);
+
+            let wasm = load_wasm(&cwd.join(&run_args.wasm_file))?;
+            if is_component(&wasm) {
+                #[cfg(feature = "preview2")]
+                return run_component(&wasm, run_args);
+                #[cfg(not(feature = "preview2"))]
+                return Err(eyre::eyre!("component support is not enabled in this build"));
+            }
+
+            let module = Module::parse_bytes(&wasm)?;
+            let is_wasi = module.imports().any(|import| import.module == "wasi_snapshot_preview1");
+
+            match run_args.engine {
+                #[cfg(feature = "wasi")]
+                Engine::Main if is_wasi => run_wasi(module, run_args),
+                #[cfg(not(feature = "wasi"))]
+                Engine::Main if is_wasi => Err(eyre::eyre!("wasi support is not enabled in this build")),
+                Engine::Main => run(module, run_args.func, &to_wasm_args(run_args.args)),
             }
         }
+        TinyWasmSubcommand::Inspect(Inspect { wasm_file }) => {
+            let module = Module::parse_bytes(&load_wasm(&cwd.join(&wasm_file))?)?;
+            inspect(&module);
+            Ok(())
+        }
+    }
+}
+
+fn load_wasm(path: &Path) -> Result<Vec<u8>> {
+    Ok(match path.extension().is_some_and(|ext| ext == "wat") {
+        #[cfg(feature = "wat")]
+        true => wat::wat2wasm(&std::fs::read_to_string(path)?),
+        #[cfg(not(feature = "wat"))]
+        true => return Err(eyre::eyre!("wat support is not enabled in this build")),
+        false => std::fs::read(path)?,
+    })
+}
+
+// modules and components share the magic number, but not the version and layer
+fn is_component(wasm: &[u8]) -> bool {
+    wasm.get(4..8).is_some_and(|version| version != [1, 0, 0, 0])
+}
+
+fn inspect(module: &Module) {
+    println!("imports:");
+    for import in module.imports() {
+        println!("  {}.{}: {}", import.module, import.name, format_extern_type(&import.ty));
+    }
+
+    println!("exports:");
+    for export in module.exports() {
+        println!("  {}: {}", export.name, format_extern_type(&export.ty));
     }
 }
 
@@ -116,3 +174,57 @@ fn run(module: Module, func: Option<String>, args: &[WasmValue]) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(feature = "wasi")]
+fn wasi_ctx(args: &Run) -> Result<tinywasm_wasi::WasiCtx> {
+    let mut wasi = tinywasm_wasi::WasiCtx::new();
+    wasi.inherit_stdio().arg(&args.wasm_file).args(&args.wasi_args);
+
+    for var in &args.env {
+        let (key, value) = var.split_once('=').ok_or_else(|| eyre::eyre!("invalid environment variable: {var}"))?;
+        wasi.env(key, value);
+    }
+
+    for dir in &args.dir {
+        let (host, guest) = dir.split_once("::").unwrap_or((dir, dir));
+        wasi.preopen_dir(host, guest)?;
+    }
+    Ok(wasi)
+}
+
+#[cfg(feature = "wasi")]
+fn run_wasi(module: Module, args: Run) -> Result<()> {
+    use tinywasm::{Imports, ModuleInstance};
+
+    let wasi = wasi_ctx(&args)?;
+    let mut store = tinywasm::Store::default();
+    let Some(func) = args.func else {
+        let code = wasi.run(&mut store, module, None)?;
+        std::process::exit(code);
+    };
+
+    let mut imports = Imports::new();
+    wasi.add_to_imports(&mut imports)?;
+    let instance = ModuleInstance::instantiate(&mut store, module, Some(imports))?;
+    let func = instance.exported_func_untyped(&store, &func)?;
+    match func.call(&mut store, &to_wasm_args(args.args)) {
+        Ok(res) => info!("{res:?}"),
+        Err(err) => std::process::exit(wasi.exit_code().ok_or(err)?),
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "preview2")]
+fn run_component(wasm: &[u8], args: Run) -> Result<()> {
+    use tinywasm::component::Component;
+
+    if args.func.is_some() {
+        return Err(eyre::eyre!("components are run through their `wasi:cli/run` export"));
+    }
+
+    let component = Component::parse_bytes(wasm)?;
+    let mut store = tinywasm::Store::default();
+    let code = wasi_ctx(&args)?.run_component(&mut store, &component, None)?;
+    std::process::exit(code);
+}
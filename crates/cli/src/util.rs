@@ -1 +1,45 @@
+use tinywasm::types::{ExternType, MemoryArch, ValType};
 
+pub fn format_val_type(ty: ValType) -> &'static str {
+    match ty {
+        ValType::I32 => "i32",
+        ValType::I64 => "i64",
+        ValType::F32 => "f32",
+        ValType::F64 => "f64",
+        ValType::V128 => "v128",
+        ValType::RefFunc => "funcref",
+        ValType::RefExtern => "externref",
+    }
+}
+
+fn format_limits(min: u64, max: Option<u64>) -> String {
+    match max {
+        Some(max) => format!("{min}..{max}"),
+        None => format!("{min}.."),
+    }
+}
+
+pub fn format_extern_type(ty: &ExternType) -> String {
+    match ty {
+        ExternType::Func(ty) => {
+            let params: Vec<_> = ty.params.iter().map(|t| format_val_type(*t)).collect();
+            let results: Vec<_> = ty.results.iter().map(|t| format_val_type(*t)).collect();
+            format!("func ({}) -> ({})", params.join(", "), results.join(", "))
+        }
+        ExternType::Table(ty) => format!(
+            "table {} {}",
+            format_val_type(ty.element_type),
+            format_limits(ty.size_initial.into(), ty.size_max.map(Into::into))
+        ),
+        ExternType::Memory(ty) => {
+            let arch = match ty.arch {
+                MemoryArch::I32 => "",
+                MemoryArch::I64 => " i64",
+            };
+            format!("memory{arch} {}", format_limits(ty.page_count_initial, ty.page_count_max))
+        }
+        ExternType::Global(ty) => {
+            format!("global {}{}", if ty.mutable { "mut " } else { "" }, format_val_type(ty.ty))
+        }
+    }
+}
@@ -0,0 +1,618 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::{Expr, ExprLit, Ident, Lit, MetaNameValue, Token};
+
+use crate::wit::{self, Func, Interface, Package, Type, TypeDef, TypeDefKind, Use, World, WorldItem};
+
+struct Options {
+    inline: Option<String>,
+    path: Option<String>,
+    world: Option<String>,
+}
+
+fn parse_options(input: Punctuated<MetaNameValue, Token![,]>) -> syn::Result<Options> {
+    let mut options = Options { inline: None, path: None, world: None };
+    for option in input {
+        let Expr::Lit(ExprLit { lit: Lit::Str(value), .. }) = &option.value else {
+            return Err(syn::Error::new_spanned(&option.value, "expected a string literal"));
+        };
+        let value = Some(value.value());
+        match option.path.get_ident().map(Ident::to_string).as_deref() {
+            Some("inline") => options.inline = value,
+            Some("path") => options.path = value,
+            Some("world") => options.world = value,
+            _ => return Err(syn::Error::new_spanned(&option.path, "expected `inline`, `path` or `world`")),
+        }
+    }
+    Ok(options)
+}
+
+// all `.wit` files of a directory and its subdirectories, in a stable order
+fn wit_files(path: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+    let mut entries = std::fs::read_dir(path)?.map(|e| e.map(|e| e.path())).collect::<std::io::Result<Vec<_>>>()?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "wit") {
+            wit_files(&entry, files)?;
+        }
+    }
+    Ok(())
+}
+
+fn snake(name: &str) -> Ident {
+    let name = name.replace('-', "_").to_lowercase();
+    match name.as_str() {
+        "self" | "super" | "crate" | "Self" => format_ident!("{name}_"),
+        _ if syn::parse_str::<Ident>(&name).is_err() => Ident::new_raw(&name, Span::call_site()),
+        _ => format_ident!("{name}"),
+    }
+}
+
+fn camel(name: &str) -> Ident {
+    let mut ident = String::new();
+    for word in name.split('-') {
+        let mut chars = word.chars();
+        ident.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+        ident.extend(chars.map(|c| c.to_ascii_lowercase()));
+    }
+    format_ident!("{ident}")
+}
+
+fn doc_attrs(docs: &[String]) -> TokenStream {
+    let docs = docs.iter().map(|doc| format!(" {doc}"));
+    quote! { #(#[doc = #docs])* }
+}
+
+/// An interface with the package it belongs to
+struct Iface<'a> {
+    package: &'a Package,
+    interface: &'a Interface,
+    // the name it's imported or exported as
+    qualified: String,
+    module: Ident,
+    imported: bool,
+    exported: bool,
+}
+
+struct Generator<'a> {
+    packages: &'a [Package],
+    ifaces: Vec<Iface<'a>>,
+}
+
+impl<'a> Generator<'a> {
+    fn find_interface(&self, package: &'a Package, path: &str) -> syn::Result<(&'a Package, &'a Interface, String)> {
+        let qualified = if path.contains(':') { path.to_string() } else { package.qualified_name(path) };
+        for package in self.packages {
+            for interface in &package.interfaces {
+                if package.qualified_name(&interface.name) == qualified {
+                    return Ok((package, interface, qualified));
+                }
+            }
+        }
+        Err(error(format!("interface `{path}` not found")))
+    }
+
+    // add an interface and the interfaces its types are used from
+    fn add(
+        &mut self,
+        package: &'a Package,
+        interface: &'a Interface,
+        qualified: String,
+        imported: bool,
+        exported: bool,
+    ) -> syn::Result<()> {
+        if let Some(iface) = self.ifaces.iter_mut().find(|i| i.qualified == qualified) {
+            iface.imported |= imported;
+            iface.exported |= exported;
+            return Ok(());
+        }
+        let module = snake(&interface.name);
+        if self.ifaces.iter().any(|i| i.module == module) {
+            return Err(error(format!("multiple interfaces are named `{}`", interface.name)));
+        }
+        self.ifaces.push(Iface { package, interface, qualified, module, imported, exported });
+        self.add_uses(package, &interface.uses)
+    }
+
+    // interfaces that only provide types
+    fn add_uses(&mut self, package: &'a Package, uses: &'a [Use]) -> syn::Result<()> {
+        for use_ in uses {
+            let (package, interface, qualified) = self.find_interface(package, &use_.path)?;
+            self.add(package, interface, qualified, false, false)?;
+        }
+        Ok(())
+    }
+
+    fn module_of(&self, package: &Package, path: &str) -> syn::Result<&Ident> {
+        let (_, _, qualified) = self.find_interface(package, path)?;
+        Ok(&self.ifaces.iter().find(|i| i.qualified == qualified).expect("used interfaces are added").module)
+    }
+
+    fn uses(&self, package: &Package, uses: &[Use], prefix: &TokenStream) -> syn::Result<TokenStream> {
+        let mut items = Vec::new();
+        for use_ in uses {
+            let module = self.module_of(package, &use_.path)?;
+            for (name, alias) in &use_.names {
+                let (name, alias) = (camel(name), camel(alias));
+                items.push(quote! { pub use #prefix #module::#name as #alias; });
+            }
+        }
+        Ok(quote! { #(#items)* })
+    }
+
+    fn interface(&self, iface: &Iface<'_>) -> syn::Result<TokenStream> {
+        let Iface { package, interface, qualified, module, .. } = iface;
+        let uses = self.uses(package, &interface.uses, &quote!(super::))?;
+        let types = interface.types.iter().map(type_def);
+
+        let host = iface.imported.then(|| {
+            let methods = interface.funcs.iter().map(|func| {
+                let sig = signature(func, false);
+                let docs = doc_attrs(&func.docs);
+                quote! { #docs fn #sig; }
+            });
+            let defines = interface.funcs.iter().map(|func| define_func(&format!("{qualified}#{}", func.name), func));
+            let docs = format!(" The host implementation of `{qualified}`");
+            quote! {
+                #[doc = #docs]
+                pub trait Host {
+                    #(#methods)*
+                }
+
+                /// Add the functions of the interface to `imports`, implemented by `host`
+                pub fn add_to_imports<T: Host + 'static>(
+                    imports: &mut ::tinywasm::component::ComponentImports,
+                    host: ::tinywasm::__private::Rc<T>,
+                ) -> ::tinywasm::Result<()> {
+                    #(#defines)*
+                    Ok(())
+                }
+            }
+        });
+
+        let exports = iface.exported.then(|| {
+            let methods = interface.funcs.iter().map(|func| call_func(&format!("{qualified}#{}", func.name), func));
+            let docs = format!(" The functions of `{qualified}` exported by a component");
+            quote! {
+                #[doc = #docs]
+                #[derive(Debug, Clone)]
+                pub struct Exports {
+                    instance: ::tinywasm::component::ComponentInstance,
+                }
+
+                impl Exports {
+                    /// Wrap an instance exporting the interface
+                    pub fn new(instance: ::tinywasm::component::ComponentInstance) -> Self {
+                        Self { instance }
+                    }
+
+                    #(#methods)*
+                }
+            }
+        });
+
+        let docs = doc_attrs(&interface.docs);
+        Ok(quote! {
+            #docs
+            #[allow(dead_code)]
+            pub mod #module {
+                #uses
+                #(#types)*
+                #host
+                #exports
+            }
+        })
+    }
+
+    fn world(&self, package: &'a Package, world: &World) -> syn::Result<TokenStream> {
+        let uses = self.uses(package, &world.uses, &quote!())?;
+        let name = camel(&world.name);
+        let imports_trait = format_ident!("{name}Imports");
+        let func_imports: Vec<&Func> = world
+            .imports
+            .iter()
+            .filter_map(|item| match item {
+                WorldItem::Func(func) => Some(func),
+                _ => None,
+            })
+            .collect();
+
+        let mut bounds = Vec::new();
+        let mut add_imports = Vec::new();
+        for iface in self.ifaces.iter().filter(|i| i.imported) {
+            let module = &iface.module;
+            bounds.push(quote! { #module::Host });
+            add_imports.push(quote! { #module::add_to_imports(imports, host.clone())?; });
+        }
+
+        let imports_trait = (!func_imports.is_empty()).then(|| {
+            bounds.push(quote! { #imports_trait });
+            add_imports.extend(func_imports.iter().map(|func| define_func(&func.name, func)));
+            let methods = func_imports.iter().map(|func| {
+                let sig = signature(func, false);
+                let docs = doc_attrs(&func.docs);
+                quote! { #docs fn #sig; }
+            });
+            let docs = format!(" The host implementation of the functions imported by `{}`", world.name);
+            quote! {
+                #[doc = #docs]
+                pub trait #imports_trait {
+                    #(#methods)*
+                }
+            }
+        });
+
+        let mut exports = Vec::new();
+        for item in &world.exports {
+            exports.push(match item {
+                WorldItem::Func(func) => call_func(&func.name, func),
+                WorldItem::Interface(path) => {
+                    let module = self.module_of(package, path)?;
+                    let docs = format!(" The exports of `{}`", self.find_interface(package, path)?.2);
+                    quote! {
+                        #[doc = #docs]
+                        pub fn #module(&self) -> #module::Exports {
+                            #module::Exports::new(self.instance.clone())
+                        }
+                    }
+                }
+                WorldItem::InlineInterface(interface) => {
+                    let module = snake(&interface.name);
+                    let docs = format!(" The exports of `{}`", interface.name);
+                    quote! {
+                        #[doc = #docs]
+                        pub fn #module(&self) -> #module::Exports {
+                            #module::Exports::new(self.instance.clone())
+                        }
+                    }
+                }
+            });
+        }
+
+        let docs = doc_attrs(&world.docs);
+        Ok(quote! {
+            #uses
+            #imports_trait
+
+            #docs
+            #[derive(Debug, Clone)]
+            pub struct #name {
+                instance: ::tinywasm::component::ComponentInstance,
+            }
+
+            #[allow(dead_code)]
+            impl #name {
+                /// Add the imports of the world to `imports`, implemented by `host`
+                pub fn add_to_imports<T: #(#bounds +)* 'static>(
+                    imports: &mut ::tinywasm::component::ComponentImports,
+                    host: ::tinywasm::__private::Rc<T>,
+                ) -> ::tinywasm::Result<()> {
+                    #(#add_imports)*
+                    Ok(())
+                }
+
+                /// Instantiate a component implementing the world
+                pub fn instantiate(
+                    store: &mut ::tinywasm::Store,
+                    component: &::tinywasm::component::Component,
+                    imports: &::tinywasm::component::ComponentImports,
+                ) -> ::tinywasm::Result<Self> {
+                    Ok(Self::new(component.instantiate(store, imports)?))
+                }
+
+                /// Wrap an instance of a component implementing the world
+                pub fn new(instance: ::tinywasm::component::ComponentInstance) -> Self {
+                    Self { instance }
+                }
+
+                /// Get the underlying instance
+                pub fn instance(&self) -> &::tinywasm::component::ComponentInstance {
+                    &self.instance
+                }
+
+                #(#exports)*
+            }
+        })
+    }
+}
+
+fn error(msg: impl std::fmt::Display) -> syn::Error {
+    syn::Error::new(Span::call_site(), msg)
+}
+
+fn rust_type(ty: &Type) -> TokenStream {
+    match ty {
+        Type::Bool => quote!(bool),
+        Type::S8 => quote!(i8),
+        Type::U8 => quote!(u8),
+        Type::S16 => quote!(i16),
+        Type::U16 => quote!(u16),
+        Type::S32 => quote!(i32),
+        Type::U32 => quote!(u32),
+        Type::S64 => quote!(i64),
+        Type::U64 => quote!(u64),
+        Type::F32 => quote!(f32),
+        Type::F64 => quote!(f64),
+        Type::Char => quote!(char),
+        Type::String => quote!(::tinywasm::__private::String),
+        Type::List(ty) => {
+            let ty = rust_type(ty);
+            quote!(::tinywasm::__private::Vec<#ty>)
+        }
+        Type::Option(ty) => {
+            let ty = rust_type(ty);
+            quote!(::core::option::Option<#ty>)
+        }
+        Type::Result(ok, err) => {
+            let ok = ok.as_deref().map_or(quote!(()), rust_type);
+            let err = err.as_deref().map_or(quote!(()), rust_type);
+            quote!(::core::result::Result<#ok, #err>)
+        }
+        Type::Tuple(types) => {
+            let types = types.iter().map(rust_type);
+            quote!((#(#types,)*))
+        }
+        Type::Named(name) => camel(name).into_token_stream(),
+    }
+}
+
+// `name(&self, params) -> Result<result>`, with a store parameter for calling exports
+fn signature(func: &Func, store: bool) -> TokenStream {
+    let name = snake(&func.name);
+    let store = store.then(|| quote! { store: &mut ::tinywasm::Store, });
+    let params = func.params.iter().map(|(name, ty)| {
+        let (name, ty) = (snake(name), rust_type(ty));
+        quote! { #name: #ty }
+    });
+    let result = func.result.as_ref().map_or(quote!(()), rust_type);
+    quote! { #name(&self, #store #(#params),*) -> ::tinywasm::Result<#result> }
+}
+
+fn tuples(func: &Func) -> (Vec<Ident>, TokenStream, TokenStream) {
+    let names: Vec<_> = func.params.iter().map(|(name, _)| snake(name)).collect();
+    let types = func.params.iter().map(|(_, ty)| rust_type(ty));
+    let results = func.result.iter().map(rust_type);
+    (names, quote! { (#(#types,)*) }, quote! { (#(#results,)*) })
+}
+
+// define a host function calling the method of `host`
+fn define_func(path: &str, func: &Func) -> TokenStream {
+    let method = snake(&func.name);
+    let (names, params, results) = tuples(func);
+    let wrap = match func.result {
+        Some(_) => quote! { .map(|result| (result,)) },
+        None => quote!(),
+    };
+    quote! {
+        {
+            let host = host.clone();
+            imports.define_typed_func::<#params, #results>(#path, move |_ctx, (#(#names,)*)| host.#method(#(#names),*)#wrap)?;
+        }
+    }
+}
+
+// a method calling an exported function
+fn call_func(path: &str, func: &Func) -> TokenStream {
+    let (names, params, results) = tuples(func);
+    let sig = signature(func, true);
+    let docs = doc_attrs(&func.docs);
+    let unwrap = match func.result {
+        Some(_) => quote! { .map(|(result,)| result) },
+        None => quote!(),
+    };
+
+    quote! {
+        #docs
+        pub fn #sig {
+            let func = self.instance.exported_func::<#params, #results>(#path)?;
+            func.call(store, (#(#names,)*))#unwrap
+        }
+    }
+}
+
+fn type_def(def: &TypeDef) -> TokenStream {
+    let name = camel(&def.name);
+    let wit_name = &def.name;
+    let docs = doc_attrs(&def.docs);
+    let val = quote!(::tinywasm::component::Val);
+    let string = |s: &str| quote! { ::tinywasm::__private::String::from(#s) };
+    let value = quote!(::tinywasm::component::ComponentValue);
+
+    let (item, into_val, from_val) = match &def.kind {
+        TypeDefKind::Alias(ty) => {
+            let ty = rust_type(ty);
+            return quote! { #docs pub type #name = #ty; };
+        }
+        TypeDefKind::Record(fields) => {
+            let idents: Vec<_> = fields.iter().map(|f| snake(&f.name)).collect();
+            let names: Vec<_> = fields.iter().map(|f| f.name.as_str()).collect();
+            let field_docs = fields.iter().map(|f| doc_attrs(&f.docs));
+            let types = fields.iter().map(|f| rust_type(f.ty.as_ref().expect("record fields have types")));
+            let strings = names.iter().map(|n| string(n));
+            (
+                quote! {
+                    #[derive(Debug, Clone, PartialEq)]
+                    pub struct #name { #(#field_docs pub #idents: #types,)* }
+                },
+                quote! { #val::Record(::tinywasm::__private::Vec::from([#((#strings, #value::into_val(self.#idents)),)*])) },
+                quote! {
+                    #[allow(unused_mut)]
+                    let mut fields = ::tinywasm::__private::record_fields(val, &[#(#names),*])?;
+                    Ok(Self { #(#idents: #value::from_val(::tinywasm::__private::next_val(&mut fields)?)?,)* })
+                },
+            )
+        }
+        TypeDefKind::Variant(cases) => {
+            let variants = cases.iter().map(|case| {
+                let (ident, docs) = (camel(&case.name), doc_attrs(&case.docs));
+                match &case.ty {
+                    Some(ty) => {
+                        let ty = rust_type(ty);
+                        quote! { #docs #ident(#ty) }
+                    }
+                    None => quote! { #docs #ident },
+                }
+            });
+            let into = cases.iter().map(|case| {
+                let (ident, name) = (camel(&case.name), string(&case.name));
+                match case.ty {
+                    Some(_) => quote! { Self::#ident(value) => #val::Variant(#name, #value::into_payload(value)) },
+                    None => quote! { Self::#ident => #val::Variant(#name, None) },
+                }
+            });
+            let from = cases.iter().map(|case| {
+                let (ident, name) = (camel(&case.name), &case.name);
+                match case.ty {
+                    Some(_) => quote! { #name => Self::#ident(#value::from_payload(payload)?) },
+                    None => quote! { #name => Self::#ident },
+                }
+            });
+            (
+                quote! {
+                    #[derive(Debug, Clone, PartialEq)]
+                    pub enum #name { #(#variants,)* }
+                },
+                quote! { match self { #(#into,)* } },
+                quote! {
+                    #[allow(unused_variables)]
+                    let (case, payload) = ::tinywasm::__private::variant_case(val)?;
+                    Ok(match case.as_str() {
+                        #(#from,)*
+                        _ => return Err(::tinywasm::__private::unknown_case(#wit_name, &case)),
+                    })
+                },
+            )
+        }
+        TypeDefKind::Enum(cases) => {
+            let idents: Vec<_> = cases.iter().map(|case| camel(&case.name)).collect();
+            let case_docs = cases.iter().map(|case| doc_attrs(&case.docs));
+            let names: Vec<_> = cases.iter().map(|case| case.name.as_str()).collect();
+            let strings = names.iter().map(|n| string(n));
+            (
+                quote! {
+                    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+                    pub enum #name { #(#case_docs #idents,)* }
+                },
+                quote! { #val::Enum(match self { #(Self::#idents => #strings,)* }) },
+                quote! {
+                    let case = ::tinywasm::__private::enum_case(val)?;
+                    Ok(match case.as_str() {
+                        #(#names => Self::#idents,)*
+                        _ => return Err(::tinywasm::__private::unknown_case(#wit_name, &case)),
+                    })
+                },
+            )
+        }
+        TypeDefKind::Flags(flags) => {
+            let idents: Vec<_> = flags.iter().map(|flag| snake(&flag.name)).collect();
+            let flag_docs = flags.iter().map(|flag| doc_attrs(&flag.docs));
+            let names: Vec<_> = flags.iter().map(|flag| flag.name.as_str()).collect();
+            let strings = names.iter().map(|n| string(n));
+            (
+                quote! {
+                    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+                    pub struct #name { #(#flag_docs pub #idents: bool,)* }
+                },
+                quote! {
+                    #[allow(unused_mut)]
+                    let mut flags = ::tinywasm::__private::Vec::new();
+                    #(if self.#idents { flags.push(#strings); })*
+                    #val::Flags(flags)
+                },
+                quote! {
+                    #[allow(unused_variables)]
+                    let flags = ::tinywasm::__private::flags(val)?;
+                    Ok(Self { #(#idents: flags.iter().any(|flag| flag == #names),)* })
+                },
+            )
+        }
+    };
+
+    quote! {
+        #docs
+        #item
+
+        impl #value for #name {
+            fn into_val(self) -> #val {
+                #into_val
+            }
+
+            fn from_val(val: #val) -> ::tinywasm::Result<Self> {
+                #from_val
+            }
+        }
+    }
+}
+
+pub(crate) fn expand(input: Punctuated<MetaNameValue, Token![,]>) -> syn::Result<TokenStream> {
+    let options = parse_options(input)?;
+    let mut sources = Vec::new();
+    let mut tracked = Vec::new();
+    match (&options.inline, &options.path) {
+        (Some(inline), None) => sources.push(("inline WIT".to_string(), inline.clone())),
+        (None, Some(path)) => {
+            let root = std::env::var("CARGO_MANIFEST_DIR").map(PathBuf::from).unwrap_or_default();
+            let mut files = Vec::new();
+            wit_files(&root.join(path), &mut files).map_err(|e| error(format!("failed to read `{path}`: {e}")))?;
+            for file in files {
+                let src = std::fs::read_to_string(&file).map_err(|e| error(format!("failed to read {file:?}: {e}")))?;
+                let file = file.display().to_string();
+                tracked.push(file.clone());
+                sources.push((file, src));
+            }
+        }
+        _ => return Err(error("expected either `path` or `inline`")),
+    }
+
+    let packages = sources
+        .iter()
+        .map(|(name, src)| wit::parse(src).map_err(|e| error(format!("{name}: {e}"))))
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let worlds: Vec<_> = packages.iter().flat_map(|p| p.worlds.iter().map(move |w| (p, w))).collect();
+    let (package, world) = match &options.world {
+        Some(name) => {
+            worlds.iter().find(|(_, w)| &w.name == name).ok_or_else(|| error(format!("world `{name}` not found")))?
+        }
+        None if worlds.len() == 1 => &worlds[0],
+        None => return Err(error("the WIT files contain multiple worlds, select one with `world`")),
+    };
+
+    let mut generator = Generator { packages: &packages, ifaces: Vec::new() };
+    for (items, export) in [(&world.imports, false), (&world.exports, true)] {
+        for item in items {
+            match item {
+                WorldItem::Interface(path) => {
+                    let (package, interface, qualified) = generator.find_interface(package, path)?;
+                    generator.add(package, interface, qualified, !export, export)?;
+                }
+                WorldItem::InlineInterface(interface) => {
+                    generator.add(package, interface, interface.name.clone(), !export, export)?;
+                }
+                WorldItem::Func(_) => {}
+            }
+        }
+    }
+    generator.add_uses(package, &world.uses)?;
+
+    let mut interfaces = BTreeMap::new();
+    for iface in &generator.ifaces {
+        interfaces.insert(iface.module.to_string(), generator.interface(iface)?);
+    }
+    let interfaces = interfaces.values();
+    let world = generator.world(package, world)?;
+
+    Ok(quote! {
+        #(const _: &str = include_str!(#tracked);)*
+        #(#interfaces)*
+        #world
+    })
+}
@@ -0,0 +1,137 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Data, DeriveInput, Fields, Index, Member, Type};
+
+// the fields of a struct, in declaration order
+fn fields(input: &DeriveInput) -> syn::Result<(Vec<Member>, Vec<&Type>, &Fields)> {
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(&input.ident, "only structs are supported"));
+    };
+
+    let members = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => Member::Named(ident.clone()),
+            None => Member::Unnamed(Index::from(i)),
+        })
+        .collect();
+
+    Ok((members, data.fields.iter().map(|f| &f.ty).collect(), &data.fields))
+}
+
+pub(crate) fn into_wasm_value_tuple(input: DeriveInput) -> syn::Result<TokenStream> {
+    let (members, _, _) = fields(&input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::tinywasm::IntoWasmValueTuple for #name #ty_generics #where_clause {
+            #[inline]
+            fn into_wasm_value_tuple(self) -> ::tinywasm::__private::Vec<::tinywasm::types::WasmValue> {
+                ::tinywasm::__private::Vec::from([#(self.#members.into()),*])
+            }
+        }
+    })
+}
+
+pub(crate) fn from_wasm_value_tuple(input: DeriveInput) -> syn::Result<TokenStream> {
+    let (members, _, fields) = fields(&input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let next = quote! { ::tinywasm::__private::next_value(&mut values)? };
+    let construct = match fields {
+        Fields::Named(_) => quote! { Self { #(#members: #next),* } },
+        Fields::Unnamed(_) => {
+            let values = members.iter().map(|_| &next);
+            quote! { Self(#(#values),*) }
+        }
+        Fields::Unit => quote! { Self },
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::tinywasm::FromWasmValueTuple for #name #ty_generics #where_clause {
+            #[inline]
+            fn from_wasm_value_tuple(values: &[::tinywasm::types::WasmValue]) -> ::tinywasm::Result<Self> {
+                #[allow(unused_variables, unused_mut)]
+                let mut values = values.iter();
+                Ok(#construct)
+            }
+        }
+    })
+}
+
+pub(crate) fn val_types_from_tuple(input: DeriveInput) -> syn::Result<TokenStream> {
+    let (_, types, _) = fields(&input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::tinywasm::ValTypesFromTuple for #name #ty_generics #where_clause {
+            #[inline]
+            fn val_types() -> ::tinywasm::__private::Box<[::tinywasm::types::ValType]> {
+                ::tinywasm::__private::Box::new([#(<#types as ::tinywasm::ToValType>::to_val_type()),*])
+            }
+        }
+    })
+}
+
+pub(crate) fn wasm_pod(input: DeriveInput) -> syn::Result<TokenStream> {
+    let (members, types, fields) = fields(&input)?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // fields are laid out like `#[repr(C)]`: each at the next offset aligned to its own alignment
+    let pod = quote! { ::tinywasm::WasmPod };
+    let align_up = quote! { ::tinywasm::__private::align_up };
+    let reads = types.iter().map(|ty| {
+        quote! {{
+            offset = #align_up(offset, <#ty as #pod>::ALIGN);
+            let value = <#ty as #pod>::read_from(&bytes[offset..offset + <#ty as #pod>::SIZE]);
+            offset += <#ty as #pod>::SIZE;
+            value
+        }}
+    });
+    let construct = match fields {
+        Fields::Named(_) => quote! { Self { #(#members: #reads),* } },
+        Fields::Unnamed(_) => quote! { Self(#(#reads),*) },
+        Fields::Unit => quote! { Self },
+    };
+
+    Ok(quote! {
+        impl #impl_generics #pod for #name #ty_generics #where_clause {
+            const ALIGN: usize = {
+                let mut align = 1;
+                #(if <#types as #pod>::ALIGN > align { align = <#types as #pod>::ALIGN; })*
+                align
+            };
+
+            const SIZE: usize = {
+                #[allow(unused_mut)]
+                let mut offset = 0;
+                #(offset = #align_up(offset, <#types as #pod>::ALIGN) + <#types as #pod>::SIZE;)*
+                #align_up(offset, <Self as #pod>::ALIGN)
+            };
+
+            #[inline]
+            fn read_from(bytes: &[u8]) -> Self {
+                #[allow(unused_variables, unused_mut, unused_assignments)]
+                let mut offset = 0;
+                #construct
+            }
+
+            #[inline]
+            fn write_to(&self, bytes: &mut [u8]) {
+                #[allow(unused_variables, unused_mut, unused_assignments)]
+                let mut offset = 0;
+                #(
+                    offset = #align_up(offset, <#types as #pod>::ALIGN);
+                    #pod::write_to(&self.#members, &mut bytes[offset..offset + <#types as #pod>::SIZE]);
+                    offset += <#types as #pod>::SIZE;
+                )*
+            }
+        }
+    })
+}
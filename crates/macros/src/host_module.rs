@@ -0,0 +1,130 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{FnArg, ImplItem, ImplItemFn, ItemImpl, LitStr, ReturnType, Type};
+
+struct HostFunc {
+    name: String,
+    func: ImplItemFn,
+}
+
+// parse and strip the `#[host(...)]` attributes of a function, returns `None` if it is skipped
+fn parse_attrs(func: &mut ImplItemFn) -> syn::Result<Option<String>> {
+    let mut name = func.sig.ident.to_string();
+    let mut skip = false;
+
+    for attr in func.attrs.iter().filter(|attr| attr.path().is_ident("host")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = meta.value()?.parse::<LitStr>()?.value();
+                Ok(())
+            } else if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `name = \"...\"` or `skip`"))
+            }
+        })?;
+    }
+
+    func.attrs.retain(|attr| !attr.path().is_ident("host"));
+    Ok((!skip).then_some(name))
+}
+
+fn last_segment_is(ty: &Type, name: &str) -> bool {
+    match ty {
+        Type::Path(path) => path.path.segments.last().is_some_and(|s| s.ident == name),
+        _ => false,
+    }
+}
+
+fn expand_func(self_ty: &Type, HostFunc { name, func }: &HostFunc) -> syn::Result<TokenStream> {
+    let sig = &func.sig;
+    if let Some(asyncness) = &sig.asyncness {
+        return Err(syn::Error::new_spanned(asyncness, "host functions can not be async"));
+    }
+    if !sig.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&sig.generics, "host functions can not be generic"));
+    }
+
+    let receiver = match sig.receiver() {
+        Some(receiver) if receiver.reference.is_none() || receiver.mutability.is_some() => {
+            return Err(syn::Error::new_spanned(
+                receiver,
+                "host functions can only take `&self`, use interior mutability to modify state",
+            ))
+        }
+        receiver => receiver.is_some(),
+    };
+
+    let mut params = sig
+        .inputs
+        .iter()
+        .filter_map(|arg| match arg {
+            FnArg::Typed(arg) => Some(&*arg.ty),
+            FnArg::Receiver(_) => None,
+        })
+        .peekable();
+
+    let takes_ctx = params.next_if(|ty| last_segment_is(ty, "FuncContext")).is_some();
+    let types: Vec<_> = params.collect();
+    let args: Vec<_> = (0..types.len()).map(|i| format_ident!("arg{i}")).collect();
+    let ctx = match takes_ctx {
+        true => format_ident!("ctx"),
+        false => format_ident!("_ctx"),
+    };
+    let call_args = takes_ctx.then(|| quote!(#ctx)).into_iter().chain(args.iter().map(|arg| quote!(#arg)));
+
+    let ident = &sig.ident;
+    let call = match receiver {
+        true => quote! { this.#ident(#(#call_args),*) },
+        false => quote! { <#self_ty>::#ident(#(#call_args),*) },
+    };
+
+    let body = match &sig.output {
+        ReturnType::Type(_, ty) if last_segment_is(ty, "Result") => call,
+        _ => quote! { Ok(#call) },
+    };
+
+    // function arguments are passed as a tuple, with a trailing comma so single values work too
+    Ok(quote! {
+        (#name, {
+            #[allow(unused_variables)]
+            let this = this.clone();
+            ::tinywasm::Extern::typed_func(
+                move |#ctx: ::tinywasm::FuncContext<'_>, (#(#args,)*): (#(#types,)*)| #body
+            )
+        })
+    })
+}
+
+pub(crate) fn expand(mut item: ItemImpl) -> syn::Result<TokenStream> {
+    if !item.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(&item.generics, "host modules can not be generic"));
+    }
+    if let Some((_, trait_, _)) = &item.trait_ {
+        return Err(syn::Error::new_spanned(trait_, "host modules have to be inherent impl blocks"));
+    }
+
+    let mut funcs = Vec::new();
+    for impl_item in &mut item.items {
+        let ImplItem::Fn(func) = impl_item else { continue };
+        if let Some(name) = parse_attrs(func)? {
+            funcs.push(HostFunc { name, func: func.clone() });
+        }
+    }
+
+    let self_ty = &item.self_ty;
+    let externs = funcs.iter().map(|func| expand_func(self_ty, func)).collect::<syn::Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        #item
+
+        impl ::tinywasm::HostModule for #self_ty {
+            fn into_externs(self) -> ::tinywasm::__private::Vec<(&'static str, ::tinywasm::Extern)> {
+                #[allow(unused_variables)]
+                let this = ::tinywasm::__private::Rc::new(self);
+                ::tinywasm::__private::Vec::from([#(#externs),*])
+            }
+        }
+    })
+}
@@ -0,0 +1,353 @@
+#![warn(missing_debug_implementations, rust_2018_idioms, unreachable_pub)]
+#![forbid(unsafe_code)]
+
+//! Derive and attribute macros for [`tinywasm`](https://docs.rs/tinywasm).
+//!
+//! These are re-exported by `tinywasm` when the `macros` feature is enabled,
+//! and generate code that refers to the `tinywasm` crate.
+
+use proc_macro::TokenStream;
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, DeriveInput, ItemImpl, MetaNameValue, Token};
+
+mod bindgen;
+mod derive;
+mod host_module;
+mod wit;
+
+/// Derive `IntoWasmValueTuple` for a struct
+///
+/// The fields are converted in declaration order, each field has to implement `Into<WasmValue>`.
+///
+/// ```rust
+/// use tinywasm::{FromWasmValueTuple, IntoWasmValueTuple, ValTypesFromTuple};
+///
+/// #[derive(Debug, PartialEq, IntoWasmValueTuple, FromWasmValueTuple, ValTypesFromTuple)]
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// #[derive(Debug, PartialEq, IntoWasmValueTuple, FromWasmValueTuple, ValTypesFromTuple)]
+/// struct Meters(f64);
+///
+/// let values = Point { x: 1, y: 2 }.into_wasm_value_tuple();
+/// assert_eq!(Point::from_wasm_value_tuple(&values).unwrap(), Point { x: 1, y: 2 });
+/// assert_eq!(Meters::val_types().len(), 1);
+/// ```
+#[proc_macro_derive(IntoWasmValueTuple)]
+pub fn derive_into_wasm_value_tuple(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive::into_wasm_value_tuple(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Derive `FromWasmValueTuple` for a struct
+///
+/// The fields are read in declaration order, each field has to implement `TryFrom<WasmValue>`.
+#[proc_macro_derive(FromWasmValueTuple)]
+pub fn derive_from_wasm_value_tuple(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive::from_wasm_value_tuple(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Derive `ValTypesFromTuple` for a struct
+///
+/// Each field has to implement `ToValType`.
+#[proc_macro_derive(ValTypesFromTuple)]
+pub fn derive_val_types_from_tuple(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive::val_types_from_tuple(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Derive `WasmPod` for a struct
+///
+/// The struct is laid out like `#[repr(C)]`: fields are stored in declaration order, each aligned to its
+/// own alignment, and the size is padded to the largest field alignment. Each field has to implement `WasmPod`.
+///
+/// ```rust
+/// use tinywasm::{WasmPod, WasmPtr};
+///
+/// #[derive(Debug, PartialEq, WasmPod)]
+/// struct Iovec {
+///     flag: u8,
+///     buf: WasmPtr<u8>,
+///     len: u32,
+/// }
+///
+/// assert_eq!(Iovec::SIZE, 12);
+/// let mut bytes = [0; 12];
+/// let iovec = Iovec { flag: 1, buf: WasmPtr::new(64), len: 5 };
+/// iovec.write_to(&mut bytes);
+/// assert_eq!(bytes, [1, 0, 0, 0, 64, 0, 0, 0, 5, 0, 0, 0]);
+/// assert_eq!(Iovec::read_from(&bytes), iovec);
+/// ```
+#[proc_macro_derive(WasmPod)]
+pub fn derive_wasm_pod(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive::wasm_pod(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Implement `HostModule` for a type using the functions of an `impl` block
+///
+/// Every function in the block becomes a typed host function named after the function.
+/// Functions can take `&self`, an optional `FuncContext<'_>` as their first parameter, and any number of
+/// parameters implementing `TryFrom<WasmValue>` and `ToValType`. They can return nothing, a value,
+/// a tuple of values or a `tinywasm::Result` of these.
+///
+/// Use `#[host(name = "...")]` on a function to change its import name, and `#[host(skip)]` to leave it out.
+/// Since host functions are shared, `&mut self` is not supported; use interior mutability instead.
+///
+/// ```rust
+/// use std::cell::Cell;
+/// use tinywasm::{host_module, FuncContext, Imports, Module, Store};
+///
+/// #[derive(Default)]
+/// struct Env {
+///     calls: Cell<i32>,
+/// }
+///
+/// #[host_module]
+/// impl Env {
+///     fn add(a: i32, b: i32) -> i32 {
+///         a + b
+///     }
+///
+///     #[host(name = "count")]
+///     fn count_call(&self, _ctx: FuncContext<'_>) -> tinywasm::Result<i32> {
+///         self.calls.set(self.calls.get() + 1);
+///         Ok(self.calls.get())
+///     }
+///
+///     #[host(skip)]
+///     fn helper(&self) {}
+/// }
+///
+/// let wasm = wat::parse_str(r#"
+///     (module
+///         (import "env" "add" (func $add (param i32 i32) (result i32)))
+///         (import "env" "count" (func $count (result i32)))
+///         (func (export "run") (result i32)
+///             (drop (call $count))
+///             (call $add (call $count) (i32.const 40))))
+/// "#).unwrap();
+///
+/// let mut imports = Imports::new();
+/// imports.define_host_module("env", Env::default())?;
+///
+/// let mut store = Store::default();
+/// let instance = Module::parse_bytes(&wasm)?.instantiate(&mut store, Some(imports))?;
+/// let run = instance.exported_func::<(), i32>(&store, "run")?;
+/// assert_eq!(run.call(&mut store, ())?, 42);
+/// # Ok::<(), tinywasm::Error>(())
+/// ```
+#[proc_macro_attribute]
+pub fn host_module(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        let attr = proc_macro2::TokenStream::from(attr);
+        return syn::Error::new_spanned(attr, "host_module does not take arguments").into_compile_error().into();
+    }
+
+    let item = parse_macro_input!(item as ItemImpl);
+    host_module::expand(item).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Generate bindings for a WIT world
+///
+/// Reads the `.wit` files at `path` (a file or a directory, relative to the crate root) or the `inline` source
+/// at compile time and generates:
+/// - a module for every interface the world uses, containing its types with `ComponentValue` implementations,
+///   a `Host` trait and an `add_to_imports` function for imported interfaces, and an `Exports` struct with
+///   typed methods for exported interfaces
+/// - a struct named after the world with `add_to_imports`, `instantiate`, methods calling the functions it
+///   exports and accessors for the interfaces it exports
+/// - a `<World>Imports` trait for functions the world imports directly
+///
+/// Use `world = "..."` to select a world if the files contain several. Records, variants, enums, flags,
+/// type aliases and `use` are supported; resources are not supported yet.
+///
+/// Host functions take `&self` and return a `tinywasm::Result`, use interior mutability to modify state.
+///
+/// ```rust
+/// use std::{cell::RefCell, rc::Rc};
+/// use tinywasm::component::{bindgen, Component, ComponentImports};
+/// use tinywasm::Store;
+///
+/// bindgen!(
+///     inline = r#"
+///         package example:greeter;
+///
+///         interface host {
+///             log: func(msg: string);
+///         }
+///
+///         world greeter {
+///             import host;
+///             export greet: func(name: string) -> string;
+///         }
+///     "#
+/// );
+///
+/// #[derive(Default)]
+/// struct Host {
+///     logs: RefCell<Vec<String>>,
+/// }
+///
+/// impl host::Host for Host {
+///     fn log(&self, msg: String) -> tinywasm::Result<()> {
+///         self.logs.borrow_mut().push(msg);
+///         Ok(())
+///     }
+/// }
+///
+/// // a component calling `log` with the name and returning it
+/// let wasm = wat::parse_str(r#"(component
+///     (import "example:greeter/host" (instance $host (export "log" (func (param "msg" string)))))
+///     (core module $m
+///         (memory (export "memory") 1)
+///         (func (export "realloc") (param i32 i32 i32 i32) (result i32) (i32.const 64))
+///         (func (export "greet") (param i32 i32) (result i32) (i32.const 0)))
+///     (core instance $libc (instantiate $m))
+///     (alias export $host "log" (func $log))
+///     (core func $log (canon lower (func $log) (memory $libc "memory")))
+///     (core module $greeter
+///         (import "libc" "memory" (memory 1))
+///         (import "host" "log" (func $log (param i32 i32)))
+///         (func (export "greet") (param i32 i32) (result i32)
+///             (call $log (local.get 0) (local.get 1))
+///             (i32.store (i32.const 0) (local.get 0))
+///             (i32.store (i32.const 4) (local.get 1))
+///             (i32.const 0)))
+///     (core instance $i (instantiate $greeter
+///         (with "libc" (instance $libc))
+///         (with "host" (instance (export "log" (func $log))))))
+///     (func (export "greet") (param "name" string) (result string)
+///         (canon lift (core func $i "greet") (memory $libc "memory") (realloc (func $libc "realloc")))))"#).unwrap();
+///
+/// let host = Rc::new(Host::default());
+/// let mut imports = ComponentImports::new();
+/// Greeter::add_to_imports(&mut imports, host.clone())?;
+///
+/// let mut store = Store::default();
+/// let greeter = Greeter::instantiate(&mut store, &Component::parse_bytes(&wasm)?, &imports)?;
+/// assert_eq!(greeter.greet(&mut store, "world".to_string())?, "world");
+/// assert_eq!(host.logs.borrow().as_slice(), ["world"]);
+/// # Ok::<(), tinywasm::Error>(())
+/// ```
+#[proc_macro]
+pub fn bindgen(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input with Punctuated::<MetaNameValue, Token![,]>::parse_terminated);
+    bindgen::expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Inputs the macros have to reject
+///
+/// Derives only support structs:
+/// ```compile_fail
+/// #[derive(tinywasm::IntoWasmValueTuple)]
+/// enum Value {
+///     A(i32),
+/// }
+/// ```
+///
+/// ```compile_fail
+/// #[derive(tinywasm::WasmPod)]
+/// enum Value {
+///     A(i32),
+/// }
+/// ```
+///
+/// `host_module` takes no arguments and only accepts inherent, non-generic impl blocks:
+/// ```compile_fail
+/// struct Env;
+///
+/// #[tinywasm::host_module(name = "env")]
+/// impl Env {
+///     fn add(a: i32, b: i32) -> i32 {
+///         a + b
+///     }
+/// }
+/// ```
+///
+/// ```compile_fail
+/// struct Env;
+///
+/// #[tinywasm::host_module]
+/// impl Default for Env {
+///     fn default() -> Self {
+///         Env
+///     }
+/// }
+/// ```
+///
+/// ```compile_fail
+/// struct Env<T>(T);
+///
+/// #[tinywasm::host_module]
+/// impl<T> Env<T> {
+///     fn add(a: i32, b: i32) -> i32 {
+///         a + b
+///     }
+/// }
+/// ```
+///
+/// Host functions can not be async or generic, and can only take `&self`:
+/// ```compile_fail
+/// struct Env;
+///
+/// #[tinywasm::host_module]
+/// impl Env {
+///     async fn add(a: i32, b: i32) -> i32 {
+///         a + b
+///     }
+/// }
+/// ```
+///
+/// ```compile_fail
+/// struct Env;
+///
+/// #[tinywasm::host_module]
+/// impl Env {
+///     fn add<T>(a: i32, b: i32) -> i32 {
+///         a + b
+///     }
+/// }
+/// ```
+///
+/// ```compile_fail
+/// struct Env(i32);
+///
+/// #[tinywasm::host_module]
+/// impl Env {
+///     fn set(&mut self, value: i32) {
+///         self.0 = value;
+///     }
+/// }
+/// ```
+///
+/// `bindgen` only takes known options with string values and needs a valid world:
+/// ```compile_fail
+/// tinywasm::component::bindgen!(file = "wit");
+/// ```
+///
+/// ```compile_fail
+/// tinywasm::component::bindgen!(inline = 1);
+/// ```
+///
+/// ```compile_fail
+/// tinywasm::component::bindgen!(path = "does-not-exist");
+/// ```
+///
+/// ```compile_fail
+/// tinywasm::component::bindgen!(
+///     inline = r#"
+///         package example:greeter;
+///
+///         world greeter {
+///             export greet: func(name: string) -> string;
+///         }
+///     "#,
+///     world = "missing"
+/// );
+/// ```
+#[cfg(doctest)]
+mod compile_fail {}
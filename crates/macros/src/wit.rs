@@ -0,0 +1,581 @@
+//! A parser for the subset of WIT used by `bindgen!`
+//!
+//! See <https://github.com/WebAssembly/component-model/blob/main/design/mvp/WIT.md>
+
+pub(crate) type Result<T> = core::result::Result<T, String>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    Version(String),
+    Sym(char),
+    Arrow,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    docs: Vec<String>,
+    line: usize,
+}
+
+fn lex(src: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut docs = Vec::new();
+    let mut chars = src.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        let tok = match c {
+            '\n' => {
+                line += 1;
+                continue;
+            }
+            c if c.is_whitespace() => continue,
+            '/' if chars.peek() == Some(&'/') => {
+                let comment: String = core::iter::from_fn(|| chars.next_if(|c| *c != '\n')).collect();
+                if let Some(doc) = comment.strip_prefix("//") {
+                    docs.push(doc.strip_prefix(' ').unwrap_or(doc).to_string());
+                }
+                continue;
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    line += (c == '\n') as usize;
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+                continue;
+            }
+            '-' if chars.peek() == Some(&'>') => {
+                chars.next();
+                Tok::Arrow
+            }
+            '@' => {
+                let mut version = String::new();
+                while let Some(&c) = chars.peek() {
+                    let dot_before_brace = c == '.' && chars.clone().nth(1) == Some('{');
+                    if !(c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '+')) || dot_before_brace {
+                        break;
+                    }
+                    version.push(c);
+                    chars.next();
+                }
+                tokens.push(Token { tok: Tok::Sym('@'), docs: core::mem::take(&mut docs), line });
+                Tok::Version(version)
+            }
+            c if c.is_ascii_alphabetic() || c == '%' => {
+                let mut ident = String::from(c).replace('%', "");
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '-') {
+                    ident.push(c);
+                }
+                Tok::Ident(ident)
+            }
+            '{' | '}' | '(' | ')' | '<' | '>' | ',' | ':' | ';' | '.' | '/' | '=' | '_' | '*' => Tok::Sym(c),
+            c => return Err(format!("line {line}: unexpected character `{c}`")),
+        };
+        tokens.push(Token { tok, docs: core::mem::take(&mut docs), line });
+    }
+    Ok(tokens)
+}
+
+/// A WIT type
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Type {
+    Bool,
+    S8,
+    U8,
+    S16,
+    U16,
+    S32,
+    U32,
+    S64,
+    U64,
+    F32,
+    F64,
+    Char,
+    String,
+    List(Box<Type>),
+    Option(Box<Type>),
+    Result(Option<Box<Type>>, Option<Box<Type>>),
+    Tuple(Vec<Type>),
+    Named(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Case {
+    pub(crate) name: String,
+    pub(crate) docs: Vec<String>,
+    pub(crate) ty: Option<Type>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TypeDefKind {
+    Record(Vec<Case>),
+    Variant(Vec<Case>),
+    Enum(Vec<Case>),
+    Flags(Vec<Case>),
+    Alias(Type),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct TypeDef {
+    pub(crate) name: String,
+    pub(crate) docs: Vec<String>,
+    pub(crate) kind: TypeDefKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Func {
+    pub(crate) name: String,
+    pub(crate) docs: Vec<String>,
+    pub(crate) params: Vec<(String, Type)>,
+    pub(crate) result: Option<Type>,
+}
+
+/// `use path.{name as alias}`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Use {
+    pub(crate) path: String,
+    pub(crate) names: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Interface {
+    pub(crate) name: String,
+    pub(crate) docs: Vec<String>,
+    pub(crate) uses: Vec<Use>,
+    pub(crate) types: Vec<TypeDef>,
+    pub(crate) funcs: Vec<Func>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum WorldItem {
+    /// An interface by its path, e.g. `host` or `wasi:cli/environment@0.2.0`
+    Interface(String),
+    /// An interface defined inline, e.g. `import host: interface { ... }`
+    InlineInterface(Interface),
+    Func(Func),
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct World {
+    pub(crate) name: String,
+    pub(crate) docs: Vec<String>,
+    pub(crate) uses: Vec<Use>,
+    pub(crate) imports: Vec<WorldItem>,
+    pub(crate) exports: Vec<WorldItem>,
+}
+
+/// The contents of a WIT file
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct Package {
+    /// `namespace:name`
+    pub(crate) name: Option<String>,
+    pub(crate) version: Option<String>,
+    pub(crate) interfaces: Vec<Interface>,
+    pub(crate) worlds: Vec<World>,
+}
+
+impl Package {
+    /// The name an interface of this package is imported or exported as
+    pub(crate) fn qualified_name(&self, interface: &str) -> String {
+        match (&self.name, &self.version) {
+            (Some(name), Some(version)) => format!("{name}/{interface}@{version}"),
+            (Some(name), None) => format!("{name}/{interface}"),
+            (None, _) => interface.to_string(),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn err<T>(&self, msg: &str) -> Result<T> {
+        match self.tokens.get(self.pos) {
+            Some(token) => Err(format!("line {}: {msg}, found `{}`", token.line, describe(&token.tok))),
+            None => Err(format!("{msg}, found the end of the file")),
+        }
+    }
+
+    fn peek(&self) -> Option<&Tok> {
+        self.tokens.get(self.pos).map(|t| &t.tok)
+    }
+
+    fn docs(&self) -> Vec<String> {
+        self.tokens.get(self.pos).map(|t| t.docs.clone()).unwrap_or_default()
+    }
+
+    fn eat(&mut self, sym: char) -> bool {
+        let found = self.peek() == Some(&Tok::Sym(sym));
+        self.pos += found as usize;
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Tok::Ident(i)) if i == keyword);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect(&mut self, sym: char) -> Result<()> {
+        match self.eat(sym) {
+            true => Ok(()),
+            false => self.err(&format!("expected `{sym}`")),
+        }
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.peek().cloned() {
+            Some(Tok::Ident(ident)) => {
+                self.pos += 1;
+                Ok(ident)
+            }
+            _ => self.err("expected an identifier"),
+        }
+    }
+
+    fn version(&mut self) -> Result<Option<String>> {
+        if !self.eat('@') {
+            return Ok(None);
+        }
+        match self.peek().cloned() {
+            Some(Tok::Version(version)) if !version.is_empty() => {
+                self.pos += 1;
+                Ok(Some(version))
+            }
+            _ => self.err("expected a version"),
+        }
+    }
+
+    /// `name` or `namespace:package/name@version`
+    fn path(&mut self) -> Result<String> {
+        let first = self.ident()?;
+        let tok = |offset: usize| self.tokens.get(self.pos + offset).map(|t| &t.tok);
+        let qualified = tok(0) == Some(&Tok::Sym(':')) && tok(2) == Some(&Tok::Sym('/'));
+        if !qualified {
+            return Ok(first);
+        }
+        self.pos += 1;
+        let package = self.ident()?;
+        self.expect('/')?;
+        let name = self.ident()?;
+        Ok(match self.version()? {
+            Some(version) => format!("{first}:{package}/{name}@{version}"),
+            None => format!("{first}:{package}/{name}"),
+        })
+    }
+
+    fn file(&mut self) -> Result<Package> {
+        let mut package = Package::default();
+        if self.eat_keyword("package") {
+            let namespace = self.ident()?;
+            self.expect(':')?;
+            package.name = Some(format!("{namespace}:{}", self.ident()?));
+            package.version = self.version()?;
+            self.expect(';')?;
+        }
+
+        while self.peek().is_some() {
+            let docs = self.docs();
+            match self.ident()?.as_str() {
+                "interface" => {
+                    let name = self.ident()?;
+                    package.interfaces.push(Interface { name, docs, ..self.interface_body()? });
+                }
+                "world" => {
+                    let name = self.ident()?;
+                    package.worlds.push(World { name, docs, ..self.world_body()? });
+                }
+                item => return Err(unsupported(item)),
+            }
+        }
+        Ok(package)
+    }
+
+    fn use_item(&mut self) -> Result<Use> {
+        let path = self.path()?;
+        self.expect('.')?;
+        self.expect('{')?;
+        let mut names = Vec::new();
+        while !self.eat('}') {
+            let name = self.ident()?;
+            let alias = if self.eat_keyword("as") { self.ident()? } else { name.clone() };
+            names.push((name, alias));
+            if !self.eat(',') {
+                self.expect('}')?;
+                break;
+            }
+        }
+        self.expect(';')?;
+        Ok(Use { path, names })
+    }
+
+    fn interface_body(&mut self) -> Result<Interface> {
+        let mut interface = Interface::default();
+        self.expect('{')?;
+        while !self.eat('}') {
+            let docs = self.docs();
+            let keyword = self.ident()?;
+            match keyword.as_str() {
+                "use" => interface.uses.push(self.use_item()?),
+                "record" | "variant" | "enum" | "flags" | "type" => {
+                    interface.types.push(TypeDef { docs, ..self.type_def(&keyword)? })
+                }
+                _ if self.peek() == Some(&Tok::Sym(':')) => {
+                    self.pos += 1;
+                    interface.funcs.push(Func { name: keyword, docs, ..self.func()? });
+                }
+                item => return Err(unsupported(item)),
+            }
+        }
+        Ok(interface)
+    }
+
+    fn world_body(&mut self) -> Result<World> {
+        let mut world = World::default();
+        self.expect('{')?;
+        while !self.eat('}') {
+            let docs = self.docs();
+            let keyword = self.ident()?;
+            let items = match keyword.as_str() {
+                "use" => {
+                    world.uses.push(self.use_item()?);
+                    continue;
+                }
+                "import" => &mut world.imports,
+                "export" => &mut world.exports,
+                "record" | "variant" | "enum" | "flags" | "type" => {
+                    return self.err("type definitions in worlds are not supported, define them in an interface")
+                }
+                item => return Err(unsupported(item)),
+            };
+
+            let path = self.path()?;
+            if !self.eat(':') {
+                self.expect(';')?;
+                items.push(WorldItem::Interface(path));
+                continue;
+            }
+            if self.eat_keyword("interface") {
+                let interface = Interface { name: path, docs, ..self.interface_body()? };
+                items.push(WorldItem::InlineInterface(interface));
+            } else {
+                items.push(WorldItem::Func(Func { name: path, docs, ..self.func()? }));
+            }
+        }
+        Ok(world)
+    }
+
+    // `func(params) -> result;`, after the name
+    fn func(&mut self) -> Result<Func> {
+        if !self.eat_keyword("func") {
+            return self.err("expected `func`");
+        }
+        self.expect('(')?;
+        let mut params = Vec::new();
+        while !self.eat(')') {
+            let name = self.ident()?;
+            self.expect(':')?;
+            params.push((name, self.ty()?));
+            if !self.eat(',') {
+                self.expect(')')?;
+                break;
+            }
+        }
+        let result = match self.peek() {
+            Some(Tok::Arrow) => {
+                self.pos += 1;
+                if self.peek() == Some(&Tok::Sym('(')) {
+                    return self.err("named results are not supported");
+                }
+                Some(self.ty()?)
+            }
+            _ => None,
+        };
+        self.expect(';')?;
+        Ok(Func { name: String::new(), docs: Vec::new(), params, result })
+    }
+
+    fn cases(&mut self, payload: bool, separator: char) -> Result<Vec<Case>> {
+        let mut cases = Vec::new();
+        self.expect('{')?;
+        while !self.eat('}') {
+            let docs = self.docs();
+            let name = self.ident()?;
+            let ty = match (payload, separator) {
+                (true, ':') => Some({
+                    self.expect(':')?;
+                    self.ty()?
+                }),
+                (true, _) if self.eat('(') => {
+                    let ty = self.ty()?;
+                    self.expect(')')?;
+                    Some(ty)
+                }
+                _ => None,
+            };
+            cases.push(Case { name, docs, ty });
+            if !self.eat(',') {
+                self.expect('}')?;
+                break;
+            }
+        }
+        Ok(cases)
+    }
+
+    fn type_def(&mut self, keyword: &str) -> Result<TypeDef> {
+        let name = self.ident()?;
+        let kind = match keyword {
+            "record" => TypeDefKind::Record(self.cases(true, ':')?),
+            "variant" => TypeDefKind::Variant(self.cases(true, '(')?),
+            "enum" => TypeDefKind::Enum(self.cases(false, ',')?),
+            "flags" => TypeDefKind::Flags(self.cases(false, ',')?),
+            _ => {
+                self.expect('=')?;
+                let ty = self.ty()?;
+                self.expect(';')?;
+                TypeDefKind::Alias(ty)
+            }
+        };
+        Ok(TypeDef { name, docs: Vec::new(), kind })
+    }
+
+    fn generic(&mut self) -> Result<Type> {
+        self.expect('<')?;
+        let ty = self.ty()?;
+        self.expect('>')?;
+        Ok(ty)
+    }
+
+    fn ty(&mut self) -> Result<Type> {
+        let name = self.ident()?;
+        Ok(match name.as_str() {
+            "bool" => Type::Bool,
+            "s8" => Type::S8,
+            "u8" => Type::U8,
+            "s16" => Type::S16,
+            "u16" => Type::U16,
+            "s32" => Type::S32,
+            "u32" => Type::U32,
+            "s64" => Type::S64,
+            "u64" => Type::U64,
+            "f32" | "float32" => Type::F32,
+            "f64" | "float64" => Type::F64,
+            "char" => Type::Char,
+            "string" => Type::String,
+            "list" => Type::List(Box::new(self.generic()?)),
+            "option" => Type::Option(Box::new(self.generic()?)),
+            "result" if self.eat('<') => {
+                let ok = if self.eat('_') { None } else { Some(Box::new(self.ty()?)) };
+                let err = if self.eat(',') { Some(Box::new(self.ty()?)) } else { None };
+                self.expect('>')?;
+                Type::Result(ok, err)
+            }
+            "result" => Type::Result(None, None),
+            "tuple" => {
+                self.expect('<')?;
+                let mut types = Vec::new();
+                while !self.eat('>') {
+                    types.push(self.ty()?);
+                    if !self.eat(',') {
+                        self.expect('>')?;
+                        break;
+                    }
+                }
+                Type::Tuple(types)
+            }
+            "own" | "borrow" | "future" | "stream" => return Err(unsupported(&name)),
+            _ => Type::Named(name),
+        })
+    }
+}
+
+fn describe(tok: &Tok) -> String {
+    match tok {
+        Tok::Ident(ident) => ident.clone(),
+        Tok::Version(version) => version.clone(),
+        Tok::Sym(c) => c.to_string(),
+        Tok::Arrow => "->".to_string(),
+    }
+}
+
+fn unsupported(item: &str) -> String {
+    match item {
+        "resource" | "own" | "borrow" => "resources are not supported by bindgen yet".to_string(),
+        item => format!("`{item}` is not supported by bindgen"),
+    }
+}
+
+/// Parse a WIT file
+pub(crate) fn parse(src: &str) -> Result<Package> {
+    Parser { tokens: lex(src)?, pos: 0 }.file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let package = parse(
+            r#"
+            package example:plugin@0.1.0;
+
+            /// Shapes
+            interface shapes {
+                record point { x: s32, y: s32 }
+                variant shape { circle(f32), polygon(list<point>), empty }
+                enum color { red, green }
+                flags style { bold, %italic }
+                type points = list<point>;
+                /* a function */
+                area: func(shape: shape) -> result<f64, string>;
+            }
+
+            world plugin {
+                use shapes.{point as pt};
+                import wasi:cli/environment@0.2.0;
+                import log: func(msg: string);
+                export shapes;
+                export run: func() -> result;
+            }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(package.qualified_name("shapes"), "example:plugin/shapes@0.1.0");
+        let shapes = &package.interfaces[0];
+        assert_eq!(shapes.docs, ["Shapes"]);
+        assert_eq!(shapes.types.len(), 5);
+        assert_eq!(shapes.types[2].kind, TypeDefKind::Enum(vec![case("red", None), case("green", None)]));
+        assert_eq!(shapes.types[3].kind, TypeDefKind::Flags(vec![case("bold", None), case("italic", None)]));
+        let polygon = case("polygon", Some(Type::List(Box::new(Type::Named("point".into())))));
+        assert_eq!(
+            shapes.types[1].kind,
+            TypeDefKind::Variant(vec![case("circle", Some(Type::F32)), polygon, case("empty", None)])
+        );
+        let result = Type::Result(Some(Box::new(Type::F64)), Some(Box::new(Type::String)));
+        assert_eq!(shapes.funcs[0].result, Some(result));
+
+        let world = &package.worlds[0];
+        assert_eq!(world.uses[0], Use { path: "shapes".into(), names: vec![("point".into(), "pt".into())] });
+        assert_eq!(world.imports[0], WorldItem::Interface("wasi:cli/environment@0.2.0".into()));
+        assert!(matches!(&world.imports[1], WorldItem::Func(f) if f.params == [("msg".to_string(), Type::String)]));
+        assert!(matches!(&world.exports[1], WorldItem::Func(f) if f.result == Some(Type::Result(None, None))));
+
+        assert!(parse("interface i { resource r; }").unwrap_err().contains("resources are not supported"));
+        assert!(parse("interface i { f: func(a: u32 }").unwrap_err().starts_with("line 1: expected `)`"));
+    }
+
+    fn case(name: &str, ty: Option<Type>) -> Case {
+        Case { name: name.into(), docs: Vec::new(), ty }
+    }
+}
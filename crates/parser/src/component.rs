@@ -0,0 +1,402 @@
+use crate::log::debug;
+use crate::module::ModuleReader;
+use crate::{conversion, ParseError, Result};
+use alloc::string::ToString;
+use alloc::{boxed::Box, format, vec::Vec};
+use tinywasm_types::{
+    CanonicalOptions, ComponentExport, ComponentExternalKind, ComponentFuncType, ComponentInitializer,
+    ComponentResource, ComponentValType, ExternalKind, StringEncoding, TinyWasmComponent, TinyWasmModule,
+};
+use wasmparser::types::{self, ComponentAnyTypeId, ComponentEntityType, ResourceId, TypesRef};
+use wasmparser::{Payload, PrimitiveValType, Validator};
+
+#[derive(Default)]
+pub(crate) struct ComponentReader {
+    // the core module that is currently being read
+    module: Option<ModuleReader>,
+
+    modules: Vec<TinyWasmModule>,
+    resources: Vec<ComponentResource>,
+    resource_ids: Vec<(ResourceId, u32)>,
+    initializers: Vec<ComponentInitializer>,
+    pub(crate) end_reached: bool,
+}
+
+impl ComponentReader {
+    pub(crate) fn new() -> ComponentReader {
+        Self::default()
+    }
+
+    pub(crate) fn process_payload(&mut self, payload: Payload<'_>, validator: &mut Validator) -> Result<()> {
+        use wasmparser::Payload::*;
+
+        if let Some(module) = &mut self.module {
+            module.process_payload(payload, validator)?;
+            if module.end_reached {
+                let module = self.module.take().expect("module is being read");
+                self.modules.push(module.into_module()?);
+            }
+            return Ok(());
+        }
+
+        match payload {
+            Version { num, encoding, range } => {
+                validator.version(num, encoding, &range)?;
+                match encoding {
+                    wasmparser::Encoding::Component => {}
+                    wasmparser::Encoding::Module => return Err(ParseError::InvalidEncoding(encoding)),
+                }
+            }
+            ModuleSection { unchecked_range, .. } => {
+                debug!("Found module section");
+                validator.module_section(&unchecked_range)?;
+                self.module = Some(ModuleReader::new());
+            }
+            InstanceSection(reader) => {
+                debug!("Found core instance section");
+                validator.instance_section(&reader)?;
+                for instance in reader {
+                    self.initializers.push(match instance? {
+                        wasmparser::Instance::Instantiate { module_index, args } => {
+                            let args = args.iter().map(|arg| (arg.name.to_string(), arg.index)).collect();
+                            ComponentInitializer::CoreInstantiate { module: module_index, args }
+                        }
+                        wasmparser::Instance::FromExports(exports) => ComponentInitializer::CoreInstanceFromExports(
+                            exports.iter().map(|e| conversion::convert_module_export(*e)).collect::<Result<_>>()?,
+                        ),
+                    });
+                }
+            }
+            CoreTypeSection(reader) => {
+                debug!("Found core type section");
+                validator.core_type_section(&reader)?;
+            }
+            ComponentTypeSection(reader) => {
+                debug!("Found component type section");
+                let first = types(validator).component_type_count();
+                validator.component_type_section(&reader)?;
+                for (idx, ty) in (first..).zip(reader) {
+                    let wasmparser::ComponentType::Resource { dtor, .. } = ty? else { continue };
+                    let ComponentAnyTypeId::Resource(id) = types(validator).component_any_type_at(idx) else {
+                        return Err(ParseError::InvalidType);
+                    };
+                    let resource = self.resource(id.resource());
+                    self.resources[resource as usize].defined = true;
+                    self.initializers.push(ComponentInitializer::DefineResource { resource, dtor });
+                }
+            }
+            ComponentImportSection(reader) => {
+                debug!("Found component import section");
+                validator.component_import_section(&reader)?;
+                for import in reader {
+                    let name = import?.name.0;
+                    let ty = types(validator).component_entity_type_of_import(name).ok_or(ParseError::InvalidType)?;
+                    self.import(name, ty, validator)?;
+                }
+            }
+            ComponentAliasSection(reader) => {
+                debug!("Found component alias section");
+                validator.component_alias_section(&reader)?;
+                for alias in reader {
+                    self.initializers.push(match alias? {
+                        wasmparser::ComponentAlias::CoreInstanceExport { kind, instance_index, name } => {
+                            let kind = match kind {
+                                wasmparser::ExternalKind::Func => ExternalKind::Func,
+                                wasmparser::ExternalKind::Table => ExternalKind::Table,
+                                wasmparser::ExternalKind::Memory => ExternalKind::Memory,
+                                wasmparser::ExternalKind::Global => ExternalKind::Global,
+                                wasmparser::ExternalKind::Tag => return Err(unsupported("core tags")),
+                            };
+                            ComponentInitializer::CoreAlias { instance: instance_index, kind, name: name.to_string() }
+                        }
+                        wasmparser::ComponentAlias::InstanceExport { kind, instance_index, name } => {
+                            match kind {
+                                wasmparser::ComponentExternalKind::Func => ComponentInitializer::Alias {
+                                    instance: instance_index,
+                                    kind: ComponentExternalKind::Func,
+                                    name: name.to_string(),
+                                },
+                                wasmparser::ComponentExternalKind::Instance => ComponentInitializer::Alias {
+                                    instance: instance_index,
+                                    kind: ComponentExternalKind::Instance,
+                                    name: name.to_string(),
+                                },
+                                // types only exist during validation
+                                wasmparser::ComponentExternalKind::Type => continue,
+                                kind => return Err(unsupported(&format!("aliases of {}", kind.desc()))),
+                            }
+                        }
+                        wasmparser::ComponentAlias::Outer { kind, .. } => match kind {
+                            wasmparser::ComponentOuterAliasKind::CoreType
+                            | wasmparser::ComponentOuterAliasKind::Type => continue,
+                            _ => return Err(unsupported("nested components")),
+                        },
+                    });
+                }
+            }
+            ComponentCanonicalSection(reader) => {
+                debug!("Found component canonical section");
+                validator.component_canonical_section(&reader)?;
+                for func in reader {
+                    let types = types(validator);
+                    let initializer = match func? {
+                        wasmparser::CanonicalFunction::Lift { core_func_index, type_index, options } => {
+                            let ComponentAnyTypeId::Func(id) = types.component_any_type_at(type_index) else {
+                                return Err(ParseError::InvalidType);
+                            };
+                            ComponentInitializer::Lift {
+                                core_func: core_func_index,
+                                ty: self.func_type(&types[id], types),
+                                options: convert_options(&options),
+                            }
+                        }
+                        wasmparser::CanonicalFunction::Lower { func_index, options } => {
+                            let id = types.component_function_at(func_index);
+                            let ty = self.func_type(&types[id], types);
+                            ComponentInitializer::Lower { func: func_index, ty, options: convert_options(&options) }
+                        }
+                        wasmparser::CanonicalFunction::ResourceNew { resource } => {
+                            ComponentInitializer::ResourceNew(self.resource_at(resource, types)?)
+                        }
+                        wasmparser::CanonicalFunction::ResourceDrop { resource } => {
+                            ComponentInitializer::ResourceDrop(self.resource_at(resource, types)?)
+                        }
+                        wasmparser::CanonicalFunction::ResourceRep { resource } => {
+                            ComponentInitializer::ResourceRep(self.resource_at(resource, types)?)
+                        }
+                    };
+                    self.initializers.push(initializer);
+                }
+            }
+            ComponentInstanceSection(reader) => {
+                debug!("Found component instance section");
+                validator.component_instance_section(&reader)?;
+                for instance in reader {
+                    let wasmparser::ComponentInstance::FromExports(exports) = instance? else {
+                        return Err(unsupported("nested components"));
+                    };
+                    let exports =
+                        exports.iter().filter_map(|e| convert_export(e).transpose()).collect::<Result<_>>()?;
+                    self.initializers.push(ComponentInitializer::InstanceFromExports(exports));
+                }
+            }
+            ComponentExportSection(reader) => {
+                debug!("Found component export section");
+                validator.component_export_section(&reader)?;
+                for export in reader {
+                    let export = export?;
+                    if export.kind == wasmparser::ComponentExternalKind::Type {
+                        let types = types(validator);
+                        if let ComponentAnyTypeId::Resource(id) = types.component_any_type_at(export.index) {
+                            let resource = self.resource(id.resource());
+                            self.name_resource(resource, export.name.0);
+                        }
+                    }
+                    if let Some(export) = convert_export(&export)? {
+                        self.initializers.push(ComponentInitializer::Export(export));
+                    }
+                }
+            }
+            ComponentSection { .. } => return Err(unsupported("nested components")),
+            ComponentStartSection { .. } => return Err(unsupported("component start functions")),
+            End(offset) => {
+                debug!("Reached end of component");
+                if self.end_reached {
+                    return Err(ParseError::DuplicateSection("End section".into()));
+                }
+
+                validator.end(offset)?;
+                self.end_reached = true;
+            }
+            CustomSection(reader) => {
+                debug!("Skipping custom section: {:?}", reader.name());
+            }
+            UnknownSection { .. } => return Err(ParseError::UnsupportedSection("Unknown section".into())),
+            section => return Err(ParseError::UnsupportedSection(format!("Unsupported section: {section:?}"))),
+        };
+
+        Ok(())
+    }
+
+    fn import(&mut self, name: &str, ty: ComponentEntityType, validator: &Validator) -> Result<()> {
+        let types = types(validator);
+        match ty {
+            ComponentEntityType::Func(id) => {
+                let ty = self.func_type(&types[id], types);
+                self.initializers.push(ComponentInitializer::ImportFunc { name: name.to_string(), ty });
+            }
+            ComponentEntityType::Instance(id) => {
+                let mut funcs = Vec::new();
+                for (export, ty) in &types[id].exports {
+                    match ty {
+                        ComponentEntityType::Func(id) => {
+                            funcs.push((export.clone(), self.func_type(&types[*id], types)));
+                        }
+                        ComponentEntityType::Type { created: ComponentAnyTypeId::Resource(id), referenced } => {
+                            let resource = self.resource(id.resource());
+                            self.name_resource(resource, &format!("{name}#{export}"));
+                            if let ComponentAnyTypeId::Resource(referenced) = referenced {
+                                self.alias_resource(referenced.resource(), resource);
+                            }
+                        }
+                        ComponentEntityType::Type { .. } => {}
+                        _ => return Err(unsupported("instance imports with values, modules or components")),
+                    }
+                }
+                let funcs = funcs.into_boxed_slice();
+                self.initializers.push(ComponentInitializer::ImportInstance { name: name.to_string(), funcs });
+            }
+            ComponentEntityType::Type { created: ComponentAnyTypeId::Resource(id), referenced } => {
+                let resource = self.resource(id.resource());
+                self.name_resource(resource, name);
+                if let ComponentAnyTypeId::Resource(referenced) = referenced {
+                    self.alias_resource(referenced.resource(), resource);
+                }
+            }
+            ComponentEntityType::Type { .. } => {}
+            _ => return Err(unsupported("imports of values, modules or components")),
+        }
+        Ok(())
+    }
+
+    // get the index of a resource, adding it if it's new
+    fn resource(&mut self, id: ResourceId) -> u32 {
+        if let Some((_, resource)) = self.resource_ids.iter().find(|(r, _)| *r == id) {
+            return *resource;
+        }
+        self.resources.push(ComponentResource::default());
+        let resource = self.resources.len() as u32 - 1;
+        self.resource_ids.push((id, resource));
+        resource
+    }
+
+    fn resource_at(&mut self, type_index: u32, types: TypesRef<'_>) -> Result<u32> {
+        match types.component_any_type_at(type_index) {
+            ComponentAnyTypeId::Resource(id) => Ok(self.resource(id.resource())),
+            _ => Err(ParseError::InvalidType),
+        }
+    }
+
+    // make another id refer to the same resource
+    fn alias_resource(&mut self, id: ResourceId, resource: u32) {
+        if !self.resource_ids.iter().any(|(r, _)| *r == id) {
+            self.resource_ids.push((id, resource));
+        }
+    }
+
+    fn name_resource(&mut self, resource: u32, name: &str) {
+        let resource = &mut self.resources[resource as usize];
+        if resource.name.is_empty() {
+            resource.name = name.to_string();
+        }
+    }
+
+    fn func_type(&mut self, ty: &types::ComponentFuncType, types: TypesRef<'_>) -> ComponentFuncType {
+        ComponentFuncType {
+            params: ty.params.iter().map(|(name, ty)| (name.to_string(), self.val_type(ty, types))).collect(),
+            results: ty.results.iter().map(|(_, ty)| self.val_type(ty, types)).collect(),
+        }
+    }
+
+    fn val_type(&mut self, ty: &types::ComponentValType, types: TypesRef<'_>) -> ComponentValType {
+        let id = match ty {
+            types::ComponentValType::Primitive(ty) => return convert_primitive(*ty),
+            types::ComponentValType::Type(id) => *id,
+        };
+
+        let boxed = |reader: &mut Self, ty: &types::ComponentValType| Box::new(reader.val_type(ty, types));
+        match &types[id] {
+            types::ComponentDefinedType::Primitive(ty) => convert_primitive(*ty),
+            types::ComponentDefinedType::Record(record) => ComponentValType::Record(
+                record.fields.iter().map(|(name, ty)| (name.to_string(), self.val_type(ty, types))).collect(),
+            ),
+            types::ComponentDefinedType::Variant(variant) => ComponentValType::Variant(
+                variant
+                    .cases
+                    .iter()
+                    .map(|(name, case)| (name.to_string(), case.ty.as_ref().map(|ty| self.val_type(ty, types))))
+                    .collect(),
+            ),
+            types::ComponentDefinedType::List(ty) => ComponentValType::List(boxed(self, ty)),
+            types::ComponentDefinedType::Tuple(tuple) => {
+                ComponentValType::Tuple(tuple.types.iter().map(|ty| self.val_type(ty, types)).collect())
+            }
+            types::ComponentDefinedType::Flags(names) => {
+                ComponentValType::Flags(names.iter().map(|name| name.to_string()).collect())
+            }
+            types::ComponentDefinedType::Enum(names) => {
+                ComponentValType::Enum(names.iter().map(|name| name.to_string()).collect())
+            }
+            types::ComponentDefinedType::Option(ty) => ComponentValType::Option(boxed(self, ty)),
+            types::ComponentDefinedType::Result { ok, err } => ComponentValType::Result {
+                ok: ok.as_ref().map(|ty| boxed(self, ty)),
+                err: err.as_ref().map(|ty| boxed(self, ty)),
+            },
+            types::ComponentDefinedType::Own(id) => ComponentValType::Own(self.resource(id.resource())),
+            types::ComponentDefinedType::Borrow(id) => ComponentValType::Borrow(self.resource(id.resource())),
+        }
+    }
+
+    pub(crate) fn into_component(self) -> Result<TinyWasmComponent> {
+        if !self.end_reached {
+            return Err(ParseError::EndNotReached);
+        }
+
+        Ok(TinyWasmComponent {
+            modules: self.modules.into_boxed_slice(),
+            resources: self.resources.into_boxed_slice(),
+            initializers: self.initializers.into_boxed_slice(),
+        })
+    }
+}
+
+fn types(validator: &Validator) -> TypesRef<'_> {
+    validator.types(0).expect("validator is inside of the component")
+}
+
+fn unsupported(what: &str) -> ParseError {
+    ParseError::Other(format!("Components with {what} are not supported"))
+}
+
+fn convert_export(export: &wasmparser::ComponentExport<'_>) -> Result<Option<ComponentExport>> {
+    let kind = match export.kind {
+        wasmparser::ComponentExternalKind::Func => ComponentExternalKind::Func,
+        wasmparser::ComponentExternalKind::Instance => ComponentExternalKind::Instance,
+        wasmparser::ComponentExternalKind::Type => return Ok(None),
+        kind => return Err(unsupported(&format!("exports of {}", kind.desc()))),
+    };
+    Ok(Some(ComponentExport { name: export.name.0.to_string(), kind, index: export.index }))
+}
+
+fn convert_options(options: &[wasmparser::CanonicalOption]) -> CanonicalOptions {
+    let mut converted = CanonicalOptions::default();
+    for option in options {
+        match *option {
+            wasmparser::CanonicalOption::UTF8 => converted.string_encoding = StringEncoding::Utf8,
+            wasmparser::CanonicalOption::UTF16 => converted.string_encoding = StringEncoding::Utf16,
+            wasmparser::CanonicalOption::CompactUTF16 => converted.string_encoding = StringEncoding::CompactUtf16,
+            wasmparser::CanonicalOption::Memory(memory) => converted.memory = Some(memory),
+            wasmparser::CanonicalOption::Realloc(realloc) => converted.realloc = Some(realloc),
+            wasmparser::CanonicalOption::PostReturn(post_return) => converted.post_return = Some(post_return),
+        }
+    }
+    converted
+}
+
+fn convert_primitive(ty: PrimitiveValType) -> ComponentValType {
+    match ty {
+        PrimitiveValType::Bool => ComponentValType::Bool,
+        PrimitiveValType::S8 => ComponentValType::S8,
+        PrimitiveValType::U8 => ComponentValType::U8,
+        PrimitiveValType::S16 => ComponentValType::S16,
+        PrimitiveValType::U16 => ComponentValType::U16,
+        PrimitiveValType::S32 => ComponentValType::S32,
+        PrimitiveValType::U32 => ComponentValType::U32,
+        PrimitiveValType::S64 => ComponentValType::S64,
+        PrimitiveValType::U64 => ComponentValType::U64,
+        PrimitiveValType::F32 => ComponentValType::F32,
+        PrimitiveValType::F64 => ComponentValType::F64,
+        PrimitiveValType::Char => ComponentValType::Char,
+        PrimitiveValType::String => ComponentValType::String,
+    }
+}
@@ -202,8 +202,8 @@ pub(crate) fn convert_module_code(
         }
     }
 
-    let (body, allocations) = process_operators_and_validate(validator, func, local_addr_map)?;
-    Ok(((body, local_counts), allocations))
+    let ((body, block_offsets), allocations) = process_operators_and_validate(validator, func, local_addr_map)?;
+    Ok(((body, local_counts, block_offsets), allocations))
 }
 
 pub(crate) fn convert_module_type(ty: wasmparser::RecGroup) -> Result<FuncType> {
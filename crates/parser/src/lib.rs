@@ -29,15 +29,45 @@ pub(crate) mod log {
     pub(crate) use info;
 }
 
+// tracing for structured spans (optional).
+#[cfg(feature = "tracing")]
+#[allow(clippy::single_component_path_imports, unused_imports)]
+use tracing;
+
+// noop fallback if tracing is disabled.
+#[cfg(not(feature = "tracing"))]
+#[allow(unused_imports, unused_macros, dead_code)]
+pub(crate) mod tracing {
+    #[derive(Debug)]
+    pub(crate) struct Span;
+    impl Span {
+        #[inline(always)]
+        pub(crate) fn entered(self) -> Self {
+            self
+        }
+        #[inline(always)]
+        pub(crate) fn record<V>(&self, _field: &str, _value: V) -> &Self {
+            self
+        }
+    }
+
+    macro_rules! info_span    ( ($($tt:tt)*) => {{ $crate::tracing::Span }} );
+    macro_rules! debug_span    ( ($($tt:tt)*) => {{ $crate::tracing::Span }} );
+    pub(crate) use debug_span;
+    pub(crate) use info_span;
+}
+
+mod component;
 mod conversion;
 mod error;
 mod module;
 mod visit;
+use component::ComponentReader;
 pub use error::*;
 use module::ModuleReader;
 use wasmparser::{Validator, WasmFeaturesInflated};
 
-pub use tinywasm_types::TinyWasmModule;
+pub use tinywasm_types::{TinyWasmComponent, TinyWasmModule};
 
 /// A WebAssembly parser
 #[derive(Default, Debug)]
@@ -63,7 +93,7 @@ impl Parser {
             multi_memory: true,
 
             gc_types: true,
-            component_model: false,
+            component_model: true,
             component_model_nested_names: false,
             component_model_values: false,
             component_model_more_flags: false,
@@ -86,6 +116,7 @@ impl Parser {
     /// Parse a [`TinyWasmModule`] from bytes
     pub fn parse_module_bytes(&self, wasm: impl AsRef<[u8]>) -> Result<TinyWasmModule> {
         let wasm = wasm.as_ref();
+        let _span = tracing::info_span!("parse", bytes = wasm.len()).entered();
         let mut validator = Self::create_validator();
         let mut reader = ModuleReader::new();
 
@@ -100,6 +131,22 @@ impl Parser {
         reader.into_module()
     }
 
+    /// Parse a [`TinyWasmComponent`] from bytes
+    ///
+    /// Nested components and component-level values are not supported.
+    pub fn parse_component_bytes(&self, wasm: impl AsRef<[u8]>) -> Result<TinyWasmComponent> {
+        let wasm = wasm.as_ref();
+        let _span = tracing::info_span!("parse_component", bytes = wasm.len()).entered();
+        let mut validator = Self::create_validator();
+        let mut reader = ComponentReader::new();
+
+        for payload in wasmparser::Parser::new(0).parse_all(wasm) {
+            reader.process_payload(payload?, &mut validator)?;
+        }
+
+        reader.into_component()
+    }
+
     #[cfg(feature = "std")]
     /// Parse a [`TinyWasmModule`] from a file. Requires `std` feature.
     pub fn parse_module_file(&self, path: impl AsRef<crate::std::path::Path> + Clone) -> Result<TinyWasmModule> {
@@ -115,6 +162,7 @@ impl Parser {
     /// Parse a [`TinyWasmModule`] from a stream. Requires `std` feature.
     pub fn parse_module_stream(&self, mut stream: impl std::io::Read) -> Result<TinyWasmModule> {
         use alloc::format;
+        let _span = tracing::info_span!("parse").entered();
 
         let mut validator = Self::create_validator();
         let mut reader = ModuleReader::new();
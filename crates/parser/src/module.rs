@@ -8,7 +8,7 @@ use tinywasm_types::{
 };
 use wasmparser::{FuncValidatorAllocations, Payload, Validator};
 
-pub(crate) type Code = (Box<[Instruction]>, ValueCounts);
+pub(crate) type Code = (Box<[Instruction]>, ValueCounts, crate::visit::BlockOffsets);
 
 #[derive(Default)]
 pub(crate) struct ModuleReader {
@@ -134,6 +134,7 @@ impl ModuleReader {
             }
             CodeSectionEntry(function) => {
                 debug!("Found code section entry");
+                let _span = crate::tracing::debug_span!("validate", func = self.code.len()).entered();
                 let v = validator.code_section_entry(&function)?;
                 let func_validator = v.into_validator(self.func_validator_allocations.take().unwrap_or_default());
                 let (code, allocations) = conversion::convert_module_code(function, func_validator)?;
@@ -193,7 +194,7 @@ impl ModuleReader {
             .code
             .into_iter()
             .zip(self.code_type_addrs)
-            .map(|((instructions, locals), ty_idx)| {
+            .map(|((instructions, locals, block_offsets), ty_idx)| {
                 let mut params = ValueCountsSmall::default();
                 let ty = self.func_types.get(ty_idx as usize).expect("No func type for func, this is a bug").clone();
                 for param in &ty.params {
@@ -204,7 +205,19 @@ impl ModuleReader {
                         ValType::RefExtern | ValType::RefFunc => params.cref += 1,
                     }
                 }
-                WasmFunction { instructions, locals, params, ty }
+
+                // block offsets are only collected with the `coverage` feature
+                #[cfg(not(feature = "coverage"))]
+                let () = block_offsets;
+
+                WasmFunction {
+                    instructions,
+                    locals,
+                    params,
+                    ty,
+                    #[cfg(feature = "coverage")]
+                    block_offsets,
+                }
             })
             .collect::<Vec<_>>()
             .into_boxed_slice();
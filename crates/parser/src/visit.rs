@@ -3,13 +3,28 @@ use crate::Result;
 use crate::conversion::{convert_heaptype, convert_valtype};
 use alloc::string::ToString;
 use alloc::{boxed::Box, vec::Vec};
+#[cfg(feature = "coverage")]
+use tinywasm_types::BlockOffset;
 use tinywasm_types::{Instruction, MemoryArg};
 use wasmparser::{FuncValidator, FuncValidatorAllocations, FunctionBody, VisitOperator, WasmModuleResources};
 
+/// The instructions of a function body and the offsets of its blocks
+pub(crate) type FunctionInstructions = (Box<[Instruction]>, BlockOffsets);
+
+/// The offsets of the blocks of a function body, only collected with the `coverage` feature
+#[cfg(feature = "coverage")]
+pub(crate) type BlockOffsets = Box<[BlockOffset]>;
+#[cfg(not(feature = "coverage"))]
+pub(crate) type BlockOffsets = ();
+
 struct ValidateThenVisit<'a, R: WasmModuleResources>(usize, &'a mut FunctionBuilder<R>);
 macro_rules! validate_then_visit {
     ($( @$proposal:ident $op:ident $({ $($arg:ident: $argty:ty),* })? => $visit:ident)*) => {$(
         fn $visit(&mut self $($(,$arg: $argty)*)?) -> Self::Output {
+            #[cfg(feature = "coverage")]
+            {
+                self.1.offset = self.0;
+            }
             self.1.$visit($($($arg.clone()),*)?);
             self.1.validator_visitor(self.0).$visit($($($arg),*)?)?;
             Ok(())
@@ -26,11 +41,18 @@ pub(crate) fn process_operators_and_validate<R: WasmModuleResources>(
     validator: FuncValidator<R>,
     body: FunctionBody<'_>,
     local_addr_map: Vec<u32>,
-) -> Result<(Box<[Instruction]>, FuncValidatorAllocations)> {
+) -> Result<(FunctionInstructions, FuncValidatorAllocations)> {
     let mut reader = body.get_operators_reader()?;
     let remaining = reader.get_binary_reader().bytes_remaining();
     let mut builder = FunctionBuilder::new(remaining, validator, local_addr_map);
 
+    // the function entry is the first region of code
+    #[cfg(feature = "coverage")]
+    {
+        builder.offset = reader.original_position();
+        builder.push_block_offset();
+    }
+
     while !reader.eof() {
         reader.visit_operator(&mut ValidateThenVisit(reader.original_position(), &mut builder))??;
     }
@@ -40,7 +62,8 @@ pub(crate) fn process_operators_and_validate<R: WasmModuleResources>(
         return Err(builder.errors.remove(0));
     }
 
-    Ok((builder.instructions.into_boxed_slice(), builder.validator.into_allocations()))
+    let instructions = core::mem::take(&mut builder.instructions).into_boxed_slice();
+    Ok(((instructions, builder.take_block_offsets()), builder.validator.into_allocations()))
 }
 
 macro_rules! define_operands {
@@ -81,6 +104,12 @@ pub(crate) struct FunctionBuilder<R: WasmModuleResources> {
     label_ptrs: Vec<usize>,
     local_addr_map: Vec<u32>,
     errors: Vec<crate::ParseError>,
+
+    // offset of the operator that is currently being visited
+    #[cfg(feature = "coverage")]
+    offset: usize,
+    #[cfg(feature = "coverage")]
+    block_offsets: Vec<BlockOffset>,
 }
 
 impl<R: WasmModuleResources> FunctionBuilder<R> {
@@ -104,9 +133,36 @@ impl<R: WasmModuleResources> FunctionBuilder<R> {
             instructions: Vec::with_capacity(instr_capacity),
             label_ptrs: Vec::with_capacity(256),
             errors: Vec::new(),
+            #[cfg(feature = "coverage")]
+            offset: 0,
+            #[cfg(feature = "coverage")]
+            block_offsets: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn push_block_offset(&mut self) {
+        #[cfg(feature = "coverage")]
+        {
+            let instr = self.instructions.len() as u32;
+
+            // a block at the very start of a function is part of the function entry
+            if self.block_offsets.last().is_some_and(|b| b.instr == instr) {
+                return;
+            }
+
+            self.block_offsets.push(BlockOffset { instr, offset: self.offset as u32 });
         }
     }
 
+    #[cfg(feature = "coverage")]
+    fn take_block_offsets(&mut self) -> BlockOffsets {
+        core::mem::take(&mut self.block_offsets).into_boxed_slice()
+    }
+
+    #[cfg(not(feature = "coverage"))]
+    fn take_block_offsets(&mut self) -> BlockOffsets {}
+
     fn unsupported(&mut self, name: &str) {
         self.errors.push(crate::ParseError::UnsupportedOperator(name.to_string()));
     }
@@ -461,6 +517,7 @@ impl<'a, R: WasmModuleResources> wasmparser::VisitOperator<'a> for FunctionBuild
     }
 
     fn visit_block(&mut self, blockty: wasmparser::BlockType) -> Self::Output {
+        self.push_block_offset();
         self.label_ptrs.push(self.instructions.len());
         self.instructions.push(match blockty {
             wasmparser::BlockType::Empty => Instruction::Block(0),
@@ -470,6 +527,7 @@ impl<'a, R: WasmModuleResources> wasmparser::VisitOperator<'a> for FunctionBuild
     }
 
     fn visit_loop(&mut self, ty: wasmparser::BlockType) -> Self::Output {
+        self.push_block_offset();
         self.label_ptrs.push(self.instructions.len());
         self.instructions.push(match ty {
             wasmparser::BlockType::Empty => Instruction::Loop(0),
@@ -479,6 +537,7 @@ impl<'a, R: WasmModuleResources> wasmparser::VisitOperator<'a> for FunctionBuild
     }
 
     fn visit_if(&mut self, ty: wasmparser::BlockType) -> Self::Output {
+        self.push_block_offset();
         self.label_ptrs.push(self.instructions.len());
         self.instructions.push(match ty {
             wasmparser::BlockType::Empty => Instruction::If(0, 0),
@@ -488,6 +547,7 @@ impl<'a, R: WasmModuleResources> wasmparser::VisitOperator<'a> for FunctionBuild
     }
 
     fn visit_else(&mut self) -> Self::Output {
+        self.push_block_offset();
         self.label_ptrs.push(self.instructions.len());
         self.instructions.push(Instruction::Else(0));
     }
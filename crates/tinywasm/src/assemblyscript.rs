@@ -0,0 +1,252 @@
+//! AssemblyScript support
+//!
+//! [`AssemblyScript`] provides the `env.abort`, `env.trace` and `env.seed` imports that modules compiled
+//! by the AssemblyScript compiler expect, and [`AssemblyScriptMemoryExt`] reads the managed objects of its
+//! runtime (strings, `ArrayBuffer`s and typed arrays) from memory.
+//!
+//! Requires the `assemblyscript` feature.
+//!
+//! ```rust
+//! use tinywasm::assemblyscript::{AssemblyScript, AssemblyScriptMemoryExt};
+//! use tinywasm::{Imports, Module, Store};
+//!
+//! // a string object with the contents "hi": a 20 byte header with the class id 2 and the byte length 4
+//! let wasm = wat::parse_str(r#"(module
+//!     (import "env" "abort" (func $abort (param i32 i32 i32 i32)))
+//!     (memory (export "memory") 1)
+//!     (data (i32.const 0) "\00\00\00\00\00\00\00\00\00\00\00\00\02\00\00\00\04\00\00\00h\00i\00")
+//!     (func (export "fail") (call $abort (i32.const 20) (i32.const 0) (i32.const 1) (i32.const 2))))"#).unwrap();
+//!
+//! let mut imports = Imports::new();
+//! AssemblyScript::new().add_to_imports(&mut imports)?;
+//!
+//! let mut store = Store::default();
+//! let instance = Module::parse_bytes(&wasm)?.instantiate(&mut store, Some(imports))?;
+//! assert_eq!(instance.exported_memory(&mut store, "memory")?.load_as_string(20)?, "hi");
+//!
+//! let err = instance.exported_func::<(), ()>(&store, "fail")?.call(&mut store, ()).unwrap_err();
+//! assert!(err.to_string().contains("hi in <unknown>(1:2)"));
+//! # Ok::<(), tinywasm::Error>(())
+//! ```
+
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::fmt::Debug;
+
+use tinywasm_types::{FuncType, ValType};
+
+use crate::{Error, Extern, FuncContext, HostModule, Imports, MemoryRef, MemoryRefLoad, MemoryRefMut, Result, WasmPod};
+
+/// The class id of `ArrayBuffer`
+pub const ARRAY_BUFFER_ID: u32 = 1;
+/// The class id of `String`
+pub const STRING_ID: u32 = 2;
+
+type TraceHandler = Rc<dyn Fn(&str, &[f64])>;
+
+// every managed object is preceded by a header, which ends with the class id and the size of the object
+const HEADER_SIZE: u32 = 20;
+
+/// The header of a managed object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectHeader {
+    /// The class id of the object, see [`ARRAY_BUFFER_ID`] and [`STRING_ID`]
+    pub id: u32,
+    /// The size of the object in bytes, excluding the header
+    pub size: u32,
+}
+
+/// The host functions of the AssemblyScript runtime
+///
+/// By default, `trace` logs its message using the `log` crate and `seed` uses the virtual source of randomness
+/// of a [deterministic](crate::deterministic) store or, with the `std` feature, the current time.
+/// `abort` always fails the call with an error containing the message and location.
+#[derive(Default, Clone)]
+pub struct AssemblyScript {
+    trace: Option<TraceHandler>,
+    seed: Option<Rc<dyn Fn() -> f64>>,
+}
+
+impl Debug for AssemblyScript {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("AssemblyScript")
+            .field("trace", &self.trace.is_some())
+            .field("seed", &self.seed.is_some())
+            .finish()
+    }
+}
+
+impl AssemblyScript {
+    /// Create the host functions with the default handlers
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle calls to `trace` with the message and the given numbers
+    pub fn on_trace(mut self, trace: impl Fn(&str, &[f64]) + 'static) -> Self {
+        self.trace = Some(Rc::new(trace));
+        self
+    }
+
+    /// Provide the seed of `Math.random`
+    pub fn with_seed(mut self, seed: impl Fn() -> f64 + 'static) -> Self {
+        self.seed = Some(Rc::new(seed));
+        self
+    }
+
+    /// Define the host functions in the `env` module
+    pub fn add_to_imports(self, imports: &mut Imports) -> Result<()> {
+        imports.define_host_module("env", self)?;
+        Ok(())
+    }
+}
+
+impl HostModule for AssemblyScript {
+    fn into_externs(self) -> Vec<(&'static str, Extern)> {
+        let abort =
+            Extern::typed_func(|mut ctx: FuncContext<'_>, (message, file, line, column): (i32, i32, i32, i32)| {
+                let memory = ctx.exported_memory("memory")?;
+                let string = |ptr: i32| match ptr {
+                    0 => Ok(String::from("<unknown>")),
+                    ptr => memory.load_as_string(ptr as u32),
+                };
+                let (message, file) = (string(message)?, string(file)?);
+                Err::<(), _>(Error::Other(format!("abort: {message} in {file}({}:{})", line as u32, column as u32)))
+            });
+
+        let trace_handler = self.trace;
+        let ty =
+            FuncType { params: [[ValType::I32; 2].as_slice(), &[ValType::F64; 5]].concat().into(), results: [].into() };
+        let trace = Extern::func(&ty, move |mut ctx, args| {
+            let message = i32::try_from(args[0]).unwrap_or_default() as u32;
+            let message = ctx.exported_memory("memory")?.load_as_string(message)?;
+            let count = (i32::try_from(args[1]).unwrap_or_default() as u32).min(5) as usize;
+            let values: Vec<f64> = args[2..2 + count].iter().map(|&v| f64::try_from(v).unwrap_or_default()).collect();
+
+            if let Some(trace) = &trace_handler {
+                trace(&message, &values);
+                return Ok(vec![]);
+            }
+
+            crate::log::info!("trace: {message} {}", values.iter().map(f64::to_string).collect::<Vec<_>>().join(", "));
+            Ok(vec![])
+        });
+
+        let seed_handler = self.seed;
+        let seed = Extern::typed_func(move |mut ctx: FuncContext<'_>, ()| {
+            if let Some(seed) = &seed_handler {
+                return Ok(seed());
+            }
+            if let Some(env) = ctx.store_mut().deterministic_env() {
+                return Ok(env.next_u64() as f64);
+            }
+            time_seed()
+        });
+
+        vec![("abort", abort), ("trace", trace), ("seed", seed)]
+    }
+}
+
+#[cfg(feature = "std")]
+fn time_seed() -> Result<f64> {
+    let now = crate::std::time::SystemTime::now().duration_since(crate::std::time::UNIX_EPOCH);
+    Ok(now.map_or(0, |d| d.as_nanos() as u64) as f64)
+}
+
+// there is no source of randomness without the standard library
+#[cfg(not(feature = "std"))]
+fn time_seed() -> Result<f64> {
+    Err(Error::Other("no seed for Math.random available, use AssemblyScript::with_seed".to_string()))
+}
+
+/// Methods for reading the managed objects of the AssemblyScript runtime from memory
+///
+/// Objects are referenced by the pointer to their data, which is what AssemblyScript functions take and return.
+pub trait AssemblyScriptMemoryExt: MemoryRefLoad {
+    /// Read the header of a managed object
+    fn load_as_header(&self, ptr: u32) -> Result<ObjectHeader> {
+        let start =
+            ptr.checked_sub(HEADER_SIZE).ok_or_else(|| Error::Other(format!("invalid object pointer {ptr}")))?;
+        let header = self.load(start as usize + 12, 8)?;
+        Ok(ObjectHeader { id: u32::read_from(&header[..4]), size: u32::read_from(&header[4..]) })
+    }
+
+    /// Read a `String`
+    fn load_as_string(&self, ptr: u32) -> Result<String> {
+        let bytes = self.load_object(ptr, STRING_ID)?;
+        let units = bytes.chunks_exact(2).map(u16::read_from);
+        char::decode_utf16(units)
+            .collect::<Result<_, _>>()
+            .map_err(|_| Error::Other("Invalid UTF-16 string".to_string()))
+    }
+
+    /// Read the contents of an `ArrayBuffer`
+    fn load_as_array_buffer(&self, ptr: u32) -> Result<Vec<u8>> {
+        self.load_object(ptr, ARRAY_BUFFER_ID).map(<[u8]>::to_vec)
+    }
+
+    /// Read the elements of a typed array, e.g. `Int32Array` as `T = i32`
+    ///
+    /// Also works for the views `DataView` and `Uint8ClampedArray` and for `Array<T>` of numbers.
+    fn load_as_typed_array<T: WasmPod>(&self, ptr: u32) -> Result<Vec<T>> {
+        let header = self.load_as_header(ptr)?;
+        if header.size < 12 {
+            return Err(Error::Other(format!("object at {ptr} is not an array view")));
+        }
+
+        // the view points into its buffer: `buffer: usize`, `dataStart: usize`, `byteLength: i32`
+        let view = self.load(ptr as usize, 12)?;
+        let (data_start, byte_length) = (u32::read_from(&view[4..8]), u32::read_from(&view[8..]));
+        if byte_length as usize % T::SIZE != 0 {
+            return Err(Error::Other(format!("array view at {ptr} does not contain whole elements")));
+        }
+        Ok(self.load(data_start as usize, byte_length as usize)?.chunks_exact(T::SIZE).map(T::read_from).collect())
+    }
+
+    #[doc(hidden)]
+    fn load_object(&self, ptr: u32, id: u32) -> Result<&[u8]> {
+        let header = self.load_as_header(ptr)?;
+        if header.id != id {
+            return Err(Error::Other(format!("expected an object with class id {id} at {ptr}, found {}", header.id)));
+        }
+        self.load(ptr as usize, header.size as usize)
+    }
+}
+
+impl AssemblyScriptMemoryExt for MemoryRef<'_> {}
+impl AssemblyScriptMemoryExt for MemoryRefMut<'_> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryInstance;
+    use tinywasm_types::MemoryType;
+
+    #[test]
+    fn test_load_objects() {
+        let mut memory = MemoryInstance::new(MemoryType::new_32(1, None), 0);
+        let header = |id: u32, size: u32| [[0; 12].as_slice(), &id.to_le_bytes(), &size.to_le_bytes()].concat();
+
+        // a string with a surrogate pair at 20
+        let string: Vec<u8> = "a😀".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        memory.store(0, 20, &header(STRING_ID, string.len() as u32)).unwrap();
+        memory.store(20, string.len(), &string).unwrap();
+
+        // an array buffer at 100 and an `Int16Array` view of its last two elements at 200
+        memory.store(80, 20, &header(ARRAY_BUFFER_ID, 6)).unwrap();
+        memory.store(100, 6, &[1, 0, 2, 0, 0xff, 0xff]).unwrap();
+        memory.store(180, 20, &header(9, 12)).unwrap();
+        memory.store(200, 12, &[[100u32, 102, 4].map(u32::to_le_bytes)].concat().concat()).unwrap();
+
+        let memory = MemoryRef(&memory);
+        assert_eq!(memory.load_as_string(20).unwrap(), "a😀");
+        assert_eq!(memory.load_as_array_buffer(100).unwrap(), [1, 0, 2, 0, 0xff, 0xff]);
+        assert_eq!(memory.load_as_typed_array::<i16>(200).unwrap(), [2, -1]);
+        assert_eq!(memory.load_as_header(200).unwrap(), ObjectHeader { id: 9, size: 12 });
+        assert!(memory.load_as_string(100).is_err());
+        assert!(memory.load_as_typed_array::<i64>(200).is_err());
+        assert!(memory.load_as_header(10).is_err());
+    }
+}
@@ -0,0 +1,768 @@
+//! The canonical ABI, see <https://github.com/WebAssembly/component-model/blob/main/design/mvp/CanonicalABI.md>
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::cell::RefCell;
+
+use tinywasm_types::{ComponentFuncType, ComponentValType, FuncAddr, FuncType, MemAddr, StringEncoding};
+use tinywasm_types::{ValType, WasmValue};
+
+use super::instance::InstanceState;
+use super::{ResourceAny, Val};
+use crate::{Error, Result, Store};
+
+pub(crate) const MAX_FLAT_PARAMS: usize = 16;
+pub(crate) const MAX_FLAT_RESULTS: usize = 1;
+
+// strings of this length are UTF-16 encoded with the compact encoding
+const UTF16_TAG: u32 = 1 << 31;
+
+/// The canonical options of a lifted or lowered function, resolved to addresses in the store
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Options {
+    pub(crate) string_encoding: StringEncoding,
+    pub(crate) memory: Option<MemAddr>,
+    pub(crate) realloc: Option<FuncAddr>,
+    pub(crate) post_return: Option<FuncAddr>,
+}
+
+pub(crate) fn call_core(store: &mut Store, addr: FuncAddr, args: &[WasmValue]) -> Result<Vec<WasmValue>> {
+    store.get_func(addr).handle(addr).call(store, args)
+}
+
+/// The core function type a component function is lowered to
+pub(crate) fn lowered_type(ty: &ComponentFuncType) -> FuncType {
+    let mut params = flatten_all(ty.params.iter().map(|(_, ty)| ty));
+    let mut results = flatten_all(ty.results.iter());
+    if params.len() > MAX_FLAT_PARAMS {
+        params = vec![ValType::I32];
+    }
+    if results.len() > MAX_FLAT_RESULTS {
+        params.push(ValType::I32);
+        results = vec![];
+    }
+    FuncType { params: params.into(), results: results.into() }
+}
+
+fn flatten_all<'a>(types: impl Iterator<Item = &'a ComponentValType>) -> Vec<ValType> {
+    let mut flat = Vec::new();
+    types.for_each(|ty| flatten(ty, &mut flat));
+    flat
+}
+
+fn flatten(ty: &ComponentValType, flat: &mut Vec<ValType>) {
+    use ComponentValType::*;
+    match ty {
+        Bool | S8 | U8 | S16 | U16 | S32 | U32 | Char | Own(_) | Borrow(_) => flat.push(ValType::I32),
+        S64 | U64 => flat.push(ValType::I64),
+        F32 => flat.push(ValType::F32),
+        F64 => flat.push(ValType::F64),
+        String | List(_) => flat.extend([ValType::I32, ValType::I32]),
+        Record(fields) => fields.iter().for_each(|(_, ty)| flatten(ty, flat)),
+        Tuple(types) => types.iter().for_each(|ty| flatten(ty, flat)),
+        Flags(names) => flat.extend(core::iter::repeat(ValType::I32).take(names.len().div_ceil(32))),
+        Variant(_) | Enum(_) | Option(_) | Result { .. } => {
+            flat.push(ValType::I32);
+            flat.extend(joined_payload(ty));
+        }
+    }
+}
+
+// the flat types of a variant's payload, shared by all cases
+fn joined_payload(ty: &ComponentValType) -> Vec<ValType> {
+    let mut joined: Vec<ValType> = Vec::new();
+    for case in cases(ty).into_iter().flatten() {
+        let mut flat = Vec::new();
+        flatten(case, &mut flat);
+        for (i, ty) in flat.into_iter().enumerate() {
+            match joined.get_mut(i) {
+                Some(joined) => *joined = join(*joined, ty),
+                None => joined.push(ty),
+            }
+        }
+    }
+    joined
+}
+
+fn join(a: ValType, b: ValType) -> ValType {
+    match (a, b) {
+        (a, b) if a == b => a,
+        (ValType::I32, ValType::F32) | (ValType::F32, ValType::I32) => ValType::I32,
+        _ => ValType::I64,
+    }
+}
+
+// the payload types of the cases of variant-like types
+fn cases(ty: &ComponentValType) -> Vec<Option<&ComponentValType>> {
+    match ty {
+        ComponentValType::Variant(cases) => cases.iter().map(|(_, ty)| ty.as_ref()).collect(),
+        ComponentValType::Enum(names) => vec![None; names.len()],
+        ComponentValType::Option(ty) => vec![None, Some(ty)],
+        ComponentValType::Result { ok, err } => vec![ok.as_deref(), err.as_deref()],
+        _ => vec![],
+    }
+}
+
+fn discriminant_size(cases: usize) -> usize {
+    match cases {
+        0..=0xff => 1,
+        0x100..=0xffff => 2,
+        _ => 4,
+    }
+}
+
+fn align_to(offset: usize, align: usize) -> usize {
+    offset.div_ceil(align) * align
+}
+
+/// The size and alignment of a type in memory
+pub(crate) fn size_align(ty: &ComponentValType) -> (usize, usize) {
+    use ComponentValType::*;
+    match ty {
+        Bool | S8 | U8 => (1, 1),
+        S16 | U16 => (2, 2),
+        S32 | U32 | F32 | Char | Own(_) | Borrow(_) => (4, 4),
+        S64 | U64 | F64 => (8, 8),
+        String | List(_) => (8, 4),
+        Record(fields) => fields_size_align(fields.iter().map(|(_, ty)| ty)),
+        Tuple(types) => fields_size_align(types.iter()),
+        Flags(names) => match names.len() {
+            0 => (0, 1),
+            1..=8 => (1, 1),
+            9..=16 => (2, 2),
+            n => (4 * n.div_ceil(32), 4),
+        },
+        Variant(_) | Enum(_) | Option(_) | Result { .. } => {
+            let cases = cases(ty);
+            let (payload_offset, align) = payload_offset(&cases);
+            let payload_size = cases.iter().flatten().map(|ty| size_align(ty).0).max().unwrap_or(0);
+            (align_to(payload_offset + payload_size, align), align)
+        }
+    }
+}
+
+fn fields_size_align<'a>(types: impl Iterator<Item = &'a ComponentValType>) -> (usize, usize) {
+    let (mut size, mut align) = (0, 1);
+    for ty in types {
+        let (field_size, field_align) = size_align(ty);
+        size = align_to(size, field_align) + field_size;
+        align = align.max(field_align);
+    }
+    (align_to(size, align), align)
+}
+
+// the offset of the payload of a variant and the alignment of the variant
+fn payload_offset(cases: &[Option<&ComponentValType>]) -> (usize, usize) {
+    let discriminant = discriminant_size(cases.len());
+    let align = cases.iter().flatten().map(|ty| size_align(ty).1).fold(discriminant, usize::max);
+    (align_to(discriminant, align), align)
+}
+
+fn mismatch(ty: &ComponentValType, val: &Val) -> Error {
+    Error::Other(format!("expected a value of type {ty:?}, found {val:?}"))
+}
+
+fn case_index(ty: &ComponentValType, val: &Val) -> Result<(usize, Option<Val>)> {
+    let position = |names: Vec<&str>, name: &str| {
+        names.iter().position(|n| *n == name).ok_or_else(|| Error::Other(format!("unknown case {name} of {ty:?}")))
+    };
+    let payload = |payload: &Option<Box<Val>>| payload.as_deref().cloned();
+
+    Ok(match (ty, val) {
+        (ComponentValType::Variant(cases), Val::Variant(name, value)) => {
+            (position(cases.iter().map(|(n, _)| n.as_str()).collect(), name)?, payload(value))
+        }
+        (ComponentValType::Enum(names), Val::Enum(name)) => {
+            (position(names.iter().map(String::as_str).collect(), name)?, None)
+        }
+        (ComponentValType::Option(_), Val::Option(None)) => (0, None),
+        (ComponentValType::Option(_), Val::Option(Some(value))) => (1, Some((**value).clone())),
+        (ComponentValType::Result { .. }, Val::Result(Ok(value))) => (0, payload(value)),
+        (ComponentValType::Result { .. }, Val::Result(Err(value))) => (1, payload(value)),
+        _ => return Err(mismatch(ty, val)),
+    })
+}
+
+fn make_case(ty: &ComponentValType, index: usize, payload: Option<Val>) -> Result<Val> {
+    let boxed = payload.map(Box::new);
+    Ok(match ty {
+        ComponentValType::Variant(cases) => Val::Variant(cases[index].0.clone(), boxed),
+        ComponentValType::Enum(names) => Val::Enum(names[index].clone()),
+        ComponentValType::Option(_) => Val::Option(boxed),
+        ComponentValType::Result { .. } if index == 0 => Val::Result(Ok(boxed)),
+        ComponentValType::Result { .. } => Val::Result(Err(boxed)),
+        _ => return Err(Error::Other(format!("{ty:?} is not a variant"))),
+    })
+}
+
+fn flags_to_bits(names: &[String], val: &Val) -> Result<Vec<u32>> {
+    let mut bits = vec![0u32; names.len().div_ceil(32)];
+    let Val::Flags(set) = val else { return Err(Error::Other(format!("expected flags, found {val:?}"))) };
+    for flag in set {
+        let i = names.iter().position(|n| n == flag).ok_or_else(|| Error::Other(format!("unknown flag {flag}")))?;
+        bits[i / 32] |= 1 << (i % 32);
+    }
+    Ok(bits)
+}
+
+fn bits_to_flags(names: &[String], bits: &[u32]) -> Val {
+    let set = names.iter().enumerate().filter(|(i, _)| bits[i / 32] & (1 << (i % 32)) != 0);
+    Val::Flags(set.map(|(_, name)| name.clone()).collect())
+}
+
+// convert a value of a variant's joined payload to the type of the case
+fn unjoin(val: WasmValue, ty: ValType) -> WasmValue {
+    match (val, ty) {
+        (WasmValue::I64(v), ValType::I32) => WasmValue::I32(v as i32),
+        (WasmValue::I32(v), ValType::F32) => WasmValue::F32(f32::from_bits(v as u32)),
+        (WasmValue::I64(v), ValType::F32) => WasmValue::F32(f32::from_bits(v as u32)),
+        (WasmValue::I64(v), ValType::F64) => WasmValue::F64(f64::from_bits(v as u64)),
+        (val, _) => val,
+    }
+}
+
+// convert a value of a case's payload to the joined type of the variant
+fn rejoin(val: WasmValue, ty: ValType) -> WasmValue {
+    match (val, ty) {
+        (WasmValue::F32(v), ValType::I32) => WasmValue::I32(v.to_bits() as i32),
+        (WasmValue::I32(v), ValType::I64) => WasmValue::I64(v as u32 as i64),
+        (WasmValue::F32(v), ValType::I64) => WasmValue::I64(v.to_bits() as i64),
+        (WasmValue::F64(v), ValType::I64) => WasmValue::I64(v.to_bits() as i64),
+        (val, _) => val,
+    }
+}
+
+/// The context of lifting and lowering values for one call
+pub(crate) struct Cx<'a> {
+    pub(crate) store: &'a mut Store,
+    pub(crate) options: &'a Options,
+    pub(crate) state: &'a RefCell<InstanceState>,
+    // handles lent to the callee which are dropped when the call returns
+    pub(crate) borrows: Vec<u32>,
+}
+
+impl<'a> Cx<'a> {
+    pub(crate) fn new(store: &'a mut Store, options: &'a Options, state: &'a RefCell<InstanceState>) -> Self {
+        Self { store, options, state, borrows: Vec::new() }
+    }
+
+    /// Lift the parameters of a lowered function from its arguments
+    pub(crate) fn lift_params(&mut self, ty: &ComponentFuncType, args: &[WasmValue]) -> Result<Vec<Val>> {
+        let types: Vec<_> = ty.params.iter().map(|(_, ty)| ty.clone()).collect();
+        if flatten_all(types.iter()).len() > MAX_FLAT_PARAMS {
+            return self.load_tuple(&types, self.ptr(args.first())?);
+        }
+        let mut args = args.iter().copied();
+        types.iter().map(|ty| self.lift(ty, &mut args)).collect()
+    }
+
+    /// Lower the results of a lowered function, storing them behind the return pointer if necessary
+    pub(crate) fn lower_results(
+        &mut self,
+        ty: &ComponentFuncType,
+        results: &[Val],
+        args: &[WasmValue],
+    ) -> Result<Vec<WasmValue>> {
+        let types = check_count(&ty.results, results)?;
+        if flatten_all(types.iter()).len() > MAX_FLAT_RESULTS {
+            self.store_tuple(&types, results, self.ptr(args.last())?)?;
+            return Ok(vec![]);
+        }
+        self.lower_all(&types, results)
+    }
+
+    /// Lower the parameters of a lifted function, storing them in memory if necessary
+    pub(crate) fn lower_params(&mut self, ty: &ComponentFuncType, params: &[Val]) -> Result<Vec<WasmValue>> {
+        let types = check_count(&ty.params.iter().map(|(_, ty)| ty.clone()).collect::<Box<_>>(), params)?;
+        if flatten_all(types.iter()).len() > MAX_FLAT_PARAMS {
+            let (size, align) = fields_size_align(types.iter());
+            let ptr = self.realloc(0, 0, align, size)?;
+            self.store_tuple(&types, params, ptr)?;
+            return Ok(vec![WasmValue::I32(ptr as i32)]);
+        }
+        self.lower_all(&types, params)
+    }
+
+    /// Lift the results of a lifted function
+    pub(crate) fn lift_results(&mut self, ty: &ComponentFuncType, results: &[WasmValue]) -> Result<Vec<Val>> {
+        if flatten_all(ty.results.iter()).len() > MAX_FLAT_RESULTS {
+            return self.load_tuple(&ty.results, self.ptr(results.first())?);
+        }
+        let mut results = results.iter().copied();
+        ty.results.iter().map(|ty| self.lift(ty, &mut results)).collect()
+    }
+
+    fn lower_all(&mut self, types: &[ComponentValType], vals: &[Val]) -> Result<Vec<WasmValue>> {
+        let mut flat = Vec::new();
+        for (ty, val) in types.iter().zip(vals) {
+            self.lower(ty, val, &mut flat)?;
+        }
+        Ok(flat)
+    }
+
+    fn ptr(&self, val: Option<&WasmValue>) -> Result<u32> {
+        match val {
+            Some(WasmValue::I32(ptr)) => Ok(*ptr as u32),
+            _ => Err(Error::Other("expected a pointer".to_string())),
+        }
+    }
+
+    fn memory(&self) -> Result<MemAddr> {
+        self.options.memory.ok_or_else(|| Error::Other("the canonical option `memory` is required".to_string()))
+    }
+
+    fn load_bytes(&self, ptr: u32, len: usize) -> Result<&[u8]> {
+        self.store.get_mem(self.memory()?).load(ptr as usize, len)
+    }
+
+    fn store_bytes(&mut self, ptr: u32, bytes: &[u8]) -> Result<()> {
+        let memory = self.memory()?;
+        self.store.get_mem_mut(memory).store(ptr as usize, bytes.len(), bytes)
+    }
+
+    fn load_int<const N: usize>(&self, ptr: u32) -> Result<[u8; N]> {
+        Ok(self.load_bytes(ptr, N)?.try_into().expect("loaded N bytes"))
+    }
+
+    fn realloc(&mut self, old_ptr: u32, old_size: usize, align: usize, new_size: usize) -> Result<u32> {
+        let realloc = self
+            .options
+            .realloc
+            .ok_or_else(|| Error::Other("the canonical option `realloc` is required".to_string()))?;
+        let args = [old_ptr as i32, old_size as i32, align as i32, new_size as i32].map(WasmValue::I32);
+        let ptr = match call_core(self.store, realloc, &args)?.first() {
+            Some(WasmValue::I32(ptr)) => *ptr as u32,
+            _ => return Err(Error::Other("realloc did not return a pointer".to_string())),
+        };
+        if ptr as usize % align != 0 {
+            return Err(Error::Other(format!("realloc returned an unaligned pointer {ptr}")));
+        }
+        // make sure the allocation is inside of the memory
+        self.load_bytes(ptr, new_size)?;
+        Ok(ptr)
+    }
+
+    fn lift(&mut self, ty: &ComponentValType, vals: &mut dyn Iterator<Item = WasmValue>) -> Result<Val> {
+        let mut next = || vals.next().ok_or_else(|| Error::Other("not enough core values".to_string()));
+        let mut next_i32 = || match next()? {
+            WasmValue::I32(v) => Ok(v),
+            v => Err(Error::Other(format!("expected an i32, found {v:?}"))),
+        };
+
+        use ComponentValType as T;
+        Ok(match ty {
+            T::Bool => Val::Bool(next_i32()? != 0),
+            T::S8 => Val::S8(next_i32()? as i8),
+            T::U8 => Val::U8(next_i32()? as u8),
+            T::S16 => Val::S16(next_i32()? as i16),
+            T::U16 => Val::U16(next_i32()? as u16),
+            T::S32 => Val::S32(next_i32()?),
+            T::U32 => Val::U32(next_i32()? as u32),
+            T::Char => Val::Char(char_from(next_i32()? as u32)?),
+            T::Own(resource) => Val::Resource(self.lift_own(*resource, next_i32()? as u32)?),
+            T::Borrow(resource) => Val::Resource(self.lift_borrow(*resource, next_i32()? as u32)?),
+            T::String | T::List(_) => {
+                let (ptr, len) = (next_i32()? as u32, next_i32()? as u32);
+                self.load_list_or_string(ty, ptr, len)?
+            }
+            T::S64 | T::U64 | T::F32 | T::F64 => match (ty, next()?) {
+                (T::S64, WasmValue::I64(v)) => Val::S64(v),
+                (T::U64, WasmValue::I64(v)) => Val::U64(v as u64),
+                (T::F32, WasmValue::F32(v)) => Val::F32(v),
+                (T::F64, WasmValue::F64(v)) => Val::F64(v),
+                (ty, v) => return Err(Error::Other(format!("expected a core value for {ty:?}, found {v:?}"))),
+            },
+            T::Record(fields) => Val::Record(
+                fields.iter().map(|(name, ty)| Ok((name.clone(), self.lift(ty, vals)?))).collect::<Result<_>>()?,
+            ),
+            T::Tuple(types) => Val::Tuple(types.iter().map(|ty| self.lift(ty, vals)).collect::<Result<_>>()?),
+            T::Flags(names) => {
+                let bits =
+                    (0..names.len().div_ceil(32)).map(|_| next_i32().map(|v| v as u32)).collect::<Result<Vec<_>>>()?;
+                bits_to_flags(names, &bits)
+            }
+            T::Variant(_) | T::Enum(_) | T::Option(_) | T::Result { .. } => {
+                let index = next_i32()? as u32 as usize;
+                let joined: Vec<WasmValue> = joined_payload(ty).iter().map(|_| next()).collect::<Result<_>>()?;
+                let cases = cases(ty);
+                let case = cases.get(index).ok_or_else(|| Error::Other(format!("invalid case {index} of {ty:?}")))?;
+                let payload = match case {
+                    Some(case) => {
+                        let mut flat = Vec::new();
+                        flatten(case, &mut flat);
+                        let mut vals = joined.into_iter().zip(flat).map(|(val, ty)| unjoin(val, ty));
+                        Some(self.lift(case, &mut vals)?)
+                    }
+                    None => None,
+                };
+                make_case(ty, index, payload)?
+            }
+        })
+    }
+
+    fn lower(&mut self, ty: &ComponentValType, val: &Val, flat: &mut Vec<WasmValue>) -> Result<()> {
+        use ComponentValType as T;
+        let int = |v: i32| WasmValue::I32(v);
+        match (ty, val) {
+            (T::Bool, Val::Bool(v)) => flat.push(int(*v as i32)),
+            (T::S8, Val::S8(v)) => flat.push(int(*v as i32)),
+            (T::U8, Val::U8(v)) => flat.push(int(*v as i32)),
+            (T::S16, Val::S16(v)) => flat.push(int(*v as i32)),
+            (T::U16, Val::U16(v)) => flat.push(int(*v as i32)),
+            (T::S32, Val::S32(v)) => flat.push(int(*v)),
+            (T::U32, Val::U32(v)) => flat.push(int(*v as i32)),
+            (T::S64, Val::S64(v)) => flat.push(WasmValue::I64(*v)),
+            (T::U64, Val::U64(v)) => flat.push(WasmValue::I64(*v as i64)),
+            (T::F32, Val::F32(v)) => flat.push(WasmValue::F32(*v)),
+            (T::F64, Val::F64(v)) => flat.push(WasmValue::F64(*v)),
+            (T::Char, Val::Char(v)) => flat.push(int(*v as i32)),
+            (T::Own(resource), Val::Resource(v)) => flat.push(int(self.lower_own(*resource, v)? as i32)),
+            (T::Borrow(resource), Val::Resource(v)) => flat.push(int(self.lower_borrow(*resource, v)? as i32)),
+            (T::String | T::List(_), _) => {
+                let (ptr, len) = self.store_list_or_string(ty, val)?;
+                flat.extend([int(ptr as i32), int(len as i32)]);
+            }
+            (T::Record(fields), Val::Record(values)) if fields.len() == values.len() => {
+                for ((name, ty), (value_name, val)) in fields.iter().zip(values) {
+                    if name != value_name {
+                        return Err(Error::Other(format!("expected field {name}, found {value_name}")));
+                    }
+                    self.lower(ty, val, flat)?;
+                }
+            }
+            (T::Tuple(types), Val::Tuple(values)) if types.len() == values.len() => {
+                for (ty, val) in types.iter().zip(values) {
+                    self.lower(ty, val, flat)?;
+                }
+            }
+            (T::Flags(names), val) => flat.extend(flags_to_bits(names, val)?.into_iter().map(|v| int(v as i32))),
+            (T::Variant(_) | T::Enum(_) | T::Option(_) | T::Result { .. }, val) => {
+                let (index, payload) = case_index(ty, val)?;
+                flat.push(int(index as i32));
+                let joined = joined_payload(ty);
+                let mut payload_flat = Vec::new();
+                match (cases(ty)[index], &payload) {
+                    (Some(case), Some(payload)) => self.lower(case, payload, &mut payload_flat)?,
+                    (None, None) => {}
+                    _ => return Err(mismatch(ty, val)),
+                }
+                let mut payload_flat = payload_flat.into_iter();
+                for ty in joined {
+                    flat.push(payload_flat.next().map_or(WasmValue::default_for(ty), |val| rejoin(val, ty)));
+                }
+            }
+            _ => return Err(mismatch(ty, val)),
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, ty: &ComponentValType, ptr: u32) -> Result<Val> {
+        use ComponentValType as T;
+        let (_, align) = size_align(ty);
+        if ptr as usize % align != 0 {
+            return Err(Error::Other(format!("unaligned pointer {ptr} for {ty:?}")));
+        }
+
+        Ok(match ty {
+            T::Bool => Val::Bool(self.load_int::<1>(ptr)?[0] != 0),
+            T::S8 => Val::S8(self.load_int::<1>(ptr)?[0] as i8),
+            T::U8 => Val::U8(self.load_int::<1>(ptr)?[0]),
+            T::S16 => Val::S16(i16::from_le_bytes(self.load_int(ptr)?)),
+            T::U16 => Val::U16(u16::from_le_bytes(self.load_int(ptr)?)),
+            T::S32 => Val::S32(i32::from_le_bytes(self.load_int(ptr)?)),
+            T::U32 => Val::U32(u32::from_le_bytes(self.load_int(ptr)?)),
+            T::S64 => Val::S64(i64::from_le_bytes(self.load_int(ptr)?)),
+            T::U64 => Val::U64(u64::from_le_bytes(self.load_int(ptr)?)),
+            T::F32 => Val::F32(f32::from_le_bytes(self.load_int(ptr)?)),
+            T::F64 => Val::F64(f64::from_le_bytes(self.load_int(ptr)?)),
+            T::Char => Val::Char(char_from(u32::from_le_bytes(self.load_int(ptr)?))?),
+            T::Own(resource) => Val::Resource(self.lift_own(*resource, u32::from_le_bytes(self.load_int(ptr)?))?),
+            T::Borrow(resource) => Val::Resource(self.lift_borrow(*resource, u32::from_le_bytes(self.load_int(ptr)?))?),
+            T::String | T::List(_) => {
+                let data = u32::from_le_bytes(self.load_int(ptr)?);
+                let len = u32::from_le_bytes(self.load_int(ptr + 4)?);
+                self.load_list_or_string(ty, data, len)?
+            }
+            T::Record(fields) => {
+                let types: Vec<_> = fields.iter().map(|(_, ty)| ty.clone()).collect();
+                let values = self.load_tuple(&types, ptr)?;
+                Val::Record(fields.iter().map(|(name, _)| name.clone()).zip(values).collect())
+            }
+            T::Tuple(types) => Val::Tuple(self.load_tuple(types, ptr)?),
+            T::Flags(names) => {
+                let bits = match size_align(ty).0 {
+                    0 => vec![],
+                    1 => vec![self.load_int::<1>(ptr)?[0] as u32],
+                    2 => vec![u16::from_le_bytes(self.load_int(ptr)?) as u32],
+                    size => (0..size as u32 / 4)
+                        .map(|i| Ok(u32::from_le_bytes(self.load_int(ptr + i * 4)?)))
+                        .collect::<Result<_>>()?,
+                };
+                bits_to_flags(names, &bits)
+            }
+            T::Variant(_) | T::Enum(_) | T::Option(_) | T::Result { .. } => {
+                let cases = cases(ty);
+                let index = match discriminant_size(cases.len()) {
+                    1 => self.load_int::<1>(ptr)?[0] as usize,
+                    2 => u16::from_le_bytes(self.load_int(ptr)?) as usize,
+                    _ => u32::from_le_bytes(self.load_int(ptr)?) as usize,
+                };
+                let case = cases.get(index).ok_or_else(|| Error::Other(format!("invalid case {index} of {ty:?}")))?;
+                let payload = match case {
+                    Some(case) => Some(self.load(case, ptr + payload_offset(&cases).0 as u32)?),
+                    None => None,
+                };
+                make_case(ty, index, payload)?
+            }
+        })
+    }
+
+    fn store(&mut self, ty: &ComponentValType, val: &Val, ptr: u32) -> Result<()> {
+        use ComponentValType as T;
+        match (ty, val) {
+            (T::Bool, Val::Bool(v)) => self.store_bytes(ptr, &[*v as u8]),
+            (T::S8, Val::S8(v)) => self.store_bytes(ptr, &v.to_le_bytes()),
+            (T::U8, Val::U8(v)) => self.store_bytes(ptr, &v.to_le_bytes()),
+            (T::S16, Val::S16(v)) => self.store_bytes(ptr, &v.to_le_bytes()),
+            (T::U16, Val::U16(v)) => self.store_bytes(ptr, &v.to_le_bytes()),
+            (T::S32, Val::S32(v)) => self.store_bytes(ptr, &v.to_le_bytes()),
+            (T::U32, Val::U32(v)) => self.store_bytes(ptr, &v.to_le_bytes()),
+            (T::S64, Val::S64(v)) => self.store_bytes(ptr, &v.to_le_bytes()),
+            (T::U64, Val::U64(v)) => self.store_bytes(ptr, &v.to_le_bytes()),
+            (T::F32, Val::F32(v)) => self.store_bytes(ptr, &v.to_le_bytes()),
+            (T::F64, Val::F64(v)) => self.store_bytes(ptr, &v.to_le_bytes()),
+            (T::Char, Val::Char(v)) => self.store_bytes(ptr, &(*v as u32).to_le_bytes()),
+            (T::Own(resource), Val::Resource(v)) => {
+                let handle = self.lower_own(*resource, v)?;
+                self.store_bytes(ptr, &handle.to_le_bytes())
+            }
+            (T::Borrow(resource), Val::Resource(v)) => {
+                let handle = self.lower_borrow(*resource, v)?;
+                self.store_bytes(ptr, &handle.to_le_bytes())
+            }
+            (T::String | T::List(_), val) => {
+                let (data, len) = self.store_list_or_string(ty, val)?;
+                self.store_bytes(ptr, &data.to_le_bytes())?;
+                self.store_bytes(ptr + 4, &len.to_le_bytes())
+            }
+            (T::Record(fields), Val::Record(values)) if fields.len() == values.len() => {
+                if let Some(((name, _), (value_name, _))) = fields.iter().zip(values).find(|((a, _), (b, _))| a != b) {
+                    return Err(Error::Other(format!("expected field {name}, found {value_name}")));
+                }
+                let types: Vec<_> = fields.iter().map(|(_, ty)| ty.clone()).collect();
+                let values: Vec<_> = values.iter().map(|(_, val)| val.clone()).collect();
+                self.store_tuple(&types, &values, ptr)
+            }
+            (T::Tuple(types), Val::Tuple(values)) if types.len() == values.len() => {
+                self.store_tuple(types, values, ptr)
+            }
+            (T::Flags(names), val) => {
+                let bits = flags_to_bits(names, val)?;
+                match size_align(ty).0 {
+                    0 => Ok(()),
+                    1 => self.store_bytes(ptr, &[bits[0] as u8]),
+                    2 => self.store_bytes(ptr, &(bits[0] as u16).to_le_bytes()),
+                    _ => self.store_bytes(ptr, &bits.iter().flat_map(|b| b.to_le_bytes()).collect::<Vec<_>>()),
+                }
+            }
+            (T::Variant(_) | T::Enum(_) | T::Option(_) | T::Result { .. }, val) => {
+                let (index, payload) = case_index(ty, val)?;
+                let cases = cases(ty);
+                match discriminant_size(cases.len()) {
+                    1 => self.store_bytes(ptr, &[index as u8])?,
+                    2 => self.store_bytes(ptr, &(index as u16).to_le_bytes())?,
+                    _ => self.store_bytes(ptr, &(index as u32).to_le_bytes())?,
+                }
+                match (cases[index], payload) {
+                    (Some(case), Some(payload)) => self.store(case, &payload, ptr + payload_offset(&cases).0 as u32),
+                    (None, None) => Ok(()),
+                    _ => Err(mismatch(ty, val)),
+                }
+            }
+            _ => Err(mismatch(ty, val)),
+        }
+    }
+
+    fn load_tuple(&mut self, types: &[ComponentValType], ptr: u32) -> Result<Vec<Val>> {
+        let mut offset = 0;
+        let mut values = Vec::with_capacity(types.len());
+        for ty in types {
+            let (size, align) = size_align(ty);
+            offset = align_to(offset, align);
+            values.push(self.load(ty, ptr + offset as u32)?);
+            offset += size;
+        }
+        Ok(values)
+    }
+
+    fn store_tuple(&mut self, types: &[ComponentValType], values: &[Val], ptr: u32) -> Result<()> {
+        let mut offset = 0;
+        for (ty, val) in types.iter().zip(values) {
+            let (size, align) = size_align(ty);
+            offset = align_to(offset, align);
+            self.store(ty, val, ptr + offset as u32)?;
+            offset += size;
+        }
+        Ok(())
+    }
+
+    fn load_list_or_string(&mut self, ty: &ComponentValType, ptr: u32, len: u32) -> Result<Val> {
+        let ComponentValType::List(elem) = ty else { return self.load_string(ptr, len).map(Val::String) };
+        let (size, align) = size_align(elem);
+        if ptr as usize % align != 0 {
+            return Err(Error::Other(format!("unaligned list pointer {ptr}")));
+        }
+        self.load_bytes(ptr, size * len as usize)?;
+        (0..len).map(|i| self.load(elem, ptr + i * size as u32)).collect::<Result<_>>().map(Val::List)
+    }
+
+    fn store_list_or_string(&mut self, ty: &ComponentValType, val: &Val) -> Result<(u32, u32)> {
+        match (ty, val) {
+            (ComponentValType::String, Val::String(string)) => self.store_string(string),
+            (ComponentValType::List(elem), Val::List(items)) => {
+                let (size, align) = size_align(elem);
+                let ptr = self.realloc(0, 0, align, size * items.len())?;
+                for (i, item) in items.iter().enumerate() {
+                    self.store(elem, item, ptr + (i * size) as u32)?;
+                }
+                Ok((ptr, items.len() as u32))
+            }
+            _ => Err(mismatch(ty, val)),
+        }
+    }
+
+    fn load_string(&self, ptr: u32, len: u32) -> Result<String> {
+        let utf16 = |len: u32| -> Result<String> {
+            if ptr % 2 != 0 {
+                return Err(Error::Other(format!("unaligned UTF-16 string pointer {ptr}")));
+            }
+            let bytes = self.load_bytes(ptr, 2 * len as usize)?;
+            let units = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]]));
+            char::decode_utf16(units).collect::<Result<_, _>>().map_err(|_| Error::Other("invalid UTF-16".into()))
+        };
+
+        match self.options.string_encoding {
+            StringEncoding::Utf8 => String::from_utf8(self.load_bytes(ptr, len as usize)?.to_vec())
+                .map_err(|_| Error::Other("invalid UTF-8".to_string())),
+            StringEncoding::Utf16 => utf16(len),
+            StringEncoding::CompactUtf16 if len & UTF16_TAG != 0 => utf16(len ^ UTF16_TAG),
+            StringEncoding::CompactUtf16 => {
+                Ok(self.load_bytes(ptr, len as usize)?.iter().map(|&b| b as char).collect())
+            }
+        }
+    }
+
+    fn store_string(&mut self, string: &str) -> Result<(u32, u32)> {
+        let latin1 = string.chars().all(|c| (c as u32) < 0x100);
+        match self.options.string_encoding {
+            StringEncoding::Utf8 => {
+                let ptr = self.realloc(0, 0, 1, string.len())?;
+                self.store_bytes(ptr, string.as_bytes())?;
+                Ok((ptr, string.len() as u32))
+            }
+            StringEncoding::CompactUtf16 if latin1 => {
+                let bytes: Vec<u8> = string.chars().map(|c| c as u8).collect();
+                let ptr = self.realloc(0, 0, 2, bytes.len())?;
+                self.store_bytes(ptr, &bytes)?;
+                Ok((ptr, bytes.len() as u32))
+            }
+            encoding => {
+                let bytes: Vec<u8> = string.encode_utf16().flat_map(u16::to_le_bytes).collect();
+                let ptr = self.realloc(0, 0, 2, bytes.len())?;
+                self.store_bytes(ptr, &bytes)?;
+                let len = bytes.len() as u32 / 2;
+                Ok((ptr, if encoding == StringEncoding::CompactUtf16 { len | UTF16_TAG } else { len }))
+            }
+        }
+    }
+
+    fn lift_own(&mut self, resource: u32, handle: u32) -> Result<ResourceAny> {
+        let handle = self.state.borrow_mut().remove_handle(handle, resource, true)?;
+        Ok(ResourceAny { resource, rep: handle.rep, owned: true })
+    }
+
+    fn lift_borrow(&mut self, resource: u32, handle: u32) -> Result<ResourceAny> {
+        let rep = self.state.borrow().handle(handle, resource)?.rep;
+        Ok(ResourceAny { resource, rep, owned: false })
+    }
+
+    fn lower_own(&mut self, resource: u32, val: &ResourceAny) -> Result<u32> {
+        self.check_resource(resource, val)?;
+        if !val.owned {
+            return Err(Error::Other(format!("expected an owned handle, found {val:?}")));
+        }
+        Ok(self.state.borrow_mut().insert_handle(resource, val.rep, true))
+    }
+
+    fn lower_borrow(&mut self, resource: u32, val: &ResourceAny) -> Result<u32> {
+        self.check_resource(resource, val)?;
+        let mut state = self.state.borrow_mut();
+        // the implementation of a resource gets the representation instead of a handle
+        if state.is_defined(resource) {
+            return Ok(val.rep);
+        }
+        let handle = state.insert_handle(resource, val.rep, false);
+        self.borrows.push(handle);
+        Ok(handle)
+    }
+
+    // the host doesn't know the indices of the resources it implements, their type comes from the signature
+    fn check_resource(&self, resource: u32, val: &ResourceAny) -> Result<()> {
+        match val.resource == resource || !self.state.borrow().is_defined(resource) {
+            true => Ok(()),
+            false => Err(Error::Other(format!("expected a handle to resource {resource}, found {val:?}"))),
+        }
+    }
+
+    /// Drop the handles lent to the callee
+    pub(crate) fn end_call(self) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        for handle in self.borrows {
+            state.drop_borrow(handle);
+        }
+        Ok(())
+    }
+}
+
+fn check_count(types: &[ComponentValType], vals: &[Val]) -> Result<Box<[ComponentValType]>> {
+    if types.len() != vals.len() {
+        return Err(Error::Other(format!("expected {} values, found {}", types.len(), vals.len())));
+    }
+    Ok(types.into())
+}
+
+fn char_from(code: u32) -> Result<char> {
+    char::from_u32(code).ok_or_else(|| Error::Other(format!("invalid char {code:#x}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ComponentValType as T;
+
+    #[test]
+    fn test_layout() {
+        let record = T::Record([("a".into(), T::U8), ("b".into(), T::U32), ("c".into(), T::U16)].into());
+        assert_eq!(size_align(&record), (12, 4));
+
+        let variant = T::Variant([("a".into(), Some(T::F32)), ("b".into(), Some(T::U64)), ("c".into(), None)].into());
+        assert_eq!(size_align(&variant), (16, 8));
+        let mut flat = Vec::new();
+        flatten(&variant, &mut flat);
+        assert_eq!(flat, [ValType::I32, ValType::I64]);
+
+        let result = T::Result { ok: Some(Box::new(T::F32)), err: Some(Box::new(T::S32)) };
+        let mut flat = Vec::new();
+        flatten(&result, &mut flat);
+        assert_eq!(flat, [ValType::I32, ValType::I32]);
+
+        let flags = T::Flags((0..40).map(|i| format!("f{i}")).collect());
+        assert_eq!(size_align(&flags), (8, 4));
+        assert_eq!(size_align(&T::Option(Box::new(T::String))), (12, 4));
+
+        let ty = ComponentFuncType { params: [("s".into(), T::String)].into(), results: [T::String].into() };
+        assert_eq!(lowered_type(&ty), FuncType { params: [ValType::I32; 3].into(), results: [].into() });
+    }
+}
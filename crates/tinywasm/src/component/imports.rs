@@ -0,0 +1,134 @@
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+use super::{ComponentTuple, Val};
+use crate::{FuncContext, Result};
+
+pub(crate) type HostComponentFunc = Rc<dyn Fn(FuncContext<'_>, &[Val]) -> Result<Vec<Val>>>;
+pub(crate) type ResourceDropFunc = Rc<dyn Fn(FuncContext<'_>, u32) -> Result<()>>;
+
+/// Imports for a component instance
+///
+/// Items are named by their path: `name` for items imported directly by the component, `instance#name` for items
+/// of an imported instance, e.g. `wasi:cli/environment@0.2.0#get-arguments`.
+///
+/// Interfaces are versioned, e.g. `wasi:io/streams@0.2.0`. If there is no item with the exact version, one with a
+/// semver-compatible version is used instead: the same major version, or the same minor version for `0.x`.
+///
+/// Host functions take and return component-level [`Val`]s, which are lifted from and lowered into the memory
+/// of the calling core module using the canonical ABI. Their types are checked against the import when they
+/// are called.
+///
+/// ## Example
+/// ```rust
+/// use tinywasm::component::{ComponentImports, Val};
+///
+/// let mut imports = ComponentImports::new();
+/// imports
+///     .define_typed_func("host#greet", |_ctx, (name,): (String,)| Ok((format!("Hello, {name}!"),)))?
+///     .define_func("log", |_ctx, args| {
+///         if let [Val::String(message)] = args {
+///             println!("{message}");
+///         }
+///         Ok(vec![])
+///     })?;
+/// # Ok::<(), tinywasm::Error>(())
+/// ```
+#[derive(Default, Clone)]
+pub struct ComponentImports {
+    pub(crate) funcs: BTreeMap<String, HostComponentFunc>,
+    pub(crate) resources: BTreeMap<String, ResourceDropFunc>,
+}
+
+impl Debug for ComponentImports {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ComponentImports")
+            .field("funcs", &self.funcs.keys().collect::<Vec<_>>())
+            .field("resources", &self.resources.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ComponentImports {
+    /// Create a new empty import set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define a host function taking and returning [`Val`]s
+    pub fn define_func(
+        &mut self,
+        name: &str,
+        func: impl Fn(FuncContext<'_>, &[Val]) -> Result<Vec<Val>> + 'static,
+    ) -> Result<&mut Self> {
+        self.funcs.insert(name.to_string(), Rc::new(func));
+        Ok(self)
+    }
+
+    /// Define a host function taking and returning tuples of Rust values, see [`ComponentTuple`]
+    pub fn define_typed_func<P: ComponentTuple, R: ComponentTuple>(
+        &mut self,
+        name: &str,
+        func: impl Fn(FuncContext<'_>, P) -> Result<R> + 'static,
+    ) -> Result<&mut Self> {
+        self.define_func(name, move |ctx, args| Ok(func(ctx, P::from_vals(args.to_vec())?)?.into_vals()))
+    }
+
+    /// Define a resource implemented by the host
+    ///
+    /// The host chooses the representations of its resources when it passes them to the component, `drop` is
+    /// called with the representation when the component drops an owned handle. Resources don't need to be
+    /// defined if nothing has to happen when they are dropped.
+    pub fn define_resource(
+        &mut self,
+        name: &str,
+        drop: impl Fn(FuncContext<'_>, u32) -> Result<()> + 'static,
+    ) -> Result<&mut Self> {
+        self.resources.insert(name.to_string(), Rc::new(drop));
+        Ok(self)
+    }
+}
+
+// find an item by its path, falling back to a semver-compatible version of its interface
+pub(crate) fn lookup<'a, T>(items: &'a BTreeMap<String, T>, name: &str) -> Option<&'a T> {
+    if let Some(item) = items.get(name) {
+        return Some(item);
+    }
+
+    let (path, item) = split_path(name);
+    let (interface, version) = path.split_once('@')?;
+    items.iter().find_map(|(key, value)| {
+        let (key_path, key_item) = split_path(key);
+        let (key_interface, key_version) = key_path.split_once('@')?;
+        (key_item == item && key_interface == interface && compatible(key_version, version)).then_some(value)
+    })
+}
+
+fn split_path(name: &str) -> (&str, Option<&str>) {
+    name.split_once('#').map_or((name, None), |(path, item)| (path, Some(item)))
+}
+
+// the same major version, or the same minor version for `0.x`
+fn compatible(a: &str, b: &str) -> bool {
+    let (mut a, mut b) = (a.split(['.', '-', '+']), b.split(['.', '-', '+']));
+    let (a_major, b_major) = (a.next(), b.next());
+    a_major == b_major && (a_major != Some("0") || a.next() == b.next())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup() {
+        let items = BTreeMap::from([("wasi:io/streams@0.2.0#write".to_string(), 1), ("log".to_string(), 2)]);
+        assert_eq!(lookup(&items, "wasi:io/streams@0.2.0#write"), Some(&1));
+        assert_eq!(lookup(&items, "wasi:io/streams@0.2.3#write"), Some(&1));
+        assert_eq!(lookup(&items, "wasi:io/streams@0.3.0#write"), None);
+        assert_eq!(lookup(&items, "wasi:io/streams@0.2.1#read"), None);
+        assert_eq!(lookup(&items, "log"), Some(&2));
+    }
+}
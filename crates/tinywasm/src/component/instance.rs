@@ -0,0 +1,374 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+use core::cell::RefCell;
+
+use tinywasm_types::*;
+
+use super::abi::{call_core, lowered_type, Cx, Options};
+use super::imports::{lookup, ComponentImports, ResourceDropFunc};
+use super::{ComponentFunc, ComponentFuncTyped, ComponentTuple, FuncKind, ResourceAny};
+use crate::{Error, Extern, ExternName, FuncContext, Imports, Module, ModuleInstance, Result, Store};
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Handle {
+    pub(crate) resource: u32,
+    pub(crate) rep: u32,
+    pub(crate) own: bool,
+}
+
+/// The resources and handles of a component instance
+pub(crate) struct InstanceState {
+    store_id: usize,
+    resources: Box<[ComponentResource]>,
+    dtors: Vec<Option<FuncAddr>>,
+    host_drops: Vec<Option<ResourceDropFunc>>,
+    // index 0 is never a valid handle
+    handles: Vec<Option<Handle>>,
+    free: Vec<u32>,
+}
+
+impl core::fmt::Debug for InstanceState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("InstanceState")
+            .field("store_id", &self.store_id)
+            .field("resources", &self.resources)
+            .field("dtors", &self.dtors)
+            .field("handles", &self.handles)
+            .finish()
+    }
+}
+
+impl InstanceState {
+    fn new(component: &TinyWasmComponent, imports: &ComponentImports, store: &Store) -> Self {
+        let host_drops = component.resources.iter().map(|r| lookup(&imports.resources, &r.name).cloned()).collect();
+        Self {
+            store_id: store.id(),
+            resources: component.resources.clone(),
+            dtors: vec![None; component.resources.len()],
+            host_drops,
+            handles: vec![None],
+            free: Vec::new(),
+        }
+    }
+
+    pub(crate) fn check_store(&self, store: &Store) -> Result<()> {
+        match self.store_id == store.id() {
+            true => Ok(()),
+            false => Err(Error::InvalidStore),
+        }
+    }
+
+    pub(crate) fn is_defined(&self, resource: u32) -> bool {
+        self.resources[resource as usize].defined
+    }
+
+    pub(crate) fn insert_handle(&mut self, resource: u32, rep: u32, own: bool) -> u32 {
+        let handle = Some(Handle { resource, rep, own });
+        match self.free.pop() {
+            Some(idx) => {
+                self.handles[idx as usize] = handle;
+                idx
+            }
+            None => {
+                self.handles.push(handle);
+                self.handles.len() as u32 - 1
+            }
+        }
+    }
+
+    pub(crate) fn handle(&self, idx: u32, resource: u32) -> Result<Handle> {
+        match self.handles.get(idx as usize).copied().flatten() {
+            Some(handle) if handle.resource == resource => Ok(handle),
+            _ => Err(Error::Other(format!("invalid handle {idx} to resource {resource}"))),
+        }
+    }
+
+    pub(crate) fn remove_handle(&mut self, idx: u32, resource: u32, own: bool) -> Result<Handle> {
+        let handle = self.handle(idx, resource)?;
+        if own && !handle.own {
+            return Err(Error::Other(format!("handle {idx} is borrowed, expected an owned handle")));
+        }
+        self.handles[idx as usize] = None;
+        self.free.push(idx);
+        Ok(handle)
+    }
+
+    pub(crate) fn drop_borrow(&mut self, idx: u32) {
+        if self.handles.get(idx as usize).copied().flatten().is_some_and(|h| !h.own) {
+            self.handles[idx as usize] = None;
+            self.free.push(idx);
+        }
+    }
+}
+
+// call the destructor of a resource
+fn drop_rep(ctx: FuncContext<'_>, state: &RefCell<InstanceState>, resource: u32, rep: u32) -> Result<()> {
+    let (dtor, host_drop) = {
+        let state = state.borrow();
+        (state.dtors[resource as usize], state.host_drops[resource as usize].clone())
+    };
+    match (dtor, host_drop) {
+        (Some(dtor), _) => call_core(ctx.store, dtor, &[WasmValue::I32(rep as i32)]).map(|_| ()),
+        (None, Some(host_drop)) => host_drop(ctx, rep),
+        (None, None) => Ok(()),
+    }
+}
+
+/// An item of a component instance
+#[derive(Debug, Clone)]
+pub(crate) enum ComponentItem {
+    Func(ComponentFunc),
+    Instance(Rc<BTreeMap<String, ComponentItem>>),
+}
+
+#[derive(Debug)]
+enum CoreInstance {
+    Module(ModuleInstance),
+    Exports(Vec<(String, ExternVal)>),
+}
+
+impl CoreInstance {
+    fn export(&self, name: &str) -> Result<ExternVal> {
+        let export = match self {
+            Self::Module(instance) => instance.export_addr(name),
+            Self::Exports(exports) => exports.iter().find(|(n, _)| n == name).map(|(_, export)| export.clone()),
+        };
+        export.ok_or_else(|| Error::Other(format!("Export not found: {name}")))
+    }
+}
+
+/// An instance of a WebAssembly component
+///
+/// Created by [`Component::instantiate`](super::Component::instantiate).
+#[derive(Debug, Clone)]
+pub struct ComponentInstance {
+    exports: Rc<BTreeMap<String, ComponentItem>>,
+    state: Rc<RefCell<InstanceState>>,
+}
+
+impl ComponentInstance {
+    fn item(&self, name: &str) -> Option<&ComponentItem> {
+        let mut path = name.split('#');
+        let mut item = lookup(&self.exports, path.next()?)?;
+        for name in path {
+            let ComponentItem::Instance(instance) = item else { return None };
+            item = instance.get(name)?;
+        }
+        Some(item)
+    }
+
+    /// Get an exported function by its path, e.g. `greet` or `wasi:cli/run@0.2.0#run`
+    pub fn exported_func_untyped(&self, name: &str) -> Result<ComponentFunc> {
+        match self.item(name) {
+            Some(ComponentItem::Func(func)) => Ok(func.clone()),
+            Some(_) => Err(Error::Other(format!("Export is not a function: {name}"))),
+            None => Err(Error::Other(format!("Export not found: {name}"))),
+        }
+    }
+
+    /// Get a typed exported function by its path
+    pub fn exported_func<P: ComponentTuple, R: ComponentTuple>(&self, name: &str) -> Result<ComponentFuncTyped<P, R>> {
+        Ok(self.exported_func_untyped(name)?.typed())
+    }
+
+    /// Get the index of a resource by the name it's imported or exported as
+    pub fn resource(&self, name: &str) -> Option<u32> {
+        self.state.borrow().resources.iter().position(|r| r.name == name).map(|idx| idx as u32)
+    }
+
+    /// Drop a resource owned by the host, calling its destructor
+    ///
+    /// Resources are not dropped automatically when the host drops a [`ResourceAny`].
+    pub fn drop_resource(&self, store: &mut Store, resource: ResourceAny) -> Result<()> {
+        if !resource.owned {
+            return Err(Error::Other("borrowed resources can't be dropped".to_string()));
+        }
+        self.state.borrow().check_store(store)?;
+        let ctx = FuncContext { store, module_addr: None, func_addr: 0 };
+        drop_rep(ctx, &self.state, resource.resource, resource.rep)
+    }
+}
+
+#[derive(Default)]
+struct IndexSpaces {
+    core_funcs: Vec<FuncAddr>,
+    core_tables: Vec<TableAddr>,
+    core_memories: Vec<MemAddr>,
+    core_globals: Vec<GlobalAddr>,
+    core_instances: Vec<CoreInstance>,
+    funcs: Vec<ComponentFunc>,
+    instances: Vec<Rc<BTreeMap<String, ComponentItem>>>,
+}
+
+impl IndexSpaces {
+    fn core(&self, kind: ExternalKind, idx: u32) -> ExternVal {
+        let space = match kind {
+            ExternalKind::Func => &self.core_funcs,
+            ExternalKind::Table => &self.core_tables,
+            ExternalKind::Memory => &self.core_memories,
+            ExternalKind::Global => &self.core_globals,
+        };
+        ExternVal::new(kind, space[idx as usize])
+    }
+
+    fn item(&self, kind: ComponentExternalKind, idx: u32) -> ComponentItem {
+        match kind {
+            ComponentExternalKind::Func => ComponentItem::Func(self.funcs[idx as usize].clone()),
+            ComponentExternalKind::Instance => ComponentItem::Instance(self.instances[idx as usize].clone()),
+        }
+    }
+
+    fn push(&mut self, item: ComponentItem) {
+        match item {
+            ComponentItem::Func(func) => self.funcs.push(func),
+            ComponentItem::Instance(instance) => self.instances.push(instance),
+        }
+    }
+
+    fn options(&self, options: &CanonicalOptions) -> Options {
+        Options {
+            string_encoding: options.string_encoding,
+            memory: options.memory.map(|idx| self.core_memories[idx as usize]),
+            realloc: options.realloc.map(|idx| self.core_funcs[idx as usize]),
+            post_return: options.post_return.map(|idx| self.core_funcs[idx as usize]),
+        }
+    }
+}
+
+pub(crate) fn instantiate(
+    component: &TinyWasmComponent,
+    store: &mut Store,
+    imports: &ComponentImports,
+) -> Result<ComponentInstance> {
+    let _span = crate::tracing::info_span!("instantiate_component").entered();
+    let state = Rc::new(RefCell::new(InstanceState::new(component, imports, store)));
+    let mut spaces = IndexSpaces::default();
+    let mut exports = BTreeMap::new();
+
+    let host_func = |name: &str| {
+        lookup(&imports.funcs, name).cloned().ok_or_else(|| Error::Other(format!("missing component import {name}")))
+    };
+
+    for initializer in component.initializers.iter() {
+        match initializer {
+            ComponentInitializer::CoreInstantiate { module, args } => {
+                let mut module_imports = Imports::new();
+                for (name, instance) in args.iter() {
+                    match &spaces.core_instances[*instance as usize] {
+                        CoreInstance::Module(instance) => module_imports.link_module(name, instance.id())?,
+                        CoreInstance::Exports(exports) => {
+                            exports.iter().fold(&mut module_imports, |imports, (n, v)| {
+                                imports.define_addr(ExternName::new(name, n), v.clone())
+                            })
+                        }
+                    };
+                }
+
+                let module = Module::from(&component.modules[*module as usize]);
+                let instance = ModuleInstance::instantiate(store, module, Some(module_imports))?;
+                // only the start function, `_start` is called by the component
+                if let Some(start) = instance.0.func_start {
+                    call_core(store, instance.resolve_func_addr(start), &[])?;
+                }
+                spaces.core_instances.push(CoreInstance::Module(instance));
+            }
+            ComponentInitializer::CoreInstanceFromExports(items) => {
+                let exports = items.iter().map(|e| (e.name.to_string(), spaces.core(e.kind, e.index))).collect();
+                spaces.core_instances.push(CoreInstance::Exports(exports));
+            }
+            ComponentInitializer::CoreAlias { instance, name, .. } => {
+                match spaces.core_instances[*instance as usize].export(name)? {
+                    ExternVal::Func(addr) => spaces.core_funcs.push(addr),
+                    ExternVal::Table(addr) => spaces.core_tables.push(addr),
+                    ExternVal::Memory(addr) => spaces.core_memories.push(addr),
+                    ExternVal::Global(addr) => spaces.core_globals.push(addr),
+                }
+            }
+            ComponentInitializer::Lower { func, ty, options } => {
+                let (target, ty, state) = (spaces.funcs[*func as usize].clone(), ty.clone(), state.clone());
+                let options = spaces.options(options);
+                let lowered = Extern::func(&lowered_type(&ty), move |ctx, args| {
+                    let params = Cx::new(&mut *ctx.store, &options, &state).lift_params(&ty, args)?;
+                    let callee = FuncContext { store: &mut *ctx.store, ..ctx };
+                    let results = target.call_from(callee, &params)?;
+                    let mut cx = Cx::new(ctx.store, &options, &state);
+                    let results = cx.lower_results(&ty, &results, args)?;
+                    cx.end_call()?;
+                    Ok(results)
+                });
+                spaces.core_funcs.push(store.add_host_func(lowered)?.addr);
+            }
+            ComponentInitializer::ResourceNew(resource) => {
+                let (resource, state) = (*resource, state.clone());
+                let ty = FuncType { params: [ValType::I32].into(), results: [ValType::I32].into() };
+                let func = Extern::func(&ty, move |_, args| {
+                    let rep = i32::try_from(args[0]).unwrap_or_default() as u32;
+                    Ok(vec![WasmValue::I32(state.borrow_mut().insert_handle(resource, rep, true) as i32)])
+                });
+                spaces.core_funcs.push(store.add_host_func(func)?.addr);
+            }
+            ComponentInitializer::ResourceRep(resource) => {
+                let (resource, state) = (*resource, state.clone());
+                let ty = FuncType { params: [ValType::I32].into(), results: [ValType::I32].into() };
+                let func = Extern::func(&ty, move |_, args| {
+                    let handle = i32::try_from(args[0]).unwrap_or_default() as u32;
+                    Ok(vec![WasmValue::I32(state.borrow().handle(handle, resource)?.rep as i32)])
+                });
+                spaces.core_funcs.push(store.add_host_func(func)?.addr);
+            }
+            ComponentInitializer::ResourceDrop(resource) => {
+                let (resource, state) = (*resource, state.clone());
+                let ty = FuncType { params: [ValType::I32].into(), results: [].into() };
+                let func = Extern::func(&ty, move |ctx, args| {
+                    let handle = i32::try_from(args[0]).unwrap_or_default() as u32;
+                    let handle = state.borrow_mut().remove_handle(handle, resource, false)?;
+                    if handle.own {
+                        drop_rep(ctx, &state, resource, handle.rep)?;
+                    }
+                    Ok(vec![])
+                });
+                spaces.core_funcs.push(store.add_host_func(func)?.addr);
+            }
+            ComponentInitializer::DefineResource { resource, dtor } => {
+                state.borrow_mut().dtors[*resource as usize] = dtor.map(|idx| spaces.core_funcs[idx as usize]);
+            }
+            ComponentInitializer::Lift { core_func, ty, options } => {
+                let kind =
+                    FuncKind::Lifted { func: spaces.core_funcs[*core_func as usize], options: spaces.options(options) };
+                spaces.funcs.push(ComponentFunc { ty: Rc::new(ty.clone()), kind, state: state.clone() });
+            }
+            ComponentInitializer::ImportFunc { name, ty } => {
+                let kind = FuncKind::Host(host_func(name)?);
+                spaces.funcs.push(ComponentFunc { ty: Rc::new(ty.clone()), kind, state: state.clone() });
+            }
+            ComponentInitializer::ImportInstance { name, funcs } => {
+                let mut instance = BTreeMap::new();
+                for (func, ty) in funcs.iter() {
+                    let kind = FuncKind::Host(host_func(&format!("{name}#{func}"))?);
+                    let func_item = ComponentFunc { ty: Rc::new(ty.clone()), kind, state: state.clone() };
+                    instance.insert(func.clone(), ComponentItem::Func(func_item));
+                }
+                spaces.instances.push(Rc::new(instance));
+            }
+            ComponentInitializer::Alias { instance, name, .. } => {
+                let item = spaces.instances[*instance as usize].get(name).cloned();
+                spaces.push(item.ok_or_else(|| Error::Other(format!("Export not found: {name}")))?);
+            }
+            ComponentInitializer::InstanceFromExports(items) => {
+                let instance = items.iter().map(|e| (e.name.clone(), spaces.item(e.kind, e.index))).collect();
+                spaces.instances.push(Rc::new(instance));
+            }
+            ComponentInitializer::Export(export) => {
+                let item = spaces.item(export.kind, export.index);
+                exports.insert(export.name.clone(), item.clone());
+                spaces.push(item);
+            }
+        }
+    }
+
+    Ok(ComponentInstance { exports: Rc::new(exports), state })
+}
@@ -0,0 +1,294 @@
+//! Component model support
+//!
+//! A [`Component`] is parsed from a WebAssembly component binary and instantiated into a
+//! [`ComponentInstance`]. Its exported functions take and return component-level values ([`Val`]) which
+//! are passed to the core modules of the component using the canonical ABI. Imports are provided as
+//! host functions by [`ComponentImports`].
+//!
+//! Supported are components containing core modules, instances, imported and exported functions and
+//! instances, and resources. Nested components and imported modules aren't supported yet.
+//!
+//! Requires the `component-model` feature.
+//!
+//! ```rust
+//! use tinywasm::component::{Component, ComponentImports};
+//! use tinywasm::Store;
+//!
+//! let wasm = wat::parse_str(r#"(component
+//!     (core module $m
+//!         (memory (export "memory") 1)
+//!         (global $heap (mut i32) (i32.const 1024))
+//!         (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+//!             (global.get $heap)
+//!             (global.set $heap (i32.add (global.get $heap) (local.get 3))))
+//!         (func (export "len") (param i32 i32) (result i32) (local.get 1)))
+//!     (core instance $i (instantiate $m))
+//!     (func (export "len") (param "s" string) (result u32)
+//!         (canon lift (core func $i "len") (memory $i "memory") (realloc (func $i "realloc")))))"#).unwrap();
+//!
+//! let mut store = Store::default();
+//! let component = Component::parse_bytes(&wasm)?;
+//! let instance = component.instantiate(&mut store, &ComponentImports::new())?;
+//!
+//! let len = instance.exported_func::<(String,), (u32,)>("len")?;
+//! assert_eq!(len.call(&mut store, ("hello".to_string(),))?, (5,));
+//! # Ok::<(), tinywasm::Error>(())
+//! ```
+
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+
+use tinywasm_types::{ComponentFuncType, FuncAddr, TinyWasmComponent};
+
+use crate::{FuncContext, Result, Store};
+use abi::{call_core, Cx, Options};
+use imports::HostComponentFunc;
+use instance::InstanceState;
+
+mod abi;
+mod imports;
+mod instance;
+mod values;
+
+pub use imports::ComponentImports;
+pub use instance::ComponentInstance;
+pub(crate) use values::bindgen_helpers;
+pub use values::{ComponentTuple, ComponentValue, ResourceAny, Val};
+
+#[cfg(feature = "macros")]
+pub use tinywasm_macros::bindgen;
+
+/// A WebAssembly component
+///
+/// See [`Component::instantiate`] and the [module documentation](self).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Component(pub(crate) TinyWasmComponent);
+
+impl From<TinyWasmComponent> for Component {
+    fn from(data: TinyWasmComponent) -> Self {
+        Self(data)
+    }
+}
+
+impl Component {
+    #[cfg(feature = "parser")]
+    /// Parse a component from bytes. Requires `parser` feature.
+    pub fn parse_bytes(wasm: &[u8]) -> Result<Self> {
+        let parser = tinywasm_parser::Parser::new();
+        Ok(parser.parse_component_bytes(wasm)?.into())
+    }
+
+    /// Instantiate the component in the given store
+    ///
+    /// Runs the start functions of its core modules, but not `_start`.
+    pub fn instantiate(&self, store: &mut Store, imports: &ComponentImports) -> Result<ComponentInstance> {
+        instance::instantiate(&self.0, store, imports)
+    }
+}
+
+#[derive(Clone)]
+pub(crate) enum FuncKind {
+    Host(HostComponentFunc),
+    Lifted { func: FuncAddr, options: Options },
+}
+
+/// A function of a component instance
+#[derive(Clone)]
+pub struct ComponentFunc {
+    pub(crate) ty: Rc<ComponentFuncType>,
+    pub(crate) kind: FuncKind,
+    pub(crate) state: Rc<RefCell<InstanceState>>,
+}
+
+impl Debug for ComponentFunc {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let kind = match self.kind {
+            FuncKind::Host(_) => "host",
+            FuncKind::Lifted { .. } => "lifted",
+        };
+        f.debug_struct("ComponentFunc").field("ty", &self.ty).field("kind", &kind).finish()
+    }
+}
+
+impl ComponentFunc {
+    /// Get the type of the function
+    pub fn ty(&self) -> &ComponentFuncType {
+        &self.ty
+    }
+
+    /// Call the function
+    ///
+    /// Fails with [`crate::Error::InvalidStore`] if the store is not the one the component was instantiated in.
+    pub fn call(&self, store: &mut Store, params: &[Val]) -> Result<Vec<Val>> {
+        self.state.borrow().check_store(store)?;
+        self.call_from(FuncContext { store, module_addr: None, func_addr: 0 }, params)
+    }
+
+    pub(crate) fn call_from(&self, ctx: FuncContext<'_>, params: &[Val]) -> Result<Vec<Val>> {
+        let (func, options) = match &self.kind {
+            FuncKind::Host(func) => return func(ctx, params),
+            FuncKind::Lifted { func, options } => (*func, options),
+        };
+
+        let _span = crate::tracing::debug_span!("call_component_func").entered();
+        let mut cx = Cx::new(ctx.store, options, &self.state);
+        let args = cx.lower_params(&self.ty, params)?;
+        let results = call_core(cx.store, func, &args)?;
+        let vals = cx.lift_results(&self.ty, &results)?;
+        if let Some(post_return) = options.post_return {
+            call_core(cx.store, post_return, &results)?;
+        }
+        cx.end_call()?;
+        Ok(vals)
+    }
+
+    /// Get a typed version of the function
+    ///
+    /// The types are checked when the function is called.
+    pub fn typed<P: ComponentTuple, R: ComponentTuple>(&self) -> ComponentFuncTyped<P, R> {
+        ComponentFuncTyped { func: self.clone(), marker: PhantomData }
+    }
+}
+
+/// A typed function of a component instance, see [`ComponentFunc::typed`]
+pub struct ComponentFuncTyped<P, R> {
+    /// The underlying function
+    pub func: ComponentFunc,
+    marker: PhantomData<(P, R)>,
+}
+
+impl<P, R> Debug for ComponentFuncTyped<P, R> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ComponentFuncTyped").field("func", &self.func).finish()
+    }
+}
+
+impl<P, R> Clone for ComponentFuncTyped<P, R> {
+    fn clone(&self) -> Self {
+        Self { func: self.func.clone(), marker: PhantomData }
+    }
+}
+
+impl<P: ComponentTuple, R: ComponentTuple> ComponentFuncTyped<P, R> {
+    /// Call the function
+    pub fn call(&self, store: &mut Store, params: P) -> Result<R> {
+        R::from_vals(self.func.call(store, &params.into_vals())?)
+    }
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+    use alloc::string::{String, ToString};
+    use alloc::{format, vec};
+
+    const COMPONENT: &str = r#"(component
+        (import "host" (instance $host (export "greet" (func (param "name" string) (result string)))))
+
+        (core module $libc
+            (memory (export "memory") 1)
+            (global $heap (mut i32) (i32.const 1024))
+            (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                (global.set $heap (i32.and (i32.add (global.get $heap) (i32.const 7)) (i32.const -8)))
+                (global.get $heap)
+                (global.set $heap (i32.add (global.get $heap) (local.get 3)))))
+        (core instance $libc (instantiate $libc))
+
+        (core module $dtor
+            (global $dropped (mut i32) (i32.const 0))
+            (func (export "dtor") (param i32) (global.set $dropped (local.get 0)))
+            (func (export "dropped") (result i32) (global.get $dropped)))
+        (core instance $dtor (instantiate $dtor))
+        (alias core export $dtor "dtor" (core func $dtor-fn))
+        (type $counter (resource (rep i32) (dtor (func $dtor-fn))))
+        (core func $new (canon resource.new $counter))
+
+        (alias export $host "greet" (func $greet))
+        (core func $greet (canon lower (func $greet) (memory $libc "memory") (realloc (func $libc "realloc"))))
+
+        (core module $m
+            (import "libc" "memory" (memory 1))
+            (import "host" "greet" (func $greet (param i32 i32 i32)))
+            (import "res" "new" (func $new (param i32) (result i32)))
+            (func (export "greet") (param i32 i32) (result i32)
+                (call $greet (local.get 0) (local.get 1) (i32.const 16))
+                (i32.const 16))
+            (func (export "new") (param i32) (result i32) (call $new (local.get 0)))
+            (func (export "get") (param i32) (result i32) (local.get 0))
+            (func (export "swap") (param i32 i32) (result i32)
+                (i32.store (i32.const 32) (local.get 1))
+                (i32.store (i32.const 36) (local.get 0))
+                (i32.const 32))
+            (func (export "first") (param i32 i32) (result i32)
+                (i32.store (i32.const 48) (i32.ne (local.get 1) (i32.const 0)))
+                (if (local.get 1) (then (i32.store (i32.const 52) (i32.load (local.get 0)))))
+                (i32.const 48)))
+        (core instance $i (instantiate $m
+            (with "libc" (instance $libc))
+            (with "host" (instance (export "greet" (func $greet))))
+            (with "res" (instance (export "new" (func $new))))))
+
+        (export $c "counter" (type $counter))
+        (type $point-def (record (field "x" s32) (field "y" s32)))
+        (export $point "point" (type $point-def))
+
+        (func (export "greet") (param "name" string) (result string)
+            (canon lift (core func $i "greet") (memory $libc "memory") (realloc (func $libc "realloc"))))
+        (func (export "new") (param "v" u32) (result (own $c)) (canon lift (core func $i "new")))
+        (func (export "get") (param "c" (borrow $c)) (result u32) (canon lift (core func $i "get")))
+        (func (export "dropped") (result u32) (canon lift (core func $dtor "dropped")))
+        (func (export "swap") (param "p" $point) (result $point) (canon lift (core func $i "swap") (memory $libc "memory")))
+        (func (export "first") (param "l" (list u32)) (result (option u32))
+            (canon lift (core func $i "first") (memory $libc "memory") (realloc (func $libc "realloc")))))"#;
+
+    #[test]
+    fn test_component() {
+        let component = Component::parse_bytes(&wat::parse_str(COMPONENT).unwrap()).unwrap();
+        let mut store = Store::default();
+        assert!(component.instantiate(&mut store, &ComponentImports::new()).is_err());
+
+        let mut imports = ComponentImports::new();
+        imports.define_typed_func("host#greet", |_, (name,): (String,)| Ok((format!("Hello, {name}!"),))).unwrap();
+        let instance = component.instantiate(&mut store, &imports).unwrap();
+
+        let greet = instance.exported_func::<(String,), (String,)>("greet").unwrap();
+        assert_eq!(greet.call(&mut store, ("world".to_string(),)).unwrap().0, "Hello, world!");
+
+        let point = Val::Record(vec![("x".to_string(), Val::S32(1)), ("y".to_string(), Val::S32(-2))]);
+        let swapped = Val::Record(vec![("x".to_string(), Val::S32(-2)), ("y".to_string(), Val::S32(1))]);
+        assert_eq!(instance.exported_func_untyped("swap").unwrap().call(&mut store, &[point]).unwrap(), [swapped]);
+
+        let first = instance.exported_func::<(Vec<u32>,), (Option<u32>,)>("first").unwrap();
+        assert_eq!(first.call(&mut store, (vec![3, 4],)).unwrap(), (Some(3),));
+        assert_eq!(first.call(&mut store, (vec![],)).unwrap(), (None,));
+
+        let (counter,) =
+            instance.exported_func::<(u32,), (ResourceAny,)>("new").unwrap().call(&mut store, (7,)).unwrap();
+        assert_eq!(counter, ResourceAny { resource: instance.resource("counter").unwrap(), rep: 7, owned: true });
+        let borrowed = ResourceAny { owned: false, ..counter };
+        let get = instance.exported_func::<(ResourceAny,), (u32,)>("get").unwrap();
+        assert_eq!(get.call(&mut store, (borrowed,)).unwrap(), (7,));
+
+        let dropped = instance.exported_func::<(), (u32,)>("dropped").unwrap();
+        assert!(instance.drop_resource(&mut store, borrowed).is_err());
+        instance.drop_resource(&mut store, counter).unwrap();
+        assert_eq!(dropped.call(&mut store, ()).unwrap(), (7,));
+    }
+
+    #[test]
+    fn test_other_store() {
+        let component = Component::parse_bytes(&wat::parse_str(COMPONENT).unwrap()).unwrap();
+        let mut imports = ComponentImports::new();
+        imports.define_typed_func("host#greet", |_, (name,): (String,)| Ok((name,))).unwrap();
+        let instance = component.instantiate(&mut Store::default(), &imports).unwrap();
+
+        let mut other = Store::default();
+        let first = instance.exported_func::<(Vec<u32>,), (Option<u32>,)>("first").unwrap();
+        assert!(matches!(first.call(&mut other, (vec![1],)), Err(crate::Error::InvalidStore)));
+        let greet = instance.exported_func::<(String,), (String,)>("greet").unwrap();
+        assert!(matches!(greet.call(&mut other, ("world".to_string(),)), Err(crate::Error::InvalidStore)));
+    }
+}
@@ -0,0 +1,295 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+
+use crate::{Error, Result};
+
+/// A component-level value
+///
+/// See <https://github.com/WebAssembly/component-model/blob/main/design/mvp/Explainer.md#type-definitions>
+#[derive(Debug, Clone, PartialEq)]
+pub enum Val {
+    /// A boolean
+    Bool(bool),
+    /// A signed 8-bit integer
+    S8(i8),
+    /// An unsigned 8-bit integer
+    U8(u8),
+    /// A signed 16-bit integer
+    S16(i16),
+    /// An unsigned 16-bit integer
+    U16(u16),
+    /// A signed 32-bit integer
+    S32(i32),
+    /// An unsigned 32-bit integer
+    U32(u32),
+    /// A signed 64-bit integer
+    S64(i64),
+    /// An unsigned 64-bit integer
+    U64(u64),
+    /// A 32-bit float
+    F32(f32),
+    /// A 64-bit float
+    F64(f64),
+    /// A Unicode scalar value
+    Char(char),
+    /// A string
+    String(String),
+    /// A list
+    List(Vec<Val>),
+    /// A record with its fields in the order of the type
+    Record(Vec<(String, Val)>),
+    /// A tuple
+    Tuple(Vec<Val>),
+    /// A case of a variant and its payload
+    Variant(String, Option<Box<Val>>),
+    /// A case of an enum
+    Enum(String),
+    /// An option
+    Option(Option<Box<Val>>),
+    /// A result with optional payloads
+    Result(core::result::Result<Option<Box<Val>>, Option<Box<Val>>>),
+    /// The names of the flags that are set
+    Flags(Vec<String>),
+    /// A handle to a resource
+    Resource(ResourceAny),
+}
+
+/// A resource passed between the host and a component
+///
+/// The representation is the `i32` that the component implementing the resource uses to identify it,
+/// for resources imported from the host it is chosen by the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceAny {
+    /// The resource type, see [`ComponentInstance::resource`](super::ComponentInstance::resource)
+    ///
+    /// Ignored when the host passes a resource it implements, which can use `0`.
+    pub resource: u32,
+    /// The representation of the resource
+    pub rep: u32,
+    /// Whether this is an owned handle, otherwise it's only borrowed for the duration of a call
+    pub owned: bool,
+}
+
+fn expected(ty: &str, val: &Val) -> Error {
+    Error::Other(format!("expected a value of type {ty}, found {val:?}"))
+}
+
+/// A Rust type that can be converted to and from a [`Val`]
+pub trait ComponentValue: Sized {
+    /// Convert the value to a [`Val`]
+    fn into_val(self) -> Val;
+
+    /// Convert a [`Val`] to the value
+    fn from_val(val: Val) -> Result<Self>;
+
+    #[doc(hidden)]
+    fn into_payload(self) -> Option<Box<Val>> {
+        Some(Box::new(self.into_val()))
+    }
+
+    #[doc(hidden)]
+    fn from_payload(payload: Option<Box<Val>>) -> Result<Self> {
+        let payload = payload.ok_or_else(|| Error::Other("expected a payload".to_string()))?;
+        Self::from_val(*payload)
+    }
+}
+
+macro_rules! impl_component_value {
+    ($($ty:ty => $variant:ident),*) => {$(
+        impl ComponentValue for $ty {
+            fn into_val(self) -> Val {
+                Val::$variant(self)
+            }
+
+            fn from_val(val: Val) -> Result<Self> {
+                match val {
+                    Val::$variant(value) => Ok(value),
+                    val => Err(expected(stringify!($ty), &val)),
+                }
+            }
+        }
+    )*};
+}
+
+impl_component_value!(
+    bool => Bool, i8 => S8, u8 => U8, i16 => S16, u16 => U16, i32 => S32, u32 => U32, i64 => S64, u64 => U64,
+    f32 => F32, f64 => F64, char => Char, String => String, ResourceAny => Resource
+);
+
+impl<T: ComponentValue> ComponentValue for Vec<T> {
+    fn into_val(self) -> Val {
+        Val::List(self.into_iter().map(T::into_val).collect())
+    }
+
+    fn from_val(val: Val) -> Result<Self> {
+        match val {
+            Val::List(items) => items.into_iter().map(T::from_val).collect(),
+            val => Err(expected("list", &val)),
+        }
+    }
+}
+
+impl<T: ComponentValue> ComponentValue for Option<T> {
+    fn into_val(self) -> Val {
+        Val::Option(self.map(|value| Box::new(value.into_val())))
+    }
+
+    fn from_val(val: Val) -> Result<Self> {
+        match val {
+            Val::Option(value) => value.map(|value| T::from_val(*value)).transpose(),
+            val => Err(expected("option", &val)),
+        }
+    }
+}
+
+impl<T: ComponentValue, E: ComponentValue> ComponentValue for core::result::Result<T, E> {
+    fn into_val(self) -> Val {
+        Val::Result(self.map(T::into_payload).map_err(E::into_payload))
+    }
+
+    fn from_val(val: Val) -> Result<Self> {
+        match val {
+            Val::Result(Ok(value)) => Ok(Ok(T::from_payload(value)?)),
+            Val::Result(Err(value)) => Ok(Err(E::from_payload(value)?)),
+            val => Err(expected("result", &val)),
+        }
+    }
+}
+
+/// The empty payload of a `result` case, e.g. `result<_, string>` is `Result<(), String>`
+impl ComponentValue for () {
+    fn into_val(self) -> Val {
+        Val::Tuple(vec![])
+    }
+
+    fn from_val(val: Val) -> Result<Self> {
+        match val {
+            Val::Tuple(values) if values.is_empty() => Ok(()),
+            val => Err(expected("tuple<>", &val)),
+        }
+    }
+
+    fn into_payload(self) -> Option<Box<Val>> {
+        None
+    }
+
+    fn from_payload(_payload: Option<Box<Val>>) -> Result<Self> {
+        Ok(())
+    }
+}
+
+/// The parameters or results of a component function as a tuple, e.g. `(String, u32)`
+pub trait ComponentTuple: Sized {
+    /// Convert the tuple to a list of values
+    fn into_vals(self) -> Vec<Val>;
+
+    /// Convert a list of values to the tuple
+    fn from_vals(vals: Vec<Val>) -> Result<Self>;
+}
+
+macro_rules! impl_component_tuple {
+    ($count:literal: $($name:ident),*) => {
+        impl<$($name: ComponentValue),*> ComponentTuple for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn into_vals(self) -> Vec<Val> {
+                let ($($name,)*) = self;
+                vec![$($name.into_val()),*]
+            }
+
+            #[allow(unused_mut, unused_variables)]
+            fn from_vals(vals: Vec<Val>) -> Result<Self> {
+                if vals.len() != $count {
+                    return Err(Error::Other(format!("expected {} values, found {}", $count, vals.len())));
+                }
+                let mut vals = vals.into_iter();
+                Ok(($($name::from_val(vals.next().expect("length was checked"))?,)*))
+            }
+        }
+
+        impl<$($name: ComponentValue),*> ComponentValue for ($($name,)*) {
+            fn into_val(self) -> Val {
+                Val::Tuple(self.into_vals())
+            }
+
+            fn from_val(val: Val) -> Result<Self> {
+                match val {
+                    Val::Tuple(values) => Self::from_vals(values),
+                    val => Err(expected("tuple", &val)),
+                }
+            }
+        }
+    };
+}
+
+impl ComponentTuple for () {
+    fn into_vals(self) -> Vec<Val> {
+        vec![]
+    }
+
+    fn from_vals(vals: Vec<Val>) -> Result<Self> {
+        match vals.is_empty() {
+            true => Ok(()),
+            false => Err(Error::Other(format!("expected no values, found {}", vals.len()))),
+        }
+    }
+}
+
+impl_component_tuple!(1: A);
+impl_component_tuple!(2: A, B);
+impl_component_tuple!(3: A, B, C);
+impl_component_tuple!(4: A, B, C, D);
+impl_component_tuple!(5: A, B, C, D, E);
+impl_component_tuple!(6: A, B, C, D, E, F);
+impl_component_tuple!(7: A, B, C, D, E, F, G);
+impl_component_tuple!(8: A, B, C, D, E, F, G, H);
+
+// used by the code generated by `bindgen!`
+pub(crate) mod bindgen_helpers {
+    use super::*;
+
+    /// The values of a record with the given field names
+    pub fn record_fields(val: Val, names: &[&str]) -> Result<vec::IntoIter<Val>> {
+        match val {
+            Val::Record(fields) if fields.iter().map(|(n, _)| n.as_str()).eq(names.iter().copied()) => {
+                Ok(fields.into_iter().map(|(_, val)| val).collect::<Vec<_>>().into_iter())
+            }
+            val => Err(expected(&format!("record {{ {} }}", names.join(", ")), &val)),
+        }
+    }
+
+    /// The next value of a record
+    pub fn next_val(vals: &mut vec::IntoIter<Val>) -> Result<Val> {
+        vals.next().ok_or_else(|| Error::Other("not enough values".to_string()))
+    }
+
+    /// The case and payload of a variant
+    pub fn variant_case(val: Val) -> Result<(String, Option<Box<Val>>)> {
+        match val {
+            Val::Variant(case, payload) => Ok((case, payload)),
+            val => Err(expected("variant", &val)),
+        }
+    }
+
+    /// The case of an enum
+    pub fn enum_case(val: Val) -> Result<String> {
+        match val {
+            Val::Enum(case) => Ok(case),
+            val => Err(expected("enum", &val)),
+        }
+    }
+
+    /// The names of the set flags
+    pub fn flags(val: Val) -> Result<Vec<String>> {
+        match val {
+            Val::Flags(flags) => Ok(flags),
+            val => Err(expected("flags", &val)),
+        }
+    }
+
+    /// The error for a case that isn't part of the type
+    pub fn unknown_case(ty: &str, case: &str) -> Error {
+        Error::Other(format!("unknown case {case} of {ty}"))
+    }
+}
@@ -0,0 +1,778 @@
+//! Guest code coverage
+//!
+//! When coverage is enabled on a [`Store`], the interpreter counts how often the entry of every
+//! WebAssembly function and every `block`, `loop`, `if` and `else` region is executed.
+//! The collected data can be turned into a [`CoverageReport`], which maps the regions back to
+//! offsets in the original WebAssembly binary (or to source lines using [`DwarfLineMap`])
+//! and can be exported in the LCOV format.
+//!
+//! Requires the `coverage` feature.
+//!
+//! ```rust
+//! use tinywasm::{Store, Module};
+//!
+//! let wasm = include_bytes!("../../../examples/wasm/add.wasm");
+//! let module = Module::parse_bytes(wasm)?;
+//!
+//! let mut store = Store::default();
+//! store.enable_coverage();
+//!
+//! let instance = module.instantiate(&mut store, None)?;
+//! let add = instance.exported_func::<(i32, i32), i32>(&store, "add")?;
+//! add.call(&mut store, (1, 2))?;
+//!
+//! let report = store.coverage_report(&instance)?;
+//! let lcov = report.to_lcov("add.wasm");
+//! assert!(lcov.contains("FNDA:1,add"));
+//! # Ok::<(), tinywasm::Error>(())
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::{boxed::Box, format, vec, vec::Vec};
+use core::fmt::Write;
+use tinywasm_types::{BlockOffset, ExternalKind, FuncAddr};
+
+use crate::{Error, Function, ModuleInstance, Result, Store};
+
+/// Execution counts collected by a [`Store`], indexed by function address
+#[derive(Debug, Default)]
+pub(crate) struct CoverageData {
+    hits: Vec<Box<[u64]>>,
+}
+
+impl CoverageData {
+    #[inline]
+    fn record(&mut self, func_addr: FuncAddr, block_offsets: &[BlockOffset], instr: u32) {
+        let Ok(idx) = block_offsets.binary_search_by_key(&instr, |b| b.instr) else {
+            return;
+        };
+
+        let addr = func_addr as usize;
+        if self.hits.len() <= addr {
+            self.hits.resize_with(addr + 1, Default::default);
+        }
+
+        let hits = &mut self.hits[addr];
+        if hits.is_empty() {
+            *hits = vec![0; block_offsets.len()].into_boxed_slice();
+        }
+
+        hits[idx] += 1;
+    }
+
+    fn hits(&self, func_addr: FuncAddr) -> Option<&[u64]> {
+        self.hits.get(func_addr as usize).map(|h| &**h).filter(|h| !h.is_empty())
+    }
+}
+
+impl Store {
+    /// Start collecting code coverage for all WebAssembly functions executed in this store
+    ///
+    /// Counts that were already collected are kept.
+    pub fn enable_coverage(&mut self) {
+        if self.coverage.is_none() {
+            self.coverage = Some(CoverageData::default());
+        }
+    }
+
+    /// Stop collecting code coverage and discard all collected counts
+    pub fn disable_coverage(&mut self) {
+        self.coverage = None;
+    }
+
+    /// Reset all collected coverage counts to zero
+    pub fn reset_coverage(&mut self) {
+        if let Some(coverage) = &mut self.coverage {
+            coverage.hits.clear();
+        }
+    }
+
+    /// Check if code coverage is being collected
+    pub fn coverage_enabled(&self) -> bool {
+        self.coverage.is_some()
+    }
+
+    /// Create a coverage report for all WebAssembly functions defined by the given module instance
+    ///
+    /// Returns an error if coverage is not enabled or the instance belongs to a different store.
+    pub fn coverage_report(&self, instance: &ModuleInstance) -> Result<CoverageReport> {
+        if instance.0.store_id != self.id() {
+            return Err(Error::InvalidStore);
+        }
+
+        let Some(coverage) = &self.coverage else {
+            return Err(Error::Other("coverage is not enabled for this store".to_string()));
+        };
+
+        let mut functions = Vec::new();
+        for (idx, &addr) in instance.0.func_addrs.iter().enumerate() {
+            let func_inst = self.get_func(addr);
+            let Function::Wasm(func) = &func_inst.func else { continue };
+
+            // imported functions are reported by the instance that defines them
+            if func_inst.owner != instance.id() {
+                continue;
+            }
+
+            let name = instance
+                .0
+                .exports
+                .iter()
+                .find(|e| e.kind == ExternalKind::Func && e.index == idx as u32)
+                .map_or_else(|| format!("func[{idx}]"), |e| e.name.to_string());
+
+            let hits = coverage.hits(addr);
+            let blocks = func
+                .block_offsets
+                .iter()
+                .enumerate()
+                .map(|(i, b)| BlockCoverage { offset: b.offset, hits: hits.map_or(0, |h| h[i]) })
+                .collect();
+
+            functions.push(FunctionCoverage { index: idx as u32, name, blocks });
+        }
+
+        Ok(CoverageReport { functions })
+    }
+
+    #[inline]
+    pub(crate) fn record_coverage(&mut self, func_addr: FuncAddr, instr: u32) {
+        let Some(coverage) = &mut self.coverage else { return };
+        if let Function::Wasm(func) = &self.data.funcs[func_addr as usize].func {
+            coverage.record(func_addr, &func.block_offsets, instr);
+        }
+    }
+}
+
+/// Coverage of all functions defined by a module instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageReport {
+    functions: Vec<FunctionCoverage>,
+}
+
+/// Coverage of a single WebAssembly function
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionCoverage {
+    /// The index of the function in the module
+    pub index: u32,
+    /// The exported name of the function, or `func[index]` if it is not exported
+    pub name: String,
+    /// The regions of code in the function, the first one is the function entry
+    pub blocks: Vec<BlockCoverage>,
+}
+
+/// Coverage of a single region of code
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockCoverage {
+    /// The byte offset of the region in the original WebAssembly binary
+    pub offset: u32,
+    /// How often the region was entered
+    pub hits: u64,
+}
+
+impl FunctionCoverage {
+    /// How often the function was called
+    pub fn calls(&self) -> u64 {
+        self.blocks.first().map_or(0, |b| b.hits)
+    }
+}
+
+/// A mapping from WebAssembly binary offsets to source locations
+pub trait SourceMap {
+    /// Look up the source location of the instruction at the given offset in the WebAssembly binary
+    fn lookup(&self, offset: u32) -> Option<SourceLocation<'_>>;
+}
+
+/// A location in a source file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLocation<'a> {
+    /// The path of the source file
+    pub file: &'a str,
+    /// The line in the source file (1-based)
+    pub line: u32,
+}
+
+impl CoverageReport {
+    /// The functions included in this report
+    pub fn functions(&self) -> &[FunctionCoverage] {
+        &self.functions
+    }
+
+    /// The number of regions of code in this report
+    pub fn total_blocks(&self) -> usize {
+        self.functions.iter().map(|f| f.blocks.len()).sum()
+    }
+
+    /// The number of regions of code that were executed at least once
+    pub fn covered_blocks(&self) -> usize {
+        self.functions.iter().flat_map(|f| &f.blocks).filter(|b| b.hits > 0).count()
+    }
+
+    /// Export the report in the LCOV format
+    ///
+    /// All regions are attributed to a single file called `source_name`, using their
+    /// offsets in the WebAssembly binary as line numbers.
+    pub fn to_lcov(&self, source_name: &str) -> String {
+        struct Offsets<'a>(&'a str);
+        impl SourceMap for Offsets<'_> {
+            fn lookup(&self, offset: u32) -> Option<SourceLocation<'_>> {
+                Some(SourceLocation { file: self.0, line: offset })
+            }
+        }
+
+        self.to_lcov_with(&Offsets(source_name))
+    }
+
+    /// Export the report in the LCOV format, using `source_map` to map offsets to source lines
+    ///
+    /// Regions without a source location are omitted.
+    pub fn to_lcov_with(&self, source_map: &dyn SourceMap) -> String {
+        #[derive(Default)]
+        struct FileRecord<'a> {
+            functions: Vec<(u32, &'a str, u64)>,
+            lines: BTreeMap<u32, u64>,
+        }
+
+        let mut files: BTreeMap<&str, FileRecord<'_>> = BTreeMap::new();
+        for func in &self.functions {
+            for (i, block) in func.blocks.iter().enumerate() {
+                let Some(loc) = source_map.lookup(block.offset) else { continue };
+                let record = files.entry(loc.file).or_default();
+                if i == 0 {
+                    record.functions.push((loc.line, &func.name, block.hits));
+                }
+
+                // a line is as covered as the most frequently executed region on it
+                let hits = record.lines.entry(loc.line).or_default();
+                *hits = (*hits).max(block.hits);
+            }
+        }
+
+        let mut out = String::new();
+        for (file, record) in files {
+            let _ = writeln!(out, "TN:");
+            let _ = writeln!(out, "SF:{file}");
+            for (line, name, _) in &record.functions {
+                let _ = writeln!(out, "FN:{line},{name}");
+            }
+            for (_, name, hits) in &record.functions {
+                let _ = writeln!(out, "FNDA:{hits},{name}");
+            }
+            let _ = writeln!(out, "FNF:{}", record.functions.len());
+            let _ = writeln!(out, "FNH:{}", record.functions.iter().filter(|f| f.2 > 0).count());
+            for (line, hits) in &record.lines {
+                let _ = writeln!(out, "DA:{line},{hits}");
+            }
+            let _ = writeln!(out, "LF:{}", record.lines.len());
+            let _ = writeln!(out, "LH:{}", record.lines.values().filter(|&&h| h > 0).count());
+            let _ = writeln!(out, "end_of_record");
+        }
+        out
+    }
+}
+
+/// A [`SourceMap`] backed by the DWARF `.debug_line` section of a WebAssembly binary
+///
+/// Supports DWARF versions 2 to 5, as emitted by LLVM-based toolchains (e.g. `clang` or `rustc` with debug info).
+#[derive(Debug, Clone)]
+pub struct DwarfLineMap {
+    files: Vec<String>,
+    sequences: Vec<Vec<LineRow>>,
+    code_offset: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LineRow {
+    address: u64,
+    file: usize,
+    line: u32,
+}
+
+impl DwarfLineMap {
+    /// Parse the line information of a WebAssembly binary
+    ///
+    /// Returns `Ok(None)` if the binary contains no `.debug_line` section.
+    pub fn parse(wasm: &[u8]) -> Result<Option<Self>> {
+        let mut reader = Reader::new(wasm);
+        if reader.bytes(8)? != b"\0asm\x01\0\0\0" {
+            return Err(dwarf_error("not a WebAssembly binary"));
+        }
+
+        let (mut code_offset, mut debug_line, mut debug_line_str, mut debug_str) = (None, None, &[][..], &[][..]);
+        while !reader.is_empty() {
+            let id = reader.u8()?;
+            let size = reader.uleb()? as usize;
+            let start = reader.pos;
+            let mut section = Reader::new(reader.bytes(size)?);
+            match id {
+                0 => {
+                    let name_len = section.uleb()? as usize;
+                    let name = section.bytes(name_len)?;
+                    let data = &section.data[section.pos..];
+                    match name {
+                        b".debug_line" => debug_line = Some(data),
+                        b".debug_line_str" => debug_line_str = data,
+                        b".debug_str" => debug_str = data,
+                        _ => {}
+                    }
+                }
+                10 => code_offset = Some(start as u32),
+                _ => {}
+            }
+        }
+
+        let Some(debug_line) = debug_line else { return Ok(None) };
+        let mut map = Self { files: Vec::new(), sequences: Vec::new(), code_offset: code_offset.unwrap_or(0) };
+        let mut reader = Reader::new(debug_line);
+        while !reader.is_empty() {
+            map.parse_unit(&mut reader, debug_line_str, debug_str)?;
+        }
+
+        map.sequences.retain(|s| s.len() > 1);
+        map.sequences.sort_by_key(|s| s[0].address);
+        Ok(Some(map))
+    }
+
+    fn parse_unit<'a>(&mut self, reader: &mut Reader<'a>, line_str: &'a [u8], str: &'a [u8]) -> Result<()> {
+        let (unit_length, offset_size) = match reader.u32()? {
+            0xffff_ffff => (reader.u64()? as usize, 8),
+            len => (len as usize, 4),
+        };
+        let mut unit = Reader::new(reader.bytes(unit_length)?);
+
+        let version = unit.u16()?;
+        if !(2..=5).contains(&version) {
+            return Err(dwarf_error("unsupported .debug_line version"));
+        }
+        if version >= 5 {
+            let _address_size = unit.u8()?;
+            let _segment_selector_size = unit.u8()?;
+        }
+
+        let header_length = unit.offset(offset_size)? as usize;
+        let program_start = unit.pos.saturating_add(header_length);
+        let min_inst_length = unit.u8()? as u64;
+        if version >= 4 {
+            let _max_ops_per_inst = unit.u8()?;
+        }
+        let _default_is_stmt = unit.u8()?;
+        let line_base = unit.u8()? as i8 as i64;
+        let line_range = unit.u8()?;
+        let opcode_base = unit.u8()?;
+        if line_range == 0 {
+            return Err(dwarf_error("invalid line range"));
+        }
+        let standard_opcode_lengths = unit.bytes(opcode_base.saturating_sub(1) as usize)?.to_vec();
+
+        // file indices are 1-based before DWARF 5, so index 0 is a placeholder
+        let mut files = Vec::new();
+        if version >= 5 {
+            let strings = StringSections { line_str, str, offset_size };
+            let dirs = parse_entries_v5(&mut unit, &strings)?;
+            for (name, dir) in parse_entries_v5(&mut unit, &strings)? {
+                let dir = dirs.get(dir as usize).map_or("", |d| &d.0);
+                files.push(join_path(dir, &name));
+            }
+        } else {
+            let mut dirs = vec![String::new()];
+            loop {
+                let dir = unit.cstr()?;
+                if dir.is_empty() {
+                    break;
+                }
+                dirs.push(dir.to_string());
+            }
+            files.push(String::new());
+            loop {
+                let name = unit.cstr()?;
+                if name.is_empty() {
+                    break;
+                }
+                let dir = unit.uleb()? as usize;
+                let _mtime = unit.uleb()?;
+                let _length = unit.uleb()?;
+                files.push(join_path(dirs.get(dir).map_or("", |d| d), name));
+            }
+        }
+
+        let file_base = self.files.len();
+        self.files.extend(files);
+
+        unit.pos = program_start;
+        let mut sequence = Vec::new();
+        let (mut address, mut file, mut line) = (0u64, 1usize, 1i64);
+        let row = |sequence: &mut Vec<LineRow>, address: u64, file: usize, line: i64| {
+            sequence.push(LineRow { address, file: file_base.wrapping_add(file), line: line.max(0) as u32 });
+        };
+
+        // the line program comes from the binary, so its arithmetic wraps instead of overflowing
+        while !unit.is_empty() {
+            let opcode = unit.u8()?;
+            if opcode >= opcode_base {
+                let adjusted = opcode - opcode_base;
+                address = address.wrapping_add((adjusted / line_range) as u64 * min_inst_length);
+                line = line.wrapping_add(line_base + (adjusted % line_range) as i64);
+                row(&mut sequence, address, file, line);
+                continue;
+            }
+
+            match opcode {
+                0 => {
+                    let len = unit.uleb()? as usize;
+                    let mut ext = Reader::new(unit.bytes(len)?);
+                    match ext.u8()? {
+                        // DW_LNE_end_sequence
+                        1 => {
+                            row(&mut sequence, address, file, line);
+                            self.sequences.push(core::mem::take(&mut sequence));
+                            (address, file, line) = (0, 1, 1);
+                        }
+                        // DW_LNE_set_address
+                        2 => address = ext.offset(len - 1)?,
+                        _ => {}
+                    }
+                }
+                1 => row(&mut sequence, address, file, line),
+                2 => address = address.wrapping_add(unit.uleb()?.wrapping_mul(min_inst_length)),
+                3 => line = line.wrapping_add(unit.sleb()?),
+                4 => file = unit.uleb()? as usize,
+                8 => address = address.wrapping_add(((255 - opcode_base) / line_range) as u64 * min_inst_length),
+                9 => address = address.wrapping_add(unit.u16()? as u64),
+                _ => {
+                    for _ in 0..standard_opcode_lengths[opcode as usize - 1] {
+                        unit.uleb()?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl SourceMap for DwarfLineMap {
+    fn lookup(&self, offset: u32) -> Option<SourceLocation<'_>> {
+        // addresses in DWARF for WebAssembly are relative to the start of the code section
+        let address = offset.checked_sub(self.code_offset)? as u64;
+        let end = self.sequences.partition_point(|s| s[0].address <= address);
+
+        self.sequences[..end].iter().rev().find_map(|sequence| {
+            if address >= sequence[sequence.len() - 1].address {
+                return None;
+            }
+            let row = &sequence[sequence.partition_point(|r| r.address <= address) - 1];
+            let file = self.files.get(row.file).filter(|f| !f.is_empty())?;
+            Some(SourceLocation { file, line: row.line })
+        })
+    }
+}
+
+fn dwarf_error(msg: &str) -> Error {
+    Error::Other(format!("invalid debug info: {msg}"))
+}
+
+fn join_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() || name.starts_with('/') {
+        name.to_string()
+    } else {
+        format!("{}/{name}", dir.trim_end_matches('/'))
+    }
+}
+
+struct StringSections<'a> {
+    line_str: &'a [u8],
+    str: &'a [u8],
+    offset_size: usize,
+}
+
+// parse a DWARF 5 directory or file name table, returning the path and directory index of each entry
+fn parse_entries_v5<'a>(unit: &mut Reader<'a>, strings: &StringSections<'a>) -> Result<Vec<(String, u64)>> {
+    const DW_LNCT_PATH: u64 = 1;
+    const DW_LNCT_DIRECTORY_INDEX: u64 = 2;
+
+    let format_count = unit.u8()?;
+    let mut formats = Vec::with_capacity(format_count as usize);
+    for _ in 0..format_count {
+        formats.push((unit.uleb()?, unit.uleb()?));
+    }
+
+    let count = unit.uleb()?;
+    let mut entries = Vec::new();
+    for _ in 0..count {
+        let (mut path, mut dir) = (String::new(), 0);
+        for &(content, form) in &formats {
+            match (content, unit.form(form, strings)?) {
+                (DW_LNCT_PATH, FormValue::Str(s)) => path = s.to_string(),
+                (DW_LNCT_DIRECTORY_INDEX, FormValue::Int(i)) => dir = i,
+                _ => {}
+            }
+        }
+        entries.push((path, dir));
+    }
+    Ok(entries)
+}
+
+enum FormValue<'a> {
+    Str(&'a str),
+    Int(u64),
+    Other,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).filter(|&end| end <= self.data.len());
+        let bytes = &self.data[self.pos..end.ok_or_else(|| dwarf_error("unexpected end of section"))?];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(self.offset(2)? as u16)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(self.offset(4)? as u32)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        self.offset(8)
+    }
+
+    // little-endian unsigned integer of `size` bytes
+    fn offset(&mut self, size: usize) -> Result<u64> {
+        Ok(self.bytes(size)?.iter().rev().fold(0, |acc, &b| (acc << 8) | b as u64))
+    }
+
+    fn uleb(&mut self) -> Result<u64> {
+        let (mut result, mut shift) = (0u64, 0);
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= ((byte & 0x7f) as u64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(result);
+            }
+        }
+    }
+
+    fn sleb(&mut self) -> Result<i64> {
+        let (mut result, mut shift) = (0i64, 0);
+        loop {
+            let byte = self.u8()?;
+            if shift < 64 {
+                result |= ((byte & 0x7f) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                return Ok(result);
+            }
+        }
+    }
+
+    fn cstr(&mut self) -> Result<&'a str> {
+        let rest = &self.data[self.pos.min(self.data.len())..];
+        let len = rest.iter().position(|&b| b == 0).ok_or_else(|| dwarf_error("unterminated string"))?;
+        let s = core::str::from_utf8(&rest[..len]).map_err(|_| dwarf_error("invalid string"))?;
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    fn form(&mut self, form: u64, strings: &StringSections<'a>) -> Result<FormValue<'a>> {
+        const DW_FORM_DATA2: u64 = 0x05;
+        const DW_FORM_DATA4: u64 = 0x06;
+        const DW_FORM_DATA8: u64 = 0x07;
+        const DW_FORM_STRING: u64 = 0x08;
+        const DW_FORM_BLOCK: u64 = 0x09;
+        const DW_FORM_DATA1: u64 = 0x0b;
+        const DW_FORM_STRP: u64 = 0x0e;
+        const DW_FORM_UDATA: u64 = 0x0f;
+        const DW_FORM_DATA16: u64 = 0x1e;
+        const DW_FORM_LINE_STRP: u64 = 0x1f;
+
+        let string_at = |section: &'a [u8], offset: u64| {
+            let mut reader = Reader::new(section);
+            reader.pos = offset as usize;
+            reader.cstr()
+        };
+
+        Ok(match form {
+            DW_FORM_STRING => FormValue::Str(self.cstr()?),
+            DW_FORM_STRP => FormValue::Str(string_at(strings.str, self.offset(strings.offset_size)?)?),
+            DW_FORM_LINE_STRP => FormValue::Str(string_at(strings.line_str, self.offset(strings.offset_size)?)?),
+            DW_FORM_DATA1 => FormValue::Int(self.offset(1)?),
+            DW_FORM_DATA2 => FormValue::Int(self.offset(2)?),
+            DW_FORM_DATA4 => FormValue::Int(self.offset(4)?),
+            DW_FORM_DATA8 => FormValue::Int(self.offset(8)?),
+            DW_FORM_UDATA => FormValue::Int(self.uleb()?),
+            DW_FORM_DATA16 => {
+                self.bytes(16)?;
+                FormValue::Other
+            }
+            DW_FORM_BLOCK => {
+                let len = self.uleb()? as usize;
+                self.bytes(len)?;
+                FormValue::Other
+            }
+            _ => return Err(dwarf_error("unsupported attribute form in line table header")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a WebAssembly binary with a 16 byte code section at offset 10 and the given DWARF 4 line program
+    fn wasm_with_line_program(program: &[u8]) -> Vec<u8> {
+        let mut header = vec![1, 1, 1, 0xfb, 14, 13, 0, 1, 1, 1, 1, 0, 0, 0, 1, 0, 0, 1];
+        header.extend_from_slice(b"src\0\0lib.rs\0\x01\0\0\0");
+
+        let mut unit = 4u16.to_le_bytes().to_vec();
+        unit.extend_from_slice(&(header.len() as u32).to_le_bytes());
+        unit.extend_from_slice(&header);
+        unit.extend_from_slice(program);
+
+        let mut debug_line = b"\x0b.debug_line".to_vec();
+        debug_line.extend_from_slice(&(unit.len() as u32).to_le_bytes());
+        debug_line.extend_from_slice(&unit);
+
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        wasm.extend_from_slice(&[10, 16]);
+        wasm.extend_from_slice(&[0; 16]);
+        wasm.extend_from_slice(&[0, debug_line.len() as u8]);
+        wasm.extend_from_slice(&debug_line);
+        wasm
+    }
+
+    fn report() -> CoverageReport {
+        let block = |offset, hits| BlockCoverage { offset, hits };
+        CoverageReport {
+            functions: vec![
+                FunctionCoverage { index: 0, name: "a".to_string(), blocks: vec![block(10, 2), block(14, 0)] },
+                FunctionCoverage { index: 1, name: "func[1]".to_string(), blocks: vec![block(20, 0)] },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_dwarf_line_map() {
+        #[rustfmt::skip]
+        let program: &[u8] = &[
+            0x00, 5, 0x02, 2, 0, 0, 0, // DW_LNE_set_address 2
+            0x03, 9, 0x01,             // line 10
+            0x02, 4, 0x03, 2, 0x01,    // address 6, line 12
+            0x02, 4, 0x00, 1, 0x01,    // address 10, DW_LNE_end_sequence
+        ];
+
+        // the code section starts at offset 10
+        let map = DwarfLineMap::parse(&wasm_with_line_program(program)).unwrap().unwrap();
+        assert_eq!(map.lookup(11), None);
+        assert_eq!(map.lookup(12), Some(SourceLocation { file: "src/lib.rs", line: 10 }));
+        assert_eq!(map.lookup(17), Some(SourceLocation { file: "src/lib.rs", line: 12 }));
+        assert_eq!(map.lookup(20), None);
+    }
+
+    #[test]
+    fn test_dwarf_line_map_overflow() {
+        #[rustfmt::skip]
+        let program: &[u8] = &[
+            0x02, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, // advance the address by u64::MAX
+            0x03, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, // advance the line by i64::MAX
+            0x20, 0x01,                                                     // special opcode and copy
+            0x04, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, // file u64::MAX
+            0x01, 0x00, 1, 0x01,                                            // DW_LNE_end_sequence
+        ];
+
+        let map = DwarfLineMap::parse(&wasm_with_line_program(program)).unwrap().unwrap();
+        assert_eq!(map.lookup(10), None);
+    }
+
+    #[test]
+    fn test_lcov() {
+        let lcov = report().to_lcov("m.wasm");
+        let expected = "TN:\nSF:m.wasm\nFN:10,a\nFN:20,func[1]\nFNDA:2,a\nFNDA:0,func[1]\nFNF:2\nFNH:1\n\
+                        DA:10,2\nDA:14,0\nDA:20,0\nLF:3\nLH:1\nend_of_record\n";
+        assert_eq!(lcov, expected);
+    }
+
+    #[test]
+    fn test_lcov_with_source_map() {
+        // both regions of `a` are on the same line, `func[1]` has no source location
+        struct Lines;
+        impl SourceMap for Lines {
+            fn lookup(&self, offset: u32) -> Option<SourceLocation<'_>> {
+                (offset < 20).then_some(SourceLocation { file: "a.rs", line: offset / 10 })
+            }
+        }
+
+        let lcov = report().to_lcov_with(&Lines);
+        assert_eq!(lcov, "TN:\nSF:a.rs\nFN:1,a\nFNDA:2,a\nFNF:1\nFNH:1\nDA:1,2\nLF:1\nLH:1\nend_of_record\n");
+    }
+
+    #[test]
+    #[cfg(feature = "parser")]
+    fn test_loop_coverage() -> Result<()> {
+        let wasm = wat::parse_str(
+            r#"(module (func (export "count") (param i32)
+                (block (loop
+                    (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                    (br_if 0 (local.get 0))))))"#,
+        )
+        .expect("valid wat");
+        let module = crate::Module::parse_bytes(&wasm)?;
+
+        let mut store = Store::default();
+        store.enable_coverage();
+        let instance = module.instantiate(&mut store, None)?;
+        instance.exported_func::<i32, ()>(&store, "count")?.call(&mut store, 3)?;
+
+        // the loop is entered once and branched back to twice
+        let report = store.coverage_report(&instance)?;
+        let hits: Vec<_> = report.functions()[0].blocks.iter().map(|b| b.hits).collect();
+        assert_eq!(hits, [1, 3]);
+        assert_eq!((report.covered_blocks(), report.total_blocks()), (2, 2));
+
+        // a loop at the start of a function shares the block of the function entry
+        let wasm = wat::parse_str(
+            r#"(module (func (export "count") (param i32)
+                (loop
+                    (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                    (br_if 0 (local.get 0)))))"#,
+        )
+        .expect("valid wat");
+        let module = crate::Module::parse_bytes(&wasm)?;
+
+        let mut store = Store::default();
+        store.enable_coverage();
+        let instance = module.instantiate(&mut store, None)?;
+        instance.exported_func::<i32, ()>(&store, "count")?.call(&mut store, 3)?;
+
+        let report = store.coverage_report(&instance)?;
+        let hits: Vec<_> = report.functions()[0].blocks.iter().map(|b| b.hits).collect();
+        assert_eq!(hits, [3]);
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+//! Deterministic execution
+//!
+//! In deterministic mode, running a module with the same inputs gives bit-identical results and memory on
+//! every machine. WebAssembly is deterministic except for the bit patterns of NaNs produced by float
+//! operations, which depend on the hardware. These are replaced by the canonical NaN (positive, quiet,
+//! with an empty payload) after every arithmetic float operation.
+//!
+//! Host functions are responsible for their own determinism. The store provides a [`DeterministicEnv`]
+//! with a seeded random number generator and a virtual clock for host functions that would otherwise
+//! use the time or randomness of the host, which `tinywasm-wasi` uses for its clocks, `random_get`
+//! and `poll_oneoff`.
+//!
+//! ```rust
+//! use tinywasm::deterministic::DeterministicConfig;
+//! use tinywasm::{Module, Store};
+//!
+//! let wasm = wat::parse_str(r#"(module
+//!     (func (export "nan") (result i32)
+//!         (i32.reinterpret_f32 (f32.div (f32.const 0) (f32.const 0)))))"#).unwrap();
+//!
+//! let mut store = Store::default();
+//! store.enable_deterministic(DeterministicConfig::new(42));
+//! let instance = Module::parse_bytes(&wasm)?.instantiate(&mut store, None)?;
+//! let nan = instance.exported_func::<(), i32>(&store, "nan")?.call(&mut store, ())?;
+//! assert_eq!(nan as u32, 0x7fc0_0000);
+//!
+//! let env = store.deterministic_env().unwrap();
+//! assert_eq!(env.next_u64(), DeterministicConfig::new(42).env().next_u64());
+//! # Ok::<(), tinywasm::Error>(())
+//! ```
+
+use crate::Store;
+
+/// The configuration of the deterministic mode of a [`Store`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeterministicConfig {
+    /// The seed of the random number generator
+    pub seed: u64,
+    /// The wall clock time when the store starts, in nanoseconds since the Unix epoch
+    pub start_time: u64,
+    /// How far the virtual clock advances every time it is read, in nanoseconds
+    ///
+    /// A non-zero step makes loops that wait for time to pass terminate.
+    pub clock_step: u64,
+}
+
+impl Default for DeterministicConfig {
+    fn default() -> Self {
+        Self { seed: 0, start_time: 0, clock_step: 1_000 }
+    }
+}
+
+impl DeterministicConfig {
+    /// Create a configuration with the given seed and default clock settings
+    pub fn new(seed: u64) -> Self {
+        Self { seed, ..Default::default() }
+    }
+
+    /// Create the environment described by this configuration
+    pub fn env(self) -> DeterministicEnv {
+        DeterministicEnv { config: self, rng: self.seed, elapsed: 0 }
+    }
+}
+
+/// Seeded, virtual sources of time and randomness for host functions
+///
+/// Random numbers are generated with SplitMix64, so the sequence for a seed never changes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeterministicEnv {
+    config: DeterministicConfig,
+    rng: u64,
+    elapsed: u64,
+}
+
+impl DeterministicEnv {
+    /// Get the configuration of the environment
+    pub fn config(&self) -> &DeterministicConfig {
+        &self.config
+    }
+
+    /// Generate a random number
+    pub fn next_u64(&mut self) -> u64 {
+        // https://prng.di.unimi.it/splitmix64.c
+        self.rng = self.rng.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Fill a buffer with random bytes
+    pub fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            chunk.copy_from_slice(&self.next_u64().to_le_bytes()[..chunk.len()]);
+        }
+    }
+
+    /// Read the monotonic clock, in nanoseconds since the store started, and advance it
+    pub fn monotonic_time(&mut self) -> u64 {
+        let time = self.elapsed;
+        self.elapsed = self.elapsed.saturating_add(self.config.clock_step);
+        time
+    }
+
+    /// Read the wall clock, in nanoseconds since the Unix epoch, and advance it
+    pub fn wall_time(&mut self) -> u64 {
+        self.config.start_time.saturating_add(self.monotonic_time())
+    }
+
+    /// Advance the clock without waiting, e.g. instead of sleeping
+    pub fn sleep(&mut self, nanos: u64) {
+        self.elapsed = self.elapsed.saturating_add(nanos);
+    }
+}
+
+impl Store {
+    /// Enable deterministic mode, replacing the environment if it was already enabled
+    ///
+    /// See the [`deterministic`](crate::deterministic) module for what this changes.
+    pub fn enable_deterministic(&mut self, config: DeterministicConfig) {
+        self.deterministic = Some(config.env());
+    }
+
+    /// Disable deterministic mode
+    pub fn disable_deterministic(&mut self) {
+        self.deterministic = None;
+    }
+
+    /// Check if deterministic mode is enabled
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic.is_some()
+    }
+
+    /// Get the sources of time and randomness for host functions, if deterministic mode is enabled
+    pub fn deterministic_env(&mut self) -> Option<&mut DeterministicEnv> {
+        self.deterministic.as_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_env() {
+        let mut env = DeterministicConfig { seed: 1, start_time: 100, clock_step: 10 }.env();
+        // reference values of splitmix64 with seed 1
+        assert_eq!(env.next_u64(), 0x910a_2dec_8902_5cc1);
+        assert_eq!(env.next_u64(), 0xbeeb_8da1_658e_ec67);
+
+        let mut buf = [0; 3];
+        env.fill_bytes(&mut buf);
+        assert_eq!(buf, [0x5e, 0x55, 0x32]);
+
+        assert_eq!((env.monotonic_time(), env.wall_time()), (0, 110));
+        env.sleep(1000);
+        assert_eq!(env.monotonic_time(), 1020);
+    }
+
+    #[test]
+    #[cfg(feature = "parser")]
+    fn test_canonical_nan() {
+        use crate::Module;
+        use alloc::{format, string::String};
+
+        // (type, bits type, op, arity), the result of every op is checked for the canonical NaN
+        let ops = [
+            ("f32", "i32", "add", 2),
+            ("f32", "i32", "sub", 2),
+            ("f32", "i32", "mul", 2),
+            ("f32", "i32", "div", 2),
+            ("f32", "i32", "min", 2),
+            ("f32", "i32", "max", 2),
+            ("f32", "i32", "sqrt", 1),
+            ("f32", "i32", "ceil", 1),
+            ("f32", "i32", "floor", 1),
+            ("f32", "i32", "trunc", 1),
+            ("f32", "i32", "nearest", 1),
+            ("f64", "i64", "add", 2),
+            ("f64", "i64", "sub", 2),
+            ("f64", "i64", "mul", 2),
+            ("f64", "i64", "div", 2),
+            ("f64", "i64", "min", 2),
+            ("f64", "i64", "max", 2),
+            ("f64", "i64", "sqrt", 1),
+            ("f64", "i64", "ceil", 1),
+            ("f64", "i64", "floor", 1),
+            ("f64", "i64", "trunc", 1),
+            ("f64", "i64", "nearest", 1),
+        ];
+
+        let mut funcs = String::new();
+        for (ty, bits, op, arity) in ops {
+            let arg = format!("({ty}.reinterpret_{bits} (local.get 0))");
+            let args = if arity == 2 { format!("{arg} {arg}") } else { arg };
+            funcs += &format!(
+                "(func (export \"{ty}.{op}\") (param {bits}) (result {bits}) ({bits}.reinterpret_{ty} ({ty}.{op} {args})))"
+            );
+        }
+        funcs += "(func (export \"f32.demote_f64\") (param i64) (result i32)
+            (i32.reinterpret_f32 (f32.demote_f64 (f64.reinterpret_i64 (local.get 0)))))";
+        funcs += "(func (export \"f64.promote_f32\") (param i32) (result i64)
+            (i64.reinterpret_f64 (f64.promote_f32 (f32.reinterpret_i32 (local.get 0)))))";
+        let wasm = wat::parse_str(format!("(module {funcs})")).unwrap();
+
+        let mut store = Store::default();
+        store.enable_deterministic(DeterministicConfig::new(0));
+        let instance = Module::parse_bytes(&wasm).unwrap().instantiate(&mut store, None).unwrap();
+
+        // negative NaNs with a payload
+        let (nan32, nan64) = (0xffa0_0001_u32 as i32, 0xfff4_0000_0000_0001_u64 as i64);
+        let (canonical32, canonical64) = (0x7fc0_0000_u32 as i32, 0x7ff8_0000_0000_0000_u64 as i64);
+        for (ty, _, op, _) in ops {
+            let name = format!("{ty}.{op}");
+            let res = match ty {
+                "f32" => {
+                    instance.exported_func::<i32, i32>(&store, &name).unwrap().call(&mut store, nan32).unwrap()
+                        == canonical32
+                }
+                _ => {
+                    instance.exported_func::<i64, i64>(&store, &name).unwrap().call(&mut store, nan64).unwrap()
+                        == canonical64
+                }
+            };
+            assert!(res, "{name} did not return the canonical NaN");
+        }
+
+        let demote = instance.exported_func::<i64, i32>(&store, "f32.demote_f64").unwrap();
+        assert_eq!(demote.call(&mut store, nan64).unwrap(), canonical32);
+        let promote = instance.exported_func::<i32, i64>(&store, "f64.promote_f32").unwrap();
+        assert_eq!(promote.call(&mut store, nan32).unwrap(), canonical64);
+    }
+}
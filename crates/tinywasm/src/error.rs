@@ -1,6 +1,10 @@
+use alloc::boxed::Box;
 use alloc::string::{String, ToString};
-use core::{fmt::Display, ops::ControlFlow};
-use tinywasm_types::FuncType;
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt::{Debug, Display};
+use core::ops::ControlFlow;
+use tinywasm_types::{FuncAddr, FuncType, ModuleInstanceAddr};
 
 #[cfg(feature = "parser")]
 pub use tinywasm_parser::ParseError;
@@ -29,6 +33,15 @@ pub enum Error {
     /// The store is not the one that the module instance was instantiated in
     InvalidStore,
 
+    /// Execution was stopped by a memory watchpoint
+    Watchpoint(crate::MemoryAccess),
+
+    /// A replayed host call differs from the recording
+    ReplayDivergence(crate::replay::Divergence),
+
+    /// A host function returned an error, see [`Error::host`]
+    Host(HostError),
+
     #[cfg(feature = "std")]
     /// An I/O error occurred
     Io(crate::std::io::Error),
@@ -56,6 +69,14 @@ pub enum LinkingError {
         /// The import name
         name: String,
     },
+
+    /// An item was defined twice in a [`crate::Linker`] that does not allow shadowing
+    DuplicateDefinition {
+        /// The module name
+        module: String,
+        /// The item name
+        name: String,
+    },
 }
 
 impl LinkingError {
@@ -127,6 +148,20 @@ pub enum Trap {
         /// The actual type
         actual: FuncType,
     },
+
+    /// An import that was left unresolved by [`crate::Linker::define_unknown_imports_as_traps`] was called
+    UnresolvedImport {
+        /// The module name
+        module: String,
+        /// The import name
+        name: String,
+    },
+
+    /// A host function panicked, see [`crate::Store::catch_host_panics`]
+    HostPanic {
+        /// The panic message
+        message: String,
+    },
 }
 
 impl Trap {
@@ -143,6 +178,8 @@ impl Trap {
             Self::UndefinedElement { .. } => "undefined element",
             Self::UninitializedElement { .. } => "uninitialized element",
             Self::IndirectCallTypeMismatch { .. } => "indirect call type mismatch",
+            Self::UnresolvedImport { .. } => "unresolved import",
+            Self::HostPanic { .. } => "host function panicked",
         }
     }
 }
@@ -153,6 +190,7 @@ impl LinkingError {
         match self {
             Self::UnknownImport { .. } => "unknown import",
             Self::IncompatibleImportType { .. } => "incompatible import type",
+            Self::DuplicateDefinition { .. } => "duplicate definition",
         }
     }
 }
@@ -169,6 +207,171 @@ impl From<Trap> for Error {
     }
 }
 
+impl From<HostError> for Error {
+    fn from(value: HostError) -> Self {
+        Self::Host(value)
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<Box<dyn crate::std::error::Error + Send + Sync>> for Error {
+    fn from(value: Box<dyn crate::std::error::Error + Send + Sync>) -> Self {
+        Self::host(value)
+    }
+}
+
+impl Error {
+    /// Create an error for a host function to return
+    ///
+    /// The value is returned unchanged from [`crate::FuncHandle::call`] as [`Error::Host`],
+    /// use [`Error::downcast_ref`] to get it back.
+    ///
+    /// ```rust
+    /// use tinywasm::{Error, Extern, FuncContext, Imports, Module, Store};
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// struct PermissionDenied(&'static str);
+    ///
+    /// impl core::fmt::Display for PermissionDenied {
+    ///     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+    ///         write!(f, "permission denied: {}", self.0)
+    ///     }
+    /// }
+    ///
+    /// let wasm = wat::parse_str(r#"
+    ///     (module
+    ///         (import "env" "open" (func $open))
+    ///         (func (export "run") (call $open)))
+    /// "#).unwrap();
+    ///
+    /// let mut imports = Imports::new();
+    /// imports.define("env", "open", Extern::typed_func(|_: FuncContext<'_>, _: ()| -> tinywasm::Result<()> {
+    ///     Err(Error::host(PermissionDenied("/etc/passwd")))
+    /// }))?;
+    ///
+    /// let mut store = Store::default();
+    /// let instance = Module::parse_bytes(&wasm)?.instantiate(&mut store, Some(imports))?;
+    /// let err = instance.exported_func::<(), ()>(&store, "run")?.call(&mut store, ()).unwrap_err();
+    ///
+    /// assert_eq!(err.downcast_ref(), Some(&PermissionDenied("/etc/passwd")));
+    /// let Error::Host(host) = err else { unreachable!() };
+    /// assert_eq!(host.backtrace().len(), 1);
+    /// # Ok::<(), tinywasm::Error>(())
+    /// ```
+    pub fn host(error: impl HostErrorValue) -> Self {
+        Self::Host(HostError::new(error))
+    }
+
+    /// Get the value of a [`Error::Host`] error if it is of type `T`
+    pub fn downcast_ref<T: HostErrorValue>(&self) -> Option<&T> {
+        match self {
+            Self::Host(err) => err.downcast_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// A value that a host function can fail with, see [`Error::host`]
+///
+/// Implemented for all types that implement `Debug` and `Display` and are `Send + Sync + 'static`,
+/// including `String`, all error types and `Box<dyn Error + Send + Sync>`.
+pub trait HostErrorValue: Any + Debug + Display + Send + Sync {
+    #[doc(hidden)]
+    fn as_any(&self) -> &dyn Any;
+
+    #[doc(hidden)]
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    #[doc(hidden)]
+    fn into_any(self: Box<Self>) -> Box<dyn Any>;
+}
+
+impl<T: Any + Debug + Display + Send + Sync> HostErrorValue for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn into_any(self: Box<Self>) -> Box<dyn Any> {
+        self
+    }
+}
+
+/// An error returned by a host function
+///
+/// Contains the original value and the WebAssembly frames that were executing when the host function
+/// was called. A `Box<dyn Error + Send + Sync>` is stored as it is, so downcast to it first to get the
+/// concrete error.
+#[derive(Debug)]
+pub struct HostError {
+    error: Box<dyn HostErrorValue>,
+    backtrace: Vec<WasmFrame>,
+}
+
+impl HostError {
+    /// Create a new host error
+    pub fn new(error: impl HostErrorValue) -> Self {
+        Self { error: Box::new(error), backtrace: Vec::new() }
+    }
+
+    /// Get the value of the error
+    pub fn get(&self) -> &dyn HostErrorValue {
+        &*self.error
+    }
+
+    /// Get the value of the error if it is of type `T`
+    pub fn downcast_ref<T: HostErrorValue>(&self) -> Option<&T> {
+        (*self.error).as_any().downcast_ref()
+    }
+
+    /// Get a mutable reference to the value of the error if it is of type `T`
+    pub fn downcast_mut<T: HostErrorValue>(&mut self) -> Option<&mut T> {
+        (*self.error).as_any_mut().downcast_mut()
+    }
+
+    /// Take the value of the error if it is of type `T`, otherwise return the error unchanged
+    pub fn downcast<T: HostErrorValue>(self) -> Result<T, Self> {
+        match (*self.error).as_any().is::<T>() {
+            true => Ok(*self.error.into_any().downcast().expect("type was checked")),
+            false => Err(self),
+        }
+    }
+
+    /// The WebAssembly frames that were executing when the host function was called, innermost first
+    ///
+    /// Host functions that call back into WebAssembly add the frames of every level of calls. Empty if
+    /// the host function was called directly with [`crate::FuncHandle::call`].
+    pub fn backtrace(&self) -> &[WasmFrame] {
+        &self.backtrace
+    }
+
+    pub(crate) fn push_frames(&mut self, frames: impl IntoIterator<Item = WasmFrame>) {
+        self.backtrace.extend(frames);
+    }
+}
+
+impl Display for HostError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        Display::fmt(&self.error, f)
+    }
+}
+
+/// A WebAssembly function that was executing, see [`HostError::backtrace`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WasmFrame {
+    /// The module instance the function belongs to
+    pub module_addr: ModuleInstanceAddr,
+    /// The address of the function in the store
+    pub func_addr: FuncAddr,
+    /// The index of the function in its module
+    pub func_index: Option<u32>,
+    /// The index of the executing call instruction in the function's compiled instructions
+    pub instr_index: usize,
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
@@ -185,6 +388,9 @@ impl Display for Error {
             Self::UnsupportedFeature(feature) => write!(f, "unsupported feature: {feature}"),
             Self::FuncDidNotReturn => write!(f, "function did not return"),
             Self::InvalidStore => write!(f, "invalid store"),
+            Self::Watchpoint(access) => write!(f, "memory watchpoint hit: {access}"),
+            Self::ReplayDivergence(divergence) => write!(f, "replay diverged: {divergence}"),
+            Self::Host(err) => write!(f, "host error: {err}"),
         }
     }
 }
@@ -196,6 +402,7 @@ impl Display for LinkingError {
             Self::IncompatibleImportType { module, name } => {
                 write!(f, "incompatible import type: {}.{}", module, name)
             }
+            Self::DuplicateDefinition { module, name } => write!(f, "duplicate definition: {}.{}", module, name),
         }
     }
 }
@@ -221,6 +428,8 @@ impl Display for Trap {
             Self::IndirectCallTypeMismatch { expected, actual } => {
                 write!(f, "indirect call type mismatch: expected={expected:?}, actual={actual:?}")
             }
+            Self::UnresolvedImport { module, name } => write!(f, "unresolved import: {module}.{name}"),
+            Self::HostPanic { message } => write!(f, "host function panicked: {message}"),
         }
     }
 }
@@ -250,3 +459,46 @@ impl<T> Controlify<T> for Result<T, Error> {
         }
     }
 }
+
+#[cfg(all(test, feature = "std", feature = "parser"))]
+mod tests {
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+
+    use crate::std::io;
+    use crate::{Error, Extern, FuncContext, Imports, Module, Store, Trap};
+
+    fn run(store: &mut Store, host: impl Fn() -> crate::Result<()> + 'static) -> Error {
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "env" "host" (func $host))
+                (func $inner (call $host))
+                (func (export "run") nop (call $inner)))"#,
+        )
+        .unwrap();
+
+        let mut imports = Imports::new();
+        imports.define("env", "host", Extern::typed_func(move |_: FuncContext<'_>, _: ()| host())).unwrap();
+        let instance = Module::parse_bytes(&wasm).unwrap().instantiate(store, Some(imports)).unwrap();
+        instance.exported_func::<(), ()>(store, "run").unwrap().call(store, ()).unwrap_err()
+    }
+
+    #[test]
+    fn test_host_error() {
+        let err = run(&mut Store::default(), || Err(Error::host(io::Error::other("denied"))));
+        assert_eq!(err.to_string(), "host error: denied");
+
+        let Error::Host(err) = err else { panic!("expected a host error") };
+        let frames: Vec<_> = err.backtrace().iter().map(|frame| (frame.func_index, frame.instr_index)).collect();
+        assert_eq!(frames, [(Some(1), 0), (Some(2), 1)]);
+        assert_eq!(err.downcast::<io::Error>().unwrap().kind(), io::ErrorKind::Other);
+    }
+
+    #[test]
+    fn test_host_panic() {
+        let mut store = Store::default();
+        store.catch_host_panics(true);
+        let err = run(&mut store, || panic!("oops"));
+        assert!(matches!(err, Error::Trap(Trap::HostPanic { message }) if message == "oops"));
+    }
+}
@@ -1,13 +1,13 @@
 use crate::interpreter::stack::{CallFrame, Stack};
 use crate::{log, unlikely, Function};
-use crate::{Error, FuncContext, Result, Store};
+use crate::{Error, ExternRef, FuncContext, FuncRef, Result, Store};
 use alloc::{boxed::Box, format, string::String, string::ToString, vec, vec::Vec};
 use tinywasm_types::{FuncType, ModuleInstanceAddr, ValType, WasmValue};
 
 #[derive(Debug)]
 /// A function handle
 pub struct FuncHandle {
-    pub(crate) module_addr: ModuleInstanceAddr,
+    pub(crate) module_addr: Option<ModuleInstanceAddr>,
     pub(crate) addr: u32,
     pub(crate) ty: FuncType,
 
@@ -16,11 +16,25 @@ pub struct FuncHandle {
 }
 
 impl FuncHandle {
+    /// Get a `funcref` to this function, e.g. to store it in a table
+    pub fn func_ref(&self) -> FuncRef {
+        FuncRef::from(self)
+    }
+
     /// Call a function (Invocation)
     ///
     /// See <https://webassembly.github.io/spec/core/exec/modules.html#invocation>
     #[inline]
     pub fn call(&self, store: &mut Store, params: &[WasmValue]) -> Result<Vec<WasmValue>> {
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+        let span = crate::tracing::info_span!(
+            "call",
+            module = self.module_addr,
+            func = self.name.as_deref(),
+            fuel = tracing::field::Empty
+        )
+        .entered();
+
         // Comments are ordered by the steps in the spec
         // In this implementation, some steps are combined and ordered differently for performance reasons
 
@@ -51,23 +65,31 @@ impl FuncHandle {
         let func_inst = store.get_func(self.addr);
         let wasm_func = match &func_inst.func {
             Function::Host(host_func) => {
-                let func = &host_func.clone().func;
-                let ctx = FuncContext { store, module_addr: self.module_addr };
-                return (func)(ctx, params);
+                let host_func = host_func.clone();
+                let ctx = FuncContext { store, module_addr: self.module_addr, func_addr: self.addr };
+                return host_func.call(ctx, params);
             }
             Function::Wasm(wasm_func) => wasm_func,
         };
 
         // 6. Let f be the dummy frame
-        let call_frame = CallFrame::new(wasm_func.clone(), func_inst.owner, params, 0);
+        let call_frame = CallFrame::new(wasm_func.clone(), self.addr, func_inst.owner, params, 0);
 
         // 7. Push the frame f to the call stack
         // & 8. Push the values to the stack (Not needed since the call frame owns the values)
         let mut stack = Stack::new(call_frame);
 
         // 9. Invoke the function instance
+        // the fuel used by a call is the number of instructions it executed
+        #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+        let instructions = store.instructions_executed;
+
         let runtime = store.runtime();
-        runtime.exec(store, &mut stack)?;
+        let res = runtime.exec(store, &mut stack);
+
+        #[cfg(feature = "tracing")]
+        span.record("fuel", store.instructions_executed - instructions);
+        res?;
 
         // Once the function returns:
         // let result_m = func_ty.results.len();
@@ -91,11 +113,19 @@ pub struct FuncHandleTyped<P, R> {
     pub(crate) marker: core::marker::PhantomData<(P, R)>,
 }
 
+/// Types that can be passed to WebAssembly as a list of values, e.g. function parameters
+///
+/// Implemented for single values and tuples of them, and can be derived with the `macros` feature.
 pub trait IntoWasmValueTuple {
+    /// Convert into a list of values
     fn into_wasm_value_tuple(self) -> Vec<WasmValue>;
 }
 
+/// Types that can be created from a list of values returned by WebAssembly, e.g. function results
+///
+/// Implemented for single values and tuples of them, and can be derived with the `macros` feature.
 pub trait FromWasmValueTuple {
+    /// Convert from a list of values
     fn from_wasm_value_tuple(values: &[WasmValue]) -> Result<Self>
     where
         Self: Sized;
@@ -187,11 +217,17 @@ macro_rules! impl_from_wasm_value_tuple_single {
     };
 }
 
+/// Types that describe a list of WebAssembly value types, e.g. a function signature
+///
+/// Implemented for single values and tuples of them, and can be derived with the `macros` feature.
 pub trait ValTypesFromTuple {
+    /// Get the value types
     fn val_types() -> Box<[ValType]>;
 }
 
+/// Types that correspond to a single WebAssembly value type
 pub trait ToValType {
+    /// Get the value type
     fn to_val_type() -> ValType;
 }
 
@@ -219,6 +255,24 @@ impl ToValType for f64 {
     }
 }
 
+impl ToValType for u128 {
+    fn to_val_type() -> ValType {
+        ValType::V128
+    }
+}
+
+impl ToValType for ExternRef {
+    fn to_val_type() -> ValType {
+        ValType::RefExtern
+    }
+}
+
+impl ToValType for FuncRef {
+    fn to_val_type() -> ValType {
+        ValType::RefFunc
+    }
+}
+
 macro_rules! impl_val_types_from_tuple {
     ($($t:ident),+) => {
         impl<$($t),+> ValTypesFromTuple for ($($t,)+)
@@ -251,11 +305,17 @@ impl_from_wasm_value_tuple_single!(i32);
 impl_from_wasm_value_tuple_single!(i64);
 impl_from_wasm_value_tuple_single!(f32);
 impl_from_wasm_value_tuple_single!(f64);
+impl_from_wasm_value_tuple_single!(u128);
+impl_from_wasm_value_tuple_single!(ExternRef);
+impl_from_wasm_value_tuple_single!(FuncRef);
 
 impl_into_wasm_value_tuple_single!(i32);
 impl_into_wasm_value_tuple_single!(i64);
 impl_into_wasm_value_tuple_single!(f32);
 impl_into_wasm_value_tuple_single!(f64);
+impl_into_wasm_value_tuple_single!(u128);
+impl_into_wasm_value_tuple_single!(ExternRef);
+impl_into_wasm_value_tuple_single!(FuncRef);
 
 impl_val_types_from_tuple!(T1);
 impl_val_types_from_tuple!(T1, T2);
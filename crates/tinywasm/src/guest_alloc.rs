@@ -0,0 +1,327 @@
+use alloc::string::String;
+use alloc::{format, vec::Vec};
+use tinywasm_types::{ExternVal, FuncType, MemAddr, ValType, WasmValue};
+
+use crate::{Error, FuncHandle, MemoryRef, MemoryRefMut, ModuleInstance, Result, Store, WasmPtr, WasmSlice};
+
+#[derive(Debug)]
+enum Strategy {
+    // `malloc(size) -> ptr` and `free(ptr)`, e.g. C, C++ and Zig
+    Malloc { malloc: FuncHandle, free: FuncHandle },
+    // `__alloc(size) -> ptr` or `__alloc(size, id) -> ptr`, and optionally `__free(ptr)`
+    Alloc { alloc: FuncHandle, free: Option<FuncHandle> },
+    // `cabi_realloc(old_ptr, old_size, align, new_size) -> ptr` from the component model, which can't free
+    CabiRealloc(FuncHandle),
+}
+
+/// Allocates memory in a guest using the allocator it exports
+///
+/// Passing strings and buffers to WebAssembly usually means allocating memory in the guest, copying the data
+/// and passing a pointer and length. The allocator is discovered from the exports of an instance, checking
+/// for these conventions in order:
+///
+/// - `malloc(size) -> ptr` and `free(ptr)`
+/// - `__alloc(size) -> ptr` or `__alloc(size, id) -> ptr` (called with an id of `0`) and an optional `__free(ptr)`
+/// - `cabi_realloc(old_ptr, old_size, align, new_size) -> ptr`, which can't free memory
+///
+/// Memory is usually managed with a [`GuestScope`], which frees its allocations when dropped.
+///
+/// ## Example
+/// ```rust
+/// # fn main() -> tinywasm::Result<()> {
+/// use tinywasm::{GuestAllocator, Module, Store};
+///
+/// // a bump allocator that never frees
+/// let wasm = wat::parse_str(r#"
+///     (module
+///         (memory (export "memory") 1)
+///         (global $next (mut i32) (i32.const 1024))
+///         (func (export "malloc") (param i32) (result i32)
+///             (global.get $next)
+///             (global.set $next (i32.add (global.get $next) (local.get 0))))
+///         (func (export "free") (param i32))
+///         (func (export "first_byte") (param i32 i32) (result i32)
+///             (i32.load8_u (local.get 0))))
+/// "#).unwrap();
+///
+/// let mut store = Store::default();
+/// let instance = Module::parse_bytes(&wasm)?.instantiate(&mut store, None)?;
+/// let allocator = GuestAllocator::new(&store, &instance)?;
+/// let first_byte = instance.exported_func::<(i32, i32), i32>(&store, "first_byte")?;
+///
+/// let mut scope = allocator.scope(&mut store);
+/// let name = scope.write_str("hello")?;
+/// let res = first_byte.call(scope.store(), (name.ptr().offset() as i32, name.len() as i32))?;
+/// assert_eq!(res, b'h' as i32);
+/// assert_eq!(scope.read_string(name)?, "hello");
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct GuestAllocator {
+    strategy: Strategy,
+    memory: MemAddr,
+    store_id: usize,
+}
+
+impl GuestAllocator {
+    /// Discover the allocator of an instance, using the memory exported as `memory`
+    pub fn new(store: &Store, instance: &ModuleInstance) -> Result<Self> {
+        Self::with_memory(store, instance, "memory")
+    }
+
+    /// Discover the allocator of an instance, using the memory exported under the given name
+    pub fn with_memory(store: &Store, instance: &ModuleInstance, memory: &str) -> Result<Self> {
+        let Some(ExternVal::Memory(memory)) = instance.export_addr(memory) else {
+            return Err(Error::Other(format!("Export is not a memory: {memory}")));
+        };
+
+        let func = |name: &str, params: &[ValType], results: &[ValType]| {
+            let func = instance.exported_func_untyped(store, name).ok()?;
+            (func.ty == FuncType { params: params.into(), results: results.into() }).then_some(func)
+        };
+
+        use ValType::I32;
+        let strategy = if let (Some(malloc), Some(free)) = (func("malloc", &[I32], &[I32]), func("free", &[I32], &[])) {
+            Strategy::Malloc { malloc, free }
+        } else if let Some(alloc) = func("__alloc", &[I32], &[I32]).or_else(|| func("__alloc", &[I32, I32], &[I32])) {
+            Strategy::Alloc { alloc, free: func("__free", &[I32], &[]) }
+        } else if let Some(realloc) = func("cabi_realloc", &[I32, I32, I32, I32], &[I32]) {
+            Strategy::CabiRealloc(realloc)
+        } else {
+            return Err(Error::Other("no supported allocator exported (malloc, __alloc or cabi_realloc)".into()));
+        };
+
+        Ok(Self { strategy, memory, store_id: store.id() })
+    }
+
+    /// Check if the allocator can free memory
+    pub fn can_free(&self) -> bool {
+        !matches!(self.strategy, Strategy::CabiRealloc(_) | Strategy::Alloc { free: None, .. })
+    }
+
+    /// Get the memory allocations are made in
+    pub fn memory<'a>(&self, store: &'a mut Store) -> Result<MemoryRefMut<'a>> {
+        self.check_store(store)?;
+        Ok(MemoryRefMut(store.get_mem_mut(self.memory)))
+    }
+
+    /// Allocate `size` bytes with the given alignment
+    ///
+    /// The alignment is only passed to `cabi_realloc`, the other allocators are expected to align
+    /// allocations suitably for any type.
+    pub fn alloc(&self, store: &mut Store, size: u32, align: u32) -> Result<WasmSlice<u8>> {
+        self.check_store(store)?;
+        let size_val = WasmValue::I32(size as i32);
+        let res = match &self.strategy {
+            Strategy::Malloc { malloc, .. } => malloc.call(store, &[size_val])?,
+            Strategy::Alloc { alloc, .. } if alloc.ty.params.len() == 2 => {
+                alloc.call(store, &[size_val, WasmValue::I32(0)])?
+            }
+            Strategy::Alloc { alloc, .. } => alloc.call(store, &[size_val])?,
+            Strategy::CabiRealloc(realloc) => {
+                realloc.call(store, &[WasmValue::I32(0), WasmValue::I32(0), WasmValue::I32(align as i32), size_val])?
+            }
+        };
+
+        let ptr =
+            WasmPtr::try_from(res[0]).map_err(|_| Error::Other("allocator returned an invalid pointer".into()))?;
+        if ptr.is_null() {
+            return Err(Error::Other(format!("guest failed to allocate {size} bytes")));
+        }
+        Ok(ptr.slice(size))
+    }
+
+    /// Free an allocation, does nothing if the allocator can't free memory
+    pub fn free(&self, store: &mut Store, slice: WasmSlice<u8>) -> Result<()> {
+        self.check_store(store)?;
+        let ptr = WasmValue::from(slice.ptr());
+        match &self.strategy {
+            Strategy::Malloc { free, .. } | Strategy::Alloc { free: Some(free), .. } => free.call(store, &[ptr])?,
+            Strategy::Alloc { free: None, .. } | Strategy::CabiRealloc(_) => return Ok(()),
+        };
+        Ok(())
+    }
+
+    /// Allocate memory and copy `data` into it
+    ///
+    /// The caller is responsible for freeing the returned slice. At least one byte is allocated,
+    /// so the pointer is never null, even for empty data.
+    pub fn write_bytes(&self, store: &mut Store, data: &[u8]) -> Result<WasmSlice<u8>> {
+        let len = u32::try_from(data.len()).map_err(|_| Error::Other("buffer too large for guest memory".into()))?;
+        let allocation = self.alloc(store, len.max(1), 1)?;
+        let slice = allocation.ptr().slice(len);
+        if let Err(err) = slice.write_slice(&mut self.memory(store)?, data) {
+            self.free(store, allocation)?;
+            return Err(err);
+        }
+        Ok(slice)
+    }
+
+    /// Read a buffer from guest memory
+    pub fn read_bytes(&self, store: &Store, slice: WasmSlice<u8>) -> Result<Vec<u8>> {
+        self.check_store(store)?;
+        slice.read_vec(&MemoryRef(store.get_mem(self.memory)))
+    }
+
+    /// Read a UTF-8 string from guest memory
+    pub fn read_string(&self, store: &Store, slice: WasmSlice<u8>) -> Result<String> {
+        String::from_utf8(self.read_bytes(store, slice)?)
+            .map_err(|_| Error::Other("guest string is not valid UTF-8".into()))
+    }
+
+    /// Read a buffer returned by the guest and free it
+    pub fn take_bytes(&self, store: &mut Store, slice: WasmSlice<u8>) -> Result<Vec<u8>> {
+        let bytes = self.read_bytes(store, slice)?;
+        self.free(store, slice)?;
+        Ok(bytes)
+    }
+
+    /// Read a UTF-8 string returned by the guest and free it
+    pub fn take_string(&self, store: &mut Store, slice: WasmSlice<u8>) -> Result<String> {
+        String::from_utf8(self.take_bytes(store, slice)?)
+            .map_err(|_| Error::Other("guest string is not valid UTF-8".into()))
+    }
+
+    /// Start a scope that frees all of its allocations when dropped
+    pub fn scope<'a>(&'a self, store: &'a mut Store) -> GuestScope<'a> {
+        GuestScope { allocator: self, store, owned: Vec::new() }
+    }
+
+    fn check_store(&self, store: &Store) -> Result<()> {
+        match self.store_id == store.id() {
+            true => Ok(()),
+            false => Err(Error::InvalidStore),
+        }
+    }
+}
+
+/// A set of guest allocations that are freed when the scope is dropped
+///
+/// Borrows the store for its lifetime, use [`GuestScope::store`] to call functions in the meantime.
+/// Errors while freeing on drop are ignored, use [`GuestScope::finish`] to handle them.
+#[derive(Debug)]
+pub struct GuestScope<'a> {
+    allocator: &'a GuestAllocator,
+    store: &'a mut Store,
+    owned: Vec<WasmSlice<u8>>,
+}
+
+impl GuestScope<'_> {
+    /// Get the store, e.g. to call a function with the allocated buffers
+    pub fn store(&mut self) -> &mut Store {
+        self.store
+    }
+
+    /// Get the memory allocations are made in
+    pub fn memory(&mut self) -> Result<MemoryRefMut<'_>> {
+        self.allocator.memory(self.store)
+    }
+
+    /// Copy a buffer into guest memory
+    pub fn write_bytes(&mut self, data: &[u8]) -> Result<WasmSlice<u8>> {
+        let slice = self.allocator.write_bytes(self.store, data)?;
+        self.owned.push(slice);
+        Ok(slice)
+    }
+
+    /// Copy a string into guest memory, without a nul terminator
+    pub fn write_str(&mut self, data: &str) -> Result<WasmSlice<u8>> {
+        self.write_bytes(data.as_bytes())
+    }
+
+    /// Read a buffer from guest memory
+    pub fn read_bytes(&self, slice: WasmSlice<u8>) -> Result<Vec<u8>> {
+        self.allocator.read_bytes(self.store, slice)
+    }
+
+    /// Read a UTF-8 string from guest memory
+    pub fn read_string(&self, slice: WasmSlice<u8>) -> Result<String> {
+        self.allocator.read_string(self.store, slice)
+    }
+
+    /// Free a buffer returned by the guest when the scope ends
+    pub fn adopt(&mut self, slice: WasmSlice<u8>) {
+        self.owned.push(slice);
+    }
+
+    /// Keep an allocation alive after the scope ends, e.g. because the guest took ownership of it
+    ///
+    /// Returns `false` if the allocation does not belong to this scope.
+    pub fn keep(&mut self, slice: WasmSlice<u8>) -> bool {
+        let Some(idx) = self.owned.iter().position(|s| s.ptr() == slice.ptr()) else { return false };
+        self.owned.swap_remove(idx);
+        true
+    }
+
+    /// Free all allocations, returning the first error
+    pub fn finish(mut self) -> Result<()> {
+        self.free_all()
+    }
+
+    fn free_all(&mut self) -> Result<()> {
+        let mut res = Ok(());
+        for slice in core::mem::take(&mut self.owned) {
+            if let Err(err) = self.allocator.free(self.store, slice) {
+                res = res.and(Err(err));
+            }
+        }
+        res
+    }
+}
+
+impl Drop for GuestScope<'_> {
+    fn drop(&mut self) {
+        let _ = self.free_all();
+    }
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+    use crate::Module;
+
+    #[test]
+    fn test_scope_frees() {
+        // a bump allocator counting its frees and summing the freed pointers
+        let wasm = wat::parse_str(
+            r#"(module
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 1024))
+                (global $frees (export "frees") (mut i32) (i32.const 0))
+                (global $freed (export "freed") (mut i32) (i32.const 0))
+                (func (export "malloc") (param i32) (result i32)
+                    (global.get $next)
+                    (global.set $next (i32.add (global.get $next) (local.get 0))))
+                (func (export "free") (param i32)
+                    (global.set $frees (i32.add (global.get $frees) (i32.const 1)))
+                    (global.set $freed (i32.add (global.get $freed) (local.get 0)))))"#,
+        )
+        .unwrap();
+
+        let mut store = Store::default();
+        let instance = Module::parse_bytes(&wasm).unwrap().instantiate(&mut store, None).unwrap();
+        let allocator = GuestAllocator::new(&store, &instance).unwrap();
+        let frees = |store: &Store| instance.exported_global(store, "frees").unwrap().get_as::<i32>().unwrap();
+        let freed = |store: &Store| instance.exported_global(store, "freed").unwrap().get_as::<i32>().unwrap();
+
+        let mut scope = allocator.scope(&mut store);
+        let a = scope.write_str("hello").unwrap();
+        let b = scope.write_bytes(&[]).unwrap();
+        let kept = scope.write_str("kept").unwrap();
+        assert!(scope.keep(kept));
+        assert!(!scope.keep(kept));
+        scope.adopt(WasmPtr::new(2048).slice(4));
+        assert_eq!(frees(scope.store()), 0);
+        drop(scope);
+
+        assert_eq!(frees(&store), 3);
+        let expected = a.ptr().offset() + b.ptr().offset() + 2048;
+        assert_eq!(freed(&store), expected as i32);
+
+        let mut scope = allocator.scope(&mut store);
+        scope.write_str("world").unwrap();
+        scope.finish().unwrap();
+        assert_eq!(frees(&store), 4);
+    }
+}
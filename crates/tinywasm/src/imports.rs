@@ -6,7 +6,7 @@ use alloc::vec::Vec;
 use core::fmt::Debug;
 
 use crate::func::{FromWasmValueTuple, IntoWasmValueTuple, ValTypesFromTuple};
-use crate::{log, LinkingError, MemoryRef, MemoryRefMut, Result};
+use crate::{log, ImportType, LinkingError, MemoryRef, MemoryRefMut, Result};
 use tinywasm_types::*;
 
 /// The internal representation of a function
@@ -41,8 +41,33 @@ impl HostFunction {
     }
 
     /// Call the function
+    ///
+    /// If the store is recording or replaying host calls, the call is recorded or answered from the recording.
+    /// See [`crate::replay`].
     pub fn call(&self, ctx: FuncContext<'_>, args: &[WasmValue]) -> Result<Vec<WasmValue>> {
-        (self.func)(ctx, args)
+        let _span = crate::tracing::debug_span!("host_call", module = ctx.module_addr, func = ctx.func_addr).entered();
+
+        #[cfg(feature = "std")]
+        if ctx.store.catch_host_panics {
+            let call = crate::std::panic::AssertUnwindSafe(|| self.call_inner(ctx, args));
+            return crate::std::panic::catch_unwind(call).unwrap_or_else(|payload| {
+                let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+                    (Some(message), _) => message.to_string(),
+                    (_, Some(message)) => message.clone(),
+                    _ => "Box<dyn Any>".to_string(),
+                };
+                Err(crate::Trap::HostPanic { message }.into())
+            });
+        }
+
+        self.call_inner(ctx, args)
+    }
+
+    fn call_inner(&self, ctx: FuncContext<'_>, args: &[WasmValue]) -> Result<Vec<WasmValue>> {
+        if ctx.store.replay.is_off() {
+            return (self.func)(ctx, args);
+        }
+        crate::replay::call_host(self, ctx, args)
     }
 }
 
@@ -52,7 +77,8 @@ pub(crate) type HostFuncInner = Box<dyn Fn(FuncContext<'_>, &[WasmValue]) -> Res
 #[derive(Debug)]
 pub struct FuncContext<'a> {
     pub(crate) store: &'a mut crate::Store,
-    pub(crate) module_addr: ModuleInstanceAddr,
+    pub(crate) module_addr: Option<ModuleInstanceAddr>,
+    pub(crate) func_addr: FuncAddr,
 }
 
 impl FuncContext<'_> {
@@ -66,19 +92,25 @@ impl FuncContext<'_> {
         self.store
     }
 
-    /// Get a reference to the module instance
-    pub fn module(&self) -> crate::ModuleInstance {
-        self.store.get_module_instance_raw(self.module_addr)
+    /// Get the module instance that called the function
+    ///
+    /// Fails if the function wasn't called by a module instance, e.g. when a handle returned by
+    /// [`crate::Store::add_host_func`] is called directly.
+    pub fn module(&self) -> Result<crate::ModuleInstance> {
+        let addr = self.module_addr.ok_or_else(|| {
+            crate::Error::Other("the host function was not called from a module instance".to_string())
+        })?;
+        self.store.get_module_instance(addr).cloned().ok_or(crate::Error::InvalidStore)
     }
 
     /// Get a reference to an exported memory
     pub fn exported_memory(&mut self, name: &str) -> Result<MemoryRef<'_>> {
-        self.module().exported_memory(self.store, name)
+        self.module()?.exported_memory(self.store, name)
     }
 
     /// Get a reference to an exported memory
     pub fn exported_memory_mut(&mut self, name: &str) -> Result<MemoryRefMut<'_>> {
-        self.module().exported_memory_mut(self.store, name)
+        self.module()?.exported_memory_mut(self.store, name)
     }
 }
 
@@ -169,11 +201,26 @@ impl Extern {
     }
 }
 
+/// A set of host functions that are defined together under one module name
+///
+/// Usually implemented using the `host_module` attribute macro (requires the `macros` feature).
+/// See [`Imports::define_host_module`] and [`crate::Linker::define_host_module`].
+pub trait HostModule {
+    /// Get the functions together with their names
+    fn into_externs(self) -> Vec<(&'static str, Extern)>;
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Ord, PartialOrd, Hash)]
 /// Name of an import
 pub struct ExternName {
-    module: String,
-    name: String,
+    pub(crate) module: String,
+    pub(crate) name: String,
+}
+
+impl ExternName {
+    pub(crate) fn new(module: &str, name: &str) -> Self {
+        Self { module: module.to_string(), name: name.to_string() }
+    }
 }
 
 impl From<&Import> for ExternName {
@@ -182,7 +229,7 @@ impl From<&Import> for ExternName {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Default)]
 /// Imports for a module instance
 ///
 /// This is used to link a module instance to its imports
@@ -220,7 +267,56 @@ impl From<&Import> for ExternName {
 #[derive(Clone)]
 pub struct Imports {
     values: BTreeMap<ExternName, Extern>,
+    addrs: BTreeMap<ExternName, ExternVal>,
     modules: BTreeMap<String, ModuleInstanceAddr>,
+    resolver: Option<Rc<dyn ImportResolver>>,
+    // the store of the items defined with `define_extern_val`
+    store_id: Option<usize>,
+}
+
+/// Provides imports on demand
+///
+/// A resolver is asked for every import that is not defined in an [`Imports`] set,
+/// see [`Imports::set_resolver`]. Returning `None` fails instantiation with an unknown import error.
+/// The returned value is checked against the import's type like any other import.
+///
+/// Implemented for closures taking an [`ImportType`].
+///
+/// ## Example
+/// ```rust
+/// use tinywasm::{Extern, Imports};
+/// use tinywasm::types::{ExternType, WasmValue};
+///
+/// let mut imports = Imports::new();
+/// imports.set_resolver(|import: &tinywasm::ImportType<'_>| match &import.ty {
+///     ExternType::Func(ty) if import.module == "generated" => {
+///         let results: Vec<_> = ty.results.iter().map(|t| WasmValue::default_for(*t)).collect();
+///         Some(Extern::func(ty, move |_, _| Ok(results.clone())))
+///     }
+///     _ => None,
+/// });
+/// ```
+pub trait ImportResolver {
+    /// Create a value for the given import, or `None` if it is unknown
+    fn resolve(&self, import: &ImportType<'_>) -> Option<Extern>;
+}
+
+impl<F: Fn(&ImportType<'_>) -> Option<Extern>> ImportResolver for F {
+    fn resolve(&self, import: &ImportType<'_>) -> Option<Extern> {
+        self(import)
+    }
+}
+
+impl Debug for Imports {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Imports")
+            .field("values", &self.values)
+            .field("addrs", &self.addrs)
+            .field("modules", &self.modules)
+            .field("resolver", &self.resolver.as_ref().map(|_| "..."))
+            .field("store_id", &self.store_id)
+            .finish()
+    }
 }
 
 pub(crate) enum ResolvedExtern<S, V> {
@@ -244,13 +340,30 @@ impl ResolvedImports {
 impl Imports {
     /// Create a new empty import set
     pub fn new() -> Self {
-        Imports { values: BTreeMap::new(), modules: BTreeMap::new() }
+        Imports {
+            values: BTreeMap::new(),
+            addrs: BTreeMap::new(),
+            modules: BTreeMap::new(),
+            resolver: None,
+            store_id: None,
+        }
     }
 
     /// Merge two import sets
     pub fn merge(mut self, other: Self) -> Self {
         self.values.extend(other.values);
+        self.addrs.extend(other.addrs);
         self.modules.extend(other.modules);
+        self.resolver = other.resolver.or(self.resolver);
+        self.store_id = other.store_id.or(self.store_id);
+        self
+    }
+
+    /// Set a resolver that is asked for imports which are not defined
+    ///
+    /// Replaces any previously set resolver. See [`ImportResolver`].
+    pub fn set_resolver(&mut self, resolver: impl ImportResolver + 'static) -> &mut Self {
+        self.resolver = Some(Rc::new(resolver));
         self
     }
 
@@ -268,23 +381,87 @@ impl Imports {
         Ok(self)
     }
 
+    /// Define all functions of a host module
+    pub fn define_host_module(&mut self, module: &str, host: impl HostModule) -> Result<&mut Self> {
+        for (name, value) in host.into_externs() {
+            self.define(module, name, value)?;
+        }
+        Ok(self)
+    }
+
+    /// Define an import using an item that is already in the store
+    ///
+    /// E.g. the export of an instance or an item added with [`crate::Store::add_extern`].
+    /// Fails with [`crate::Error::InvalidStore`] if the item is not in the store or other items were defined
+    /// from a different store. Instantiating a module with these imports in a different store fails as well.
+    pub fn define_extern_val(
+        &mut self,
+        store: &crate::Store,
+        module: &str,
+        name: &str,
+        value: ExternVal,
+    ) -> Result<&mut Self> {
+        if !store.contains_extern(&value) || self.store_id.is_some_and(|id| id != store.id()) {
+            return Err(crate::Error::InvalidStore);
+        }
+
+        self.store_id = Some(store.id());
+        self.addrs.insert(ExternName::new(module, name), value);
+        Ok(self)
+    }
+
+    /// Define an import that is already in the store
+    pub(crate) fn define_addr(&mut self, name: ExternName, addr: ExternVal) -> &mut Self {
+        self.addrs.insert(name, addr);
+        self
+    }
+
     pub(crate) fn take(
         &mut self,
         store: &mut crate::Store,
         import: &Import,
+        func_types: &[FuncType],
     ) -> Option<ResolvedExtern<ExternVal, Extern>> {
         let name = ExternName::from(import);
         if let Some(v) = self.values.get(&name) {
             return Some(ResolvedExtern::Extern(v.clone()));
         }
-        if let Some(addr) = self.modules.get(&name.module) {
-            let instance = store.get_module_instance(*addr)?;
-            return Some(ResolvedExtern::Store(instance.export_addr(&import.name)?));
+        if let Some(addr) = self.addrs.get(&name) {
+            return Some(ResolvedExtern::Store(addr.clone()));
+        }
+        // a linked module that doesn't export the item falls through to the resolver
+        let linked = self.modules.get(&name.module).and_then(|addr| store.get_module_instance(*addr));
+        if let Some(addr) = linked.and_then(|instance| instance.export_addr(&import.name)) {
+            return Some(ResolvedExtern::Store(addr));
+        }
+        if let Some(resolver) = &self.resolver {
+            let ty = crate::module::import_type(func_types, &import.kind);
+            let import = ImportType { module: &import.module, name: &import.name, ty };
+            return resolver.resolve(&import).map(ResolvedExtern::Extern);
         }
 
         None
     }
 
+    // check that a host item can be used for an import
+    pub(crate) fn check_extern(import: &Import, value: &Extern, func_types: &[FuncType]) -> Result<()> {
+        match (value, &import.kind) {
+            (Extern::Global { ty, .. }, ImportKind::Global(import_ty)) => Self::compare_types(import, ty, import_ty),
+            (Extern::Table { ty, .. }, ImportKind::Table(import_ty)) => {
+                Self::compare_table_types(import, ty, import_ty)
+            }
+            (Extern::Memory { ty }, ImportKind::Memory(import_ty)) => {
+                Self::compare_memory_types(import, ty, import_ty, None)
+            }
+            (Extern::Function(extern_func), ImportKind::Function(ty)) => {
+                let import_func_type =
+                    func_types.get(*ty as usize).ok_or_else(|| LinkingError::incompatible_import_type(import))?;
+                Self::compare_types(import, extern_func.ty(), import_func_type)
+            }
+            _ => Err(LinkingError::incompatible_import_type(import).into()),
+        }
+    }
+
     fn compare_types<T: Debug + PartialEq>(import: &Import, actual: &T, expected: &T) -> Result<()> {
         if expected != actual {
             log::error!("failed to link import {}, expected {:?}, got {:?}", import.name, expected, actual);
@@ -344,41 +521,33 @@ impl Imports {
         module: &crate::Module,
         idx: ModuleInstanceAddr,
     ) -> Result<ResolvedImports> {
-        let mut imports = ResolvedImports::new();
+        if self.store_id.is_some_and(|id| id != store.id()) {
+            return Err(crate::Error::InvalidStore);
+        }
 
+        let mut imports = ResolvedImports::new();
         for import in &module.0.imports {
-            let val = self.take(store, import).ok_or_else(|| LinkingError::unknown_import(import))?;
+            let val =
+                self.take(store, import, &module.0.func_types).ok_or_else(|| LinkingError::unknown_import(import))?;
 
             match val {
                 // A link to something that needs to be added to the store
-                ResolvedExtern::Extern(ex) => match (ex, &import.kind) {
-                    (Extern::Global { ty, val }, ImportKind::Global(import_ty)) => {
-                        Self::compare_types(import, &ty, import_ty)?;
-                        imports.globals.push(store.add_global(ty, val.into(), idx)?);
+                ResolvedExtern::Extern(ex) => {
+                    Self::check_extern(import, &ex, &module.0.func_types)?;
+                    match ex {
+                        Extern::Global { ty, val } => imports.globals.push(store.add_global(ty, val.into(), idx)?),
+                        Extern::Table { ty, .. } => imports.tables.push(store.add_table(ty, idx)?),
+                        Extern::Memory { ty } => imports.memories.push(store.add_mem(ty, idx)?),
+                        Extern::Function(extern_func) => imports.funcs.push(store.add_func(extern_func, idx)?),
                     }
-                    (Extern::Table { ty, .. }, ImportKind::Table(import_ty)) => {
-                        Self::compare_table_types(import, &ty, import_ty)?;
-                        imports.tables.push(store.add_table(ty, idx)?);
-                    }
-                    (Extern::Memory { ty }, ImportKind::Memory(import_ty)) => {
-                        Self::compare_memory_types(import, &ty, import_ty, None)?;
-                        imports.memories.push(store.add_mem(ty, idx)?);
-                    }
-                    (Extern::Function(extern_func), ImportKind::Function(ty)) => {
-                        let import_func_type = module
-                            .0
-                            .func_types
-                            .get(*ty as usize)
-                            .ok_or_else(|| LinkingError::incompatible_import_type(import))?;
-
-                        Self::compare_types(import, extern_func.ty(), import_func_type)?;
-                        imports.funcs.push(store.add_func(extern_func, idx)?);
-                    }
-                    _ => return Err(LinkingError::incompatible_import_type(import).into()),
-                },
+                }
 
                 // A link to something already in the store
                 ResolvedExtern::Store(val) => {
+                    if !store.contains_extern(&val) {
+                        return Err(crate::Error::InvalidStore);
+                    }
+
                     // check if the kind matches
                     if val.kind() != (&import.kind).into() {
                         return Err(LinkingError::incompatible_import_type(import).into());
@@ -423,3 +592,94 @@ impl Imports {
         Ok(imports)
     }
 }
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+    use crate::{Module, Store};
+
+    #[test]
+    fn test_host_func_param_order() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "env" "sub" (func $sub (param i32 i32) (result i32)))
+                (import "env" "mixed" (func $mixed (param i32 i64 i32) (result i64)))
+                (table funcref (elem $sub))
+                (func (export "sub") (result i32) (call $sub (i32.const 10) (i32.const 3)))
+                (func (export "sub_indirect") (result i32)
+                    (call_indirect (param i32 i32) (result i32) (i32.const 10) (i32.const 3) (i32.const 0)))
+                (func (export "mixed") (result i64) (call $mixed (i32.const 1) (i64.const 20) (i32.const 300))))"#,
+        )
+        .unwrap();
+
+        let mut imports = Imports::new();
+        imports.define("env", "sub", Extern::typed_func(|_, (a, b): (i32, i32)| Ok(a - b))).unwrap();
+        let mixed = |_: FuncContext<'_>, (a, b, c): (i32, i64, i32)| Ok(a as i64 * 1000 + b * 10 + c as i64);
+        imports.define("env", "mixed", Extern::typed_func(mixed)).unwrap();
+
+        let mut store = Store::default();
+        let instance = Module::parse_bytes(&wasm).unwrap().instantiate(&mut store, Some(imports)).unwrap();
+        let call = |store: &mut Store, name| instance.exported_func_untyped(store, name).unwrap().call(store, &[]);
+        assert_eq!(call(&mut store, "sub").unwrap(), [WasmValue::I32(7)]);
+        assert_eq!(call(&mut store, "sub_indirect").unwrap(), [WasmValue::I32(7)]);
+        assert_eq!(call(&mut store, "mixed").unwrap(), [WasmValue::I64(1500)]);
+    }
+
+    #[test]
+    fn test_resolver() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "lib" "one" (func $one (result i32)))
+                (import "lib" "two" (func $two (result i32)))
+                (func (export "sum") (result i32) (i32.add (call $one) (call $two))))"#,
+        )
+        .unwrap();
+        let module = Module::parse_bytes(&wasm).unwrap();
+        let lib = wat::parse_str(r#"(module (func (export "one") (result i32) (i32.const 1)))"#).unwrap();
+
+        // `lib.two` isn't exported by the linked module, so the resolver is asked for it
+        let mut store = Store::default();
+        let lib = Module::parse_bytes(&lib).unwrap().instantiate(&mut store, None).unwrap();
+        let mut imports = Imports::new();
+        imports.link_module("lib", lib.id()).unwrap();
+        imports.set_resolver(|import: &ImportType<'_>| match import.name {
+            "one" => panic!("defined imports are not resolved"),
+            "two" => Some(Extern::typed_func(|_, ()| Ok(2))),
+            _ => None,
+        });
+        let instance = module.clone().instantiate(&mut store, Some(imports)).unwrap();
+        let sum = instance.exported_func::<(), i32>(&store, "sum").unwrap();
+        assert_eq!(sum.call(&mut store, ()).unwrap(), 3);
+
+        // resolved values are checked against the import type
+        let mut imports = Imports::new();
+        imports.set_resolver(|_: &ImportType<'_>| Some(Extern::typed_func(|_, ()| Ok(1i64))));
+        let err = module.clone().instantiate(&mut store, Some(imports)).unwrap_err();
+        assert!(matches!(err, crate::Error::Linker(LinkingError::IncompatibleImportType { .. })));
+
+        let mut imports = Imports::new();
+        imports.set_resolver(|_: &ImportType<'_>| None);
+        let err = module.instantiate(&mut store, Some(imports)).unwrap_err();
+        assert!(matches!(err, crate::Error::Linker(LinkingError::UnknownImport { .. })));
+    }
+
+    #[test]
+    fn test_define_extern_val() {
+        let wasm = wat::parse_str(r#"(module (import "env" "memory" (memory 1)))"#).unwrap();
+        let module = Module::parse_bytes(&wasm).unwrap();
+        let (mut store, mut other) = (Store::default(), Store::default());
+        let memory = store.add_extern(Extern::memory(MemoryType::new_32(1, None))).unwrap();
+        other.add_extern(Extern::memory(MemoryType::new_32(1, None))).unwrap();
+
+        let mut imports = Imports::new();
+        let err = imports.define_extern_val(&store, "env", "memory", ExternVal::Memory(1)).unwrap_err();
+        assert!(matches!(err, crate::Error::InvalidStore));
+        imports.define_extern_val(&store, "env", "memory", memory.clone()).unwrap();
+        let err = imports.define_extern_val(&other, "env", "other", memory).unwrap_err();
+        assert!(matches!(err, crate::Error::InvalidStore));
+
+        let err = module.clone().instantiate(&mut other, Some(imports.clone())).unwrap_err();
+        assert!(matches!(err, crate::Error::InvalidStore));
+        module.instantiate(&mut store, Some(imports)).unwrap();
+    }
+}
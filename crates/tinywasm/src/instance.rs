@@ -2,7 +2,8 @@ use alloc::{boxed::Box, format, rc::Rc, string::ToString};
 use tinywasm_types::*;
 
 use crate::func::{FromWasmValueTuple, IntoWasmValueTuple};
-use crate::{Error, FuncHandle, FuncHandleTyped, Imports, MemoryRef, MemoryRefMut, Module, Result, Store};
+use crate::{Error, FuncHandle, FuncHandleTyped, GlobalRef, Imports, MemoryRef, MemoryRefMut, Module, Result, Store};
+use crate::{ExportType, ImportType, TableRef, TableRefMut};
 
 /// An instanciated WebAssembly module
 ///
@@ -63,6 +64,7 @@ impl ModuleInstance {
         // don't need to create a auxiliary frame etc.
 
         let idx = store.next_module_instance_idx();
+        let _span = crate::tracing::info_span!("instantiate", module = idx).entered();
         let mut addrs = imports.unwrap_or_default().link(store, &module, idx)?;
 
         addrs.funcs.extend(store.init_funcs(module.0.funcs.into(), idx)?);
@@ -99,17 +101,44 @@ impl ModuleInstance {
         }
     }
 
+    /// Get the imports of the instance's module in the order they are declared
+    pub fn imports(&self) -> impl Iterator<Item = ImportType<'_>> {
+        self.0.imports.iter().map(|import| ImportType {
+            module: &import.module,
+            name: &import.name,
+            ty: crate::module::import_type(&self.0.types, &import.kind),
+        })
+    }
+
+    /// Get the exports of the instance in the order they are declared
+    ///
+    /// Tables and memories report their current size as the minimum.
+    pub fn exports<'a>(&'a self, store: &'a Store) -> Result<impl Iterator<Item = ExportType<'a>>> {
+        if self.0.store_id != store.id() {
+            return Err(Error::InvalidStore);
+        }
+
+        Ok(self.0.exports.iter().filter_map(|export| {
+            let ty = store.extern_type(&self.resolve_export(export)?)?;
+            Some(ExportType { name: &export.name, ty })
+        }))
+    }
+
     /// Get a export by name
     pub fn export_addr(&self, name: &str) -> Option<ExternVal> {
-        let exports = self.0.exports.iter().find(|e| e.name == name.into())?;
-        let addr = match exports.kind {
-            ExternalKind::Func => self.0.func_addrs.get(exports.index as usize)?,
-            ExternalKind::Table => self.0.table_addrs.get(exports.index as usize)?,
-            ExternalKind::Memory => self.0.mem_addrs.get(exports.index as usize)?,
-            ExternalKind::Global => self.0.global_addrs.get(exports.index as usize)?,
+        let export = self.0.exports.iter().find(|e| e.name == name.into())?;
+        self.resolve_export(export)
+    }
+
+    pub(crate) fn resolve_export(&self, export: &Export) -> Option<ExternVal> {
+        let addr = match export.kind {
+            ExternalKind::Func => self.0.func_addrs.get(export.index as usize)?,
+            ExternalKind::Table => self.0.table_addrs.get(export.index as usize)?,
+            ExternalKind::Memory => self.0.mem_addrs.get(export.index as usize)?,
+            ExternalKind::Global => self.0.global_addrs.get(export.index as usize)?,
         };
 
-        Some(ExternVal::new(exports.kind, *addr))
+        Some(ExternVal::new(export.kind, *addr))
     }
 
     #[inline]
@@ -122,11 +151,6 @@ impl ModuleInstance {
         &self.0.types[addr as usize]
     }
 
-    #[inline]
-    pub(crate) fn func_addrs(&self) -> &[FuncAddr] {
-        &self.0.func_addrs
-    }
-
     // resolve a function address to the global store address
     #[inline]
     pub(crate) fn resolve_func_addr(&self, addr: FuncAddr) -> FuncAddr {
@@ -175,7 +199,7 @@ impl ModuleInstance {
         };
 
         let ty = store.get_func(func_addr).func.ty();
-        Ok(FuncHandle { addr: func_addr, module_addr: self.id(), name: Some(name.to_string()), ty: ty.clone() })
+        Ok(FuncHandle { addr: func_addr, module_addr: Some(self.id()), name: Some(name.to_string()), ty: ty.clone() })
     }
 
     /// Get a typed exported function by name
@@ -208,14 +232,69 @@ impl ModuleInstance {
         self.memory_mut(store, mem_addr)
     }
 
+    /// Get an exported table by name
+    pub fn exported_table<'a>(&self, store: &'a Store, name: &str) -> Result<TableRef<'a>> {
+        if self.0.store_id != store.id() {
+            return Err(Error::InvalidStore);
+        }
+
+        let export = self.export_addr(name).ok_or_else(|| Error::Other(format!("Export not found: {name}")))?;
+        let ExternVal::Table(table_addr) = export else {
+            return Err(Error::Other(format!("Export is not a table: {}", name)));
+        };
+
+        Ok(TableRef(store.get_table(table_addr)))
+    }
+
+    /// Get an exported table by name (mutable)
+    pub fn exported_table_mut<'a>(&self, store: &'a mut Store, name: &str) -> Result<TableRefMut<'a>> {
+        if self.0.store_id != store.id() {
+            return Err(Error::InvalidStore);
+        }
+
+        let export = self.export_addr(name).ok_or_else(|| Error::Other(format!("Export not found: {name}")))?;
+        let ExternVal::Table(table_addr) = export else {
+            return Err(Error::Other(format!("Export is not a table: {}", name)));
+        };
+
+        let (func_count, store_id) = (store.data.funcs.len(), store.id());
+        Ok(TableRefMut { table: store.get_table_mut(table_addr), func_count, store_id })
+    }
+
+    /// Get an exported global by name
+    pub fn exported_global<'a>(&self, store: &'a Store, name: &str) -> Result<GlobalRef<'a>> {
+        if self.0.store_id != store.id() {
+            return Err(Error::InvalidStore);
+        }
+
+        let export = self.export_addr(name).ok_or_else(|| Error::Other(format!("Export not found: {name}")))?;
+        let ExternVal::Global(global_addr) = export else {
+            return Err(Error::Other(format!("Export is not a global: {}", name)));
+        };
+
+        Ok(GlobalRef {
+            global: store.get_global(global_addr),
+            func_count: store.data.funcs.len(),
+            store_id: store.id(),
+        })
+    }
+
     /// Get a memory by address
     pub fn memory<'a>(&self, store: &'a Store, addr: MemAddr) -> Result<MemoryRef<'a>> {
+        if self.0.store_id != store.id() {
+            return Err(Error::InvalidStore);
+        }
+
         let mem = store.get_mem(self.resolve_mem_addr(addr));
         Ok(MemoryRef(mem))
     }
 
     /// Get a memory by address (mutable)
     pub fn memory_mut<'a>(&self, store: &'a mut Store, addr: MemAddr) -> Result<MemoryRefMut<'a>> {
+        if self.0.store_id != store.id() {
+            return Err(Error::InvalidStore);
+        }
+
         let mem = store.get_mem_mut(self.resolve_mem_addr(addr));
         Ok(MemoryRefMut(mem))
     }
@@ -247,7 +326,7 @@ impl ModuleInstance {
         let func_inst = store.get_func(func_addr);
         let ty = func_inst.func.ty();
 
-        Ok(Some(FuncHandle { module_addr: self.id(), addr: func_addr, ty: ty.clone(), name: None }))
+        Ok(Some(FuncHandle { module_addr: Some(self.id()), addr: func_addr, ty: ty.clone(), name: None }))
     }
 
     /// Invoke the start function of the module
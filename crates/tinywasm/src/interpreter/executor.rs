@@ -12,6 +12,14 @@ use super::stack::{BlockFrame, BlockType, Stack};
 use super::values::*;
 use crate::*;
 
+// run a float operation and canonicalize a NaN result in deterministic mode
+macro_rules! float_op {
+    ($self:ident, $ty:ty, $op:expr) => {{
+        $op.to_cf()?;
+        $self.canonicalize_nan::<$ty>();
+    }};
+}
+
 pub(super) struct Executor<'store, 'stack> {
     cf: CallFrame,
     module: ModuleInstance,
@@ -23,6 +31,10 @@ impl<'store, 'stack> Executor<'store, 'stack> {
     pub(crate) fn new(store: &'store mut Store, stack: &'stack mut Stack) -> Result<Self> {
         let current_frame = stack.call_stack.pop().expect("no call frame, this is a bug");
         let current_module = store.get_module_instance_raw(current_frame.module_addr());
+
+        #[cfg(feature = "coverage")]
+        store.record_coverage(current_frame.func_addr(), 0);
+
         Ok(Self { cf: current_frame, module: current_module, stack, store })
     }
 
@@ -41,6 +53,12 @@ impl<'store, 'stack> Executor<'store, 'stack> {
     #[inline(always)]
     fn exec_next(&mut self) -> ControlFlow<Option<Error>> {
         use tinywasm_types::Instruction::*;
+
+        #[cfg(feature = "stats")]
+        {
+            self.store.instructions_executed += 1;
+        }
+
         match self.cf.fetch_instr() {
             Nop | BrLabel(_) | I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => {}
             Unreachable => self.exec_unreachable()?,
@@ -99,7 +117,7 @@ impl<'store, 'stack> Executor<'store, 'stack> {
             I64Const(val) => self.exec_const(*val),
             F32Const(val) => self.exec_const(*val),
             F64Const(val) => self.exec_const(*val),
-            RefFunc(func_idx) => self.exec_const::<ValueRef>(Some(*func_idx)),
+            RefFunc(func_idx) => self.exec_const::<ValueRef>(Some(self.module.resolve_func_addr(*func_idx))),
             RefNull(_) => self.exec_const::<ValueRef>(None),
             RefIsNull => self.exec_ref_is_null(),
 
@@ -181,21 +199,21 @@ impl<'store, 'stack> Executor<'store, 'stack> {
 
             I32Add => self.stack.values.calculate_same::<i32>(|a, b| Ok(a.wrapping_add(b))).to_cf()?,
             I64Add => self.stack.values.calculate_same::<i64>(|a, b| Ok(a.wrapping_add(b))).to_cf()?,
-            F32Add => self.stack.values.calculate_same::<f32>(|a, b| Ok(a + b)).to_cf()?,
-            F64Add => self.stack.values.calculate_same::<f64>(|a, b| Ok(a + b)).to_cf()?,
+            F32Add => float_op!(self, f32, self.stack.values.calculate_same::<f32>(|a, b| Ok(a + b))),
+            F64Add => float_op!(self, f64, self.stack.values.calculate_same::<f64>(|a, b| Ok(a + b))),
 
             I32Sub => self.stack.values.calculate_same::<i32>(|a, b| Ok(a.wrapping_sub(b))).to_cf()?,
             I64Sub => self.stack.values.calculate_same::<i64>(|a, b| Ok(a.wrapping_sub(b))).to_cf()?,
-            F32Sub => self.stack.values.calculate_same::<f32>(|a, b| Ok(a - b)).to_cf()?,
-            F64Sub => self.stack.values.calculate_same::<f64>(|a, b| Ok(a - b)).to_cf()?,
+            F32Sub => float_op!(self, f32, self.stack.values.calculate_same::<f32>(|a, b| Ok(a - b))),
+            F64Sub => float_op!(self, f64, self.stack.values.calculate_same::<f64>(|a, b| Ok(a - b))),
 
-            F32Div => self.stack.values.calculate_same::<f32>(|a, b| Ok(a / b)).to_cf()?,
-            F64Div => self.stack.values.calculate_same::<f64>(|a, b| Ok(a / b)).to_cf()?,
+            F32Div => float_op!(self, f32, self.stack.values.calculate_same::<f32>(|a, b| Ok(a / b))),
+            F64Div => float_op!(self, f64, self.stack.values.calculate_same::<f64>(|a, b| Ok(a / b))),
 
             I32Mul => self.stack.values.calculate_same::<i32>(|a, b| Ok(a.wrapping_mul(b))).to_cf()?,
             I64Mul => self.stack.values.calculate_same::<i64>(|a, b| Ok(a.wrapping_mul(b))).to_cf()?,
-            F32Mul => self.stack.values.calculate_same::<f32>(|a, b| Ok(a * b)).to_cf()?,
-            F64Mul => self.stack.values.calculate_same::<f64>(|a, b| Ok(a * b)).to_cf()?,
+            F32Mul => float_op!(self, f32, self.stack.values.calculate_same::<f32>(|a, b| Ok(a * b))),
+            F64Mul => float_op!(self, f64, self.stack.values.calculate_same::<f64>(|a, b| Ok(a * b))),
 
             I32DivS => self.stack.values.calculate_same::<i32>(|a, b| a.wasm_checked_div(b)).to_cf()?,
             I64DivS => self.stack.values.calculate_same::<i64>(|a, b| a.wasm_checked_div(b)).to_cf()?,
@@ -248,27 +266,27 @@ impl<'store, 'stack> Executor<'store, 'stack> {
             I64ExtendI32S => self.stack.values.replace_top::<i32, _>(|v| Ok(v as i64)).to_cf()?,
             I32WrapI64 => self.stack.values.replace_top::<i64, _>(|v| Ok(v as i32)).to_cf()?,
 
-            F32DemoteF64 => self.stack.values.replace_top::<f64, _>(|v| Ok(v as f32)).to_cf()?,
-            F64PromoteF32 => self.stack.values.replace_top::<f32, _>(|v| Ok(v as f64)).to_cf()?,
+            F32DemoteF64 => float_op!(self, f32, self.stack.values.replace_top::<f64, _>(|v| Ok(v as f32))),
+            F64PromoteF32 => float_op!(self, f64, self.stack.values.replace_top::<f32, _>(|v| Ok(v as f64))),
 
             F32Abs => self.stack.values.replace_top_same::<f32>(|v| Ok(v.abs())).to_cf()?,
             F64Abs => self.stack.values.replace_top_same::<f64>(|v| Ok(v.abs())).to_cf()?,
             F32Neg => self.stack.values.replace_top_same::<f32>(|v| Ok(-v)).to_cf()?,
             F64Neg => self.stack.values.replace_top_same::<f64>(|v| Ok(-v)).to_cf()?,
-            F32Ceil => self.stack.values.replace_top_same::<f32>(|v| Ok(v.ceil())).to_cf()?,
-            F64Ceil => self.stack.values.replace_top_same::<f64>(|v| Ok(v.ceil())).to_cf()?,
-            F32Floor => self.stack.values.replace_top_same::<f32>(|v| Ok(v.floor())).to_cf()?,
-            F64Floor => self.stack.values.replace_top_same::<f64>(|v| Ok(v.floor())).to_cf()?,
-            F32Trunc => self.stack.values.replace_top_same::<f32>(|v| Ok(v.trunc())).to_cf()?,
-            F64Trunc => self.stack.values.replace_top_same::<f64>(|v| Ok(v.trunc())).to_cf()?,
-            F32Nearest => self.stack.values.replace_top_same::<f32>(|v| Ok(v.tw_nearest())).to_cf()?,
-            F64Nearest => self.stack.values.replace_top_same::<f64>(|v| Ok(v.tw_nearest())).to_cf()?,
-            F32Sqrt => self.stack.values.replace_top_same::<f32>(|v| Ok(v.sqrt())).to_cf()?,
-            F64Sqrt => self.stack.values.replace_top_same::<f64>(|v| Ok(v.sqrt())).to_cf()?,
-            F32Min => self.stack.values.calculate_same::<f32>(|a, b| Ok(a.tw_minimum(b))).to_cf()?,
-            F64Min => self.stack.values.calculate_same::<f64>(|a, b| Ok(a.tw_minimum(b))).to_cf()?,
-            F32Max => self.stack.values.calculate_same::<f32>(|a, b| Ok(a.tw_maximum(b))).to_cf()?,
-            F64Max => self.stack.values.calculate_same::<f64>(|a, b| Ok(a.tw_maximum(b))).to_cf()?,
+            F32Ceil => float_op!(self, f32, self.stack.values.replace_top_same::<f32>(|v| Ok(v.ceil()))),
+            F64Ceil => float_op!(self, f64, self.stack.values.replace_top_same::<f64>(|v| Ok(v.ceil()))),
+            F32Floor => float_op!(self, f32, self.stack.values.replace_top_same::<f32>(|v| Ok(v.floor()))),
+            F64Floor => float_op!(self, f64, self.stack.values.replace_top_same::<f64>(|v| Ok(v.floor()))),
+            F32Trunc => float_op!(self, f32, self.stack.values.replace_top_same::<f32>(|v| Ok(v.trunc()))),
+            F64Trunc => float_op!(self, f64, self.stack.values.replace_top_same::<f64>(|v| Ok(v.trunc()))),
+            F32Nearest => float_op!(self, f32, self.stack.values.replace_top_same::<f32>(|v| Ok(v.tw_nearest()))),
+            F64Nearest => float_op!(self, f64, self.stack.values.replace_top_same::<f64>(|v| Ok(v.tw_nearest()))),
+            F32Sqrt => float_op!(self, f32, self.stack.values.replace_top_same::<f32>(|v| Ok(v.sqrt()))),
+            F64Sqrt => float_op!(self, f64, self.stack.values.replace_top_same::<f64>(|v| Ok(v.sqrt()))),
+            F32Min => float_op!(self, f32, self.stack.values.calculate_same::<f32>(|a, b| Ok(a.tw_minimum(b)))),
+            F64Min => float_op!(self, f64, self.stack.values.calculate_same::<f64>(|a, b| Ok(a.tw_minimum(b)))),
+            F32Max => float_op!(self, f32, self.stack.values.calculate_same::<f32>(|a, b| Ok(a.tw_maximum(b)))),
+            F64Max => float_op!(self, f64, self.stack.values.calculate_same::<f64>(|a, b| Ok(a.tw_maximum(b)))),
             F32Copysign => self.stack.values.calculate_same::<f32>(|a, b| Ok(a.copysign(b))).to_cf()?,
             F64Copysign => self.stack.values.calculate_same::<f64>(|a, b| Ok(a.copysign(b))).to_cf()?,
 
@@ -311,35 +329,68 @@ impl<'store, 'stack> Executor<'store, 'stack> {
         ControlFlow::Continue(())
     }
 
+    #[inline(always)]
+    fn canonicalize_nan<T: InternalValue + CanonicalNan>(&mut self) {
+        if self.store.deterministic.is_some() {
+            // canonicalization can't fail
+            let _ = T::replace_top(&mut self.stack.values, |v| Ok(v.canonicalize_nan()));
+        }
+    }
+
     #[cold]
     fn exec_unreachable(&self) -> ControlFlow<Option<Error>> {
         ControlFlow::Break(Some(Trap::Unreachable.into()))
     }
 
-    fn exec_call(&mut self, wasm_func: Rc<WasmFunction>, owner: ModuleInstanceAddr) -> ControlFlow<Option<Error>> {
+    fn exec_call(
+        &mut self,
+        wasm_func: Rc<WasmFunction>,
+        func_addr: FuncAddr,
+        owner: ModuleInstanceAddr,
+    ) -> ControlFlow<Option<Error>> {
         let locals = self.stack.values.pop_locals(wasm_func.params, wasm_func.locals);
-        let new_call_frame = CallFrame::new_raw(wasm_func, owner, locals, self.stack.blocks.len() as u32);
+        self.sample_stack_depth();
+        let new_call_frame = CallFrame::new_raw(wasm_func, func_addr, owner, locals, self.stack.blocks.len() as u32);
         self.cf.incr_instr_ptr(); // skip the call instruction
         self.stack.call_stack.push(core::mem::replace(&mut self.cf, new_call_frame))?;
         self.module.swap_with(self.cf.module_addr(), self.store);
+
+        #[cfg(feature = "coverage")]
+        self.store.record_coverage(func_addr, 0);
+
         ControlFlow::Continue(())
     }
+    // add the frames of this execution to the backtrace of an error returned by a host function
+    #[cold]
+    fn host_error(&self, mut err: Error) -> Error {
+        if let Error::Host(host) = &mut err {
+            // the callers already point past their call instruction
+            let callers =
+                self.stack.call_stack.frames().rev().map(|frame| crate::WasmFrame {
+                    instr_index: frame.instr_ptr() - 1,
+                    ..frame.wasm_frame(self.store)
+                });
+            host.push_frames(core::iter::once(self.cf.wasm_frame(self.store)).chain(callers));
+        }
+        err
+    }
     fn exec_call_direct(&mut self, v: u32) -> ControlFlow<Option<Error>> {
-        let func_inst = self.store.get_func(self.module.resolve_func_addr(v));
+        let func_addr = self.module.resolve_func_addr(v);
+        let func_inst = self.store.get_func(func_addr);
         let wasm_func = match &func_inst.func {
             crate::Function::Wasm(wasm_func) => wasm_func,
             crate::Function::Host(host_func) => {
                 let func = &host_func.clone();
                 let params = self.stack.values.pop_params(&host_func.ty.params);
-                let res =
-                    (func.func)(FuncContext { store: self.store, module_addr: self.module.id() }, &params).to_cf()?;
+                let ctx = FuncContext { store: self.store, module_addr: Some(self.module.id()), func_addr };
+                let res = func.call(ctx, &params).map_err(|err| self.host_error(err)).to_cf()?;
                 self.stack.values.extend_from_wasmvalues(&res);
                 self.cf.incr_instr_ptr();
                 return ControlFlow::Continue(());
             }
         };
 
-        self.exec_call(wasm_func.clone(), func_inst.owner)
+        self.exec_call(wasm_func.clone(), func_addr, func_inst.owner)
     }
     fn exec_call_indirect(&mut self, type_addr: u32, table_addr: u32) -> ControlFlow<Option<Error>> {
         // verify that the table is of the right type, this should be validated by the parser already
@@ -370,11 +421,11 @@ impl<'store, 'stack> Executor<'store, 'stack> {
 
                 let host_func = host_func.clone();
                 let params = self.stack.values.pop_params(&host_func.ty.params);
-                let res =
-                    match (host_func.func)(FuncContext { store: self.store, module_addr: self.module.id() }, &params) {
-                        Ok(res) => res,
-                        Err(e) => return ControlFlow::Break(Some(e)),
-                    };
+                let ctx = FuncContext { store: self.store, module_addr: Some(self.module.id()), func_addr: func_ref };
+                let res = match host_func.call(ctx, &params) {
+                    Ok(res) => res,
+                    Err(e) => return ControlFlow::Break(Some(self.host_error(e))),
+                };
 
                 self.stack.values.extend_from_wasmvalues(&res);
                 self.cf.incr_instr_ptr();
@@ -388,7 +439,7 @@ impl<'store, 'stack> Executor<'store, 'stack> {
             ));
         }
 
-        self.exec_call(wasm_func.clone(), func_inst.owner)
+        self.exec_call(wasm_func.clone(), func_ref, func_inst.owner)
     }
 
     fn exec_if(&mut self, else_offset: u32, end_offset: u32, (params, results): (StackHeight, StackHeight)) {
@@ -415,7 +466,24 @@ impl<'store, 'stack> Executor<'store, 'stack> {
         let ty = self.module.func_ty(idx);
         ((&*ty.params).into(), (&*ty.results).into())
     }
+    #[inline(always)]
+    fn sample_stack_depth(&mut self) {
+        #[cfg(feature = "stats")]
+        {
+            self.store.peak_stack_depth = self.store.peak_stack_depth.max(self.stack.values.len());
+        }
+    }
+    #[inline(always)]
+    fn record_block_coverage(&mut self) {
+        // blocks at the start of a function are recorded as part of the function entry
+        #[cfg(feature = "coverage")]
+        if self.cf.instr_ptr() != 0 {
+            self.store.record_coverage(self.cf.func_addr(), self.cf.instr_ptr() as u32);
+        }
+    }
     fn enter_block(&mut self, end_instr_offset: u32, ty: BlockType, (params, results): (StackHeight, StackHeight)) {
+        self.record_block_coverage();
+        self.sample_stack_depth();
         self.stack.blocks.push(BlockFrame {
             instr_ptr: self.cf.instr_ptr(),
             end_instr_offset,
@@ -425,8 +493,23 @@ impl<'store, 'stack> Executor<'store, 'stack> {
             ty,
         });
     }
+    #[inline(always)]
+    fn break_to(&mut self, to: u32) -> Option<()> {
+        self.cf.break_to(to, &mut self.stack.values, &mut self.stack.blocks)?;
+
+        // branching back to a loop enters it again, even if it shares the function entry's block
+        #[cfg(feature = "coverage")]
+        if matches!(
+            self.cf.fetch_instr(),
+            Instruction::Loop(..) | Instruction::LoopWithType(..) | Instruction::LoopWithFuncType(..)
+        ) {
+            self.store.record_coverage(self.cf.func_addr(), self.cf.instr_ptr() as u32);
+        }
+
+        Some(())
+    }
     fn exec_br(&mut self, to: u32) -> ControlFlow<Option<Error>> {
-        if self.cf.break_to(to, &mut self.stack.values, &mut self.stack.blocks).is_none() {
+        if self.break_to(to).is_none() {
             return self.exec_return();
         }
 
@@ -434,9 +517,7 @@ impl<'store, 'stack> Executor<'store, 'stack> {
         ControlFlow::Continue(())
     }
     fn exec_br_if(&mut self, to: u32) -> ControlFlow<Option<Error>> {
-        if self.stack.values.pop::<i32>() != 0
-            && self.cf.break_to(to, &mut self.stack.values, &mut self.stack.blocks).is_none()
-        {
+        if self.stack.values.pop::<i32>() != 0 && self.break_to(to).is_none() {
             return self.exec_return();
         }
         self.cf.incr_instr_ptr();
@@ -460,7 +541,7 @@ impl<'store, 'stack> Executor<'store, 'stack> {
             _ => return ControlFlow::Break(Some(Error::Other("br_table out of bounds".to_string()))),
         };
 
-        if self.cf.break_to(to, &mut self.stack.values, &mut self.stack.blocks).is_none() {
+        if self.break_to(to).is_none() {
             return self.exec_return();
         }
 
@@ -738,7 +819,7 @@ impl<'store, 'stack> Executor<'store, 'stack> {
             return Ok(());
         }
 
-        table.fill(self.module.func_addrs(), i as usize, n as usize, val.into())
+        table.fill(i as usize, n as usize, val.into())
     }
 
     fn exec_local_copy<T: InternalValue>(&mut self, from: u16, to: u16) {
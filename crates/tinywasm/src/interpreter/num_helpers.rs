@@ -120,6 +120,33 @@ macro_rules! impl_wasm_float_ops {
 
 impl_wasm_float_ops! { f32 f64 }
 
+pub(crate) trait CanonicalNan {
+    fn canonicalize_nan(self) -> Self;
+}
+
+// https://webassembly.github.io/spec/core/syntax/values.html#canonical-nan
+impl CanonicalNan for f32 {
+    #[inline]
+    fn canonicalize_nan(self) -> Self {
+        if self.is_nan() {
+            f32::from_bits(0x7fc0_0000)
+        } else {
+            self
+        }
+    }
+}
+
+impl CanonicalNan for f64 {
+    #[inline]
+    fn canonicalize_nan(self) -> Self {
+        if self.is_nan() {
+            f64::from_bits(0x7ff8_0000_0000_0000)
+        } else {
+            self
+        }
+    }
+}
+
 pub(crate) trait WasmIntOps {
     fn wasm_shl(self, rhs: Self) -> Self;
     fn wasm_shr(self, rhs: Self) -> Self;
@@ -7,7 +7,7 @@ use crate::{unlikely, Error};
 
 use alloc::boxed::Box;
 use alloc::{rc::Rc, vec, vec::Vec};
-use tinywasm_types::{Instruction, LocalAddr, ModuleInstanceAddr, WasmFunction, WasmValue};
+use tinywasm_types::{FuncAddr, Instruction, LocalAddr, ModuleInstanceAddr, WasmFunction, WasmValue};
 
 pub(crate) const MAX_CALL_STACK_SIZE: usize = 1024;
 
@@ -35,12 +35,17 @@ impl CallStack {
         self.stack.push(call_frame);
         ControlFlow::Continue(())
     }
+
+    pub(crate) fn frames(&self) -> core::slice::Iter<'_, CallFrame> {
+        self.stack.iter()
+    }
 }
 
 #[derive(Debug)]
 pub(crate) struct CallFrame {
     instr_ptr: usize,
     func_instance: Rc<WasmFunction>,
+    func_addr: FuncAddr,
     block_ptr: u32,
     module_addr: ModuleInstanceAddr,
     pub(crate) locals: Locals,
@@ -85,6 +90,24 @@ impl CallFrame {
         self.module_addr
     }
 
+    #[inline(always)]
+    #[cfg_attr(not(feature = "coverage"), allow(dead_code))]
+    pub(crate) fn func_addr(&self) -> FuncAddr {
+        self.func_addr
+    }
+
+    pub(crate) fn wasm_frame(&self, store: &crate::Store) -> crate::WasmFrame {
+        let func_index = store
+            .get_module_instance(self.module_addr)
+            .and_then(|instance| instance.0.func_addrs.iter().position(|&addr| addr == self.func_addr));
+        crate::WasmFrame {
+            module_addr: self.module_addr,
+            func_addr: self.func_addr,
+            func_index: func_index.map(|index| index as u32),
+            instr_index: self.instr_ptr,
+        }
+    }
+
     #[inline(always)]
     pub(crate) fn block_ptr(&self) -> u32 {
         self.block_ptr
@@ -143,6 +166,7 @@ impl CallFrame {
     #[inline(always)]
     pub(crate) fn new(
         wasm_func_inst: Rc<WasmFunction>,
+        func_addr: FuncAddr,
         owner: ModuleInstanceAddr,
         params: &[WasmValue],
         block_ptr: u32,
@@ -179,17 +203,18 @@ impl CallFrame {
             }
         };
 
-        Self { instr_ptr: 0, func_instance: wasm_func_inst, module_addr: owner, block_ptr, locals }
+        Self { instr_ptr: 0, func_instance: wasm_func_inst, func_addr, module_addr: owner, block_ptr, locals }
     }
 
     #[inline]
     pub(crate) fn new_raw(
         wasm_func_inst: Rc<WasmFunction>,
+        func_addr: FuncAddr,
         owner: ModuleInstanceAddr,
         locals: Locals,
         block_ptr: u32,
     ) -> Self {
-        Self { instr_ptr: 0, func_instance: wasm_func_inst, module_addr: owner, block_ptr, locals }
+        Self { instr_ptr: 0, func_instance: wasm_func_inst, func_addr, module_addr: owner, block_ptr, locals }
     }
 
     #[inline(always)]
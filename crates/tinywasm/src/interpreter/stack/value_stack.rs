@@ -27,6 +27,12 @@ impl ValueStack {
         }
     }
 
+    // total number of values on all stacks
+    #[cfg(feature = "stats")]
+    pub(crate) fn len(&self) -> usize {
+        self.stack_32.len() + self.stack_64.len() + self.stack_128.len() + self.stack_ref.len()
+    }
+
     pub(crate) fn height(&self) -> StackLocation {
         StackLocation {
             s32: self.stack_32.len() as u32,
@@ -92,7 +98,9 @@ impl ValueStack {
     }
 
     pub(crate) fn pop_params(&mut self, val_types: &[ValType]) -> Vec<WasmValue> {
-        val_types.iter().map(|val_type| self.pop_wasmvalue(*val_type)).collect::<Vec<_>>()
+        let mut params = val_types.iter().rev().map(|val_type| self.pop_wasmvalue(*val_type)).collect::<Vec<_>>();
+        params.reverse();
+        params
     }
 
     pub(crate) fn pop_results(&mut self, val_types: &[ValType]) -> Vec<WasmValue> {
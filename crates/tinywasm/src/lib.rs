@@ -22,6 +22,22 @@
 //!  Enables the `tinywasm-parser` crate. This is enabled by default.
 //!- **`archive`**\
 //!  Enables pre-parsing of archives. This is enabled by default.
+//!- **`tracing`**\
+//!  Emits spans for parsing, instantiation, function calls and host calls using the `tracing` crate.
+//!  Function call spans record the fuel they used, which is the number of executed instructions.
+//!- **`stats`**\
+//!  Counts executed instructions and samples the peak stack depth for [`Store::stats`]. Enabled by `tracing`.
+//!- **`coverage`**\
+//!  Enables collecting code coverage of executed WebAssembly functions, see [`coverage`].
+//!  Parsed modules also keep the binary offsets of their blocks, so archived modules are only compatible with builds using the same setting.
+//!- **`assemblyscript`**\
+//!  Enables the host functions and memory helpers for modules compiled by AssemblyScript, see [`assemblyscript`].
+//!- **`component-model`**\
+//!  Enables parsing and instantiating WebAssembly components, see [`component`].
+//!- **`macros`**\
+//!  Enables derive macros for [`IntoWasmValueTuple`], [`FromWasmValueTuple`], [`ValTypesFromTuple`] and [`WasmPod`],
+//!  and the `host_module` attribute for defining host functions, see [`HostModule`]. Together with `component-model`,
+//!  also enables `component::bindgen!` for generating bindings from WIT files.
 //!
 //! With all these features disabled, `TinyWasm` only depends on `core`, `alloc` and `libm`.
 //! By disabling `std`, you can use `TinyWasm` in `no_std` environments. This requires
@@ -70,6 +86,9 @@
 //! and other modules to be linked into the module when it is instantiated.
 //!
 //! See the [`Imports`] documentation for more information.
+//!
+//! To link many modules together, use a [`Linker`] instead. It can be reused
+//! for multiple instantiations and registers instances under module names.
 
 mod std;
 extern crate alloc;
@@ -91,24 +110,99 @@ pub(crate) mod log {
     pub(crate) use info;
 }
 
+// tracing for structured spans (optional).
+#[cfg(feature = "tracing")]
+#[allow(clippy::single_component_path_imports, unused_imports)]
+use tracing;
+
+// noop fallback if tracing is disabled.
+#[cfg(not(feature = "tracing"))]
+#[allow(unused_imports, unused_macros, dead_code)]
+pub(crate) mod tracing {
+    #[derive(Debug)]
+    pub(crate) struct Span;
+    impl Span {
+        #[inline(always)]
+        pub(crate) fn entered(self) -> Self {
+            self
+        }
+        #[inline(always)]
+        pub(crate) fn record<V>(&self, _field: &str, _value: V) -> &Self {
+            self
+        }
+    }
+
+    macro_rules! info_span    ( ($($tt:tt)*) => {{ $crate::tracing::Span }} );
+    macro_rules! debug_span    ( ($($tt:tt)*) => {{ $crate::tracing::Span }} );
+    pub(crate) use debug_span;
+    pub(crate) use info_span;
+}
+
 mod error;
 pub use error::*;
-pub use func::{FuncHandle, FuncHandleTyped};
+pub use func::{FromWasmValueTuple, FuncHandle, FuncHandleTyped, IntoWasmValueTuple, ToValType, ValTypesFromTuple};
+pub use guest_alloc::{GuestAllocator, GuestScope};
 pub use imports::*;
 pub use instance::ModuleInstance;
-pub use module::Module;
+pub use linker::Linker;
+pub use module::{ExportType, ImportType, Module};
+pub use pointer::{WasmPod, WasmPtr, WasmSlice};
 pub use reference::*;
 pub use store::*;
 
 mod func;
+mod guest_alloc;
 mod imports;
 mod instance;
+mod linker;
 mod module;
+mod pointer;
 mod reference;
 mod store;
 
+pub mod deterministic;
+pub mod replay;
+
+#[cfg(feature = "coverage")]
+pub mod coverage;
+
+#[cfg(feature = "assemblyscript")]
+pub mod assemblyscript;
+
+#[cfg(feature = "component-model")]
+pub mod component;
+
 /// Runtime for executing WebAssembly modules.
 pub mod interpreter;
+
+#[cfg(feature = "macros")]
+pub use tinywasm_macros::{host_module, FromWasmValueTuple, IntoWasmValueTuple, ValTypesFromTuple, WasmPod};
+
+// used by code generated by `tinywasm-macros`
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::boxed::Box;
+    pub use alloc::rc::Rc;
+    pub use alloc::string::String;
+    pub use alloc::vec::Vec;
+
+    use crate::{Error, Result};
+    use alloc::{format, string::ToString};
+    use tinywasm_types::WasmValue;
+
+    pub fn next_value<T: TryFrom<WasmValue>>(values: &mut core::slice::Iter<'_, WasmValue>) -> Result<T> {
+        let value = *values.next().ok_or_else(|| Error::Other("Not enough values in WasmValue vector".to_string()))?;
+        T::try_from(value).map_err(|_| Error::Other(format!("Could not convert {value:?} to expected type")))
+    }
+
+    /// Round `offset` up to a multiple of `align`
+    pub const fn align_up(offset: usize, align: usize) -> usize {
+        offset.div_ceil(align) * align
+    }
+
+    #[cfg(feature = "component-model")]
+    pub use crate::component::bindgen_helpers::*;
+}
 pub use interpreter::InterpreterRuntime;
 
 #[cfg(feature = "parser")]
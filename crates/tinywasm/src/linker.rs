@@ -0,0 +1,277 @@
+use alloc::collections::BTreeMap;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use tinywasm_types::*;
+
+use crate::imports::ExternName;
+use crate::{Error, Extern, HostModule, Imports, LinkingError, Module, ModuleInstance, Result, Store, Trap};
+
+#[derive(Debug, Clone)]
+enum Definition {
+    // a host item that is added to the store the first time it is used
+    Extern(Extern),
+    // an item already in the store, e.g. the export of a registered instance
+    Store(ExternVal),
+}
+
+/// A namespace of named items used to instantiate modules
+///
+/// Unlike [`Imports`], a linker is not consumed by instantiation and can be reused for many modules.
+/// Items are defined either directly or by registering all exports of an instance under a module name,
+/// similar to `register` in the WebAssembly script format.
+///
+/// Host items are added to the store the first time a module imports them and are shared by all later
+/// modules, so a memory defined in the linker is the same memory for every module importing it.
+/// Because of this, a linker can only be used with a single store.
+///
+/// ## Example
+/// ```rust
+/// # fn main() -> tinywasm::Result<()> {
+/// use tinywasm::{Extern, FuncContext, Linker, Module, Store};
+///
+/// let math = Module::parse_bytes(&wat::parse_str(r#"
+///     (module (func (export "add") (param i32 i32) (result i32)
+///         (i32.add (local.get 0) (local.get 1))))
+/// "#).unwrap())?;
+///
+/// let app = Module::parse_bytes(&wat::parse_str(r#"
+///     (module
+///         (import "math" "add" (func $add (param i32 i32) (result i32)))
+///         (import "env" "double" (func $double (param i32) (result i32)))
+///         (func (export "run") (result i32)
+///             (call $double (call $add (i32.const 1) (i32.const 2)))))
+/// "#).unwrap())?;
+///
+/// let mut store = Store::default();
+/// let mut linker = Linker::new();
+/// linker.define("env", "double", Extern::typed_func(|_: FuncContext<'_>, x: i32| Ok(x * 2)))?;
+/// linker.module(&mut store, "math", math)?;
+///
+/// let app = linker.instantiate(&mut store, app)?;
+/// let run = app.exported_func::<(), i32>(&store, "run")?;
+/// assert_eq!(run.call(&mut store, ())?, 6);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Linker {
+    items: BTreeMap<ExternName, Definition>,
+    allow_shadowing: bool,
+    store_id: Option<usize>,
+}
+
+impl Linker {
+    /// Create a new empty linker
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow later definitions to replace earlier ones with the same name
+    ///
+    /// Disabled by default, in which case defining an existing name fails with
+    /// [`LinkingError::DuplicateDefinition`].
+    pub fn allow_shadowing(&mut self, allow: bool) -> &mut Self {
+        self.allow_shadowing = allow;
+        self
+    }
+
+    /// Define a host item
+    pub fn define(&mut self, module: &str, name: &str, value: Extern) -> Result<&mut Self> {
+        let name = ExternName::new(module, name);
+        self.check_shadowing(&name)?;
+        self.items.insert(name, Definition::Extern(value));
+        Ok(self)
+    }
+
+    /// Define all functions of a host module
+    pub fn define_host_module(&mut self, module: &str, host: impl HostModule) -> Result<&mut Self> {
+        for (name, value) in host.into_externs() {
+            self.define(module, name, value)?;
+        }
+        Ok(self)
+    }
+
+    /// Register all exports of an instance under the given module name
+    pub fn instance(&mut self, module: &str, instance: &ModuleInstance) -> Result<&mut Self> {
+        self.check_store(instance.0.store_id)?;
+
+        let exports = instance
+            .0
+            .exports
+            .iter()
+            .filter_map(|export| Some((ExternName::new(module, &export.name), instance.resolve_export(export)?)))
+            .collect::<Vec<_>>();
+
+        for (name, _) in &exports {
+            self.check_shadowing(name)?;
+        }
+
+        self.items.extend(exports.into_iter().map(|(name, addr)| (name, Definition::Store(addr))));
+        Ok(self)
+    }
+
+    /// Instantiate a module and register its exports under the given module name
+    pub fn module(&mut self, store: &mut Store, name: &str, module: Module) -> Result<ModuleInstance> {
+        let instance = self.instantiate(store, module)?;
+        self.instance(name, &instance)?;
+        Ok(instance)
+    }
+
+    /// Define every function import of the module that is not defined yet as a function that traps
+    ///
+    /// The stubs fail with [`Trap::UnresolvedImport`] when called. This allows instantiating modules
+    /// that import functions which are never used. Other kinds of imports are left untouched.
+    pub fn define_unknown_imports_as_traps(&mut self, module: &Module) -> Result<&mut Self> {
+        for import in module.0.imports.iter() {
+            let ImportKind::Function(ty) = import.kind else { continue };
+            let name = ExternName::from(import);
+            if self.items.contains_key(&name) {
+                continue;
+            }
+
+            let ty = module.0.func_types.get(ty as usize).ok_or_else(|| LinkingError::unknown_import(import))?;
+            let (module, name_str) = (import.module.to_string(), import.name.to_string());
+            let stub = Extern::func(ty, move |_, _| {
+                Err(Trap::UnresolvedImport { module: module.clone(), name: name_str.clone() }.into())
+            });
+            self.items.insert(name, Definition::Extern(stub));
+        }
+
+        Ok(self)
+    }
+
+    /// Get the address of a defined item
+    ///
+    /// Returns `None` if the item is not defined or is a host item that has not been imported yet.
+    pub fn get(&self, module: &str, name: &str) -> Option<ExternVal> {
+        match self.items.get(&ExternName::new(module, name))? {
+            Definition::Store(addr) => Some(addr.clone()),
+            Definition::Extern(_) => None,
+        }
+    }
+
+    /// Instantiate a module, resolving all of its imports against the linker
+    ///
+    /// Runs the start function if it exists, like [`Module::instantiate`].
+    /// Host items are only shared with later modules if the instantiation succeeds.
+    pub fn instantiate(&mut self, store: &mut Store, module: Module) -> Result<ModuleInstance> {
+        self.check_store(store.id())?;
+
+        // check all imports first, so a module that can't be linked doesn't add anything to the store
+        for import in module.0.imports.iter() {
+            match self.items.get(&ExternName::from(import)) {
+                Some(Definition::Extern(value)) => Imports::check_extern(import, value, &module.0.func_types)?,
+                Some(Definition::Store(_)) => {}
+                None => return Err(LinkingError::unknown_import(import).into()),
+            }
+        }
+
+        let idx = store.next_module_instance_idx();
+        let mut imports = Imports::new();
+        let mut added = BTreeMap::new();
+        for import in module.0.imports.iter() {
+            let name = ExternName::from(import);
+            let addr = match (&self.items[&name], added.get(&name)) {
+                (Definition::Store(addr), _) | (Definition::Extern(_), Some(addr)) => addr.clone(),
+                (Definition::Extern(value), None) => {
+                    let addr = Self::add_to_store(store, value.clone(), idx)?;
+                    added.insert(name.clone(), addr.clone());
+                    addr
+                }
+            };
+            imports.define_addr(name, addr);
+        }
+
+        let instance = module.instantiate(store, Some(imports))?;
+        self.items.extend(added.into_iter().map(|(name, addr)| (name, Definition::Store(addr))));
+        Ok(instance)
+    }
+
+    pub(crate) fn add_to_store(store: &mut Store, value: Extern, idx: ModuleInstanceAddr) -> Result<ExternVal> {
+        Ok(match value {
+            Extern::Global { ty, val } => ExternVal::Global(store.add_global(ty, val.into(), idx)?),
+            Extern::Table { ty, .. } => ExternVal::Table(store.add_table(ty, idx)?),
+            Extern::Memory { ty } => ExternVal::Memory(store.add_mem(ty, idx)?),
+            Extern::Function(func) => ExternVal::Func(store.add_func(func, idx)?),
+        })
+    }
+
+    fn check_shadowing(&self, name: &ExternName) -> Result<()> {
+        if !self.allow_shadowing && self.items.contains_key(name) {
+            return Err(
+                LinkingError::DuplicateDefinition { module: name.module.clone(), name: name.name.clone() }.into()
+            );
+        }
+        Ok(())
+    }
+
+    fn check_store(&mut self, store_id: usize) -> Result<()> {
+        match self.store_id {
+            Some(id) if id != store_id => Err(Error::InvalidStore),
+            _ => {
+                self.store_id = Some(store_id);
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+
+    fn module(wat: &str) -> Module {
+        Module::parse_bytes(&wat::parse_str(wat).unwrap()).unwrap()
+    }
+
+    fn import_global(store: &mut Store, linker: &mut Linker) -> Result<WasmValue> {
+        let instance = linker.instantiate(store, module(r#"(module (global (export "g") (import "env" "g") i32))"#))?;
+        Ok(instance.exported_global(store, "g")?.get())
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let mut store = Store::default();
+        let mut linker = Linker::new();
+        linker.define("env", "g", Extern::global(WasmValue::I32(1), false)).unwrap();
+        let err = linker.define("env", "g", Extern::global(WasmValue::I32(2), false)).unwrap_err();
+        assert!(matches!(err, Error::Linker(LinkingError::DuplicateDefinition { .. })));
+        assert_eq!(import_global(&mut store, &mut linker).unwrap(), WasmValue::I32(1));
+
+        linker.allow_shadowing(true);
+        linker.define("env", "g", Extern::global(WasmValue::I32(2), false)).unwrap();
+        assert_eq!(import_global(&mut store, &mut linker).unwrap(), WasmValue::I32(2));
+
+        // the exports of an instance shadow host items as well
+        let other = linker.module(&mut store, "env", module(r#"(module (global (export "g") i32 (i32.const 3)))"#));
+        let (addr, other) = (linker.get("env", "g"), other.unwrap().export_addr("g"));
+        assert!(matches!((addr, other), (Some(ExternVal::Global(a)), Some(ExternVal::Global(b))) if a == b));
+        assert_eq!(import_global(&mut store, &mut linker).unwrap(), WasmValue::I32(3));
+    }
+
+    #[test]
+    fn test_failed_instantiation() {
+        let mut store = Store::default();
+        let mut linker = Linker::new();
+        linker.define("env", "g", Extern::global(WasmValue::I32(1), false)).unwrap();
+        linker.define("env", "f", Extern::typed_func(|_, ()| Ok(()))).unwrap();
+        let globals = store.data.globals.len();
+
+        // missing and incompatible imports are found before anything is added to the store
+        let missing = module(r#"(module (import "env" "g" (global i32)) (import "env" "missing" (func)))"#);
+        let err = linker.instantiate(&mut store, missing).unwrap_err();
+        assert!(matches!(err, Error::Linker(LinkingError::UnknownImport { .. })));
+        let incompatible = module(r#"(module (import "env" "g" (global i32)) (import "env" "f" (func (param i32))))"#);
+        let err = linker.instantiate(&mut store, incompatible).unwrap_err();
+        assert!(matches!(err, Error::Linker(LinkingError::IncompatibleImportType { .. })));
+        assert_eq!(store.data.globals.len(), globals);
+
+        // host items of a module that traps while being instantiated aren't shared
+        let trap = module(r#"(module (import "env" "g" (global i32)) (func $start unreachable) (start $start))"#);
+        assert!(matches!(linker.instantiate(&mut store, trap), Err(Error::Trap(Trap::Unreachable))));
+        assert!(linker.get("env", "g").is_none());
+
+        assert_eq!(import_global(&mut store, &mut linker).unwrap(), WasmValue::I32(1));
+        assert!(linker.get("env", "g").is_some());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{Imports, ModuleInstance, Result, Store};
-use tinywasm_types::TinyWasmModule;
+use tinywasm_types::{ExternType, ExternalKind, FuncType, ImportKind, TinyWasmModule};
 
 /// A WebAssembly Module
 ///
@@ -7,6 +7,35 @@ use tinywasm_types::TinyWasmModule;
 #[derive(Debug, Clone)]
 pub struct Module(pub(crate) TinyWasmModule);
 
+/// An import of a module, see [`Module::imports`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportType<'a> {
+    /// The module the item is imported from
+    pub module: &'a str,
+    /// The name of the imported item
+    pub name: &'a str,
+    /// The expected type of the imported item
+    pub ty: ExternType,
+}
+
+/// An export of a module, see [`Module::exports`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportType<'a> {
+    /// The name of the exported item
+    pub name: &'a str,
+    /// The type of the exported item
+    pub ty: ExternType,
+}
+
+pub(crate) fn import_type(types: &[FuncType], kind: &ImportKind) -> ExternType {
+    match kind {
+        ImportKind::Function(ty) => ExternType::Func(types[*ty as usize].clone()),
+        ImportKind::Table(ty) => ExternType::Table(ty.clone()),
+        ImportKind::Memory(ty) => ExternType::Memory(*ty),
+        ImportKind::Global(ty) => ExternType::Global(*ty),
+    }
+}
+
 impl From<&TinyWasmModule> for Module {
     fn from(data: &TinyWasmModule) -> Self {
         Self(data.clone())
@@ -44,6 +73,37 @@ impl Module {
         Ok(data.into())
     }
 
+    /// Get the imports of the module in the order they are declared
+    pub fn imports(&self) -> impl Iterator<Item = ImportType<'_>> {
+        self.0.imports.iter().map(|import| ImportType {
+            module: &import.module,
+            name: &import.name,
+            ty: import_type(&self.0.func_types, &import.kind),
+        })
+    }
+
+    /// Get the exports of the module in the order they are declared
+    pub fn exports(&self) -> impl Iterator<Item = ExportType<'_>> {
+        self.0.exports.iter().map(|export| {
+            // imported items come first in each index space
+            let mut imported = self.0.imports.iter().filter(|i| ExternalKind::from(&i.kind) == export.kind);
+            let imported_count = imported.clone().count();
+            let idx = export.index as usize;
+
+            let ty = match imported.nth(idx) {
+                Some(import) => import_type(&self.0.func_types, &import.kind),
+                None => match export.kind {
+                    ExternalKind::Func => ExternType::Func(self.0.funcs[idx - imported_count].ty.clone()),
+                    ExternalKind::Table => ExternType::Table(self.0.table_types[idx - imported_count].clone()),
+                    ExternalKind::Memory => ExternType::Memory(self.0.memory_types[idx - imported_count]),
+                    ExternalKind::Global => ExternType::Global(self.0.globals[idx - imported_count].ty),
+                },
+            };
+
+            ExportType { name: &export.name, ty }
+        })
+    }
+
     /// Instantiate the module in the given store
     ///
     /// Runs the start function if it exists
@@ -56,3 +116,74 @@ impl Module {
         Ok(instance)
     }
 }
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+    use crate::{Error, Extern, FuncContext};
+    use alloc::vec::Vec;
+    use tinywasm_types::{GlobalType, ValType};
+
+    #[test]
+    fn test_exports_with_imports() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "env" "f" (func $f (param i32)))
+                (import "env" "g" (global $g i32))
+                (func $local (result i64) (i64.const 0))
+                (global $local_global (mut f32) (f32.const 0))
+                (export "f" (func $f))
+                (export "local" (func $local))
+                (export "g" (global $g))
+                (export "local_global" (global $local_global)))"#,
+        )
+        .unwrap();
+
+        let func = |params: &[ValType], results: &[ValType]| {
+            ExternType::Func(FuncType { params: params.into(), results: results.into() })
+        };
+        let global = |ty, mutable| ExternType::Global(GlobalType { ty, mutable });
+        let expected = [
+            ("f", func(&[ValType::I32], &[])),
+            ("local", func(&[], &[ValType::I64])),
+            ("g", global(ValType::I32, false)),
+            ("local_global", global(ValType::F32, true)),
+        ];
+
+        let module = Module::parse_bytes(&wasm).unwrap();
+        let exports: Vec<_> = module.exports().map(|e| (e.name, e.ty)).collect();
+        assert_eq!(exports, expected);
+
+        let mut imports = Imports::new();
+        imports.define("env", "f", Extern::typed_func(|_: FuncContext<'_>, _: i32| Ok(()))).unwrap();
+        imports.define("env", "g", Extern::global(tinywasm_types::WasmValue::I32(1), false)).unwrap();
+        let mut store = Store::default();
+        let instance = module.instantiate(&mut store, Some(imports)).unwrap();
+        let exports: Vec<_> = instance.exports(&store).unwrap().map(|e| (e.name, e.ty)).collect();
+        assert_eq!(exports, expected);
+    }
+
+    #[test]
+    fn test_exports_foreign_store() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (memory (export "memory") 1)
+                (table (export "table") 1 funcref)
+                (global (export "global") (mut i32) (i32.const 0)))"#,
+        )
+        .unwrap();
+        let module = Module::parse_bytes(&wasm).unwrap();
+        let instance = module.clone().instantiate(&mut Store::default(), None).unwrap();
+
+        // an empty store, and one with items at the same addresses
+        let mut other = Store::default();
+        module.instantiate(&mut other, None).unwrap();
+        for mut store in [Store::default(), other] {
+            assert!(matches!(instance.exported_table(&store, "table"), Err(Error::InvalidStore)));
+            assert!(matches!(instance.exported_table_mut(&mut store, "table"), Err(Error::InvalidStore)));
+            assert!(matches!(instance.exported_global(&store, "global"), Err(Error::InvalidStore)));
+            assert!(matches!(instance.exported_memory(&mut store, "memory"), Err(Error::InvalidStore)));
+            assert!(matches!(instance.exported_memory_mut(&mut store, "memory"), Err(Error::InvalidStore)));
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use alloc::{format, vec, vec::Vec};
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use tinywasm_types::{ValType, WasmValue};
+
+use crate::func::{FromWasmValueTuple, IntoWasmValueTuple, ToValType};
+use crate::{Error, MemoryRefLoad, MemoryRefMut, Result};
+
+/// Plain data that can be read from and written to linear memory
+///
+/// Values are stored in little-endian byte order. Implemented for integers, floats, arrays and guest pointers,
+/// and can be derived for structs with the `macros` feature, using the same layout as `#[repr(C)]`.
+pub trait WasmPod: Sized {
+    /// The size of the value in bytes
+    const SIZE: usize;
+
+    /// The alignment of the value in bytes, used for struct layout
+    const ALIGN: usize;
+
+    /// Decode a value from exactly [`WasmPod::SIZE`] bytes
+    fn read_from(bytes: &[u8]) -> Self;
+
+    /// Encode the value into exactly [`WasmPod::SIZE`] bytes
+    fn write_to(&self, bytes: &mut [u8]);
+}
+
+macro_rules! impl_wasm_pod {
+    ($($t:ty),*) => {
+        $(
+            impl WasmPod for $t {
+                const SIZE: usize = core::mem::size_of::<$t>();
+                const ALIGN: usize = core::mem::size_of::<$t>();
+
+                #[inline]
+                fn read_from(bytes: &[u8]) -> Self {
+                    let mut buf = [0; core::mem::size_of::<$t>()];
+                    buf.copy_from_slice(bytes);
+                    <$t>::from_le_bytes(buf)
+                }
+
+                #[inline]
+                fn write_to(&self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_wasm_pod!(u8, i8, u16, i16, u32, i32, u64, i64, u128, i128, f32, f64);
+
+impl<T: WasmPod, const N: usize> WasmPod for [T; N] {
+    const SIZE: usize = T::SIZE * N;
+    const ALIGN: usize = T::ALIGN;
+
+    fn read_from(bytes: &[u8]) -> Self {
+        core::array::from_fn(|i| T::read_from(&bytes[i * T::SIZE..(i + 1) * T::SIZE]))
+    }
+
+    fn write_to(&self, bytes: &mut [u8]) {
+        for (i, value) in self.iter().enumerate() {
+            value.write_to(&mut bytes[i * T::SIZE..(i + 1) * T::SIZE]);
+        }
+    }
+}
+
+/// A pointer into the linear memory of a guest
+///
+/// Can be used as a parameter or result of typed functions, where it is passed as an `i32`.
+/// Dereferencing is bounds-checked against the memory.
+pub struct WasmPtr<T> {
+    offset: u32,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> WasmPtr<T> {
+    /// Create a pointer to the given offset
+    pub const fn new(offset: u32) -> Self {
+        Self { offset, marker: PhantomData }
+    }
+
+    /// Create a null pointer
+    pub const fn null() -> Self {
+        Self::new(0)
+    }
+
+    /// Get the offset in memory
+    pub const fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Check if the pointer is null
+    pub const fn is_null(&self) -> bool {
+        self.offset == 0
+    }
+
+    /// Reinterpret the pointer as a pointer to another type
+    pub const fn cast<U>(self) -> WasmPtr<U> {
+        WasmPtr::new(self.offset)
+    }
+}
+
+impl<T: WasmPod> WasmPtr<T> {
+    /// Get a pointer to the element `count` positions after this one
+    pub fn offset_by(self, count: u32) -> Result<Self> {
+        count
+            .checked_mul(T::SIZE as u32)
+            .and_then(|bytes| self.offset.checked_add(bytes))
+            .map(Self::new)
+            .ok_or_else(|| Error::Other(format!("pointer offset overflow: {} + {count}", self.offset)))
+    }
+
+    /// Get a slice of `len` elements starting at this pointer
+    pub const fn slice(self, len: u32) -> WasmSlice<T> {
+        WasmSlice { ptr: self, len }
+    }
+
+    /// Read the value the pointer points to
+    pub fn read(&self, memory: &impl MemoryRefLoad) -> Result<T> {
+        memory.load(self.offset as usize, T::SIZE).map(T::read_from)
+    }
+
+    /// Write a value to the location the pointer points to
+    pub fn write(&self, memory: &mut MemoryRefMut<'_>, value: T) -> Result<()> {
+        memory.write(self.offset as usize, value)
+    }
+}
+
+// manual impls to avoid requiring `T: Trait` for the derives
+impl<T> Clone for WasmPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for WasmPtr<T> {}
+
+impl<T> PartialEq for WasmPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.offset == other.offset
+    }
+}
+
+impl<T> Eq for WasmPtr<T> {}
+
+impl<T> Debug for WasmPtr<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "WasmPtr<{}>({:#x})", core::any::type_name::<T>(), self.offset)
+    }
+}
+
+impl<T> WasmPod for WasmPtr<T> {
+    const SIZE: usize = 4;
+    const ALIGN: usize = 4;
+
+    fn read_from(bytes: &[u8]) -> Self {
+        Self::new(u32::read_from(bytes))
+    }
+
+    fn write_to(&self, bytes: &mut [u8]) {
+        self.offset.write_to(bytes);
+    }
+}
+
+impl<T> From<WasmPtr<T>> for WasmValue {
+    fn from(ptr: WasmPtr<T>) -> Self {
+        WasmValue::I32(ptr.offset as i32)
+    }
+}
+
+impl<T> TryFrom<WasmValue> for WasmPtr<T> {
+    type Error = ();
+
+    fn try_from(value: WasmValue) -> core::result::Result<Self, Self::Error> {
+        i32::try_from(value).map(|offset| Self::new(offset as u32))
+    }
+}
+
+impl<T> ToValType for WasmPtr<T> {
+    fn to_val_type() -> ValType {
+        ValType::I32
+    }
+}
+
+impl<T> IntoWasmValueTuple for WasmPtr<T> {
+    fn into_wasm_value_tuple(self) -> Vec<WasmValue> {
+        vec![self.into()]
+    }
+}
+
+impl<T> FromWasmValueTuple for WasmPtr<T> {
+    fn from_wasm_value_tuple(values: &[WasmValue]) -> Result<Self> {
+        crate::__private::next_value(&mut values.iter())
+    }
+}
+
+/// A pointer to a number of consecutive values in the linear memory of a guest
+#[derive(Debug)]
+pub struct WasmSlice<T> {
+    ptr: WasmPtr<T>,
+    len: u32,
+}
+
+impl<T> Clone for WasmSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for WasmSlice<T> {}
+
+impl<T: WasmPod> WasmSlice<T> {
+    /// Create a slice of `len` elements starting at `ptr`
+    pub const fn new(ptr: WasmPtr<T>, len: u32) -> Self {
+        Self { ptr, len }
+    }
+
+    /// Get a pointer to the first element
+    pub const fn ptr(&self) -> WasmPtr<T> {
+        self.ptr
+    }
+
+    /// Get the number of elements
+    pub const fn len(&self) -> u32 {
+        self.len
+    }
+
+    /// Check if the slice has no elements
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get a pointer to the element at `index`, or `None` if it is out of bounds
+    pub fn index(&self, index: u32) -> Option<WasmPtr<T>> {
+        (index < self.len).then(|| self.ptr.offset_by(index).ok()).flatten()
+    }
+
+    /// Read the element at `index`
+    pub fn get(&self, memory: &impl MemoryRefLoad, index: u32) -> Result<T> {
+        self.index(index).ok_or_else(|| self.index_oob(index))?.read(memory)
+    }
+
+    /// Write the element at `index`
+    pub fn set(&self, memory: &mut MemoryRefMut<'_>, index: u32, value: T) -> Result<()> {
+        self.index(index).ok_or_else(|| self.index_oob(index))?.write(memory, value)
+    }
+
+    /// Read all elements
+    pub fn read_vec(&self, memory: &impl MemoryRefLoad) -> Result<Vec<T>> {
+        let bytes = memory.load(self.ptr.offset as usize, self.byte_len()?)?;
+        Ok((0..self.len as usize).map(|i| T::read_from(&bytes[i * T::SIZE..(i + 1) * T::SIZE])).collect())
+    }
+
+    /// Write all elements, `values` has to have the same length as the slice
+    pub fn write_slice(&self, memory: &mut MemoryRefMut<'_>, values: &[T]) -> Result<()> {
+        if values.len() != self.len as usize {
+            return Err(Error::Other(format!("slice length mismatch: expected {}, got {}", self.len, values.len())));
+        }
+
+        let mut bytes = vec![0; self.byte_len()?];
+        for (i, value) in values.iter().enumerate() {
+            value.write_to(&mut bytes[i * T::SIZE..(i + 1) * T::SIZE]);
+        }
+        memory.store(self.ptr.offset as usize, bytes.len(), &bytes)
+    }
+
+    fn byte_len(&self) -> Result<usize> {
+        (self.len as usize).checked_mul(T::SIZE).ok_or_else(|| Error::Other("slice too large".into()))
+    }
+
+    fn index_oob(&self, index: u32) -> Error {
+        Error::Other(format!("slice index out of bounds: {index} >= {}", self.len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_array_roundtrip() {
+        let value: [u16; 3] = [1, 0x0203, 0xffff];
+        let mut bytes = [0; 6];
+        value.write_to(&mut bytes);
+        assert_eq!(bytes, [1, 0, 3, 2, 0xff, 0xff]);
+        assert_eq!(<[u16; 3]>::read_from(&bytes), value);
+    }
+
+    #[test]
+    fn test_ptr_add() {
+        let ptr = WasmPtr::<u64>::new(8);
+        assert_eq!(ptr.offset_by(2).unwrap().offset(), 24);
+        assert!(ptr.offset_by(u32::MAX).is_err());
+        assert_eq!(ptr.slice(2).index(1).map(|p| p.offset()), Some(16));
+        assert_eq!(ptr.slice(2).index(2), None);
+    }
+}
@@ -1,10 +1,19 @@
 use core::ffi::CStr;
 
+use alloc::boxed::Box;
 use alloc::ffi::CString;
+use alloc::format;
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
+use core::any::Any;
+use core::ops::Range;
+use tinywasm_types::{ExternAddr, FuncAddr, GlobalType, TableType, ValType, WasmValue};
 
-use crate::{MemoryInstance, Result};
+use crate::{
+    Error, FuncHandle, GlobalInstance, MemoryAccess, MemoryInstance, Result, Store, TableElement, TableInstance,
+    WasmPod,
+};
+use crate::{WatchAction, WatchMode, WatchpointId};
 
 // This module essentially contains the public APIs to interact with the data stored in the store
 
@@ -40,6 +49,18 @@ impl MemoryRef<'_> {
     pub fn load_vec(&self, offset: usize, len: usize) -> Result<Vec<u8>> {
         self.load(offset, len).map(<[u8]>::to_vec)
     }
+
+    /// Read a little-endian value from memory
+    pub fn read<T: WasmPod>(&self, offset: usize) -> Result<T> {
+        self.load(offset, T::SIZE).map(T::read_from)
+    }
+
+    /// Get the logged memory accesses, oldest first
+    ///
+    /// Empty unless access logging was enabled with [`MemoryRefMut::enable_access_log`].
+    pub fn access_log(&self) -> Vec<MemoryAccess> {
+        self.0.hooks.as_ref().map(|h| h.log()).unwrap_or_default()
+    }
 }
 
 impl MemoryRefMut<'_> {
@@ -53,6 +74,18 @@ impl MemoryRefMut<'_> {
         self.load(offset, len).map(<[u8]>::to_vec)
     }
 
+    /// Read a little-endian value from memory
+    pub fn read<T: WasmPod>(&self, offset: usize) -> Result<T> {
+        self.load(offset, T::SIZE).map(T::read_from)
+    }
+
+    /// Write a value to memory in little-endian byte order
+    pub fn write<T: WasmPod>(&mut self, offset: usize, value: T) -> Result<()> {
+        let mut bytes = alloc::vec![0; T::SIZE];
+        value.write_to(&mut bytes);
+        self.store(offset, T::SIZE, &bytes)
+    }
+
     /// Grow the memory by the given number of pages
     pub fn grow(&mut self, delta_pages: i32) -> Option<i32> {
         self.0.grow(delta_pages)
@@ -63,6 +96,13 @@ impl MemoryRefMut<'_> {
         self.0.page_count
     }
 
+    /// Get the contents of the memory
+    ///
+    /// Accesses through the slice are not seen by watchpoints or the access log.
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.0.data
+    }
+
     /// Copy a slice of memory to another place in memory
     pub fn copy_within(&mut self, src: usize, dst: usize, len: usize) -> Result<()> {
         self.0.copy_within(src, dst, len)
@@ -77,6 +117,53 @@ impl MemoryRefMut<'_> {
     pub fn store(&mut self, offset: usize, len: usize, data: &[u8]) -> Result<()> {
         self.0.store(offset, len, data)
     }
+
+    /// Watch a range of memory
+    ///
+    /// The callback is called before every matching access that touches the range, including accesses
+    /// made by the host through [`MemoryRef`] and [`MemoryRefMut`]. If it returns [`WatchAction::Break`],
+    /// the access is not performed and the current call fails with [`crate::Error::Watchpoint`].
+    pub fn watch(
+        &mut self,
+        range: Range<usize>,
+        mode: WatchMode,
+        callback: impl Fn(&MemoryAccess) -> WatchAction + 'static,
+    ) -> WatchpointId {
+        self.0.hooks_mut().watch(range, mode, Box::new(callback))
+    }
+
+    /// Remove a watchpoint, returns `false` if it did not exist
+    pub fn unwatch(&mut self, id: WatchpointId) -> bool {
+        let removed = self.0.hooks_mut().unwatch(id);
+        self.0.prune_hooks();
+        removed
+    }
+
+    /// Log every access to this memory in a ring buffer holding the last `capacity` accesses
+    ///
+    /// Replaces any previously logged accesses.
+    pub fn enable_access_log(&mut self, capacity: usize) {
+        self.0.hooks_mut().set_log_capacity(Some(capacity));
+        self.0.prune_hooks();
+    }
+
+    /// Stop logging memory accesses and discard the log
+    pub fn disable_access_log(&mut self) {
+        self.0.hooks_mut().set_log_capacity(None);
+        self.0.prune_hooks();
+    }
+
+    /// Get the logged memory accesses, oldest first
+    pub fn access_log(&self) -> Vec<MemoryAccess> {
+        self.0.hooks.as_ref().map(|h| h.log()).unwrap_or_default()
+    }
+
+    /// Discard all logged memory accesses, keeping logging enabled
+    pub fn clear_access_log(&mut self) {
+        if let Some(hooks) = &self.0.hooks {
+            hooks.clear_log();
+        }
+    }
 }
 
 #[doc(hidden)]
@@ -133,3 +220,492 @@ pub trait MemoryStringExt: MemoryRefLoad {
 
 impl MemoryStringExt for MemoryRef<'_> {}
 impl MemoryStringExt for MemoryRefMut<'_> {}
+
+/// A reference to a table instance
+#[derive(Debug)]
+pub struct TableRef<'a>(pub(crate) &'a TableInstance);
+
+/// A borrowed reference to a table instance
+///
+/// Function references stored in a table are addresses in the store, e.g. taken from
+/// [`FuncHandle::func_ref`] or read from another table.
+#[derive(Debug)]
+pub struct TableRefMut<'a> {
+    pub(crate) table: &'a mut TableInstance,
+    pub(crate) func_count: usize,
+    pub(crate) store_id: usize,
+}
+
+impl TableRef<'_> {
+    /// Get the type of the table
+    pub fn ty(&self) -> &TableType {
+        &self.0.kind
+    }
+
+    /// Get the current number of elements
+    pub fn size(&self) -> u32 {
+        self.0.size() as u32
+    }
+
+    /// Get the element at the given index
+    pub fn get(&self, index: u32) -> Result<WasmValue> {
+        self.0.get_wasm_val(index)
+    }
+}
+
+impl TableRefMut<'_> {
+    /// Get the type of the table
+    pub fn ty(&self) -> &TableType {
+        &self.table.kind
+    }
+
+    /// Get the current number of elements
+    pub fn size(&self) -> u32 {
+        self.table.size() as u32
+    }
+
+    /// Get the element at the given index
+    pub fn get(&self, index: u32) -> Result<WasmValue> {
+        self.table.get_wasm_val(index)
+    }
+
+    /// Set the element at the given index
+    pub fn set(&mut self, index: u32, value: impl Into<WasmValue> + 'static) -> Result<()> {
+        let value = self.element(value)?;
+        self.table.set(index, value)
+    }
+
+    /// Grow the table by `delta` elements initialized to `init`, returning the previous size
+    pub fn grow(&mut self, delta: u32, init: impl Into<WasmValue> + 'static) -> Result<u32> {
+        let init = self.element(init)?;
+        let size = self.size();
+        let delta = i32::try_from(delta).map_err(|_| Error::Other(format!("Invalid table growth: {delta}")))?;
+        self.table.grow(delta, init)?;
+        Ok(size)
+    }
+
+    /// Set `len` elements starting at `offset` to `value`
+    pub fn fill(&mut self, offset: u32, len: u32, value: impl Into<WasmValue> + 'static) -> Result<()> {
+        let value = self.element(value)?;
+        self.table.fill(offset as usize, len as usize, value)
+    }
+
+    /// Copy `len` elements from `src` to `dst`, the ranges may overlap
+    pub fn copy(&mut self, dst: u32, src: u32, len: u32) -> Result<()> {
+        self.table.copy_within(dst as usize, src as usize, len as usize)
+    }
+
+    fn element(&self, value: impl Into<WasmValue> + 'static) -> Result<TableElement> {
+        check_extern_ref(&value, self.store_id)?;
+        let value = value.into();
+        let ty = self.table.kind.element_type;
+        if value.val_type() != ty {
+            return Err(Error::Other(format!("Invalid table element: expected {ty:?}, got {value:?}")));
+        }
+
+        Ok(match value {
+            WasmValue::RefFunc(addr) if addr as usize >= self.func_count => {
+                return Err(Error::Other(format!("Invalid function reference: {addr}")))
+            }
+            WasmValue::RefFunc(addr) | WasmValue::RefExtern(addr) => TableElement::Initialized(addr),
+            _ => TableElement::Uninitialized,
+        })
+    }
+}
+
+/// A reference to a global instance
+#[derive(Debug)]
+pub struct GlobalRef<'a> {
+    pub(crate) global: &'a GlobalInstance,
+    pub(crate) func_count: usize,
+    pub(crate) store_id: usize,
+}
+
+impl GlobalRef<'_> {
+    /// Get the type of the global
+    pub fn ty(&self) -> GlobalType {
+        self.global.ty
+    }
+
+    /// Get the current value
+    pub fn get(&self) -> WasmValue {
+        self.global.value.get().attach_type(self.global.ty.ty)
+    }
+
+    /// Get the current value as a Rust type
+    ///
+    /// Fails if the global's type does not match `T`.
+    pub fn get_as<T: TryFrom<WasmValue>>(&self) -> Result<T> {
+        let value = self.get();
+        T::try_from(value).map_err(|_| Error::Other(format!("Invalid global type: {:?}", value.val_type())))
+    }
+
+    /// Set the value
+    ///
+    /// Fails if the global is immutable or the value has a different type.
+    pub fn set(&self, value: impl Into<WasmValue> + 'static) -> Result<()> {
+        check_extern_ref(&value, self.store_id)?;
+        let value = value.into();
+        if !self.global.ty.mutable {
+            return Err(Error::Other("Global is immutable".to_string()));
+        }
+
+        if value.val_type() != self.global.ty.ty {
+            return Err(Error::Other(format!("Invalid global value: expected {:?}, got {value:?}", self.global.ty.ty)));
+        }
+
+        if let WasmValue::RefFunc(addr) = value {
+            if addr as usize >= self.func_count {
+                return Err(Error::Other(format!("Invalid function reference: {addr}")));
+            }
+        }
+
+        self.global.value.set(value.into());
+        Ok(())
+    }
+}
+
+/// A reference to a host value, passed to WebAssembly as an `externref`
+///
+/// The value itself is owned by the [`Store`] it was created in and lives as long as the store, or until
+/// it is removed using [`ExternRef::take`]. Guest code can only pass the reference around, the host can get
+/// the value back using [`ExternRef::downcast_ref`].
+///
+/// References created with [`ExternRef::new`] only return their value from the store they were created in.
+/// References converted from a [`WasmValue`], e.g. host function arguments, don't know their store and
+/// have to be used with the store of the instance they came from. The slots of taken values are reused,
+/// references to a taken value never return the value that replaced it.
+///
+/// ```rust
+/// # use tinywasm::{ExternRef, Store};
+/// let mut store = Store::default();
+/// let greeting = ExternRef::new(&mut store, "hello".to_string());
+/// assert_eq!(greeting.downcast_ref::<String>(&store).map(String::as_str), Some("hello"));
+/// assert!(greeting.downcast_ref::<u32>(&store).is_none());
+/// assert!(greeting.downcast_ref::<String>(&Store::default()).is_none());
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExternRef {
+    addr: Option<ExternAddr>,
+    store_id: Option<usize>,
+}
+
+impl ExternRef {
+    /// Move a host value into the store and create a reference to it
+    ///
+    /// Panics if the store holds more than 2^24 values.
+    pub fn new<T: Any>(store: &mut Store, value: T) -> Self {
+        Self { addr: Some(store.data.externs.insert(Box::new(value))), store_id: Some(store.id()) }
+    }
+
+    /// Create a null reference
+    pub const fn null() -> Self {
+        Self { addr: None, store_id: None }
+    }
+
+    /// Check if the reference is null
+    pub const fn is_null(&self) -> bool {
+        self.addr.is_none()
+    }
+
+    /// Get the address of the referenced value in the store
+    pub const fn addr(&self) -> Option<ExternAddr> {
+        self.addr
+    }
+
+    /// Get the referenced value if it is of type `T`
+    ///
+    /// Returns `None` for null references, if the value is of a different type, was already taken or
+    /// belongs to a different store.
+    pub fn downcast_ref<'a, T: Any>(&self, store: &'a Store) -> Option<&'a T> {
+        store.data.externs.get(self.store_addr(store)?)?.downcast_ref()
+    }
+
+    /// Get the referenced value mutably if it is of type `T`
+    pub fn downcast_mut<'a, T: Any>(&self, store: &'a mut Store) -> Option<&'a mut T> {
+        let addr = self.store_addr(store)?;
+        store.data.externs.get_mut(addr)?.downcast_mut()
+    }
+
+    /// Remove the referenced value from the store if it is of type `T`
+    ///
+    /// Afterwards, [`ExternRef::downcast_ref`] returns `None` for all copies of the reference.
+    pub fn take<T: Any>(&self, store: &mut Store) -> Option<T> {
+        let addr = self.store_addr(store)?;
+        if !store.data.externs.get(addr)?.is::<T>() {
+            return None;
+        }
+        store.data.externs.take(addr)?.downcast().ok().map(|value| *value)
+    }
+
+    // the address of the value, if the reference can belong to the store
+    fn store_addr(&self, store: &Store) -> Option<ExternAddr> {
+        match self.store_id {
+            Some(id) if id != store.id() => None,
+            _ => self.addr,
+        }
+    }
+}
+
+// the store is not part of the reference when it is passed to WebAssembly
+impl PartialEq for ExternRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.addr == other.addr
+    }
+}
+
+impl Eq for ExternRef {}
+
+impl core::hash::Hash for ExternRef {
+    fn hash<H: core::hash::Hasher>(&self, state: &mut H) {
+        self.addr.hash(state);
+    }
+}
+
+// setting an `ExternRef` from a different store fails, even though a `WasmValue` doesn't know its store
+fn check_extern_ref(value: &dyn Any, store_id: usize) -> Result<()> {
+    match value.downcast_ref::<ExternRef>() {
+        Some(ExternRef { store_id: Some(id), .. }) if *id != store_id => Err(Error::InvalidStore),
+        _ => Ok(()),
+    }
+}
+
+impl From<ExternRef> for WasmValue {
+    fn from(value: ExternRef) -> Self {
+        match value.addr {
+            Some(addr) => WasmValue::RefExtern(addr),
+            None => WasmValue::RefNull(ValType::RefExtern),
+        }
+    }
+}
+
+impl TryFrom<WasmValue> for ExternRef {
+    type Error = ();
+
+    fn try_from(value: WasmValue) -> core::result::Result<Self, Self::Error> {
+        match value {
+            WasmValue::RefExtern(addr) => Ok(Self { addr: Some(addr), store_id: None }),
+            WasmValue::RefNull(ValType::RefExtern) => Ok(Self::null()),
+            _ => Err(()),
+        }
+    }
+}
+
+/// A reference to a function, passed to WebAssembly as a `funcref`
+///
+/// Can be used in typed function signatures, e.g. to return functions from a table.
+/// Get one from an exported function using [`FuncHandle::func_ref`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct FuncRef(Option<FuncAddr>);
+
+impl FuncRef {
+    /// Create a null reference
+    pub const fn null() -> Self {
+        Self(None)
+    }
+
+    /// Check if the reference is null
+    pub const fn is_null(&self) -> bool {
+        self.0.is_none()
+    }
+
+    /// Get the address of the referenced function in the store
+    pub const fn addr(&self) -> Option<FuncAddr> {
+        self.0
+    }
+
+    /// Get a handle to call the referenced function
+    ///
+    /// Returns `None` for null references or if the function is not in the store.
+    pub fn func(&self, store: &Store) -> Option<FuncHandle> {
+        let addr = self.0?;
+        Some(store.data.funcs.get(addr as usize)?.handle(addr))
+    }
+}
+
+impl From<&FuncHandle> for FuncRef {
+    fn from(func: &FuncHandle) -> Self {
+        Self(Some(func.addr))
+    }
+}
+
+impl From<FuncRef> for WasmValue {
+    fn from(value: FuncRef) -> Self {
+        match value.0 {
+            Some(addr) => WasmValue::RefFunc(addr),
+            None => WasmValue::RefNull(ValType::RefFunc),
+        }
+    }
+}
+
+impl TryFrom<WasmValue> for FuncRef {
+    type Error = ();
+
+    fn try_from(value: WasmValue) -> core::result::Result<Self, Self::Error> {
+        match value {
+            WasmValue::RefFunc(addr) => Ok(Self(Some(addr))),
+            WasmValue::RefNull(ValType::RefFunc) => Ok(Self(None)),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+    use crate::{Extern, FuncContext, Imports, Module};
+    use alloc::string::{String, ToString};
+
+    #[test]
+    fn test_extern_ref() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "env" "len" (func $len (param externref) (result i32)))
+                (func (export "pass") (param externref) (result externref) (local.get 0))
+                (func (export "len") (param externref) (result i32) (call $len (local.get 0))))"#,
+        )
+        .unwrap();
+
+        let mut imports = Imports::new();
+        let len = |ctx: FuncContext<'_>, value: ExternRef| {
+            Ok(value.downcast_ref::<String>(ctx.store()).map_or(-1, |s| s.len() as i32))
+        };
+        imports.define("env", "len", Extern::typed_func(len)).unwrap();
+
+        let mut store = Store::default();
+        let instance = Module::parse_bytes(&wasm).unwrap().instantiate(&mut store, Some(imports)).unwrap();
+        let value = ExternRef::new(&mut store, "hello".to_string());
+        let other = ExternRef::new(&mut store, 1u32);
+
+        let pass = instance.exported_func::<ExternRef, ExternRef>(&store, "pass").unwrap();
+        assert_eq!(pass.call(&mut store, value).unwrap(), value);
+        assert!(pass.call(&mut store, ExternRef::null()).unwrap().is_null());
+
+        let len = instance.exported_func::<ExternRef, i32>(&store, "len").unwrap();
+        assert_eq!(len.call(&mut store, value).unwrap(), 5);
+        assert_eq!(len.call(&mut store, other).unwrap(), -1);
+        assert_eq!(store.stats().extern_refs, 2);
+
+        assert_eq!(value.take::<u32>(&mut store), None);
+        assert_eq!(value.take::<String>(&mut store).as_deref(), Some("hello"));
+        assert_eq!(value.take::<String>(&mut store), None);
+        assert_eq!(len.call(&mut store, value).unwrap(), -1);
+        assert_eq!(store.stats().extern_refs, 1);
+
+        // the slot is reused, but the old reference doesn't see the new value
+        let reused = ExternRef::new(&mut store, 2u32);
+        assert_ne!(reused, value);
+        assert_eq!(reused.addr().map(|addr| addr & 0xff_ffff), value.addr());
+        assert_eq!(value.downcast_ref::<u32>(&store), None);
+        assert_eq!(value.take::<u32>(&mut store), None);
+        assert_eq!(reused.downcast_ref::<u32>(&store), Some(&2));
+        assert_eq!(other.downcast_mut::<u32>(&mut store).map(|v| *v), Some(1));
+        assert_eq!(store.stats().extern_refs, 2);
+    }
+
+    #[test]
+    fn test_extern_ref_slots() {
+        let mut store = Store::default();
+        let first = ExternRef::new(&mut store, 0u32);
+        first.take::<u32>(&mut store).unwrap();
+
+        // a slot is retired once its generation is exhausted
+        let mut last = first;
+        for i in 1..256u32 {
+            last = ExternRef::new(&mut store, i);
+            assert_eq!(last.addr(), Some(i << 24));
+            last.take::<u32>(&mut store).unwrap();
+        }
+        assert_eq!(ExternRef::new(&mut store, 0u32).addr(), Some(1));
+        assert_eq!(first.downcast_ref::<u32>(&store), None);
+        assert_eq!(last.downcast_ref::<u32>(&store), None);
+    }
+
+    #[test]
+    fn test_extern_ref_foreign_store() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (table (export "table") 1 externref)
+                (global (export "global") (mut externref) (ref.null extern)))"#,
+        )
+        .unwrap();
+
+        let mut store = Store::default();
+        let instance = Module::parse_bytes(&wasm).unwrap().instantiate(&mut store, None).unwrap();
+        let local = ExternRef::new(&mut store, 1u32);
+
+        // a store with a value at the same address
+        let mut other = Store::default();
+        let foreign = ExternRef::new(&mut other, 2u32);
+        assert_eq!(foreign.addr(), local.addr());
+        assert_eq!(foreign.downcast_ref::<u32>(&store), None);
+        assert_eq!(foreign.downcast_mut::<u32>(&mut store), None);
+        assert_eq!(foreign.take::<u32>(&mut store), None);
+        assert_eq!(local.downcast_ref::<u32>(&store), Some(&1));
+
+        let mut table = instance.exported_table_mut(&mut store, "table").unwrap();
+        assert!(matches!(table.set(0, foreign), Err(Error::InvalidStore)));
+        assert!(matches!(table.fill(0, 1, foreign), Err(Error::InvalidStore)));
+        assert!(matches!(table.grow(1, foreign), Err(Error::InvalidStore)));
+        table.set(0, local).unwrap();
+
+        let global = instance.exported_global(&store, "global").unwrap();
+        assert!(matches!(global.set(foreign), Err(Error::InvalidStore)));
+        global.set(local).unwrap();
+        assert_eq!(global.get_as::<ExternRef>().unwrap(), local);
+    }
+
+    #[test]
+    fn test_func_ref_table() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (table (export "table") 1 funcref)
+                (func (export "double") (param i32) (result i32) (i32.mul (local.get 0) (i32.const 2)))
+                (func (export "call") (param i32) (result i32) (call_indirect (param i32) (result i32) (local.get 0) (i32.const 0))))"#,
+        )
+        .unwrap();
+
+        let mut store = Store::default();
+        let instance = Module::parse_bytes(&wasm).unwrap().instantiate(&mut store, None).unwrap();
+        let double = instance.exported_func_untyped(&store, "double").unwrap().func_ref();
+        instance.exported_table_mut(&mut store, "table").unwrap().set(0, double).unwrap();
+
+        let call = instance.exported_func::<i32, i32>(&store, "call").unwrap();
+        assert_eq!(call.call(&mut store, 21).unwrap(), 42);
+        assert_eq!(instance.exported_table(&store, "table").unwrap().get(0).unwrap(), double.into());
+    }
+
+    #[test]
+    fn test_table_global_type_mismatch() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (table (export "table") 2 funcref)
+                (global (export "mut") (mut i32) (i32.const 1))
+                (global (export "const") i64 (i64.const 2)))"#,
+        )
+        .unwrap();
+
+        let mut store = Store::default();
+        let instance = Module::parse_bytes(&wasm).unwrap().instantiate(&mut store, None).unwrap();
+
+        let mut table = instance.exported_table_mut(&mut store, "table").unwrap();
+        assert!(table.set(0, WasmValue::I32(0)).is_err());
+        assert!(table.set(0, WasmValue::RefNull(ValType::RefExtern)).is_err());
+        assert!(table.set(0, WasmValue::RefFunc(99)).is_err());
+        assert!(table.fill(0, 2, WasmValue::I64(0)).is_err());
+        assert!(table.grow(1, WasmValue::F32(0.0)).is_err());
+        assert_eq!(table.size(), 2);
+        assert_eq!(table.get(0).unwrap(), WasmValue::RefNull(ValType::RefFunc));
+        assert!(table.get(2).is_err());
+
+        let global = instance.exported_global(&store, "mut").unwrap();
+        assert!(global.set(2i64).is_err());
+        assert!(global.get_as::<f32>().is_err());
+        global.set(2).unwrap();
+        assert_eq!(global.get_as::<i32>().unwrap(), 2);
+
+        let global = instance.exported_global(&store, "const").unwrap();
+        assert!(global.set(3i64).is_err());
+        assert_eq!(global.get(), WasmValue::I64(2));
+    }
+}
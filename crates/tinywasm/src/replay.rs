@@ -0,0 +1,604 @@
+//! Deterministic record and replay of host function calls
+//!
+//! While recording, every call to a [`HostFunction`] records the arguments, the results and
+//! all writes the host made to linear memory. Replaying a [`Recording`] in a fresh store
+//! runs the same module without calling the real host functions: each call is answered from
+//! the recording instead, and any difference in the calls the guest makes is reported as a
+//! [`Divergence`].
+//!
+//! Host calls made while another host call is being recorded (e.g. a host function calling back
+//! into WebAssembly) are part of the outer call and are not recorded on their own.
+//! Changes the host makes to globals and tables are not recorded.
+//!
+//! Calls are identified by the address of the host function in the store, so the replay has to
+//! instantiate the same modules with the same imports in the same order as the recorded run.
+//! Errors returned by host functions are only recorded as their message and are replayed as a
+//! [`RecordedError`], e.g. a WASI `ProcExit` can't be downcast to its original type during a replay.
+//!
+//! ```rust
+//! use tinywasm::{Extern, FuncContext, Imports, Module, Store};
+//!
+//! let wasm = wat::parse_str(r#"(module
+//!     (import "env" "random" (func $random (result i32)))
+//!     (func (export "run") (result i32) (call $random)))"#).unwrap();
+//! let module = Module::parse_bytes(&wasm)?;
+//!
+//! let mut imports = Imports::new();
+//! imports.define("env", "random", Extern::typed_func(|_: FuncContext<'_>, _: ()| Ok(4)))?;
+//!
+//! // record a run using the real host functions
+//! let mut store = Store::default();
+//! store.start_recording();
+//! let instance = module.clone().instantiate(&mut store, Some(imports))?;
+//! let result = instance.exported_func::<(), i32>(&store, "run")?.call(&mut store, ())?;
+//! let recording = store.stop_recording().unwrap();
+//!
+//! // replay the run without them
+//! let mut imports = Imports::new();
+//! imports.define("env", "random", Extern::typed_func(|_: FuncContext<'_>, _: ()| -> tinywasm::Result<i32> { unreachable!() }))?;
+//!
+//! let mut store = Store::default();
+//! store.start_replay(tinywasm::replay::Recording::from_bytes(&recording.to_bytes())?);
+//! let instance = module.instantiate(&mut store, Some(imports))?;
+//! assert_eq!(instance.exported_func::<(), i32>(&store, "run")?.call(&mut store, ())?, result);
+//! store.finish_replay()?;
+//! # Ok::<(), tinywasm::Error>(())
+//! ```
+
+use alloc::string::{String, ToString};
+use alloc::{format, vec::Vec};
+use core::fmt::Display;
+use core::ops::Range;
+use tinywasm_types::{FuncAddr, MemAddr, ValType, WasmValue};
+
+use crate::{Error, FuncContext, HostFunction, Result, Store};
+
+/// The host function calls recorded while running a module
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Recording {
+    /// The recorded calls, in the order they were made
+    pub calls: Vec<HostCall>,
+}
+
+/// A single recorded host function call
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostCall {
+    /// The address of the called function in the store
+    pub func: FuncAddr,
+    /// The arguments passed to the function
+    pub args: Vec<WasmValue>,
+    /// The values returned by the function, or the message of the error it returned (see [`RecordedError`])
+    pub result: core::result::Result<Vec<WasmValue>, String>,
+    /// Memories that were grown by the host, with their new size in pages
+    pub memory_sizes: Vec<(MemAddr, usize)>,
+    /// Writes to linear memory made by the host, in the order they were made
+    pub memory_writes: Vec<MemoryWrite>,
+}
+
+/// A write to linear memory made by the host
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryWrite {
+    /// The address of the memory in the store
+    pub memory: MemAddr,
+    /// The offset of the first written byte
+    pub offset: usize,
+    /// The written bytes
+    pub data: Vec<u8>,
+}
+
+/// The error a replayed host call fails with if the recorded call returned an error
+///
+/// Returned as an [`Error::Host`], use [`Error::downcast_ref`] to tell it apart from other errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedError {
+    /// The message of the recorded error
+    pub message: String,
+}
+
+impl Display for RecordedError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// A difference between the host calls made during a replay and the recording
+#[derive(Debug, Clone, PartialEq)]
+pub enum Divergence {
+    /// A host function was called after all recorded calls were replayed
+    UnexpectedCall {
+        /// The index of the call
+        index: usize,
+        /// The called function
+        func: FuncAddr,
+    },
+
+    /// A different host function than the recorded one was called
+    Function {
+        /// The index of the call
+        index: usize,
+        /// The recorded function
+        expected: FuncAddr,
+        /// The called function
+        actual: FuncAddr,
+    },
+
+    /// A host function was called with different arguments than recorded
+    Arguments {
+        /// The index of the call
+        index: usize,
+        /// The recorded arguments
+        expected: Vec<WasmValue>,
+        /// The actual arguments
+        actual: Vec<WasmValue>,
+    },
+
+    /// A host function wrote to or grew a memory that does not exist in the store
+    Memory {
+        /// The index of the call
+        index: usize,
+        /// The recorded memory
+        memory: MemAddr,
+    },
+
+    /// The replay finished before all recorded calls were made
+    MissingCalls {
+        /// The index of the first call that was not made
+        index: usize,
+        /// The number of calls that were not made
+        remaining: usize,
+    },
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::UnexpectedCall { index, func } => write!(f, "unexpected host call #{index} to function {func}"),
+            Self::Function { index, expected, actual } => {
+                write!(f, "host call #{index} called function {actual}, expected function {expected}")
+            }
+            Self::Arguments { index, expected, actual } => {
+                write!(f, "host call #{index} was called with {actual:?}, expected {expected:?}")
+            }
+            Self::Memory { index, memory } => write!(f, "host call #{index} changed unknown memory {memory}"),
+            Self::MissingCalls { index, remaining } => {
+                write!(f, "replay finished with {remaining} host calls remaining, starting at #{index}")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) enum ReplayState {
+    #[default]
+    Off,
+    Recording {
+        recording: Recording,
+        in_host_call: bool,
+    },
+    Replaying {
+        recording: Recording,
+        next: usize,
+    },
+}
+
+impl ReplayState {
+    #[inline]
+    pub(crate) fn is_off(&self) -> bool {
+        matches!(self, Self::Off)
+    }
+}
+
+impl Store {
+    /// Start recording all host function calls
+    ///
+    /// Replaces any recording or replay in progress.
+    pub fn start_recording(&mut self) {
+        self.replay = ReplayState::Recording { recording: Recording::default(), in_host_call: false };
+    }
+
+    /// Stop recording and return the recorded calls
+    ///
+    /// Returns `None` if the store was not recording.
+    pub fn stop_recording(&mut self) -> Option<Recording> {
+        match core::mem::take(&mut self.replay) {
+            ReplayState::Recording { recording, .. } => Some(recording),
+            state => {
+                self.replay = state;
+                None
+            }
+        }
+    }
+
+    /// Answer all host function calls from a recording instead of calling the host functions
+    ///
+    /// Calls that differ from the recording fail with [`Error::ReplayDivergence`].
+    pub fn start_replay(&mut self, recording: Recording) {
+        self.replay = ReplayState::Replaying { recording, next: 0 };
+    }
+
+    /// Stop replaying, checking that all recorded calls were made
+    pub fn finish_replay(&mut self) -> Result<()> {
+        match core::mem::take(&mut self.replay) {
+            ReplayState::Replaying { recording, next } if next < recording.calls.len() => {
+                Err(Error::ReplayDivergence(Divergence::MissingCalls {
+                    index: next,
+                    remaining: recording.calls.len() - next,
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// called by `HostFunction::call` while recording or replaying
+pub(crate) fn call_host(func: &HostFunction, ctx: FuncContext<'_>, args: &[WasmValue]) -> Result<Vec<WasmValue>> {
+    let FuncContext { store, module_addr, func_addr } = ctx;
+    match &mut store.replay {
+        ReplayState::Recording { in_host_call, .. } if !*in_host_call => {
+            *in_host_call = true;
+            let page_counts: Vec<usize> = store.data.memories.iter().map(|m| m.page_count).collect();
+            store.data.memories.iter_mut().for_each(|m| m.hooks_mut().start_journal());
+
+            let result = (func.func)(FuncContext { store: &mut *store, module_addr, func_addr }, args);
+
+            let mut memory_sizes = Vec::new();
+            let mut memory_writes = Vec::new();
+            for (addr, memory) in store.data.memories.iter_mut().enumerate() {
+                if page_counts.get(addr).is_some_and(|&pages| pages != memory.page_count) {
+                    memory_sizes.push((addr as MemAddr, memory.page_count));
+                }
+
+                let journal = memory.hooks_mut().take_journal();
+                memory.prune_hooks();
+                memory_writes.extend(journal.into_iter().map(|Range { start, end }| MemoryWrite {
+                    memory: addr as MemAddr,
+                    offset: start,
+                    data: memory.data[start..end].to_vec(),
+                }));
+            }
+
+            let call = HostCall {
+                func: func_addr,
+                args: args.to_vec(),
+                result: result.as_ref().cloned().map_err(error_message),
+                memory_sizes,
+                memory_writes,
+            };
+
+            if let ReplayState::Recording { recording, in_host_call } = &mut store.replay {
+                recording.calls.push(call);
+                *in_host_call = false;
+            }
+            result
+        }
+        ReplayState::Replaying { recording, next } => {
+            let index = *next;
+            let Some(call) = recording.calls.get(index) else {
+                return Err(Error::ReplayDivergence(Divergence::UnexpectedCall { index, func: func_addr }));
+            };
+
+            if call.func != func_addr {
+                return Err(Error::ReplayDivergence(Divergence::Function {
+                    index,
+                    expected: call.func,
+                    actual: func_addr,
+                }));
+            }
+
+            if encode_values(&call.args) != encode_values(args) {
+                return Err(Error::ReplayDivergence(Divergence::Arguments {
+                    index,
+                    expected: call.args.clone(),
+                    actual: args.to_vec(),
+                }));
+            }
+
+            *next += 1;
+            let call = call.clone();
+            let unknown_memory = |memory| Error::ReplayDivergence(Divergence::Memory { index, memory });
+            for (addr, pages) in call.memory_sizes {
+                let memory = store.data.memories.get_mut(addr as usize).ok_or_else(|| unknown_memory(addr))?;
+                let delta = i32::try_from(pages.saturating_sub(memory.page_count)).ok();
+                if delta.and_then(|delta| memory.grow(delta)).is_none() {
+                    return Err(Error::Other(format!("replay failed to grow memory {addr} to {pages} pages")));
+                }
+            }
+            for write in call.memory_writes {
+                let memory =
+                    store.data.memories.get_mut(write.memory as usize).ok_or_else(|| unknown_memory(write.memory))?;
+                memory.store(write.offset, write.data.len(), &write.data)?;
+            }
+
+            call.result.map_err(|message| Error::host(RecordedError { message }))
+        }
+        _ => (func.func)(FuncContext { store, module_addr, func_addr }, args),
+    }
+}
+
+// host errors are replayed as host errors, so only their own message is recorded
+fn error_message(error: &Error) -> String {
+    match error {
+        Error::Host(error) => error.to_string(),
+        error => error.to_string(),
+    }
+}
+
+const MAGIC: &[u8; 4] = b"TWRR";
+const VERSION: u8 = 1;
+
+impl Recording {
+    /// Serialize the recording into a compact binary format
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend_from_slice(MAGIC);
+        buf.push(VERSION);
+        write_u64(&mut buf, self.calls.len() as u64);
+
+        for call in &self.calls {
+            write_u64(&mut buf, call.func as u64);
+            write_values(&mut buf, &call.args);
+            match &call.result {
+                Ok(values) => {
+                    buf.push(0);
+                    write_values(&mut buf, values);
+                }
+                Err(message) => {
+                    buf.push(1);
+                    write_bytes(&mut buf, message.as_bytes());
+                }
+            }
+
+            write_u64(&mut buf, call.memory_sizes.len() as u64);
+            for &(memory, pages) in &call.memory_sizes {
+                write_u64(&mut buf, memory as u64);
+                write_u64(&mut buf, pages as u64);
+            }
+
+            write_u64(&mut buf, call.memory_writes.len() as u64);
+            for write in &call.memory_writes {
+                write_u64(&mut buf, write.memory as u64);
+                write_u64(&mut buf, write.offset as u64);
+                write_bytes(&mut buf, &write.data);
+            }
+        }
+
+        buf
+    }
+
+    /// Deserialize a recording created with [`Recording::to_bytes`]
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader(bytes);
+        if reader.take(4)? != MAGIC || reader.take(1)?[0] != VERSION {
+            return Err(invalid_recording());
+        }
+
+        let mut calls = Vec::new();
+        for _ in 0..reader.u64()? {
+            let func = reader.u32()?;
+            let args = reader.values()?;
+            let result = match reader.take(1)?[0] {
+                0 => Ok(reader.values()?),
+                1 => Err(String::from_utf8(reader.bytes()?.to_vec()).map_err(|_| invalid_recording())?),
+                _ => return Err(invalid_recording()),
+            };
+
+            let mut memory_sizes = Vec::new();
+            for _ in 0..reader.u64()? {
+                memory_sizes.push((reader.u32()?, reader.u64()? as usize));
+            }
+
+            let mut memory_writes = Vec::new();
+            for _ in 0..reader.u64()? {
+                let memory = reader.u32()?;
+                let offset = reader.u64()? as usize;
+                memory_writes.push(MemoryWrite { memory, offset, data: reader.bytes()?.to_vec() });
+            }
+
+            calls.push(HostCall { func, args, result, memory_sizes, memory_writes });
+        }
+
+        Ok(Self { calls })
+    }
+}
+
+fn invalid_recording() -> Error {
+    Error::Other("invalid recording".to_string())
+}
+
+fn write_u64(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    write_u64(buf, bytes.len() as u64);
+    buf.extend_from_slice(bytes);
+}
+
+fn val_type_tag(ty: ValType) -> u8 {
+    match ty {
+        ValType::I32 => 0,
+        ValType::I64 => 1,
+        ValType::F32 => 2,
+        ValType::F64 => 3,
+        ValType::V128 => 4,
+        ValType::RefFunc => 5,
+        ValType::RefExtern => 6,
+    }
+}
+
+fn write_values(buf: &mut Vec<u8>, values: &[WasmValue]) {
+    write_u64(buf, values.len() as u64);
+    for value in values {
+        match *value {
+            WasmValue::I32(v) => buf.extend_from_slice(&[&[0], &v.to_le_bytes()[..]].concat()),
+            WasmValue::I64(v) => buf.extend_from_slice(&[&[1], &v.to_le_bytes()[..]].concat()),
+            WasmValue::F32(v) => buf.extend_from_slice(&[&[2], &v.to_bits().to_le_bytes()[..]].concat()),
+            WasmValue::F64(v) => buf.extend_from_slice(&[&[3], &v.to_bits().to_le_bytes()[..]].concat()),
+            WasmValue::V128(v) => buf.extend_from_slice(&[&[4], &v.to_le_bytes()[..]].concat()),
+            WasmValue::RefFunc(v) => buf.extend_from_slice(&[&[5], &v.to_le_bytes()[..]].concat()),
+            WasmValue::RefExtern(v) => buf.extend_from_slice(&[&[6], &v.to_le_bytes()[..]].concat()),
+            WasmValue::RefNull(ty) => buf.extend_from_slice(&[7, val_type_tag(ty)]),
+        }
+    }
+}
+
+// values are compared by their encoding, so NaNs with the same bit pattern are equal
+fn encode_values(values: &[WasmValue]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    write_values(&mut buf, values);
+    buf
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid_recording());
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        self.take(N)?.try_into().map_err(|_| invalid_recording())
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        u32::try_from(self.u64()?).map_err(|_| invalid_recording())
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8]> {
+        let len = usize::try_from(self.u64()?).map_err(|_| invalid_recording())?;
+        self.take(len)
+    }
+
+    fn values(&mut self) -> Result<Vec<WasmValue>> {
+        let mut values = Vec::new();
+        for _ in 0..self.u64()? {
+            values.push(match self.take(1)?[0] {
+                0 => WasmValue::I32(i32::from_le_bytes(self.array()?)),
+                1 => WasmValue::I64(i64::from_le_bytes(self.array()?)),
+                2 => WasmValue::F32(f32::from_bits(u32::from_le_bytes(self.array()?))),
+                3 => WasmValue::F64(f64::from_bits(u64::from_le_bytes(self.array()?))),
+                4 => WasmValue::V128(u128::from_le_bytes(self.array()?)),
+                5 => WasmValue::RefFunc(u32::from_le_bytes(self.array()?)),
+                6 => WasmValue::RefExtern(u32::from_le_bytes(self.array()?)),
+                7 => WasmValue::RefNull(match self.take(1)?[0] {
+                    0 => ValType::I32,
+                    1 => ValType::I64,
+                    2 => ValType::F32,
+                    3 => ValType::F64,
+                    4 => ValType::V128,
+                    5 => ValType::RefFunc,
+                    6 => ValType::RefExtern,
+                    _ => return Err(invalid_recording()),
+                }),
+                _ => return Err(invalid_recording()),
+            });
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use super::*;
+    use crate::{Extern, Imports, Module};
+
+    const WAT: &str = r#"(module
+        (import "env" "read" (func $read (param i32) (result i32)))
+        (import "env" "fail" (func $fail))
+        (memory (export "memory") 1)
+        (func (export "read") (param i32) (result i32) (call $read (local.get 0)))
+        (func (export "fail") (call $fail)))"#;
+
+    fn instantiate(store: &mut Store, real: bool) -> crate::ModuleInstance {
+        let module = Module::parse_bytes(&wat::parse_str(WAT).unwrap()).unwrap();
+        let mut imports = Imports::new();
+        let read = Extern::typed_func(move |mut ctx: FuncContext<'_>, ptr: i32| {
+            assert!(real, "host function called during replay");
+            ctx.exported_memory_mut("memory")?.store(ptr as usize, 3, b"abc")?;
+            Ok(3)
+        });
+        imports.define("env", "read", read).unwrap();
+        imports.define("env", "fail", Extern::typed_func(|_, ()| Err::<(), _>(Error::host(7u8)))).unwrap();
+        module.instantiate(store, Some(imports)).unwrap()
+    }
+
+    fn record() -> Recording {
+        let mut store = Store::default();
+        store.start_recording();
+        let instance = instantiate(&mut store, true);
+        assert_eq!(instance.exported_func::<i32, i32>(&store, "read").unwrap().call(&mut store, 16).unwrap(), 3);
+        let err = instance.exported_func::<(), ()>(&store, "fail").unwrap().call(&mut store, ()).unwrap_err();
+        assert_eq!(err.downcast_ref::<u8>(), Some(&7));
+        store.stop_recording().unwrap()
+    }
+
+    #[test]
+    fn test_replay() {
+        let recording = Recording::from_bytes(&record().to_bytes()).unwrap();
+        assert_eq!(recording, record());
+        assert_eq!(recording.calls[0].memory_writes, [MemoryWrite { memory: 0, offset: 16, data: b"abc".to_vec() }]);
+
+        let mut store = Store::default();
+        store.start_replay(recording);
+        let instance = instantiate(&mut store, false);
+        assert_eq!(instance.exported_func::<i32, i32>(&store, "read").unwrap().call(&mut store, 16).unwrap(), 3);
+        assert_eq!(instance.exported_memory(&mut store, "memory").unwrap().load(16, 3).unwrap(), b"abc");
+
+        // only the message of a host error is recorded
+        let err = instance.exported_func::<(), ()>(&store, "fail").unwrap().call(&mut store, ()).unwrap_err();
+        assert_eq!(err.downcast_ref::<u8>(), None);
+        assert_eq!(err.downcast_ref::<RecordedError>(), Some(&RecordedError { message: "7".to_string() }));
+        store.finish_replay().unwrap();
+    }
+
+    #[test]
+    fn test_divergence() {
+        let mut store = Store::default();
+        store.start_replay(record());
+        let instance = instantiate(&mut store, false);
+        let read = instance.exported_func::<i32, i32>(&store, "read").unwrap();
+        let err = read.call(&mut store, 32).unwrap_err();
+        assert!(matches!(err, Error::ReplayDivergence(Divergence::Arguments { index: 0, .. })));
+
+        let err = instance.exported_func::<(), ()>(&store, "fail").unwrap().call(&mut store, ()).unwrap_err();
+        assert!(matches!(err, Error::ReplayDivergence(Divergence::Function { index: 0, .. })));
+
+        assert_eq!(read.call(&mut store, 16).unwrap(), 3);
+        let err = store.finish_replay().unwrap_err();
+        assert!(matches!(err, Error::ReplayDivergence(Divergence::MissingCalls { index: 1, remaining: 1 })));
+        assert!(Recording::from_bytes(b"TWRR\x02").is_err());
+    }
+
+    #[test]
+    fn test_mismatched_recording() {
+        let replay = |recording: Recording| {
+            let mut store = Store::default();
+            store.start_replay(recording);
+            let instance = instantiate(&mut store, false);
+            instance.exported_func::<i32, i32>(&store, "read").unwrap().call(&mut store, 16).unwrap_err()
+        };
+
+        let mut recording = record();
+        recording.calls[0].memory_writes[0].memory = 5;
+        let err = replay(recording);
+        assert!(matches!(err, Error::ReplayDivergence(Divergence::Memory { index: 0, memory: 5 })));
+
+        let mut recording = record();
+        recording.calls[0].memory_sizes.push((5, 2));
+        let err = replay(recording);
+        assert!(matches!(err, Error::ReplayDivergence(Divergence::Memory { index: 0, memory: 5 })));
+
+        let mut recording = record();
+        recording.calls[0].memory_sizes.push((0, usize::MAX));
+        assert!(matches!(replay(recording), Error::Other(_)));
+    }
+}
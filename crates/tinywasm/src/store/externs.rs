@@ -0,0 +1,69 @@
+use alloc::{boxed::Box, vec::Vec};
+use core::any::Any;
+use tinywasm_types::ExternAddr;
+
+// an address holds the index of its slot in the low bits and the generation of the slot in the high bits
+const INDEX_BITS: u32 = 24;
+const INDEX_MASK: ExternAddr = (1 << INDEX_BITS) - 1;
+
+/// The host values referenced by [`crate::ExternRef`]s
+///
+/// Slots are reused after their value is taken. Every reuse increments the generation of the slot, so stale
+/// references don't see the new value; a slot whose generation is exhausted is not reused again.
+#[derive(Default)]
+pub(crate) struct ExternSlots {
+    slots: Vec<ExternSlot>,
+    free: Vec<usize>,
+}
+
+#[derive(Default)]
+struct ExternSlot {
+    generation: u8,
+    value: Option<Box<dyn Any>>,
+}
+
+impl ExternSlot {
+    fn matches(&self, addr: ExternAddr) -> bool {
+        ExternAddr::from(self.generation) == addr >> INDEX_BITS
+    }
+}
+
+impl ExternSlots {
+    pub(crate) fn insert(&mut self, value: Box<dyn Any>) -> ExternAddr {
+        let index = self.free.pop().unwrap_or_else(|| {
+            assert!(self.slots.len() <= INDEX_MASK as usize, "too many extern references in the store");
+            self.slots.push(ExternSlot::default());
+            self.slots.len() - 1
+        });
+
+        let slot = &mut self.slots[index];
+        slot.value = Some(value);
+        index as ExternAddr | ExternAddr::from(slot.generation) << INDEX_BITS
+    }
+
+    pub(crate) fn get(&self, addr: ExternAddr) -> Option<&dyn Any> {
+        let slot = self.slots.get((addr & INDEX_MASK) as usize)?;
+        slot.matches(addr).then_some(slot.value.as_deref()).flatten()
+    }
+
+    pub(crate) fn get_mut(&mut self, addr: ExternAddr) -> Option<&mut dyn Any> {
+        let slot = self.slots.get_mut((addr & INDEX_MASK) as usize)?;
+        slot.matches(addr).then_some(slot.value.as_deref_mut()).flatten()
+    }
+
+    pub(crate) fn take(&mut self, addr: ExternAddr) -> Option<Box<dyn Any>> {
+        let index = (addr & INDEX_MASK) as usize;
+        let slot = self.slots.get_mut(index).filter(|slot| slot.matches(addr))?;
+        let value = slot.value.take()?;
+        if let Some(generation) = slot.generation.checked_add(1) {
+            slot.generation = generation;
+            self.free.push(index);
+        }
+        Some(value)
+    }
+
+    // the number of values in the store
+    pub(crate) fn len(&self) -> usize {
+        self.slots.iter().filter(|slot| slot.value.is_some()).count()
+    }
+}
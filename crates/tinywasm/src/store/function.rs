@@ -8,11 +8,20 @@ use tinywasm_types::*;
 /// See <https://webassembly.github.io/spec/core/exec/runtime.html#function-instances>
 pub(crate) struct FunctionInstance {
     pub(crate) func: Function,
-    pub(crate) owner: ModuleInstanceAddr, // index into store.module_instances, none for host functions
+    pub(crate) owner: ModuleInstanceAddr, // index into store.module_instances, meaningless for host functions
 }
 
 impl FunctionInstance {
     pub(crate) fn new_wasm(func: WasmFunction, owner: ModuleInstanceAddr) -> Self {
         Self { func: Function::Wasm(Rc::new(func)), owner }
     }
+
+    // a handle without a module instance for host functions, see `FuncContext::module`
+    pub(crate) fn handle(&self, addr: FuncAddr) -> crate::FuncHandle {
+        let module_addr = match self.func {
+            Function::Wasm(_) => Some(self.owner),
+            Function::Host(_) => None,
+        };
+        crate::FuncHandle { module_addr, addr, ty: self.func.ty().clone(), name: None }
+    }
 }
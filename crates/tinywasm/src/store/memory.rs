@@ -1,8 +1,10 @@
+use alloc::boxed::Box;
 use alloc::vec;
 use alloc::vec::Vec;
 use tinywasm_types::{MemoryType, ModuleInstanceAddr};
 
-use crate::{cold, log, Error, Result};
+use super::watch::MemoryHooks;
+use crate::{cold, log, AccessKind, Error, MemoryAccess, Result};
 
 const PAGE_SIZE: usize = 65536;
 const MAX_PAGES: usize = 65536;
@@ -17,6 +19,7 @@ pub(crate) struct MemoryInstance {
     pub(crate) data: Vec<u8>,
     pub(crate) page_count: usize,
     pub(crate) _owner: ModuleInstanceAddr, // index into store.module_instances
+    pub(crate) hooks: Option<Box<MemoryHooks>>,
 }
 
 impl MemoryInstance {
@@ -29,6 +32,7 @@ impl MemoryInstance {
             data: vec![0; PAGE_SIZE * kind.page_count_initial as usize],
             page_count: kind.page_count_initial as usize,
             _owner: owner,
+            hooks: None,
         }
     }
 
@@ -43,6 +47,26 @@ impl MemoryInstance {
         Error::Trap(crate::Trap::MemoryOutOfBounds { offset: addr, len, max: self.data.len() })
     }
 
+    // report an in-bounds access to the watchpoints and access log, if any
+    #[inline(always)]
+    fn on_access(&self, kind: AccessKind, offset: usize, len: usize) -> Result<()> {
+        match &self.hooks {
+            None => Ok(()),
+            Some(hooks) => hooks.on_access(MemoryAccess { kind, offset, len }),
+        }
+    }
+
+    pub(crate) fn hooks_mut(&mut self) -> &mut MemoryHooks {
+        self.hooks.get_or_insert_with(Default::default)
+    }
+
+    // drop the hooks once they are no longer used, keeping memory accesses on the fast path
+    pub(crate) fn prune_hooks(&mut self) {
+        if self.hooks.as_ref().is_some_and(|h| h.is_empty()) {
+            self.hooks = None;
+        }
+    }
+
     pub(crate) fn store(&mut self, addr: usize, len: usize, data: &[u8]) -> Result<()> {
         let Some(end) = addr.checked_add(len) else {
             cold();
@@ -53,6 +77,7 @@ impl MemoryInstance {
             cold();
             return Err(self.trap_oob(addr, data.len()));
         }
+        self.on_access(AccessKind::Write, addr, len)?;
         self.data[addr..end].copy_from_slice(data);
         Ok(())
     }
@@ -72,6 +97,7 @@ impl MemoryInstance {
             return Err(self.trap_oob(addr, len));
         }
 
+        self.on_access(AccessKind::Read, addr, len)?;
         Ok(&self.data[addr..end])
     }
 
@@ -84,6 +110,8 @@ impl MemoryInstance {
             return Err(self.trap_oob(addr, SIZE));
         }
 
+        self.on_access(AccessKind::Read, addr, SIZE)?;
+
         Ok(T::from_le_bytes(match self.data[addr..end].try_into() {
             Ok(bytes) => bytes,
             Err(_) => return Err(self.trap_oob(addr, SIZE)),
@@ -95,6 +123,7 @@ impl MemoryInstance {
         if end > self.data.len() {
             return Err(self.trap_oob(addr, len));
         }
+        self.on_access(AccessKind::Write, addr, len)?;
         self.data[addr..end].fill_with(|| val);
         Ok(())
     }
@@ -105,6 +134,7 @@ impl MemoryInstance {
             return Err(self.trap_oob(dst, src.len()));
         }
 
+        self.on_access(AccessKind::Write, dst, src.len())?;
         self.data[dst..end].copy_from_slice(src);
         Ok(())
     }
@@ -122,6 +152,9 @@ impl MemoryInstance {
             return Err(self.trap_oob(dst, len));
         }
 
+        self.on_access(AccessKind::Read, src, len)?;
+        self.on_access(AccessKind::Write, dst, len)?;
+
         // Perform the copy
         self.data.copy_within(src..src_end, dst);
         Ok(())
@@ -238,6 +271,38 @@ mod memory_instance_tests {
         assert!(memory.copy_within(memory.data.len(), 0, 10).is_err());
     }
 
+    #[test]
+    fn test_memory_watchpoint() {
+        use crate::{WatchAction, WatchMode};
+
+        let mut memory = create_test_memory();
+        memory.hooks_mut().watch(8..12, WatchMode::Write, Box::new(|_| WatchAction::Break));
+
+        assert!(memory.store(0, 8, &[1; 8]).is_ok());
+        assert!(memory.load(8, 4).is_ok());
+        assert!(matches!(memory.store(11, 2, &[1; 2]), Err(Error::Watchpoint(a)) if a.offset == 11 && a.len == 2));
+        assert_eq!(&memory.data[11..13], &[0; 2]);
+    }
+
+    #[test]
+    fn test_memory_access_log() {
+        let mut memory = create_test_memory();
+        memory.hooks_mut().set_log_capacity(Some(2));
+
+        memory.fill(0, 4, 1).unwrap();
+        memory.load(4, 4).unwrap();
+        memory.copy_within(8, 0, 4).unwrap();
+
+        let log = memory.hooks.as_ref().unwrap().log();
+        assert_eq!(
+            log,
+            [
+                MemoryAccess { kind: AccessKind::Read, offset: 0, len: 4 },
+                MemoryAccess { kind: AccessKind::Write, offset: 8, len: 4 }
+            ]
+        );
+    }
+
     #[test]
     fn test_memory_grow() {
         let mut memory = create_test_memory();
@@ -4,16 +4,19 @@ use core::sync::atomic::{AtomicUsize, Ordering};
 use tinywasm_types::*;
 
 use crate::interpreter::{self, InterpreterRuntime, TinyWasmValue};
-use crate::{cold, Error, Function, ModuleInstance, Result, Trap};
+use crate::{cold, Error, FuncHandle, Function, ModuleInstance, Result, Trap};
 
 mod data;
 mod element;
+mod externs;
 mod function;
 mod global;
 mod memory;
 mod table;
+mod watch;
 
-pub(crate) use {data::*, element::*, function::*, global::*, memory::*, table::*};
+pub use watch::{AccessKind, MemoryAccess, WatchAction, WatchMode, WatchpointId};
+pub(crate) use {data::*, element::*, externs::*, function::*, global::*, memory::*, table::*};
 
 // global store id counter
 static STORE_ID: AtomicUsize = AtomicUsize::new(0);
@@ -33,6 +36,18 @@ pub struct Store {
 
     pub(crate) data: StoreData,
     pub(crate) runtime: Runtime,
+
+    pub(crate) replay: crate::replay::ReplayState,
+    pub(crate) deterministic: Option<crate::deterministic::DeterministicEnv>,
+
+    pub(crate) instructions_executed: u64,
+    pub(crate) peak_stack_depth: usize,
+
+    #[cfg(feature = "std")]
+    pub(crate) catch_host_panics: bool,
+
+    #[cfg(feature = "coverage")]
+    pub(crate) coverage: Option<crate::coverage::CoverageData>,
 }
 
 impl Debug for Store {
@@ -66,6 +81,142 @@ impl Store {
         self.module_instances[addr as usize].clone()
     }
 
+    /// Add a host function to the store
+    ///
+    /// The returned handle can be called directly, or turned into a [`crate::FuncRef`] using
+    /// [`FuncHandle::func_ref`] to pass it to WebAssembly as a callback or store it in a table.
+    ///
+    /// ```rust
+    /// # fn main() -> tinywasm::Result<()> {
+    /// use tinywasm::{Extern, FuncContext, FuncRef, Module, Store};
+    ///
+    /// let wasm = wat::parse_str(r#"
+    ///     (module
+    ///         (type $cb (func (param i32) (result i32)))
+    ///         (table (export "callbacks") 1 funcref)
+    ///         (func (export "apply") (param funcref i32) (result i32)
+    ///             (table.set (i32.const 0) (local.get 0))
+    ///             (call_indirect (type $cb) (local.get 1) (i32.const 0))))
+    /// "#).unwrap();
+    ///
+    /// let mut store = Store::default();
+    /// let instance = Module::parse_bytes(&wasm)?.instantiate(&mut store, None)?;
+    ///
+    /// let double = store.add_host_func(Extern::typed_func(|_: FuncContext<'_>, x: i32| Ok(x * 2)))?;
+    /// let apply = instance.exported_func::<(FuncRef, i32), i32>(&store, "apply")?;
+    /// assert_eq!(apply.call(&mut store, (double.func_ref(), 21))?, 42);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_host_func(&mut self, func: crate::Extern) -> Result<FuncHandle> {
+        let crate::Extern::Function(func) = func else {
+            return Err(Error::Other("Expected a function".to_string()));
+        };
+
+        // host functions don't belong to a module, calls use the caller's module instance as context
+        let addr = self.add_func(func, 0)?;
+        Ok(self.get_func(addr).handle(addr))
+    }
+
+    /// Add a host item to the store
+    ///
+    /// The item doesn't belong to a module instance. It can be accessed using its address, e.g. with
+    /// [`Store::memory_mut`], and imported by any number of modules using [`crate::Imports::define_extern_val`].
+    ///
+    /// ```rust
+    /// # fn main() -> tinywasm::Result<()> {
+    /// use tinywasm::types::{ExternVal, MemoryType};
+    /// use tinywasm::{Extern, Imports, Module, Store};
+    ///
+    /// let wasm = wat::parse_str(r#"(module (import "env" "memory" (memory 1)) (data (i32.const 0) "hi"))"#).unwrap();
+    /// let mut store = Store::default();
+    /// let memory = store.add_extern(Extern::memory(MemoryType::new_32(1, None)))?;
+    ///
+    /// let mut imports = Imports::new();
+    /// imports.define_extern_val(&store, "env", "memory", memory.clone())?;
+    /// Module::parse_bytes(&wasm)?.instantiate(&mut store, Some(imports))?;
+    ///
+    /// let ExternVal::Memory(addr) = memory else { unreachable!() };
+    /// assert_eq!(store.memory(addr)?.load(0, 2)?, b"hi");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_extern(&mut self, value: crate::Extern) -> Result<ExternVal> {
+        crate::Linker::add_to_store(self, value, 0)
+    }
+
+    /// Get the type of an item in the store
+    ///
+    /// Tables and memories report their current size as the minimum.
+    pub fn extern_type(&self, item: &ExternVal) -> Option<ExternType> {
+        Some(match *item {
+            ExternVal::Func(addr) => ExternType::Func(self.data.funcs.get(addr as usize)?.func.ty().clone()),
+            ExternVal::Table(addr) => {
+                let table = self.data.tables.get(addr as usize)?;
+                ExternType::Table(TableType { size_initial: table.size() as u32, ..table.kind.clone() })
+            }
+            ExternVal::Memory(addr) => {
+                let mem = self.data.memories.get(addr as usize)?;
+                ExternType::Memory(MemoryType { page_count_initial: mem.page_count as u64, ..mem.kind })
+            }
+            ExternVal::Global(addr) => ExternType::Global(self.data.globals.get(addr as usize)?.ty),
+        })
+    }
+
+    pub(crate) fn contains_extern(&self, item: &ExternVal) -> bool {
+        match *item {
+            ExternVal::Func(addr) => (addr as usize) < self.data.funcs.len(),
+            ExternVal::Table(addr) => (addr as usize) < self.data.tables.len(),
+            ExternVal::Memory(addr) => (addr as usize) < self.data.memories.len(),
+            ExternVal::Global(addr) => (addr as usize) < self.data.globals.len(),
+        }
+    }
+
+    /// Get a function by its address in the store
+    pub fn func(&self, addr: FuncAddr) -> Result<FuncHandle> {
+        let func = self.data.funcs.get(addr as usize).ok_or_else(|| Self::not_found_error("function"))?;
+        Ok(func.handle(addr))
+    }
+
+    /// Get a memory by its address in the store
+    pub fn memory(&self, addr: MemAddr) -> Result<crate::MemoryRef<'_>> {
+        let mem = self.data.memories.get(addr as usize).ok_or_else(|| Self::not_found_error("memory"))?;
+        Ok(crate::MemoryRef(mem))
+    }
+
+    /// Get a memory by its address in the store (mutable)
+    pub fn memory_mut(&mut self, addr: MemAddr) -> Result<crate::MemoryRefMut<'_>> {
+        let mem = self.data.memories.get_mut(addr as usize).ok_or_else(|| Self::not_found_error("memory"))?;
+        Ok(crate::MemoryRefMut(mem))
+    }
+
+    /// Get a table by its address in the store
+    pub fn table(&self, addr: TableAddr) -> Result<crate::TableRef<'_>> {
+        let table = self.data.tables.get(addr as usize).ok_or_else(|| Self::not_found_error("table"))?;
+        Ok(crate::TableRef(table))
+    }
+
+    /// Get a table by its address in the store (mutable)
+    pub fn table_mut(&mut self, addr: TableAddr) -> Result<crate::TableRefMut<'_>> {
+        let func_count = self.data.funcs.len();
+        let table = self.data.tables.get_mut(addr as usize).ok_or_else(|| Self::not_found_error("table"))?;
+        Ok(crate::TableRefMut { table, func_count, store_id: self.id })
+    }
+
+    /// Get a global by its address in the store
+    pub fn global(&self, addr: GlobalAddr) -> Result<crate::GlobalRef<'_>> {
+        let global = self.data.globals.get(addr as usize).ok_or_else(|| Self::not_found_error("global"))?;
+        Ok(crate::GlobalRef { global, func_count: self.data.funcs.len(), store_id: self.id })
+    }
+
+    /// Catch panics in host functions and turn them into [`crate::Trap::HostPanic`] traps
+    ///
+    /// Disabled by default. The panic hook still runs, so the panic message is printed as usual.
+    #[cfg(feature = "std")]
+    pub fn catch_host_panics(&mut self, enabled: bool) {
+        self.catch_host_panics = enabled;
+    }
+
     /// Create a new store with the given runtime
     pub(crate) fn runtime(&self) -> interpreter::InterpreterRuntime {
         match self.runtime {
@@ -83,7 +234,20 @@ impl PartialEq for Store {
 impl Default for Store {
     fn default() -> Self {
         let id = STORE_ID.fetch_add(1, Ordering::Relaxed);
-        Self { id, module_instances: Vec::new(), data: StoreData::default(), runtime: Runtime::Default }
+        Self {
+            id,
+            module_instances: Vec::new(),
+            data: StoreData::default(),
+            runtime: Runtime::Default,
+            replay: Default::default(),
+            deterministic: None,
+            instructions_executed: 0,
+            peak_stack_depth: 0,
+            #[cfg(feature = "std")]
+            catch_host_panics: false,
+            #[cfg(feature = "coverage")]
+            coverage: None,
+        }
     }
 }
 
@@ -99,6 +263,46 @@ pub(crate) struct StoreData {
     pub(crate) globals: Vec<GlobalInstance>,
     pub(crate) elements: Vec<ElementInstance>,
     pub(crate) datas: Vec<DataInstance>,
+    pub(crate) externs: ExternSlots,
+}
+
+/// Statistics about the objects allocated in a [`Store`] and the code it executed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    /// Number of module instances
+    pub module_instances: usize,
+    /// Number of functions, including host functions
+    pub functions: usize,
+    /// Number of tables
+    pub tables: usize,
+    /// Number of linear memories
+    pub memories: usize,
+    /// Number of globals
+    pub globals: usize,
+    /// Number of element segments
+    pub element_segments: usize,
+    /// Number of data segments
+    pub data_segments: usize,
+    /// Number of host values referenced by [`crate::ExternRef`]s
+    pub extern_refs: usize,
+
+    /// Bytes used by linear memories
+    pub memory_bytes: usize,
+    /// Bytes used by table elements
+    pub table_bytes: usize,
+    /// Bytes used by element segments that were not dropped
+    pub element_segment_bytes: usize,
+    /// Bytes used by data segments that were not dropped
+    pub data_segment_bytes: usize,
+
+    /// The highest number of values on the value stack, sampled at every call and block entry
+    ///
+    /// Only collected with the `stats` feature, otherwise always `0`.
+    pub peak_stack_depth: usize,
+    /// Number of instructions executed
+    ///
+    /// Only collected with the `stats` feature, otherwise always `0`.
+    pub instructions_executed: u64,
 }
 
 impl Store {
@@ -107,6 +311,40 @@ impl Store {
         self.id
     }
 
+    /// Get statistics about the objects allocated in this store and the code it executed
+    pub fn stats(&self) -> StoreStats {
+        let data = &self.data;
+        let element_size = core::mem::size_of::<TableElement>();
+
+        StoreStats {
+            module_instances: self.module_instances.len(),
+            functions: data.funcs.len(),
+            tables: data.tables.len(),
+            memories: data.memories.len(),
+            globals: data.globals.len(),
+            element_segments: data.elements.len(),
+            data_segments: data.datas.len(),
+            extern_refs: data.externs.len(),
+            memory_bytes: data.memories.iter().map(|m| m.data.len()).sum(),
+            table_bytes: data.tables.iter().map(|t| t.elements.len() * element_size).sum(),
+            element_segment_bytes: data
+                .elements
+                .iter()
+                .filter_map(|e| e.items.as_ref())
+                .map(|i| i.len() * element_size)
+                .sum(),
+            data_segment_bytes: data.datas.iter().filter_map(|d| d.data.as_ref()).map(Vec::len).sum(),
+            peak_stack_depth: self.peak_stack_depth,
+            instructions_executed: self.instructions_executed,
+        }
+    }
+
+    /// Reset the peak stack depth and the number of executed instructions reported by [`Store::stats`]
+    pub fn reset_stats(&mut self) {
+        self.peak_stack_depth = 0;
+        self.instructions_executed = 0;
+    }
+
     pub(crate) fn next_module_instance_idx(&self) -> ModuleInstanceAddr {
         self.module_instances.len() as ModuleInstanceAddr
     }
@@ -284,6 +522,7 @@ impl Store {
                 })?)
             }
             ElementItem::Expr(ConstInstruction::RefNull(_ty)) => None,
+            ElementItem::Expr(ConstInstruction::RefExtern(addr)) => Some(*addr),
             ElementItem::Expr(ConstInstruction::GlobalGet(addr)) => {
                 let addr = globals.get(*addr as usize).copied().ok_or_else(|| {
                     Error::Other(format!("global {addr} not found. This should have been caught by the validator"))
@@ -451,6 +690,7 @@ impl Store {
                 global.value.get()
             }
             RefNull(t) => t.default_value().into(),
+            RefExtern(addr) => TinyWasmValue::ValueRef(Some(*addr)),
             RefFunc(idx) => TinyWasmValue::ValueRef(Some(*module_func_addrs.get(*idx as usize).ok_or_else(|| {
                 Error::Other(format!("function {idx} not found. This should have been caught by the validator"))
             })?)),
@@ -471,3 +711,68 @@ fn get_pair_mut<T>(slice: &mut [T], i: usize, j: usize) -> Option<(&mut T, &mut
     let pair = if i < j { (&mut x[0], &mut y[0]) } else { (&mut y[0], &mut x[0]) };
     Some(pair)
 }
+
+#[cfg(all(test, feature = "parser"))]
+mod tests {
+    use crate::{Extern, FuncContext, FuncRef, Module, Store};
+
+    #[test]
+    fn test_stats() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (memory 1)
+                (table 2 funcref)
+                (global (mut i32) (i32.const 0))
+                (data "abc")
+                (func (export "run") (result i32)
+                    (i32.const 1) (block (result i32) (i32.const 2)) (i32.add)))"#,
+        )
+        .unwrap();
+
+        let mut store = Store::default();
+        let instance = Module::parse_bytes(&wasm).unwrap().instantiate(&mut store, None).unwrap();
+        instance.exported_func::<(), i32>(&store, "run").unwrap().call(&mut store, ()).unwrap();
+
+        let stats = store.stats();
+        assert_eq!((stats.module_instances, stats.functions, stats.tables, stats.memories), (1, 1, 1, 1));
+        assert_eq!((stats.globals, stats.data_segments, stats.data_segment_bytes), (1, 1, 3));
+        assert_eq!(stats.memory_bytes, 65536);
+
+        #[cfg(feature = "stats")]
+        {
+            assert!(stats.instructions_executed > 0);
+            assert!(stats.peak_stack_depth > 0);
+        }
+
+        store.reset_stats();
+        let stats = store.stats();
+        assert_eq!((stats.instructions_executed, stats.peak_stack_depth), (0, 0));
+        assert_eq!(stats.memory_bytes, 65536);
+    }
+
+    #[test]
+    fn test_host_func_module() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (type $cb (func (result i32)))
+                (table 1 funcref)
+                (func (export "apply") (param funcref) (result i32)
+                    (table.set (i32.const 0) (local.get 0))
+                    (call_indirect (type $cb) (i32.const 0))))"#,
+        )
+        .unwrap();
+
+        let mut store = Store::default();
+        let func = store
+            .add_host_func(Extern::typed_func(|ctx: FuncContext<'_>, ()| Ok(ctx.module()?.id() as i32 + 1)))
+            .unwrap();
+
+        // there's no calling module instance, even though none exist yet
+        assert!(func.call(&mut store, &[]).is_err());
+
+        let instance = Module::parse_bytes(&wasm).unwrap().instantiate(&mut store, None).unwrap();
+        let apply = instance.exported_func::<FuncRef, i32>(&store, "apply").unwrap();
+        assert_eq!(apply.call(&mut store, func.func_ref()).unwrap(), instance.id() as i32 + 1);
+        assert!(func.call(&mut store, &[]).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::fmt::{Debug, Display};
+use core::ops::Range;
+
+use crate::{Error, Result};
+
+/// The kind of a memory access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    /// Memory was read
+    Read,
+    /// Memory was written
+    Write,
+}
+
+/// A single access to a linear memory
+///
+/// Accesses are reported before they are performed, so a watchpoint callback sees the memory
+/// as it was before a write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// The kind of the access
+    pub kind: AccessKind,
+    /// The offset of the first accessed byte
+    pub offset: usize,
+    /// The number of accessed bytes
+    pub len: usize,
+}
+
+impl MemoryAccess {
+    /// Check if the access touches any byte in the given range
+    pub fn overlaps(&self, range: &Range<usize>) -> bool {
+        self.offset < range.end && range.start < self.offset.saturating_add(self.len)
+    }
+}
+
+impl Display for MemoryAccess {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let kind = match self.kind {
+            AccessKind::Read => "read",
+            AccessKind::Write => "write",
+        };
+        write!(f, "{kind} of {} bytes at offset {}", self.len, self.offset)
+    }
+}
+
+/// The kinds of accesses a watchpoint fires on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchMode {
+    /// Fire on reads
+    Read,
+    /// Fire on writes
+    Write,
+    /// Fire on reads and writes
+    ReadWrite,
+}
+
+impl WatchMode {
+    fn matches(self, kind: AccessKind) -> bool {
+        matches!((self, kind), (Self::ReadWrite, _) | (Self::Read, AccessKind::Read) | (Self::Write, AccessKind::Write))
+    }
+}
+
+/// What to do after a watchpoint fired
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchAction {
+    /// Perform the access and continue execution
+    Continue,
+    /// Stop execution before the access is performed
+    ///
+    /// The current call returns [`Error::Watchpoint`], leaving the memory untouched
+    /// so it can be inspected.
+    Break,
+}
+
+/// A handle to a watchpoint, used to remove it again
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WatchpointId(u32);
+
+type WatchCallback = Box<dyn Fn(&MemoryAccess) -> WatchAction>;
+
+struct Watchpoint {
+    id: WatchpointId,
+    range: Range<usize>,
+    mode: WatchMode,
+    callback: WatchCallback,
+}
+
+struct AccessLog {
+    entries: VecDeque<MemoryAccess>,
+    capacity: usize,
+}
+
+/// Watchpoints and access logging of a memory instance
+#[derive(Default)]
+pub(crate) struct MemoryHooks {
+    watchpoints: Vec<Watchpoint>,
+    next_id: u32,
+    // accesses are also logged by `&self` loads
+    log: Option<RefCell<AccessLog>>,
+    // ranges written while a host call is recorded
+    journal: Option<RefCell<Vec<Range<usize>>>>,
+}
+
+impl Debug for MemoryHooks {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("MemoryHooks")
+            .field("watchpoints", &self.watchpoints.len())
+            .field("log", &self.log.as_ref().map(|l| l.borrow().capacity))
+            .finish()
+    }
+}
+
+impl MemoryHooks {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.watchpoints.is_empty() && self.log.is_none() && self.journal.is_none()
+    }
+
+    pub(crate) fn on_access(&self, access: MemoryAccess) -> Result<()> {
+        if let Some(log) = &self.log {
+            let mut log = log.borrow_mut();
+            if log.entries.len() == log.capacity {
+                log.entries.pop_front();
+            }
+            log.entries.push_back(access);
+        }
+
+        if let (Some(journal), AccessKind::Write) = (&self.journal, access.kind) {
+            journal.borrow_mut().push(access.offset..access.offset + access.len);
+        }
+
+        for watchpoint in &self.watchpoints {
+            if watchpoint.mode.matches(access.kind)
+                && access.overlaps(&watchpoint.range)
+                && (watchpoint.callback)(&access) == WatchAction::Break
+            {
+                return Err(Error::Watchpoint(access));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn watch(&mut self, range: Range<usize>, mode: WatchMode, callback: WatchCallback) -> WatchpointId {
+        let id = WatchpointId(self.next_id);
+        self.next_id += 1;
+        self.watchpoints.push(Watchpoint { id, range, mode, callback });
+        id
+    }
+
+    pub(crate) fn unwatch(&mut self, id: WatchpointId) -> bool {
+        let len = self.watchpoints.len();
+        self.watchpoints.retain(|w| w.id != id);
+        self.watchpoints.len() != len
+    }
+
+    pub(crate) fn set_log_capacity(&mut self, capacity: Option<usize>) {
+        self.log = capacity
+            .filter(|&c| c > 0)
+            .map(|capacity| RefCell::new(AccessLog { entries: VecDeque::with_capacity(capacity), capacity }));
+    }
+
+    pub(crate) fn log(&self) -> Vec<MemoryAccess> {
+        self.log.as_ref().map(|log| log.borrow().entries.iter().copied().collect()).unwrap_or_default()
+    }
+
+    pub(crate) fn start_journal(&mut self) {
+        self.journal = Some(RefCell::default());
+    }
+
+    pub(crate) fn take_journal(&mut self) -> Vec<Range<usize>> {
+        self.journal.take().map(RefCell::into_inner).unwrap_or_default()
+    }
+
+    pub(crate) fn clear_log(&self) {
+        if let Some(log) = &self.log {
+            log.borrow_mut().entries.clear();
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use alloc::{boxed::Box, string::String};
+
+use crate::{Export, ExternalKind, TinyWasmModule};
+
+/// A `TinyWasm` WebAssembly Component
+///
+/// This is the internal representation of a WebAssembly component in `TinyWasm`.
+/// Like [`TinyWasmModule`]s, components are validated before being created.
+///
+/// Components are described by their core modules and a list of [`ComponentInitializer`]s which are
+/// evaluated in order when the component is instantiated. Each initializer defines the next item of one of
+/// the index spaces of the component, exactly like the sections of the original binary.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TinyWasmComponent {
+    /// The core modules of the component
+    pub modules: Box<[TinyWasmModule]>,
+
+    /// The resource types used by the component
+    pub resources: Box<[ComponentResource]>,
+
+    /// The items of the component in the order they are defined
+    pub initializers: Box<[ComponentInitializer]>,
+}
+
+/// A resource type of a component
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ComponentResource {
+    /// The name the resource is imported or exported as, e.g. `counter` or `wasi:io/streams@0.2.0#input-stream`
+    ///
+    /// Empty if the resource is neither imported nor exported.
+    pub name: String,
+
+    /// Whether the resource is defined by the component instead of being imported
+    pub defined: bool,
+}
+
+/// An item of a component, see [`TinyWasmComponent::initializers`]
+///
+/// Core items refer to the core index spaces (`core_funcs`, `core_tables`, `core_memories`, `core_globals` and
+/// `core_instances`), component items to the `funcs` and `instances` index spaces.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComponentInitializer {
+    /// Instantiate a core module with the given core instances as imports, defining a core instance
+    CoreInstantiate {
+        /// The index of the module in [`TinyWasmComponent::modules`]
+        module: u32,
+        /// The core instances to use for each imported module name
+        args: Box<[(String, u32)]>,
+    },
+
+    /// Define a core instance from a list of core items
+    CoreInstanceFromExports(Box<[Export]>),
+
+    /// Define a core item from an export of a core instance
+    CoreAlias {
+        /// The core instance
+        instance: u32,
+        /// The kind of the item, which also decides its index space
+        kind: ExternalKind,
+        /// The name of the export
+        name: String,
+    },
+
+    /// Define a core function which calls a component function, see [`ComponentInitializer::Lift`]
+    Lower {
+        /// The component function
+        func: u32,
+        /// The type of the component function
+        ty: ComponentFuncType,
+        /// How values are passed to the core function
+        options: CanonicalOptions,
+    },
+
+    /// Define a core function creating a handle from the representation of a resource
+    ResourceNew(u32),
+    /// Define a core function dropping a handle to a resource
+    ResourceDrop(u32),
+    /// Define a core function getting the representation of a resource from a handle
+    ResourceRep(u32),
+
+    /// Set the destructor of a resource defined by the component
+    DefineResource {
+        /// The resource, an index into [`TinyWasmComponent::resources`]
+        resource: u32,
+        /// The core function called with the representation when an owned handle is dropped
+        dtor: Option<u32>,
+    },
+
+    /// Define a component function which calls a core function using the canonical ABI
+    Lift {
+        /// The core function
+        core_func: u32,
+        /// The type of the component function
+        ty: ComponentFuncType,
+        /// How values are passed to the core function
+        options: CanonicalOptions,
+    },
+
+    /// Import a function, defining a component function
+    ImportFunc {
+        /// The name of the import
+        name: String,
+        /// The type of the function
+        ty: ComponentFuncType,
+    },
+
+    /// Import an instance of functions, defining a component instance
+    ImportInstance {
+        /// The name of the import, e.g. `wasi:cli/stdout@0.2.0`
+        name: String,
+        /// The functions of the instance
+        funcs: Box<[(String, ComponentFuncType)]>,
+    },
+
+    /// Define a component item from an export of a component instance
+    Alias {
+        /// The component instance
+        instance: u32,
+        /// The kind of the item, which also decides its index space
+        kind: ComponentExternalKind,
+        /// The name of the export
+        name: String,
+    },
+
+    /// Define a component instance from a list of component items
+    InstanceFromExports(Box<[ComponentExport]>),
+
+    /// Export a component item, which also defines a new item in its index space
+    Export(ComponentExport),
+}
+
+/// The kind of a component item that exists at runtime
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComponentExternalKind {
+    /// A component function
+    Func,
+    /// A component instance
+    Instance,
+}
+
+/// An export of a component or a component instance
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentExport {
+    /// The name of the export
+    pub name: String,
+    /// The kind of the exported item
+    pub kind: ComponentExternalKind,
+    /// The index of the item in its index space
+    pub index: u32,
+}
+
+/// The options of a lifted or lowered function
+///
+/// See <https://github.com/WebAssembly/component-model/blob/main/design/mvp/Explainer.md#canonical-abi>
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CanonicalOptions {
+    /// The encoding of strings in memory
+    pub string_encoding: StringEncoding,
+    /// The core memory that strings and lists are stored in
+    pub memory: Option<u32>,
+    /// The core function used to allocate memory, `(func (param i32 i32 i32 i32) (result i32))`
+    pub realloc: Option<u32>,
+    /// The core function called with the results after they have been read
+    pub post_return: Option<u32>,
+}
+
+/// The encoding of strings in memory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StringEncoding {
+    /// UTF-8
+    #[default]
+    Utf8,
+    /// UTF-16, little endian
+    Utf16,
+    /// Latin-1 if possible, UTF-16 otherwise
+    CompactUtf16,
+}
+
+/// The type of a component function
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ComponentFuncType {
+    /// The named parameters of the function
+    pub params: Box<[(String, ComponentValType)]>,
+    /// The results of the function
+    pub results: Box<[ComponentValType]>,
+}
+
+/// The type of a component value
+///
+/// See <https://github.com/WebAssembly/component-model/blob/main/design/mvp/Explainer.md#type-definitions>
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComponentValType {
+    /// A boolean
+    Bool,
+    /// A signed 8-bit integer
+    S8,
+    /// An unsigned 8-bit integer
+    U8,
+    /// A signed 16-bit integer
+    S16,
+    /// An unsigned 16-bit integer
+    U16,
+    /// A signed 32-bit integer
+    S32,
+    /// An unsigned 32-bit integer
+    U32,
+    /// A signed 64-bit integer
+    S64,
+    /// An unsigned 64-bit integer
+    U64,
+    /// A 32-bit float
+    F32,
+    /// A 64-bit float
+    F64,
+    /// A Unicode scalar value
+    Char,
+    /// A Unicode string
+    String,
+    /// A list of values of the same type
+    List(Box<ComponentValType>),
+    /// Named fields
+    Record(Box<[(String, ComponentValType)]>),
+    /// Unnamed fields
+    Tuple(Box<[ComponentValType]>),
+    /// One of several named cases, each with an optional payload
+    Variant(Box<[(String, Option<ComponentValType>)]>),
+    /// One of several named cases without payloads
+    Enum(Box<[String]>),
+    /// An optional value
+    Option(Box<ComponentValType>),
+    /// A success or error value, each with an optional payload
+    Result {
+        /// The payload of the success case
+        ok: Option<Box<ComponentValType>>,
+        /// The payload of the error case
+        err: Option<Box<ComponentValType>>,
+    },
+    /// A set of named flags
+    Flags(Box<[String]>),
+    /// An owned handle to a resource, an index into [`TinyWasmComponent::resources`]
+    Own(u32),
+    /// A borrowed handle to a resource, an index into [`TinyWasmComponent::resources`]
+    Borrow(u32),
+}
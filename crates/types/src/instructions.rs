@@ -1,4 +1,4 @@
-use super::{FuncAddr, GlobalAddr, LabelAddr, LocalAddr, TableAddr, TypeAddr, ValType};
+use super::{ExternAddr, FuncAddr, GlobalAddr, LabelAddr, LocalAddr, TableAddr, TypeAddr, ValType};
 use crate::{DataAddr, ElemAddr, MemAddr};
 
 /// Represents a memory immediate in a WebAssembly memory instruction.
@@ -24,6 +24,7 @@ pub enum ConstInstruction {
     GlobalGet(GlobalAddr),
     RefNull(ValType),
     RefFunc(FuncAddr),
+    RefExtern(ExternAddr),
 }
 
 /// A WebAssembly Instruction
@@ -29,8 +29,10 @@ pub(crate) mod log {
     pub(crate) use info;
 }
 
+mod component;
 mod instructions;
 mod value;
+pub use component::*;
 pub use instructions::*;
 pub use value::*;
 
@@ -112,6 +114,34 @@ pub enum ExternalKind {
     Global,
 }
 
+/// The type of an imported or exported item.
+///
+/// See <https://webassembly.github.io/spec/core/syntax/types.html#external-types>
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExternType {
+    /// A function with the given signature.
+    Func(FuncType),
+    /// A table.
+    Table(TableType),
+    /// A linear memory.
+    Memory(MemoryType),
+    /// A global variable.
+    Global(GlobalType),
+}
+
+impl ExternType {
+    /// Get the kind of the item.
+    #[inline]
+    pub fn kind(&self) -> ExternalKind {
+        match self {
+            Self::Func(_) => ExternalKind::Func,
+            Self::Table(_) => ExternalKind::Table,
+            Self::Memory(_) => ExternalKind::Memory,
+            Self::Global(_) => ExternalKind::Global,
+        }
+    }
+}
+
 /// A WebAssembly Address.
 ///
 /// These are indexes into the respective stores.
@@ -202,6 +232,23 @@ pub struct WasmFunction {
     pub locals: ValueCounts,
     pub params: ValueCountsSmall,
     pub ty: FuncType,
+
+    /// Offsets of the function entry and every `block`, `loop`, `if` and `else` instruction
+    /// in the original WebAssembly binary, sorted by instruction index.
+    /// Only available with the `coverage` feature.
+    #[cfg(feature = "coverage")]
+    pub block_offsets: Box<[BlockOffset]>,
+}
+
+/// Maps an instruction that starts a region of code to its position in the original WebAssembly binary.
+#[cfg(feature = "coverage")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "archive", derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize), archive(check_bytes))]
+pub struct BlockOffset {
+    /// Index of the instruction in [`WasmFunction::instructions`]
+    pub instr: u32,
+    /// Byte offset of the instruction in the original WebAssembly binary
+    pub offset: u32,
 }
 
 /// A WebAssembly Module Export
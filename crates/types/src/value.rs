@@ -34,8 +34,7 @@ impl WasmValue {
             Self::F64(i) => ConstInstruction::F64Const(*i),
             Self::RefFunc(i) => ConstInstruction::RefFunc(*i),
             Self::RefNull(ty) => ConstInstruction::RefNull(*ty),
-
-            // Self::RefExtern(addr) => ConstInstruction::RefExtern(*addr),
+            Self::RefExtern(addr) => ConstInstruction::RefExtern(*addr),
             _ => unimplemented!("no const_instr for {:?}", self),
         }
     }
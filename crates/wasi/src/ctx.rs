@@ -0,0 +1,319 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+#[cfg(feature = "preview2")]
+use tinywasm::component::{Component, ComponentImports};
+use tinywasm::{Error, Extern, HostModule, Imports, Linker, Module, ModuleInstance, Result, Store};
+
+use crate::fs::{FileSystem, WasiFile};
+use crate::types::{rights, Errno};
+use crate::{WasiClocks, WasiRandom};
+
+/// The error a call fails with when the guest exits with `proc_exit` or `wasi:cli/exit`
+///
+/// Returned as a [`tinywasm::Error::Host`], use [`tinywasm::Error::downcast_ref`] to tell it apart from other errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProcExit {
+    /// The exit code
+    pub code: i32,
+}
+
+impl core::fmt::Display for ProcExit {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "process exited with code {}", self.code)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ProcExit {}
+
+pub(crate) enum Descriptor {
+    File { file: Box<dyn WasiFile>, rights: u64, append: bool },
+    Dir { fs: Rc<dyn FileSystem>, path: String, preopen: Option<String> },
+}
+
+pub(crate) struct WasiState {
+    pub(crate) args: Vec<String>,
+    pub(crate) env: Vec<(String, String)>,
+    pub(crate) fds: BTreeMap<u32, Descriptor>,
+    pub(crate) clocks: Box<dyn WasiClocks>,
+    pub(crate) random: Box<dyn WasiRandom>,
+    // the store's environment while a host function runs in deterministic mode
+    pub(crate) deterministic: Option<tinywasm::deterministic::DeterministicEnv>,
+    pub(crate) exit_code: Option<i32>,
+    #[cfg(feature = "preview2")]
+    pub(crate) resources: crate::preview2::Resources,
+}
+
+impl WasiState {
+    pub(crate) fn fd(&mut self, fd: u32) -> Result<&mut Descriptor, Errno> {
+        self.fds.get_mut(&fd).ok_or(Errno::BADF)
+    }
+
+    // the lowest unused file descriptor
+    pub(crate) fn insert_fd(&mut self, descriptor: Descriptor) -> Result<u32, Errno> {
+        let fd = (0..=u32::MAX).find(|fd| !self.fds.contains_key(fd)).ok_or(Errno::MFILE)?;
+        self.fds.insert(fd, descriptor);
+        Ok(fd)
+    }
+
+    // resolve a guest path relative to a directory descriptor
+    pub(crate) fn resolve(&mut self, fd: u32, path: &str) -> Result<(Rc<dyn FileSystem>, String), Errno> {
+        match self.fd(fd)? {
+            Descriptor::Dir { fs, path: base, .. } => Ok((fs.clone(), crate::fs::join(base, path)?)),
+            Descriptor::File { .. } => Err(Errno::NOTDIR),
+        }
+    }
+}
+
+fn stream(file: impl WasiFile + 'static, rights: u64) -> Descriptor {
+    Descriptor::File { file: Box::new(file), rights, append: false }
+}
+
+/// The state of a WASI program
+///
+/// Holds the arguments, environment variables, standard streams and preopened directories visible to the guest,
+/// and is shared by all host functions created from it. Cloning returns a handle to the same state.
+///
+/// By default there are no arguments, environment variables or preopened directories, stdin is empty and
+/// output is discarded. Directories are provided by a [`FileSystem`], see the [`fs`](crate::fs) module.
+///
+/// ## Example
+/// ```rust
+/// # fn main() -> tinywasm::Result<()> {
+/// use tinywasm::{Imports, Module, Store};
+/// use tinywasm_wasi::WasiCtx;
+///
+/// let wasm = wat::parse_str(r#"
+///     (module
+///         (import "wasi_snapshot_preview1" "proc_exit" (func $exit (param i32)))
+///         (memory (export "memory") 1)
+///         (func (export "_start") (call $exit (i32.const 3))))
+/// "#).unwrap();
+///
+/// let mut wasi = WasiCtx::new();
+/// wasi.args(["app", "--verbose"]).env("HOME", "/");
+///
+/// let mut store = Store::default();
+/// let module = Module::parse_bytes(&wasm)?;
+/// assert_eq!(wasi.run(&mut store, module, Some(Imports::new()))?, 3);
+/// assert_eq!(wasi.exit_code(), Some(3));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct WasiCtx(pub(crate) Rc<RefCell<WasiState>>);
+
+impl core::fmt::Debug for WasiCtx {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let state = self.0.borrow();
+        f.debug_struct("WasiCtx")
+            .field("args", &state.args)
+            .field("env", &state.env)
+            .field("fds", &state.fds.len())
+            .field("exit_code", &state.exit_code)
+            .finish()
+    }
+}
+
+impl Default for WasiCtx {
+    fn default() -> Self {
+        use crate::fs::Null;
+        let fds = BTreeMap::from([
+            (0, stream(Null, rights::FD_READ)),
+            (1, stream(Null, rights::FD_WRITE)),
+            (2, stream(Null, rights::FD_WRITE)),
+        ]);
+
+        Self(Rc::new(RefCell::new(WasiState {
+            args: Vec::new(),
+            env: Vec::new(),
+            fds,
+            #[cfg(feature = "std")]
+            clocks: Box::new(crate::SystemClocks::default()),
+            #[cfg(feature = "std")]
+            random: Box::new(crate::SystemRandom),
+            #[cfg(not(feature = "std"))]
+            clocks: Box::new(crate::system::Unsupported),
+            #[cfg(not(feature = "std"))]
+            random: Box::new(crate::system::Unsupported),
+            deterministic: None,
+            exit_code: None,
+            #[cfg(feature = "preview2")]
+            resources: Default::default(),
+        })))
+    }
+}
+
+impl WasiCtx {
+    /// Create a new context
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a command line argument, the first argument is usually the program name
+    pub fn arg(&mut self, arg: impl Into<String>) -> &mut Self {
+        self.0.borrow_mut().args.push(arg.into());
+        self
+    }
+
+    /// Add command line arguments
+    pub fn args(&mut self, args: impl IntoIterator<Item = impl Into<String>>) -> &mut Self {
+        self.0.borrow_mut().args.extend(args.into_iter().map(Into::into));
+        self
+    }
+
+    /// Pass the arguments of the host process
+    #[cfg(feature = "std")]
+    pub fn inherit_args(&mut self) -> &mut Self {
+        self.args(std::env::args())
+    }
+
+    /// Set an environment variable
+    pub fn env(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.0.borrow_mut().env.push((key.into(), value.into()));
+        self
+    }
+
+    /// Pass the environment variables of the host process
+    #[cfg(feature = "std")]
+    pub fn inherit_env(&mut self) -> &mut Self {
+        self.0.borrow_mut().env.extend(std::env::vars());
+        self
+    }
+
+    /// Set the standard input, e.g. a [`Pipe`](crate::fs::Pipe)
+    pub fn stdin(&mut self, stdin: impl WasiFile + 'static) -> &mut Self {
+        self.0.borrow_mut().fds.insert(0, stream(stdin, rights::FD_READ));
+        self
+    }
+
+    /// Set the standard output, e.g. a [`Pipe`](crate::fs::Pipe)
+    pub fn stdout(&mut self, stdout: impl WasiFile + 'static) -> &mut Self {
+        self.0.borrow_mut().fds.insert(1, stream(stdout, rights::FD_WRITE));
+        self
+    }
+
+    /// Set the standard error output, e.g. a [`Pipe`](crate::fs::Pipe)
+    pub fn stderr(&mut self, stderr: impl WasiFile + 'static) -> &mut Self {
+        self.0.borrow_mut().fds.insert(2, stream(stderr, rights::FD_WRITE));
+        self
+    }
+
+    /// Use the standard streams of the host process
+    #[cfg(feature = "std")]
+    pub fn inherit_stdio(&mut self) -> &mut Self {
+        use crate::fs::{ReadStream, WriteStream};
+        self.stdin(ReadStream(std::io::stdin()))
+            .stdout(WriteStream(std::io::stdout()))
+            .stderr(WriteStream(std::io::stderr()))
+    }
+
+    /// Give the guest access to a filesystem under the given guest path
+    pub fn preopen(&mut self, fs: impl FileSystem + 'static, guest: &str) -> Result<&mut Self> {
+        let dir = Descriptor::Dir { fs: Rc::new(fs), path: String::new(), preopen: Some(guest.to_string()) };
+        self.0.borrow_mut().insert_fd(dir).map_err(|_| Error::Other("too many open files".into()))?;
+        Ok(self)
+    }
+
+    /// Give the guest access to a host directory under the given guest path
+    ///
+    /// The guest can access everything inside the directory, but not outside of it, see [`HostFs`](crate::fs::HostFs).
+    #[cfg(feature = "std")]
+    pub fn preopen_dir(&mut self, host: impl AsRef<std::path::Path>, guest: &str) -> Result<&mut Self> {
+        let host = host.as_ref();
+        let fs = crate::fs::HostFs::new(host)
+            .map_err(|_| Error::Other(alloc::format!("not a directory: {}", host.display())))?;
+        self.preopen(fs, guest)
+    }
+
+    /// Set the clocks used by the guest, by default the [`SystemClocks`](crate::SystemClocks) of the host
+    pub fn clocks(&mut self, clocks: impl WasiClocks + 'static) -> &mut Self {
+        self.0.borrow_mut().clocks = Box::new(clocks);
+        self
+    }
+
+    /// Set the source of random bytes used by the guest, by default the [`SystemRandom`](crate::SystemRandom) of the host
+    pub fn random(&mut self, random: impl WasiRandom + 'static) -> &mut Self {
+        self.0.borrow_mut().random = Box::new(random);
+        self
+    }
+
+    /// Get the exit code passed to `proc_exit`, if the guest exited
+    pub fn exit_code(&self) -> Option<i32> {
+        self.0.borrow().exit_code
+    }
+
+    /// Define the `wasi_snapshot_preview1` functions
+    pub fn add_to_imports(&self, imports: &mut Imports) -> Result<()> {
+        imports.define_host_module(crate::MODULE, self.clone())?;
+        Ok(())
+    }
+
+    /// Define the `wasi_snapshot_preview1` functions in a linker
+    pub fn add_to_linker(&self, linker: &mut Linker) -> Result<()> {
+        linker.define_host_module(crate::MODULE, self.clone())?;
+        Ok(())
+    }
+
+    /// Instantiate and run a command, returning its exit code
+    ///
+    /// The WASI functions are added to `imports`, and the module is started by calling its start function
+    /// or `_start` export. Returns `0` if it returns normally, or the code passed to `proc_exit`.
+    pub fn run(&self, store: &mut Store, module: Module, imports: Option<Imports>) -> Result<i32> {
+        let mut imports = imports.unwrap_or_default();
+        self.add_to_imports(&mut imports)?;
+
+        // `Module::instantiate` would already call `_start`
+        let instance = ModuleInstance::instantiate(store, module, Some(imports))?;
+        match instance.start(store) {
+            Ok(_) => Ok(0),
+            Err(err) => exit_code(err),
+        }
+    }
+
+    /// Define the WASI 0.2 interfaces of the `wasi:cli/command` world for a component
+    #[cfg(feature = "preview2")]
+    pub fn add_to_component_imports(&self, imports: &mut ComponentImports) -> Result<()> {
+        crate::preview2::add_to_imports(self, imports)
+    }
+
+    /// Instantiate and run a component exporting `wasi:cli/run`, returning its exit code
+    ///
+    /// The WASI interfaces are added to `imports`. Returns `0` if `run` returns `ok`, `1` if it returns `err`,
+    /// or the code passed to `wasi:cli/exit`.
+    #[cfg(feature = "preview2")]
+    pub fn run_component(
+        &self,
+        store: &mut Store,
+        component: &Component,
+        imports: Option<ComponentImports>,
+    ) -> Result<i32> {
+        let mut imports = imports.unwrap_or_default();
+        self.add_to_component_imports(&mut imports)?;
+
+        let instance = component.instantiate(store, &imports)?;
+        let run = instance.exported_func::<(), (core::result::Result<(), ()>,)>("wasi:cli/run@0.2.0#run")?;
+        match run.call(store, ()) {
+            Ok((status,)) => Ok(status.is_err() as i32),
+            Err(err) => exit_code(err),
+        }
+    }
+}
+
+fn exit_code(err: Error) -> Result<i32> {
+    match err.downcast_ref::<ProcExit>() {
+        Some(exit) => Ok(exit.code),
+        None => Err(err),
+    }
+}
+
+impl HostModule for WasiCtx {
+    fn into_externs(self) -> Vec<(&'static str, Extern)> {
+        crate::preview1::externs(&self)
+    }
+}
@@ -0,0 +1,642 @@
+//! Emscripten compatibility
+//!
+//! Modules built by Emscripten with `-sSTANDALONE_WASM` use WASI for most of their system interface, but still
+//! import a few functions from `env`: memory helpers like `emscripten_resize_heap`, the `invoke_*` wrappers and
+//! `_emscripten_throw_longjmp` used to implement `setjmp`/`longjmp`, and `__syscall_*` functions for filesystem
+//! operations that WASI has no direct equivalent for.
+//!
+//! [`Emscripten`] provides these on top of a [`WasiCtx`]: files opened by the syscalls share their file descriptors
+//! and preopened directories with the WASI functions, and absolute paths are resolved against the preopened
+//! directories the way `wasi-libc` does. Syscalls that are not implemented return `-ENOSYS`.
+//!
+//! Requires the `emscripten` feature.
+//!
+//! ```rust
+//! # fn main() -> tinywasm::Result<()> {
+//! use tinywasm::{Imports, Module, Store};
+//! use tinywasm_wasi::fs::{FileSystem, MemFs};
+//! use tinywasm_wasi::{emscripten::Emscripten, WasiCtx};
+//!
+//! let wasm = wat::parse_str(r#"(module
+//!     (import "env" "__syscall_mkdirat" (func $mkdirat (param i32 i32 i32) (result i32)))
+//!     (memory (export "memory") 1)
+//!     (data (i32.const 0) "/data/logs\00")
+//!     ;; mkdirat(AT_FDCWD, "/data/logs", 0o755)
+//!     (func (export "_start") (drop (call $mkdirat (i32.const -100) (i32.const 0) (i32.const 493)))))"#).unwrap();
+//!
+//! let data = MemFs::new();
+//! let mut wasi = WasiCtx::new();
+//! wasi.preopen(data.clone(), "/data")?;
+//!
+//! let module = Module::parse_bytes(&wasm)?;
+//! let mut imports = Imports::new();
+//! Emscripten::new(&wasi).add_to_imports(&mut imports, &module)?;
+//!
+//! wasi.run(&mut Store::default(), module, Some(imports))?;
+//! assert!(data.read_dir("logs").is_ok());
+//! # Ok(())
+//! # }
+//! ```
+
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use tinywasm::types::{ExternType, FuncType, ValType, WasmValue};
+use tinywasm::{Error, Extern, FuncContext, FuncRef, Imports, Module, ModuleInstance, Result, Store};
+
+use crate::ctx::{Descriptor, WasiState};
+use crate::fs::{FileSystem, OpenOptions};
+use crate::preview1::{arg32, arg64, file, now, ptr_add, same_fs, store, with_state, Memory};
+use crate::types::{clockid, filetype, Errno, Filestat};
+use crate::WasiCtx;
+
+const PAGE_SIZE: usize = 0x10000;
+const PATH_MAX: usize = 4096;
+
+const AT_FDCWD: i32 = -100;
+const AT_SYMLINK_NOFOLLOW: u32 = 0x100;
+const AT_REMOVEDIR: u32 = 0x200;
+const AT_EMPTY_PATH: u32 = 0x1000;
+
+const O_ACCMODE: u32 = 0o3;
+const O_WRONLY: u32 = 0o1;
+const O_RDWR: u32 = 0o2;
+const O_CREAT: u32 = 0o100;
+const O_EXCL: u32 = 0o200;
+const O_TRUNC: u32 = 0o1000;
+const O_APPEND: u32 = 0o2000;
+const O_DIRECTORY: u32 = 0o200000;
+const O_NOFOLLOW: u32 = 0o400000;
+
+const UTIME_NOW: u32 = 0x3fff_ffff;
+const UTIME_OMIT: u32 = 0x3fff_fffe;
+
+// the size of a `struct dirent` record written by `getdents64`, like Emscripten's own implementation
+const DIRENT_SIZE: u32 = 280;
+
+#[derive(Debug)]
+struct EmscriptenState {
+    cwd: String,
+    // the position of `getdents64` in directories opened by `openat`
+    dir_positions: BTreeMap<u32, usize>,
+    temp_ret0: i32,
+}
+
+/// The `env` imports of Emscripten modules, see the [module documentation](self)
+///
+/// Cloning returns a handle to the same state, which holds the current working directory.
+#[derive(Debug, Clone)]
+pub struct Emscripten {
+    wasi: WasiCtx,
+    state: Rc<RefCell<EmscriptenState>>,
+}
+
+impl Emscripten {
+    /// Create the imports on top of a WASI context, starting in the root directory
+    pub fn new(wasi: &WasiCtx) -> Self {
+        let state = EmscriptenState { cwd: "/".into(), dir_positions: BTreeMap::new(), temp_ret0: 0 };
+        Self { wasi: wasi.clone(), state: Rc::new(RefCell::new(state)) }
+    }
+
+    /// Define the `env` functions imported by `module`
+    ///
+    /// The `invoke_*` wrappers depend on the signatures the module imports them with. Other `env` imports are left
+    /// for the embedder to define.
+    pub fn add_to_imports(&self, imports: &mut Imports, module: &Module) -> Result<()> {
+        for import in module.imports().filter(|import| import.module == "env") {
+            let ExternType::Func(ty) = &import.ty else { continue };
+            let name = import.name;
+
+            let func = match name {
+                _ if name.starts_with("invoke_") => invoke(ty),
+                _ if name.starts_with("__syscall_") => match SYSCALLS.iter().find(|(n, ..)| *n == name) {
+                    Some(&(_, params, syscall)) => self.syscall(params, syscall),
+                    None => unsupported(ty),
+                },
+                _ => match self.func(name) {
+                    Some(func) => func,
+                    None => continue,
+                },
+            };
+            imports.define("env", name, func)?;
+        }
+        Ok(())
+    }
+
+    fn func(&self, name: &str) -> Option<Extern> {
+        let state = self.state.clone();
+        Some(match name {
+            "emscripten_memcpy_big" | "emscripten_memcpy_js" | "_emscripten_memcpy_js" => {
+                Extern::typed_func(|mut ctx: FuncContext<'_>, (dest, src, len): (i32, i32, i32)| {
+                    ctx.exported_memory_mut("memory")?.copy_within(
+                        src as u32 as usize,
+                        dest as u32 as usize,
+                        len as u32 as usize,
+                    )
+                })
+            }
+            "emscripten_resize_heap" => Extern::typed_func(|mut ctx: FuncContext<'_>, requested: i32| {
+                let mut memory = ctx.exported_memory_mut("memory")?;
+                let (pages, needed) = (memory.page_count(), (requested as u32 as usize).div_ceil(PAGE_SIZE));
+                Ok(i32::from(needed <= pages || memory.grow((needed - pages) as i32).is_some()))
+            }),
+            "emscripten_notify_memory_growth" => Extern::typed_func(|_, _: i32| Ok(())),
+            "abort" | "_abort_js" => Extern::typed_func(|_, ()| Err::<(), _>(Error::Other("abort() called".into()))),
+            "getTempRet0" => Extern::typed_func(move |_, ()| Ok(state.borrow().temp_ret0)),
+            "setTempRet0" => Extern::typed_func(move |_, value: i32| {
+                state.borrow_mut().temp_ret0 = value;
+                Ok(())
+            }),
+            "_emscripten_throw_longjmp" => Extern::typed_func(|_, ()| Err::<(), _>(Error::host(Longjmp))),
+            "emscripten_longjmp" => Extern::typed_func(move |mut ctx: FuncContext<'_>, (env, value): (i32, i32)| {
+                let module = ctx.module()?;
+                let args = [WasmValue::I32(env), WasmValue::I32(if value == 0 { 1 } else { value })];
+                call_export(ctx.store_mut(), &module, &["setThrew"], &args)?;
+                Err::<(), _>(Error::host(Longjmp))
+            }),
+            _ => return None,
+        })
+    }
+
+    fn syscall(&self, params: &'static [ValType], syscall: Syscall) -> Extern {
+        let (wasi, state) = (self.wasi.clone(), self.state.clone());
+        let ty = FuncType { params: params.into(), results: [ValType::I32].into() };
+        Extern::func(&ty, move |mut ctx, args| {
+            let result = with_state(&wasi, &mut ctx, |wasi, memory| {
+                syscall(&mut Sys { wasi, em: &mut state.borrow_mut(), memory }, args)
+            })?;
+            Ok(vec![WasmValue::I32(result.unwrap_or_else(|errno| -i32::from(errno.raw())))])
+        })
+    }
+}
+
+// call a function from the table, catching a `longjmp` out of it like Emscripten's JavaScript glue code
+fn invoke(ty: &FuncType) -> Extern {
+    let results = ty.results.clone();
+    Extern::func(ty, move |mut ctx, args| {
+        let module = ctx.module()?;
+        let store = ctx.store_mut();
+        let index = arg32(args, 0);
+        let func = FuncRef::try_from(module.exported_table(store, "__indirect_function_table")?.get(index)?)
+            .ok()
+            .and_then(|func| func.func(store))
+            .ok_or_else(|| Error::Other(format!("invalid function pointer: {index}")))?;
+
+        let stack = call_export(store, &module, &["stackSave", "emscripten_stack_get_current"], &[])?;
+        match func.call(store, &args[1..]) {
+            Err(err) if err.downcast_ref::<Longjmp>().is_some() => {
+                if let Some(stack) = stack {
+                    call_export(store, &module, &["stackRestore", "_emscripten_stack_restore"], &stack)?;
+                }
+                call_export(store, &module, &["setThrew"], &[WasmValue::I32(1), WasmValue::I32(0)])?
+                    .ok_or_else(|| Error::Other("longjmp requires the setThrew export".into()))?;
+                Ok(results.iter().map(|&ty| WasmValue::default_for(ty)).collect())
+            }
+            result => result,
+        }
+    })
+}
+
+// unwinds to the closest `invoke_*` call, or fails the outermost call if there is none
+#[derive(Debug)]
+struct Longjmp;
+
+impl core::fmt::Display for Longjmp {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "longjmp without a matching setjmp")
+    }
+}
+
+// call the first of the exports that exists, returning `None` if none does
+fn call_export(
+    store: &mut Store,
+    module: &ModuleInstance,
+    names: &[&str],
+    args: &[WasmValue],
+) -> Result<Option<Vec<WasmValue>>> {
+    match names.iter().find_map(|name| module.exported_func_untyped(store, name).ok()) {
+        Some(func) => func.call(store, args).map(Some),
+        None => Ok(None),
+    }
+}
+
+fn unsupported(ty: &FuncType) -> Extern {
+    let mut results: Vec<_> = ty.results.iter().map(|&ty| WasmValue::default_for(ty)).collect();
+    if let Some(result @ WasmValue::I32(_)) = results.first_mut() {
+        *result = WasmValue::I32(-i32::from(Errno::NOSYS.raw()));
+    }
+    Extern::func(ty, move |_, _| Ok(results.clone()))
+}
+
+struct Sys<'a, 'b, 'c> {
+    wasi: &'a mut WasiState,
+    em: &'a mut EmscriptenState,
+    memory: Memory<'b, 'c>,
+}
+
+type Syscall = fn(&mut Sys<'_, '_, '_>, &[WasmValue]) -> core::result::Result<i32, Errno>;
+
+const I32: ValType = ValType::I32;
+
+#[rustfmt::skip]
+const SYSCALLS: &[(&str, &[ValType], Syscall)] = &[
+    ("__syscall_chdir", &[I32], chdir),
+    ("__syscall_faccessat", &[I32; 4], faccessat),
+    ("__syscall_fcntl64", &[I32; 3], fcntl64),
+    ("__syscall_fstat64", &[I32; 2], fstat64),
+    ("__syscall_ftruncate64", &[I32, ValType::I64], ftruncate64),
+    ("__syscall_getcwd", &[I32; 2], getcwd),
+    ("__syscall_getdents64", &[I32; 3], getdents64),
+    ("__syscall_ioctl", &[I32; 3], ioctl),
+    ("__syscall_lstat64", &[I32; 2], lstat64),
+    ("__syscall_mkdirat", &[I32; 3], mkdirat),
+    ("__syscall_newfstatat", &[I32; 4], newfstatat),
+    ("__syscall_openat", &[I32; 4], openat),
+    ("__syscall_readlinkat", &[I32; 4], readlinkat),
+    ("__syscall_renameat", &[I32; 4], renameat),
+    ("__syscall_rmdir", &[I32], rmdir),
+    ("__syscall_stat64", &[I32; 2], stat64),
+    ("__syscall_symlinkat", &[I32; 3], symlinkat),
+    ("__syscall_unlinkat", &[I32; 3], unlinkat),
+    ("__syscall_utimensat", &[I32; 4], utimensat),
+];
+
+// split a path into its components, resolving `.` and `..` without leaving the root
+fn components(path: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts
+}
+
+impl Sys<'_, '_, '_> {
+    fn cstr(&mut self, ptr: u32) -> core::result::Result<String, Errno> {
+        let max = (self.memory.page_count() * PAGE_SIZE).saturating_sub(ptr as usize).min(PATH_MAX);
+        let bytes = self.memory.load(ptr as usize, max)?;
+        let len = bytes.iter().position(|&b| b == 0).ok_or(Errno::NAMETOOLONG)?;
+        String::from_utf8(bytes[..len].to_vec()).map_err(|_| Errno::ILSEQ)
+    }
+
+    // the absolute guest path of a path relative to the current directory
+    fn absolute(&self, path: &str) -> String {
+        let path = if path.starts_with('/') { path.to_string() } else { format!("{}/{path}", self.em.cwd) };
+        format!("/{}", components(&path).join("/"))
+    }
+
+    // resolve a path relative to a directory, absolute paths are looked up in the preopened directory with the
+    // longest matching prefix
+    fn resolve(&mut self, dirfd: i32, path: &str) -> core::result::Result<(Rc<dyn FileSystem>, String), Errno> {
+        if path.is_empty() {
+            return Err(Errno::NOENT);
+        }
+        if dirfd != AT_FDCWD && !path.starts_with('/') {
+            return self.wasi.resolve(dirfd as u32, path);
+        }
+
+        let absolute = self.absolute(path);
+        let parts = components(&absolute);
+        let mut found: Option<(usize, &Rc<dyn FileSystem>, &str)> = None;
+        for descriptor in self.wasi.fds.values() {
+            let Descriptor::Dir { fs, path: base, preopen: Some(name) } = descriptor else { continue };
+            let prefix = components(name);
+            if parts.starts_with(&prefix) && found.map_or(true, |(len, ..)| prefix.len() > len) {
+                found = Some((prefix.len(), fs, base));
+            }
+        }
+
+        let (len, fs, base) = found.ok_or(Errno::NOENT)?;
+        Ok((fs.clone(), crate::fs::join(base, &parts[len..].join("/"))?))
+    }
+
+    fn path_arg(
+        &mut self,
+        args: &[WasmValue],
+        dirfd: usize,
+        ptr: usize,
+    ) -> core::result::Result<(Rc<dyn FileSystem>, String), Errno> {
+        let path = self.cstr(arg32(args, ptr))?;
+        self.resolve(arg32(args, dirfd) as i32, &path)
+    }
+
+    fn fstat(&mut self, fd: u32) -> core::result::Result<Filestat, Errno> {
+        match self.wasi.fd(fd)? {
+            Descriptor::File { file, .. } => file.filestat(),
+            Descriptor::Dir { fs, path, .. } => fs.stat(path, true),
+        }
+    }
+
+    // Emscripten's `struct stat`
+    fn write_stat(&mut self, ptr: u32, stat: Filestat) -> core::result::Result<i32, Errno> {
+        let mode: u32 = match stat.filetype {
+            filetype::DIRECTORY => 0o040755,
+            filetype::SYMBOLIC_LINK => 0o120777,
+            filetype::CHARACTER_DEVICE => 0o020666,
+            _ => 0o100644,
+        };
+
+        let mut buf = [0u8; 96];
+        let mut put = |offset: usize, bytes: &[u8]| buf[offset..offset + bytes.len()].copy_from_slice(bytes);
+        put(0, &(stat.dev as u32).to_le_bytes());
+        put(4, &mode.to_le_bytes());
+        put(8, &(stat.nlink as u32).to_le_bytes());
+        put(24, &stat.size.to_le_bytes());
+        put(32, &4096u32.to_le_bytes());
+        put(36, &(stat.size.div_ceil(512) as u32).to_le_bytes());
+        for (offset, time) in [(40, stat.atim), (56, stat.mtim), (72, stat.ctim)] {
+            put(offset, &(time / 1_000_000_000).to_le_bytes());
+            put(offset + 8, &((time % 1_000_000_000) as u32).to_le_bytes());
+        }
+        put(88, &stat.ino.to_le_bytes());
+        store(self.memory, ptr, &buf)?;
+        Ok(0)
+    }
+}
+
+fn chdir(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let path = sys.cstr(arg32(args, 0))?;
+    let (fs, resolved) = sys.resolve(AT_FDCWD, &path)?;
+    if fs.stat(&resolved, true)?.filetype != filetype::DIRECTORY {
+        return Err(Errno::NOTDIR);
+    }
+    sys.em.cwd = sys.absolute(&path);
+    Ok(0)
+}
+
+fn faccessat(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let (fs, path) = sys.path_arg(args, 0, 1)?;
+    fs.stat(&path, true).map(|_| 0)
+}
+
+fn fcntl64(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let descriptor = sys.wasi.fd(arg32(args, 0))?;
+    match arg32(args, 1) {
+        // F_GETFL
+        3 => match descriptor {
+            Descriptor::File { append: true, .. } => Ok((O_RDWR | O_APPEND) as i32),
+            Descriptor::File { .. } => Ok(O_RDWR as i32),
+            Descriptor::Dir { .. } => Ok(O_DIRECTORY as i32),
+        },
+        // F_GETFD, F_SETFD, F_SETFL and the locks, which have no effect
+        1 | 2 | 4..=7 | 12..=14 => Ok(0),
+        _ => Err(Errno::INVAL),
+    }
+}
+
+fn fstat64(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let stat = sys.fstat(arg32(args, 0))?;
+    sys.write_stat(arg32(args, 1), stat)
+}
+
+fn ftruncate64(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    file(sys.wasi, arg32(args, 0))?.set_size(arg64(args, 1)).map(|_| 0)
+}
+
+fn getcwd(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let mut cwd = sys.em.cwd.clone().into_bytes();
+    cwd.push(0);
+    if cwd.len() > arg32(args, 1) as usize {
+        return Err(Errno::RANGE);
+    }
+    store(sys.memory, arg32(args, 0), &cwd)?;
+    Ok(cwd.len() as i32)
+}
+
+fn getdents64(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let (fd, ptr, count) = (arg32(args, 0), arg32(args, 1), arg32(args, 2));
+    let Descriptor::Dir { fs, path, .. } = sys.wasi.fd(fd)? else { return Err(Errno::NOTDIR) };
+    let entries = fs.read_dir(path)?;
+    let dots = [".", ".."].map(|name| (name.to_string(), filetype::DIRECTORY, 0));
+    let entries = dots.into_iter().chain(entries.into_iter().map(|entry| (entry.name, entry.filetype, entry.ino)));
+
+    let position = sys.em.dir_positions.get(&fd).copied().unwrap_or_default();
+    let mut written = 0;
+    for (idx, (name, filetype, ino)) in entries.enumerate().skip(position) {
+        if count - written < DIRENT_SIZE {
+            break;
+        }
+        let d_type: u8 = match filetype {
+            filetype::CHARACTER_DEVICE => 2,
+            filetype::DIRECTORY => 4,
+            filetype::REGULAR_FILE => 8,
+            filetype::SYMBOLIC_LINK => 10,
+            _ => 0,
+        };
+
+        // d_ino, d_off, d_reclen, d_type and the null-terminated d_name
+        let mut record = vec![0u8; DIRENT_SIZE as usize];
+        let name = &name.as_bytes()[..name.len().min(255)];
+        record[..8].copy_from_slice(&ino.to_le_bytes());
+        record[8..16].copy_from_slice(&((idx + 1) as u64 * DIRENT_SIZE as u64).to_le_bytes());
+        record[16..18].copy_from_slice(&(DIRENT_SIZE as u16).to_le_bytes());
+        record[18] = d_type;
+        record[19..19 + name.len()].copy_from_slice(name);
+        store(sys.memory, ptr_add(ptr, written)?, &record)?;
+
+        written += DIRENT_SIZE;
+        sys.em.dir_positions.insert(fd, idx + 1);
+    }
+
+    if written == 0 && count < DIRENT_SIZE {
+        return Err(Errno::INVAL);
+    }
+    Ok(written as i32)
+}
+
+// there are no terminals
+fn ioctl(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    sys.wasi.fd(arg32(args, 0))?;
+    Err(Errno::NOTTY)
+}
+
+fn lstat64(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let path = sys.cstr(arg32(args, 0))?;
+    let (fs, path) = sys.resolve(AT_FDCWD, &path)?;
+    let stat = fs.stat(&path, false)?;
+    sys.write_stat(arg32(args, 1), stat)
+}
+
+fn mkdirat(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let (fs, path) = sys.path_arg(args, 0, 1)?;
+    fs.create_dir(&path).map(|_| 0)
+}
+
+fn newfstatat(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let (dirfd, flags) = (arg32(args, 0), arg32(args, 3));
+    let path = sys.cstr(arg32(args, 1))?;
+    let stat = match path.is_empty() && flags & AT_EMPTY_PATH != 0 {
+        true => sys.fstat(dirfd)?,
+        false => {
+            let (fs, path) = sys.resolve(dirfd as i32, &path)?;
+            fs.stat(&path, flags & AT_SYMLINK_NOFOLLOW == 0)?
+        }
+    };
+    sys.write_stat(arg32(args, 2), stat)
+}
+
+fn openat(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let (fs, path) = sys.path_arg(args, 0, 1)?;
+    let flags = arg32(args, 2);
+
+    let stat = fs.stat(&path, flags & O_NOFOLLOW == 0);
+    let is_dir = matches!(stat, Ok(ref stat) if stat.filetype == filetype::DIRECTORY);
+    let descriptor = if flags & O_DIRECTORY != 0 || is_dir {
+        if !is_dir {
+            return Err(stat.err().unwrap_or(Errno::NOTDIR));
+        }
+        if flags & (O_CREAT | O_TRUNC) != 0 || flags & O_ACCMODE != 0 {
+            return Err(Errno::ISDIR);
+        }
+        Descriptor::Dir { fs, path, preopen: None }
+    } else {
+        let access = flags & O_ACCMODE;
+        let options = OpenOptions {
+            read: access != O_WRONLY,
+            write: access != 0 || flags & O_APPEND != 0,
+            create: flags & O_CREAT != 0,
+            create_new: flags & O_CREAT != 0 && flags & O_EXCL != 0,
+            truncate: flags & O_TRUNC != 0,
+        };
+        Descriptor::File {
+            file: fs.open(&path, options)?,
+            rights: crate::types::rights::ALL,
+            append: flags & O_APPEND != 0,
+        }
+    };
+
+    let fd = sys.wasi.insert_fd(descriptor)?;
+    sys.em.dir_positions.remove(&fd);
+    Ok(fd as i32)
+}
+
+fn readlinkat(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let (fs, path) = sys.path_arg(args, 0, 1)?;
+    let target = fs.read_link(&path)?;
+    let len = target.len().min(arg32(args, 3) as usize);
+    store(sys.memory, arg32(args, 2), &target.as_bytes()[..len])?;
+    Ok(len as i32)
+}
+
+fn renameat(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let (from_fs, from) = sys.path_arg(args, 0, 1)?;
+    let (to_fs, to) = sys.path_arg(args, 2, 3)?;
+    same_fs(&from_fs, &to_fs)?;
+    from_fs.rename(&from, &to).map(|_| 0)
+}
+
+fn rmdir(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let path = sys.cstr(arg32(args, 0))?;
+    let (fs, path) = sys.resolve(AT_FDCWD, &path)?;
+    fs.remove_dir(&path).map(|_| 0)
+}
+
+fn stat64(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let path = sys.cstr(arg32(args, 0))?;
+    let (fs, path) = sys.resolve(AT_FDCWD, &path)?;
+    let stat = fs.stat(&path, true)?;
+    sys.write_stat(arg32(args, 1), stat)
+}
+
+fn symlinkat(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let target = sys.cstr(arg32(args, 0))?;
+    let (fs, link) = sys.path_arg(args, 1, 2)?;
+    fs.symlink(&target, &link).map(|_| 0)
+}
+
+fn unlinkat(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let (fs, path) = sys.path_arg(args, 0, 1)?;
+    match arg32(args, 2) & AT_REMOVEDIR != 0 {
+        true => fs.remove_dir(&path).map(|_| 0),
+        false => fs.remove_file(&path).map(|_| 0),
+    }
+}
+
+fn utimensat(sys: &mut Sys<'_, '_, '_>, args: &[WasmValue]) -> core::result::Result<i32, Errno> {
+    let (dirfd, path, times) = (arg32(args, 0), arg32(args, 1), arg32(args, 2));
+
+    // two `struct timespec` with a 64-bit `tv_sec` and a 32-bit `tv_nsec`, or null to set both to now
+    let mut time = |idx: u32| -> core::result::Result<Option<u64>, Errno> {
+        let (sec, nsec) = match times {
+            0 => (0, UTIME_NOW),
+            _ => {
+                let timespec = ptr_add(times, idx * 16)?;
+                (sys.memory.read::<u64>(timespec as usize)?, sys.memory.read::<u32>(ptr_add(timespec, 8)? as usize)?)
+            }
+        };
+        match nsec {
+            UTIME_NOW => now(sys.wasi, clockid::REALTIME).map(Some),
+            UTIME_OMIT => Ok(None),
+            nsec => Ok(Some(sec.saturating_mul(1_000_000_000).saturating_add(nsec as u64))),
+        }
+    };
+    let (atim, mtim) = (time(0)?, time(1)?);
+
+    // `futimens` passes a null path
+    if path == 0 {
+        return match sys.wasi.fd(dirfd)? {
+            Descriptor::File { file, .. } => file.set_times(atim, mtim).map(|_| 0),
+            Descriptor::Dir { fs, path, .. } => fs.set_times(path, atim, mtim).map(|_| 0),
+        };
+    }
+
+    let (fs, path) = sys.path_arg(args, 0, 1)?;
+    fs.set_times(&path, atim, mtim).map(|_| 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_components() {
+        assert_eq!(components("/data/./logs/../app.log"), ["data", "app.log"]);
+        assert_eq!(components("../../etc"), ["etc"]);
+        assert!(components("/").is_empty());
+    }
+
+    #[test]
+    fn test_longjmp() {
+        let wasm = wat::parse_str(
+            r#"(module
+                (import "env" "invoke_vi" (func $invoke_vi (param i32 i32)))
+                (import "env" "_emscripten_throw_longjmp" (func $throw))
+                (memory (export "memory") 1)
+                (global $threw (mut i32) (i32.const 0))
+                (table (export "__indirect_function_table") 2 funcref)
+                (elem (i32.const 0) $jump $trap)
+                (func $jump (param i32) (call $throw))
+                (func $trap (param i32) unreachable)
+                (func (export "setThrew") (param i32 i32) (global.set $threw (local.get 0)))
+                (func (export "jump") (result i32) (call $invoke_vi (i32.const 0) (i32.const 0)) (global.get $threw))
+                (func (export "trap") (call $invoke_vi (i32.const 1) (i32.const 0)))
+                (func (export "throw") (call $throw)))"#,
+        )
+        .unwrap();
+
+        let module = Module::parse_bytes(&wasm).unwrap();
+        let mut imports = Imports::new();
+        Emscripten::new(&WasiCtx::new()).add_to_imports(&mut imports, &module).unwrap();
+        let mut store = Store::default();
+        let instance = module.instantiate(&mut store, Some(imports)).unwrap();
+
+        let jump = instance.exported_func::<(), i32>(&store, "jump").unwrap();
+        assert_eq!(jump.call(&mut store, ()).unwrap(), 1);
+
+        // a longjmp without an `invoke_*` call fails the call, and doesn't affect later traps
+        let err = instance.exported_func::<(), ()>(&store, "throw").unwrap().call(&mut store, ()).unwrap_err();
+        assert!(err.downcast_ref::<Longjmp>().is_some());
+        let err = instance.exported_func::<(), ()>(&store, "trap").unwrap().call(&mut store, ()).unwrap_err();
+        assert!(matches!(err, Error::Trap(_)));
+    }
+}
@@ -0,0 +1,228 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use std::fs::{self, File, FileTimes, Metadata};
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::{DirEntry, FileSystem, OpenOptions, SeekFrom, WasiFile};
+use crate::types::{filetype, Errno, Filestat};
+
+/// A directory of the host
+///
+/// The guest can access everything inside the directory, but nothing outside of it: paths are resolved
+/// relative to the directory, and symbolic links pointing outside of it are rejected with
+/// [`Errno::NOTCAPABLE`].
+#[derive(Debug, Clone)]
+pub struct HostFs {
+    root: PathBuf,
+}
+
+impl HostFs {
+    /// Use a host directory as the root of the filesystem
+    pub fn new(root: impl AsRef<Path>) -> Result<Self, Errno> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(Errno::NOTDIR);
+        }
+        Ok(Self { root })
+    }
+
+    /// Get the host directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    // resolve a path to a host path inside of the root
+    fn path(&self, path: &str, follow_symlinks: bool) -> Result<PathBuf, Errno> {
+        if path.is_empty() {
+            return Ok(self.root.clone());
+        }
+
+        let full = self.root.join(path);
+        let (Some(parent), Some(name)) = (full.parent(), full.file_name()) else { return Err(Errno::NOTCAPABLE) };
+
+        // symbolic links in the parent directories are always followed by the host
+        let parent = parent.canonicalize()?;
+        if !parent.starts_with(&self.root) {
+            return Err(Errno::NOTCAPABLE);
+        }
+
+        let full = parent.join(name);
+        if !follow_symlinks || !fs::symlink_metadata(&full).is_ok_and(|meta| meta.is_symlink()) {
+            return Ok(full);
+        }
+
+        // a dangling link could be used to create a file outside of the root
+        match full.canonicalize() {
+            Ok(target) if target.starts_with(&self.root) => Ok(target),
+            _ => Err(Errno::NOTCAPABLE),
+        }
+    }
+}
+
+fn timestamp(time: std::io::Result<SystemTime>) -> u64 {
+    time.ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).map_or(0, |d| d.as_nanos() as u64)
+}
+
+fn filestat(metadata: &Metadata) -> Filestat {
+    #[cfg(unix)]
+    let (dev, ino, nlink) = {
+        use std::os::unix::fs::MetadataExt;
+        (metadata.dev(), metadata.ino(), metadata.nlink())
+    };
+    #[cfg(not(unix))]
+    let (dev, ino, nlink) = (0, 0, 1);
+
+    let file_type = metadata.file_type();
+    let filetype = match () {
+        _ if file_type.is_dir() => filetype::DIRECTORY,
+        _ if file_type.is_file() => filetype::REGULAR_FILE,
+        _ if file_type.is_symlink() => filetype::SYMBOLIC_LINK,
+        _ => filetype::UNKNOWN,
+    };
+
+    Filestat {
+        dev,
+        ino,
+        filetype,
+        nlink,
+        size: metadata.len(),
+        atim: timestamp(metadata.accessed()),
+        mtim: timestamp(metadata.modified()),
+        ctim: timestamp(metadata.created()),
+    }
+}
+
+fn file_times(atim: Option<u64>, mtim: Option<u64>) -> FileTimes {
+    let time = |nanos| UNIX_EPOCH + Duration::from_nanos(nanos);
+    let mut times = FileTimes::new();
+    if let Some(atim) = atim {
+        times = times.set_accessed(time(atim));
+    }
+    if let Some(mtim) = mtim {
+        times = times.set_modified(time(mtim));
+    }
+    times
+}
+
+impl FileSystem for HostFs {
+    fn open(&self, path: &str, options: OpenOptions) -> Result<Box<dyn WasiFile>, Errno> {
+        let file = fs::OpenOptions::new()
+            .read(options.read)
+            .write(options.write)
+            .create(options.create)
+            .create_new(options.create_new)
+            .truncate(options.truncate)
+            .open(self.path(path, true)?)?;
+        Ok(Box::new(HostFile(file)))
+    }
+
+    fn stat(&self, path: &str, follow_symlinks: bool) -> Result<Filestat, Errno> {
+        let path = self.path(path, follow_symlinks)?;
+        Ok(filestat(&if follow_symlinks { fs::metadata(path)? } else { fs::symlink_metadata(path)? }))
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, Errno> {
+        let entries = fs::read_dir(self.path(path, true)?)?.map(|entry| {
+            let entry = entry?;
+            let stat = filestat(&entry.path().symlink_metadata()?);
+            Ok(DirEntry {
+                name: entry.file_name().to_string_lossy().into_owned(),
+                filetype: stat.filetype,
+                ino: stat.ino,
+            })
+        });
+        Ok(entries.collect::<std::io::Result<_>>()?)
+    }
+
+    fn create_dir(&self, path: &str) -> Result<(), Errno> {
+        Ok(fs::create_dir(self.path(path, false)?)?)
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<(), Errno> {
+        let path = self.path(path, false)?;
+        if !fs::symlink_metadata(&path)?.is_dir() {
+            return Err(Errno::NOTDIR);
+        }
+        if fs::read_dir(&path)?.next().is_some() {
+            return Err(Errno::NOTEMPTY);
+        }
+        Ok(fs::remove_dir(path)?)
+    }
+
+    fn remove_file(&self, path: &str) -> Result<(), Errno> {
+        let path = self.path(path, false)?;
+        if fs::symlink_metadata(&path)?.is_dir() {
+            return Err(Errno::ISDIR);
+        }
+        Ok(fs::remove_file(path)?)
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), Errno> {
+        Ok(fs::rename(self.path(from, false)?, self.path(to, false)?)?)
+    }
+
+    fn set_times(&self, path: &str, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        Ok(File::open(self.path(path, true)?)?.set_times(file_times(atim, mtim))?)
+    }
+
+    fn hard_link(&self, from: &str, to: &str) -> Result<(), Errno> {
+        Ok(fs::hard_link(self.path(from, false)?, self.path(to, false)?)?)
+    }
+
+    fn symlink(&self, target: &str, link: &str) -> Result<(), Errno> {
+        let link = self.path(link, false)?;
+        #[cfg(unix)]
+        return Ok(std::os::unix::fs::symlink(target, link)?);
+        #[cfg(not(unix))]
+        return {
+            let _ = (target, link);
+            Err(Errno::NOTSUP)
+        };
+    }
+
+    fn read_link(&self, path: &str) -> Result<String, Errno> {
+        Ok(fs::read_link(self.path(path, false)?)?.to_string_lossy().into_owned())
+    }
+}
+
+#[derive(Debug)]
+struct HostFile(File);
+
+impl WasiFile for HostFile {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+        Ok(self.0.read(buf)?)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Errno> {
+        self.0.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Errno> {
+        let pos = match pos {
+            SeekFrom::Start(offset) => std::io::SeekFrom::Start(offset),
+            SeekFrom::Current(offset) => std::io::SeekFrom::Current(offset),
+            SeekFrom::End(offset) => std::io::SeekFrom::End(offset),
+        };
+        Ok(self.0.seek(pos)?)
+    }
+
+    fn filestat(&self) -> Result<Filestat, Errno> {
+        Ok(filestat(&self.0.metadata()?))
+    }
+
+    fn set_size(&mut self, size: u64) -> Result<(), Errno> {
+        Ok(self.0.set_len(size)?)
+    }
+
+    fn set_times(&mut self, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        Ok(self.0.set_times(file_times(atim, mtim))?)
+    }
+
+    fn sync(&mut self) -> Result<(), Errno> {
+        Ok(self.0.sync_all()?)
+    }
+}
@@ -0,0 +1,434 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+
+use super::{normalize, split, DirEntry, FileSystem, OpenOptions, SeekFrom, WasiFile};
+use crate::types::{filetype, Errno, Filestat};
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Meta {
+    ino: u64,
+    atim: u64,
+    mtim: u64,
+}
+
+impl Meta {
+    fn set_times(&mut self, atim: Option<u64>, mtim: Option<u64>) {
+        self.atim = atim.unwrap_or(self.atim);
+        self.mtim = mtim.unwrap_or(self.mtim);
+    }
+}
+
+#[derive(Debug, Default)]
+struct MemFile {
+    meta: Meta,
+    data: Vec<u8>,
+    nlink: u64,
+}
+
+#[derive(Debug, Default)]
+struct MemDir {
+    meta: Meta,
+    entries: BTreeMap<String, Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    File(Rc<RefCell<MemFile>>),
+    Dir(Rc<RefCell<MemDir>>),
+}
+
+impl Node {
+    fn dir(&self) -> Result<&Rc<RefCell<MemDir>>, Errno> {
+        match self {
+            Self::Dir(dir) => Ok(dir),
+            Self::File(_) => Err(Errno::NOTDIR),
+        }
+    }
+
+    fn filestat(&self) -> Filestat {
+        match self {
+            Self::File(file) => {
+                let file = file.borrow();
+                let (ino, atim, mtim) = (file.meta.ino, file.meta.atim, file.meta.mtim);
+                let (filetype, nlink, size) = (filetype::REGULAR_FILE, file.nlink, file.data.len() as u64);
+                Filestat { dev: 0, ino, filetype, nlink, size, atim, mtim, ctim: mtim }
+            }
+            Self::Dir(dir) => {
+                let Meta { ino, atim, mtim } = dir.borrow().meta;
+                Filestat { dev: 0, ino, filetype: filetype::DIRECTORY, nlink: 1, size: 0, atim, mtim, ctim: mtim }
+            }
+        }
+    }
+}
+
+/// A filesystem that keeps all files in memory
+///
+/// Works without the standard library, so it can provide files to guests on targets without an OS filesystem.
+/// Timestamps start at `0` and only change when they are set explicitly, symbolic links are not supported.
+/// Files can't grow beyond [`MemFs::max_file_size`], which is 1 GiB by default.
+/// Cloning returns a handle to the same filesystem, so the host can keep a clone to inspect the files
+/// after the guest ran.
+///
+/// ## Example
+/// ```rust
+/// use tinywasm_wasi::fs::{MemFs, ReadOnlyFs};
+/// use tinywasm_wasi::WasiCtx;
+///
+/// let config = MemFs::new();
+/// config.write_file("app/config.toml", b"verbose = true").unwrap();
+///
+/// let mut wasi = WasiCtx::new();
+/// wasi.preopen(ReadOnlyFs::new(config), "/etc").unwrap();
+/// wasi.preopen(MemFs::new(), "/tmp").unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct MemFs {
+    root: Rc<RefCell<MemDir>>,
+    next_ino: Rc<Cell<u64>>,
+    max_file_size: Rc<Cell<usize>>,
+}
+
+const DEFAULT_MAX_FILE_SIZE: usize = 1 << 30;
+
+impl Default for MemFs {
+    fn default() -> Self {
+        let root = MemDir { meta: Meta { ino: 1, ..Default::default() }, entries: BTreeMap::new() };
+        let max_file_size = Rc::new(Cell::new(DEFAULT_MAX_FILE_SIZE));
+        Self { root: Rc::new(RefCell::new(root)), next_ino: Rc::new(Cell::new(2)), max_file_size }
+    }
+}
+
+impl MemFs {
+    /// Create an empty filesystem
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum size of a file in bytes
+    ///
+    /// Writes and resizes beyond it fail with [`Errno::FBIG`]. Applies to all clones of the filesystem.
+    pub fn max_file_size(&self, size: usize) {
+        self.max_file_size.set(size);
+    }
+
+    /// Create a filesystem with the contents of a tar archive
+    ///
+    /// Regular files, directories and hard links are extracted, other entries like symbolic links are skipped.
+    /// Supports the ustar, GNU and pax formats, compressed archives have to be decompressed first.
+    pub fn from_tar(archive: &[u8]) -> Result<Self, Errno> {
+        let fs = Self::new();
+        fs.extract_tar(archive)?;
+        Ok(fs)
+    }
+
+    /// Extract a tar archive into the filesystem, replacing existing files
+    pub fn extract_tar(&self, archive: &[u8]) -> Result<(), Errno> {
+        super::tar::extract(self, archive)
+    }
+
+    /// Create a file with the given contents, creating its parent directories if needed
+    ///
+    /// Paths are relative to the root of the filesystem, a leading `/` is ignored.
+    pub fn write_file(&self, path: &str, data: &[u8]) -> Result<(), Errno> {
+        let path = host_path(path)?;
+        self.create_dir_all(split(&path).0)?;
+        let mut file =
+            self.open(&path, OpenOptions { write: true, create: true, truncate: true, ..Default::default() })?;
+        file.write(data).map(|_| ())
+    }
+
+    /// Get the contents of a file
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, Errno> {
+        match self.lookup(&host_path(path)?)? {
+            Node::File(file) => Ok(file.borrow().data.clone()),
+            Node::Dir(_) => Err(Errno::ISDIR),
+        }
+    }
+
+    /// Create a directory and all of its missing parents
+    pub fn create_dir_all(&self, path: &str) -> Result<(), Errno> {
+        let mut dir = self.root.clone();
+        for part in normalize(&host_path(path)?)? {
+            let next = match dir.borrow().entries.get(part) {
+                Some(node) => Some(node.dir()?.clone()),
+                None => None,
+            };
+            dir = match next {
+                Some(next) => next,
+                None => {
+                    let next = Rc::new(RefCell::new(MemDir { meta: self.meta(), entries: BTreeMap::new() }));
+                    dir.borrow_mut().entries.insert(part.to_string(), Node::Dir(next.clone()));
+                    next
+                }
+            };
+        }
+        Ok(())
+    }
+
+    fn meta(&self) -> Meta {
+        let ino = self.next_ino.get();
+        self.next_ino.set(ino + 1);
+        Meta { ino, ..Default::default() }
+    }
+
+    fn lookup(&self, path: &str) -> Result<Node, Errno> {
+        let mut node = Node::Dir(self.root.clone());
+        for part in path.split('/').filter(|part| !part.is_empty()) {
+            let next = node.dir()?.borrow().entries.get(part).cloned().ok_or(Errno::NOENT)?;
+            node = next;
+        }
+        Ok(node)
+    }
+
+    // the directory containing a path and the name of the entry, which fails for the root
+    fn parent<'a>(&self, path: &'a str) -> Result<(Rc<RefCell<MemDir>>, &'a str), Errno> {
+        let (parent, name) = split(path);
+        if name.is_empty() {
+            return Err(Errno::BUSY);
+        }
+        Ok((self.lookup(parent)?.dir()?.clone(), name))
+    }
+}
+
+// paths passed by the host may start with `/`
+fn host_path(path: &str) -> Result<String, Errno> {
+    Ok(normalize(path.trim_start_matches('/'))?.join("/"))
+}
+
+impl FileSystem for MemFs {
+    fn open(&self, path: &str, options: OpenOptions) -> Result<Box<dyn WasiFile>, Errno> {
+        let file = match self.lookup(path) {
+            Ok(Node::Dir(_)) => return Err(Errno::ISDIR),
+            Ok(Node::File(_)) if options.create_new => return Err(Errno::EXIST),
+            Ok(Node::File(file)) => file,
+            Err(Errno::NOENT) if options.create || options.create_new => {
+                let (parent, name) = self.parent(path)?;
+                let file = Rc::new(RefCell::new(MemFile { meta: self.meta(), data: Vec::new(), nlink: 1 }));
+                parent.borrow_mut().entries.insert(name.to_string(), Node::File(file.clone()));
+                file
+            }
+            Err(err) => return Err(err),
+        };
+
+        if options.truncate {
+            file.borrow_mut().data.clear();
+        }
+        let max_size = self.max_file_size.clone();
+        Ok(Box::new(MemFileHandle { file, max_size, pos: 0, read: options.read, write: options.write }))
+    }
+
+    fn stat(&self, path: &str, _: bool) -> Result<Filestat, Errno> {
+        Ok(self.lookup(path)?.filestat())
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, Errno> {
+        let node = self.lookup(path)?;
+        let dir = node.dir()?.borrow();
+        let entries = dir.entries.iter().map(|(name, node)| {
+            let stat = node.filestat();
+            DirEntry { name: name.clone(), filetype: stat.filetype, ino: stat.ino }
+        });
+        Ok(entries.collect())
+    }
+
+    fn create_dir(&self, path: &str) -> Result<(), Errno> {
+        let (parent, name) = self.parent(path).map_err(|err| if err == Errno::BUSY { Errno::EXIST } else { err })?;
+        let mut parent = parent.borrow_mut();
+        if parent.entries.contains_key(name) {
+            return Err(Errno::EXIST);
+        }
+        let dir = MemDir { meta: self.meta(), entries: BTreeMap::new() };
+        parent.entries.insert(name.to_string(), Node::Dir(Rc::new(RefCell::new(dir))));
+        Ok(())
+    }
+
+    fn remove_dir(&self, path: &str) -> Result<(), Errno> {
+        let (parent, name) = self.parent(path)?;
+        let mut parent = parent.borrow_mut();
+        match parent.entries.get(name).ok_or(Errno::NOENT)? {
+            Node::File(_) => return Err(Errno::NOTDIR),
+            Node::Dir(dir) if !dir.borrow().entries.is_empty() => return Err(Errno::NOTEMPTY),
+            Node::Dir(_) => {}
+        }
+        parent.entries.remove(name);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &str) -> Result<(), Errno> {
+        let (parent, name) = self.parent(path).map_err(|err| if err == Errno::BUSY { Errno::ISDIR } else { err })?;
+        let mut parent = parent.borrow_mut();
+        match parent.entries.get(name).ok_or(Errno::NOENT)? {
+            Node::Dir(_) => Err(Errno::ISDIR),
+            Node::File(file) => {
+                file.borrow_mut().nlink -= 1;
+                parent.entries.remove(name);
+                Ok(())
+            }
+        }
+    }
+
+    fn rename(&self, from: &str, to: &str) -> Result<(), Errno> {
+        let (from_parent, from_name) = self.parent(from)?;
+        let (to_parent, to_name) = self.parent(to)?;
+        let node = from_parent.borrow().entries.get(from_name).cloned().ok_or(Errno::NOENT)?;
+
+        if from == to {
+            return Ok(());
+        }
+
+        match (&node, to_parent.borrow().entries.get(to_name)) {
+            // a directory can't be moved into itself
+            (Node::Dir(_), _) if to.starts_with(from) && to.as_bytes().get(from.len()) == Some(&b'/') => {
+                return Err(Errno::INVAL)
+            }
+            (Node::Dir(_), Some(Node::File(_))) => return Err(Errno::NOTDIR),
+            (Node::File(_), Some(Node::Dir(_))) => return Err(Errno::ISDIR),
+            (Node::Dir(_), Some(Node::Dir(dir))) if !dir.borrow().entries.is_empty() => return Err(Errno::NOTEMPTY),
+            (_, Some(Node::File(file))) => file.borrow_mut().nlink -= 1,
+            _ => {}
+        }
+
+        from_parent.borrow_mut().entries.remove(from_name);
+        to_parent.borrow_mut().entries.insert(to_name.to_string(), node);
+        Ok(())
+    }
+
+    fn set_times(&self, path: &str, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        match self.lookup(path)? {
+            Node::File(file) => file.borrow_mut().meta.set_times(atim, mtim),
+            Node::Dir(dir) => dir.borrow_mut().meta.set_times(atim, mtim),
+        }
+        Ok(())
+    }
+
+    fn hard_link(&self, from: &str, to: &str) -> Result<(), Errno> {
+        let Node::File(file) = self.lookup(from)? else { return Err(Errno::PERM) };
+        let (parent, name) = self.parent(to).map_err(|err| if err == Errno::BUSY { Errno::EXIST } else { err })?;
+        let mut parent = parent.borrow_mut();
+        if parent.entries.contains_key(name) {
+            return Err(Errno::EXIST);
+        }
+        file.borrow_mut().nlink += 1;
+        parent.entries.insert(name.to_string(), Node::File(file));
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct MemFileHandle {
+    file: Rc<RefCell<MemFile>>,
+    max_size: Rc<Cell<usize>>,
+    pos: u64,
+    read: bool,
+    write: bool,
+}
+
+impl WasiFile for MemFileHandle {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+        if !self.read {
+            return Err(Errno::BADF);
+        }
+        let file = self.file.borrow();
+        let start = (self.pos as usize).min(file.data.len());
+        let len = buf.len().min(file.data.len() - start);
+        buf[..len].copy_from_slice(&file.data[start..start + len]);
+        self.pos += len as u64;
+        Ok(len)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Errno> {
+        if !self.write {
+            return Err(Errno::BADF);
+        }
+        let mut file = self.file.borrow_mut();
+        let start = usize::try_from(self.pos).map_err(|_| Errno::FBIG)?;
+        let end = start.checked_add(buf.len()).ok_or(Errno::FBIG)?;
+        if file.data.len() < end {
+            resize(&mut file.data, end, self.max_size.get())?;
+        }
+        file.data[start..end].copy_from_slice(buf);
+        self.pos = end as u64;
+        Ok(buf.len())
+    }
+
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Errno> {
+        let len = self.file.borrow().data.len() as u64;
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+            SeekFrom::End(offset) => len.checked_add_signed(offset),
+        };
+        self.pos = pos.ok_or(Errno::INVAL)?;
+        Ok(self.pos)
+    }
+
+    fn filestat(&self) -> Result<Filestat, Errno> {
+        Ok(Node::File(self.file.clone()).filestat())
+    }
+
+    fn set_size(&mut self, size: u64) -> Result<(), Errno> {
+        if !self.write {
+            return Err(Errno::BADF);
+        }
+        let size = usize::try_from(size).map_err(|_| Errno::FBIG)?;
+        resize(&mut self.file.borrow_mut().data, size, self.max_size.get())
+    }
+
+    fn set_times(&mut self, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        self.file.borrow_mut().meta.set_times(atim, mtim);
+        Ok(())
+    }
+}
+
+// resize a file without aborting the process if the allocation fails
+fn resize(data: &mut Vec<u8>, len: usize, max_len: usize) -> Result<(), Errno> {
+    if len > max_len {
+        return Err(Errno::FBIG);
+    }
+    data.try_reserve(len.saturating_sub(data.len())).map_err(|_| Errno::NOSPC)?;
+    data.resize(len, 0);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mem_fs() {
+        let fs = MemFs::new();
+        fs.write_file("/a/b/c.txt", b"hello").unwrap();
+        fs.hard_link("a/b/c.txt", "a/link.txt").unwrap();
+        assert_eq!(fs.stat("a/link.txt", true).map(|stat| stat.nlink), Ok(2));
+
+        fs.rename("a/b", "d").unwrap();
+        assert_eq!(fs.read_file("d/c.txt").as_deref(), Ok(&b"hello"[..]));
+        assert_eq!(fs.lookup("a/b").err(), Some(Errno::NOENT));
+        assert_eq!(fs.rename("d", "d/e"), Err(Errno::INVAL));
+        assert_eq!(fs.remove_dir("d"), Err(Errno::NOTEMPTY));
+
+        let names: Vec<_> = fs.read_dir("").unwrap().into_iter().map(|entry| entry.name).collect();
+        assert_eq!(names, ["a", "d"]);
+    }
+
+    #[test]
+    fn test_max_file_size() {
+        let fs = MemFs::new();
+        let options = OpenOptions { write: true, create: true, ..Default::default() };
+        let mut file = fs.open("file", options).unwrap();
+
+        file.seek(SeekFrom::Start(1 << 44)).unwrap();
+        assert_eq!(file.write(b"x"), Err(Errno::FBIG));
+        assert_eq!(file.set_size(u64::MAX >> 1), Err(Errno::FBIG));
+
+        fs.max_file_size(4);
+        file.seek(SeekFrom::Start(0)).unwrap();
+        assert_eq!(file.write(b"abcde"), Err(Errno::FBIG));
+        assert_eq!(file.write(b"abcd"), Ok(4));
+        assert_eq!(fs.read_file("file").as_deref(), Ok(&b"abcd"[..]));
+    }
+}
@@ -0,0 +1,220 @@
+//! Filesystems that can be made available to a WASI program
+//!
+//! A [`FileSystem`] is mounted under a guest path with [`WasiCtx::preopen`](crate::WasiCtx::preopen).
+//! The guest can only access paths inside of it, so the filesystem never sees absolute paths or `..`
+//! components: paths are relative to its root, use `/` as separator and are empty for the root itself.
+//!
+//! - [`MemFs`] keeps everything in memory and can be populated from a tar archive
+//! - [`HostFs`] passes through to a directory of the host (requires the `std` feature)
+//! - [`ReadOnlyFs`] makes any filesystem read-only
+//!
+//! Standard streams are [`WasiFile`]s as well, see [`Pipe`] and the `std` adapters [`ReadStream`] and
+//! [`WriteStream`].
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::types::{filetype, Errno, Filestat};
+
+#[cfg(feature = "std")]
+mod host;
+mod mem;
+mod readonly;
+mod stream;
+mod tar;
+
+#[cfg(feature = "std")]
+pub use host::HostFs;
+pub use mem::MemFs;
+pub use readonly::ReadOnlyFs;
+pub(crate) use stream::Null;
+pub use stream::Pipe;
+#[cfg(feature = "std")]
+pub use stream::{ReadStream, WriteStream};
+
+/// A position to seek to in a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeekFrom {
+    /// An offset from the start of the file
+    Start(u64),
+    /// An offset from the current position
+    Current(i64),
+    /// An offset from the end of the file
+    End(i64),
+}
+
+/// How a file is opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpenOptions {
+    /// Open the file for reading
+    pub read: bool,
+    /// Open the file for writing
+    pub write: bool,
+    /// Create the file if it doesn't exist
+    pub create: bool,
+    /// Create the file, failing if it already exists
+    pub create_new: bool,
+    /// Truncate the file to a length of zero
+    pub truncate: bool,
+}
+
+/// An entry of a directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    /// The name of the entry
+    pub name: String,
+    /// The type of the entry, see [`filetype`]
+    pub filetype: u8,
+    /// The inode number of the entry, or `0` if unknown
+    pub ino: u64,
+}
+
+/// An open file or stream
+///
+/// Only reading and writing are required, the other operations are rejected by default the way they are
+/// for a character device.
+pub trait WasiFile {
+    /// Read into `buf`, returning the number of bytes read or `0` at the end of the file
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno>;
+
+    /// Write from `buf`, returning the number of bytes written
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Errno>;
+
+    /// Change the position in the file, returning the new position
+    fn seek(&mut self, pos: SeekFrom) -> Result<u64, Errno> {
+        let _ = pos;
+        Err(Errno::SPIPE)
+    }
+
+    /// Get the attributes of the file
+    fn filestat(&self) -> Result<Filestat, Errno> {
+        Ok(Filestat { filetype: filetype::CHARACTER_DEVICE, nlink: 1, ..Default::default() })
+    }
+
+    /// Truncate or extend the file to `size` bytes
+    fn set_size(&mut self, size: u64) -> Result<(), Errno> {
+        let _ = size;
+        Err(Errno::INVAL)
+    }
+
+    /// Set the access and modification times in nanoseconds since the Unix epoch, `None` leaves them unchanged
+    fn set_times(&mut self, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        let _ = (atim, mtim);
+        Err(Errno::NOTSUP)
+    }
+
+    /// Flush buffered data to the underlying storage
+    fn sync(&mut self) -> Result<(), Errno> {
+        Ok(())
+    }
+}
+
+/// A directory tree that can be preopened for a WASI program
+///
+/// Paths are relative to the root of the filesystem, see the [module documentation](self).
+/// Operations that are not supported by default return [`Errno::NOTSUP`].
+pub trait FileSystem {
+    /// Open a file, directories are not opened through this
+    fn open(&self, path: &str, options: OpenOptions) -> Result<Box<dyn WasiFile>, Errno>;
+
+    /// Get the attributes of a file or directory
+    fn stat(&self, path: &str, follow_symlinks: bool) -> Result<Filestat, Errno>;
+
+    /// List the entries of a directory, without `.` and `..`
+    fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, Errno>;
+
+    /// Create a directory, its parent has to exist
+    fn create_dir(&self, path: &str) -> Result<(), Errno>;
+
+    /// Remove an empty directory
+    fn remove_dir(&self, path: &str) -> Result<(), Errno>;
+
+    /// Remove a file or symbolic link
+    fn remove_file(&self, path: &str) -> Result<(), Errno>;
+
+    /// Move a file or directory, replacing the destination if it exists
+    fn rename(&self, from: &str, to: &str) -> Result<(), Errno>;
+
+    /// Set the access and modification times in nanoseconds since the Unix epoch, `None` leaves them unchanged
+    fn set_times(&self, path: &str, atim: Option<u64>, mtim: Option<u64>) -> Result<(), Errno> {
+        let _ = (path, atim, mtim);
+        Err(Errno::NOTSUP)
+    }
+
+    /// Create a hard link to a file
+    fn hard_link(&self, from: &str, to: &str) -> Result<(), Errno> {
+        let _ = (from, to);
+        Err(Errno::NOTSUP)
+    }
+
+    /// Create a symbolic link at `link` pointing to `target`
+    fn symlink(&self, target: &str, link: &str) -> Result<(), Errno> {
+        let _ = (target, link);
+        Err(Errno::NOTSUP)
+    }
+
+    /// Read the target of a symbolic link
+    fn read_link(&self, path: &str) -> Result<String, Errno> {
+        self.stat(path, false)?;
+        Err(Errno::INVAL)
+    }
+}
+
+/// Normalize a relative guest path, removing `.` and resolving `..`
+///
+/// Absolute paths and `..` components that would leave the directory are rejected. This is a lexical check,
+/// symbolic links are handled by the filesystem.
+pub(crate) fn normalize(path: &str) -> Result<Vec<&str>, Errno> {
+    if path.contains('\0') {
+        return Err(Errno::ILSEQ);
+    }
+    if path.starts_with('/') {
+        return Err(Errno::NOTCAPABLE);
+    }
+
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop().ok_or(Errno::NOTCAPABLE)?;
+            }
+            part => parts.push(part),
+        }
+    }
+    Ok(parts)
+}
+
+/// Join a guest path onto a directory of a filesystem without leaving it
+pub(crate) fn join(base: &str, path: &str) -> Result<String, Errno> {
+    let mut joined = String::from(base);
+    for part in normalize(path)? {
+        if !joined.is_empty() {
+            joined.push('/');
+        }
+        joined.push_str(part);
+    }
+    Ok(joined)
+}
+
+/// Split a path into its parent directory and file name
+pub(crate) fn split(path: &str) -> (&str, &str) {
+    path.rsplit_once('/').unwrap_or(("", path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join() {
+        assert_eq!(join("sandbox", "a/./b/../c.txt").as_deref(), Ok("sandbox/a/c.txt"));
+        assert_eq!(join("", "a//b/").as_deref(), Ok("a/b"));
+        assert_eq!(join("sandbox", ".").as_deref(), Ok("sandbox"));
+        assert_eq!(join("sandbox", "a/../.."), Err(Errno::NOTCAPABLE));
+        assert_eq!(join("", "/etc/passwd"), Err(Errno::NOTCAPABLE));
+        assert_eq!(split("a/b/c"), ("a/b", "c"));
+        assert_eq!(split("c"), ("", "c"));
+    }
+}
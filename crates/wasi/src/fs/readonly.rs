@@ -0,0 +1,74 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{DirEntry, FileSystem, OpenOptions, WasiFile};
+use crate::types::{Errno, Filestat};
+
+/// A read-only view of another filesystem
+///
+/// Files can only be opened for reading, everything that would modify the filesystem fails with
+/// [`Errno::ROFS`]. Useful to share configuration or assets with a guest without letting it change them.
+#[derive(Debug, Clone)]
+pub struct ReadOnlyFs<F>(F);
+
+impl<F: FileSystem> ReadOnlyFs<F> {
+    /// Wrap a filesystem
+    pub fn new(fs: F) -> Self {
+        Self(fs)
+    }
+
+    /// Get the wrapped filesystem
+    pub fn into_inner(self) -> F {
+        self.0
+    }
+}
+
+impl<F: FileSystem> FileSystem for ReadOnlyFs<F> {
+    fn open(&self, path: &str, options: OpenOptions) -> Result<Box<dyn WasiFile>, Errno> {
+        if options.write || options.create || options.create_new || options.truncate {
+            return Err(Errno::ROFS);
+        }
+        self.0.open(path, options)
+    }
+
+    fn stat(&self, path: &str, follow_symlinks: bool) -> Result<Filestat, Errno> {
+        self.0.stat(path, follow_symlinks)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<DirEntry>, Errno> {
+        self.0.read_dir(path)
+    }
+
+    fn create_dir(&self, _: &str) -> Result<(), Errno> {
+        Err(Errno::ROFS)
+    }
+
+    fn remove_dir(&self, _: &str) -> Result<(), Errno> {
+        Err(Errno::ROFS)
+    }
+
+    fn remove_file(&self, _: &str) -> Result<(), Errno> {
+        Err(Errno::ROFS)
+    }
+
+    fn rename(&self, _: &str, _: &str) -> Result<(), Errno> {
+        Err(Errno::ROFS)
+    }
+
+    fn set_times(&self, _: &str, _: Option<u64>, _: Option<u64>) -> Result<(), Errno> {
+        Err(Errno::ROFS)
+    }
+
+    fn hard_link(&self, _: &str, _: &str) -> Result<(), Errno> {
+        Err(Errno::ROFS)
+    }
+
+    fn symlink(&self, _: &str, _: &str) -> Result<(), Errno> {
+        Err(Errno::ROFS)
+    }
+
+    fn read_link(&self, path: &str) -> Result<String, Errno> {
+        self.0.read_link(path)
+    }
+}
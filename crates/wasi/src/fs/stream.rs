@@ -0,0 +1,110 @@
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use super::WasiFile;
+use crate::types::Errno;
+
+/// An in-memory byte stream, e.g. to provide stdin or capture stdout
+///
+/// Reads take bytes from the front and writes append to the back. Cloning returns a handle to the same
+/// buffer, so the host can keep a clone to inspect what the guest wrote.
+#[derive(Debug, Clone, Default)]
+pub struct Pipe(Rc<RefCell<VecDeque<u8>>>);
+
+impl Pipe {
+    /// Create an empty pipe
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the buffered bytes without consuming them
+    pub fn contents(&self) -> Vec<u8> {
+        self.0.borrow().iter().copied().collect()
+    }
+
+    /// Take all buffered bytes
+    pub fn take(&self) -> Vec<u8> {
+        self.0.borrow_mut().drain(..).collect()
+    }
+}
+
+impl From<&[u8]> for Pipe {
+    fn from(data: &[u8]) -> Self {
+        Self(Rc::new(RefCell::new(data.iter().copied().collect())))
+    }
+}
+
+impl From<Vec<u8>> for Pipe {
+    fn from(data: Vec<u8>) -> Self {
+        Self(Rc::new(RefCell::new(data.into())))
+    }
+}
+
+impl WasiFile for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+        let mut data = self.0.borrow_mut();
+        let len = buf.len().min(data.len());
+        for (dst, src) in buf.iter_mut().zip(data.drain(..len)) {
+            *dst = src;
+        }
+        Ok(len)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Errno> {
+        self.0.borrow_mut().extend(buf);
+        Ok(buf.len())
+    }
+}
+
+// the default standard streams, which are empty and discard output
+#[derive(Debug)]
+pub(crate) struct Null;
+
+impl WasiFile for Null {
+    fn read(&mut self, _: &mut [u8]) -> Result<usize, Errno> {
+        Ok(0)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Errno> {
+        Ok(buf.len())
+    }
+}
+
+/// A readable stream backed by a [`std::io::Read`], e.g. the stdin of the host
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ReadStream<R>(pub R);
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> WasiFile for ReadStream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Errno> {
+        Ok(self.0.read(buf)?)
+    }
+
+    fn write(&mut self, _: &[u8]) -> Result<usize, Errno> {
+        Err(Errno::BADF)
+    }
+}
+
+/// A writable stream backed by a [`std::io::Write`], e.g. the stdout of the host
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct WriteStream<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W: std::io::Write> WasiFile for WriteStream<W> {
+    fn read(&mut self, _: &mut [u8]) -> Result<usize, Errno> {
+        Err(Errno::BADF)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Errno> {
+        self.0.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn sync(&mut self) -> Result<(), Errno> {
+        Ok(self.0.flush()?)
+    }
+}
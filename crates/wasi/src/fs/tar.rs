@@ -0,0 +1,120 @@
+//! A minimal tar reader, which works without the standard library
+//!
+//! See <https://www.gnu.org/software/tar/manual/html_node/Standard.html> and
+//! <https://pubs.opengroup.org/onlinepubs/9699919799/utilities/pax.html>
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use super::{FileSystem, MemFs};
+use crate::types::Errno;
+
+const BLOCK: usize = 512;
+
+pub(crate) fn extract(fs: &MemFs, archive: &[u8]) -> Result<(), Errno> {
+    let mut offset = 0;
+    let mut long_name = None;
+    let mut long_link = None;
+
+    while let Some(header) = archive.get(offset..offset + BLOCK) {
+        // the archive ends with two zero blocks
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        verify_checksum(header)?;
+
+        let size = usize::try_from(number(&header[124..136])?).map_err(|_| Errno::FBIG)?;
+        let start = offset + BLOCK;
+        let data = archive.get(start..start.checked_add(size).ok_or(Errno::INVAL)?).ok_or(Errno::INVAL)?;
+        offset = start + size.div_ceil(BLOCK) * BLOCK;
+
+        let name = match long_name.take() {
+            Some(name) => name,
+            None if &header[257..262] == b"ustar" && header[345] != 0 => {
+                let mut name = Vec::from(cstr(&header[345..500]));
+                name.push(b'/');
+                name.extend(cstr(&header[..100]));
+                name
+            }
+            None => cstr(&header[..100]).into(),
+        };
+        let link = long_link.take().unwrap_or_else(|| cstr(&header[157..257]).into());
+
+        match header[156] {
+            // GNU long names and links
+            b'L' => long_name = Some(cstr(data).into()),
+            b'K' => long_link = Some(cstr(data).into()),
+            // pax extended headers for the next entry
+            b'x' => {
+                for (key, value) in pax_records(data)? {
+                    match key {
+                        b"path" => long_name = Some(value.into()),
+                        b"linkpath" => long_link = Some(value.into()),
+                        _ => {}
+                    }
+                }
+            }
+            b'0' | b'\0' | b'7' => fs.write_file(&path(name)?, data)?,
+            b'5' => fs.create_dir_all(&path(name)?)?,
+            b'1' => {
+                let (from, to) = (path(link)?, path(name)?);
+                let _ = fs.remove_file(&to);
+                fs.hard_link(&from, &to)?;
+            }
+            // symbolic links, devices, global pax headers and other entries are skipped
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn path(name: Vec<u8>) -> Result<String, Errno> {
+    let name = String::from_utf8(name).map_err(|_| Errno::ILSEQ)?;
+    let parts = super::normalize(name.trim_start_matches('/'))?;
+    Ok(parts.join("/"))
+}
+
+fn cstr(bytes: &[u8]) -> &[u8] {
+    bytes.iter().position(|&b| b == 0).map_or(bytes, |end| &bytes[..end])
+}
+
+// octal, or big-endian binary if the high bit is set (GNU extension for large files)
+fn number(field: &[u8]) -> Result<u64, Errno> {
+    if field[0] & 0x80 != 0 {
+        let bytes = &field[field.len() - 8..];
+        return Ok(bytes.iter().fold(0, |acc, &b| acc << 8 | b as u64));
+    }
+
+    let mut digits = cstr(field).iter().filter(|b| !b.is_ascii_whitespace());
+    digits.try_fold(0u64, |acc, &b| match b {
+        b'0'..=b'7' => acc.checked_mul(8).map(|acc| acc + (b - b'0') as u64).ok_or(Errno::OVERFLOW),
+        _ => Err(Errno::INVAL),
+    })
+}
+
+// the checksum is the sum of the header bytes, with the checksum field itself counted as spaces
+fn verify_checksum(header: &[u8]) -> Result<(), Errno> {
+    let expected = number(&header[148..156])?;
+    let sum: u64 = header.iter().enumerate().map(|(i, &b)| if (148..156).contains(&i) { 32 } else { b as u64 }).sum();
+    match sum == expected {
+        true => Ok(()),
+        false => Err(Errno::ILSEQ),
+    }
+}
+
+type PaxRecord<'a> = (&'a [u8], &'a [u8]);
+
+// records have the form `<length> <key>=<value>\n`, where the length includes the whole record
+fn pax_records(mut data: &[u8]) -> Result<Vec<PaxRecord<'_>>, Errno> {
+    let mut records = Vec::new();
+    while !data.is_empty() && data[0] != 0 {
+        let space = data.iter().position(|&b| b == b' ').ok_or(Errno::INVAL)?;
+        let len = core::str::from_utf8(&data[..space]).ok().and_then(|len| len.parse().ok()).ok_or(Errno::INVAL)?;
+        let record = data.get(space + 1..len).and_then(|record| record.strip_suffix(b"\n")).ok_or(Errno::INVAL)?;
+        let eq = record.iter().position(|&b| b == b'=').ok_or(Errno::INVAL)?;
+        records.push((&record[..eq], &record[eq + 1..]));
+        data = &data[len..];
+    }
+    Ok(records)
+}
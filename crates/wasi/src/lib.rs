@@ -0,0 +1,53 @@
+#![warn(missing_docs, missing_debug_implementations, rust_2018_idioms, unreachable_pub)]
+#![forbid(unsafe_code)]
+#![no_std]
+
+//! WASI support for [`tinywasm`](https://docs.rs/tinywasm)
+//!
+//! Implements [WASI preview 1](https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/docs.md)
+//! (`wasi_snapshot_preview1`), which is used by the `wasm32-wasip1` Rust target, `wasi-libc` and most
+//! other toolchains targeting WASI. See [`WasiCtx`] for how to configure and run a program.
+//!
+//! With the `preview2` feature, components targeting the `wasi:cli/command` world of WASI 0.2 (e.g. built for the
+//! `wasm32-wasip2` Rust target) can be run with [`WasiCtx::run_component`]. They share the arguments, environment,
+//! standard streams and preopened directories of the context.
+//!
+//! Guests can access preopened directories, but nothing outside of them. Directories are provided by a
+//! [`FileSystem`](fs::FileSystem), which can be a host directory, an in-memory filesystem or a tar archive,
+//! see the [`fs`] module. Sockets are not supported. When the guest exits, the call fails with a [`ProcExit`] error.
+//!
+//! ## Features
+//!- **`std`**\
+//!  Enables host directories and the standard streams, clocks and randomness of the host. This is enabled by default.
+//!- **`emscripten`**\
+//!  Enables the `env` imports of modules built by Emscripten, see [`emscripten`].
+//!- **`preview2`**\
+//!  Enables the WASI 0.2 interfaces for components, see [`WasiCtx::add_to_component_imports`].
+//!
+//! Clocks and randomness are provided by [`WasiClocks`] and [`WasiRandom`]. Without `std`, this crate only depends
+//! on `alloc`. Unless the store is in [deterministic mode](tinywasm::deterministic) or they are set with
+//! [`WasiCtx::clocks`] and [`WasiCtx::random`], clocks return [`Errno::NOTSUP`] and `random_get` returns
+//! [`Errno::NOSYS`], but programs can still read and write files in a [`MemFs`](fs::MemFs).
+
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+mod ctx;
+#[cfg(feature = "emscripten")]
+pub mod emscripten;
+pub mod fs;
+mod preview1;
+#[cfg(feature = "preview2")]
+mod preview2;
+mod system;
+pub mod types;
+
+pub use ctx::{ProcExit, WasiCtx};
+#[cfg(feature = "std")]
+pub use system::{SystemClocks, SystemRandom};
+pub use system::{WasiClocks, WasiRandom};
+pub use types::Errno;
+
+/// The module name of the preview 1 imports
+pub const MODULE: &str = "wasi_snapshot_preview1";
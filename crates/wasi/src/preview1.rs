@@ -0,0 +1,867 @@
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+
+use tinywasm::types::{FuncType, ValType, WasmValue};
+use tinywasm::{Error, Extern, FuncContext, MemoryRefMut, WasmPod, WasmPtr};
+
+use crate::ctx::{Descriptor, WasiState};
+use crate::fs::{FileSystem, OpenOptions, SeekFrom, WasiFile};
+use crate::types::*;
+use crate::{ProcExit, WasiCtx};
+
+pub(crate) type Memory<'a, 'b> = &'a mut MemoryRefMut<'b>;
+type HostFn = fn(&mut WasiState, Memory<'_, '_>, &[WasmValue]) -> Result<(), Errno>;
+
+macro_rules! funcs {
+    ($($name:ident($($ty:ident),*)),* $(,)?) => {
+        [$((stringify!($name), &[$(ValType::$ty),*] as &[ValType], $name as HostFn)),*]
+    };
+}
+
+// every function returns an errno, except `proc_exit` which is defined separately
+const FUNCS: [(&str, &[ValType], HostFn); 45] = funcs![
+    args_get(I32, I32),
+    args_sizes_get(I32, I32),
+    environ_get(I32, I32),
+    environ_sizes_get(I32, I32),
+    clock_res_get(I32, I32),
+    clock_time_get(I32, I64, I32),
+    fd_advise(I32, I64, I64, I32),
+    fd_allocate(I32, I64, I64),
+    fd_close(I32),
+    fd_datasync(I32),
+    fd_fdstat_get(I32, I32),
+    fd_fdstat_set_flags(I32, I32),
+    fd_fdstat_set_rights(I32, I64, I64),
+    fd_filestat_get(I32, I32),
+    fd_filestat_set_size(I32, I64),
+    fd_filestat_set_times(I32, I64, I64, I32),
+    fd_pread(I32, I32, I32, I64, I32),
+    fd_prestat_get(I32, I32),
+    fd_prestat_dir_name(I32, I32, I32),
+    fd_pwrite(I32, I32, I32, I64, I32),
+    fd_read(I32, I32, I32, I32),
+    fd_readdir(I32, I32, I32, I64, I32),
+    fd_renumber(I32, I32),
+    fd_seek(I32, I64, I32, I32),
+    fd_sync(I32),
+    fd_tell(I32, I32),
+    fd_write(I32, I32, I32, I32),
+    path_create_directory(I32, I32, I32),
+    path_filestat_get(I32, I32, I32, I32, I32),
+    path_filestat_set_times(I32, I32, I32, I32, I64, I64, I32),
+    path_link(I32, I32, I32, I32, I32, I32, I32),
+    path_open(I32, I32, I32, I32, I32, I64, I64, I32, I32),
+    path_readlink(I32, I32, I32, I32, I32, I32),
+    path_remove_directory(I32, I32, I32),
+    path_rename(I32, I32, I32, I32, I32, I32),
+    path_symlink(I32, I32, I32, I32, I32),
+    path_unlink_file(I32, I32, I32),
+    poll_oneoff(I32, I32, I32, I32),
+    proc_raise(I32),
+    sched_yield(),
+    random_get(I32, I32),
+    sock_accept(I32, I32, I32),
+    sock_recv(I32, I32, I32, I32, I32, I32),
+    sock_send(I32, I32, I32, I32, I32),
+    sock_shutdown(I32, I32),
+];
+
+pub(crate) fn externs(ctx: &WasiCtx) -> Vec<(&'static str, Extern)> {
+    let mut externs: Vec<_> = FUNCS
+        .iter()
+        .map(|&(name, params, func)| {
+            let ty = FuncType { params: params.into(), results: [ValType::I32].into() };
+            let ctx = ctx.clone();
+            let func = Extern::func(&ty, move |mut fctx, args| {
+                let errno = with_state(&ctx, &mut fctx, |state, memory| func(state, memory, args))?;
+                Ok(vec![WasmValue::I32(errno.err().unwrap_or(Errno::SUCCESS).raw() as i32)])
+            });
+            (name, func)
+        })
+        .collect();
+
+    let ctx = ctx.clone();
+    let ty = FuncType { params: [ValType::I32].into(), results: [].into() };
+    let proc_exit = Extern::func(&ty, move |_, args| {
+        let code = arg32(args, 0) as i32;
+        ctx.0.borrow_mut().exit_code = Some(code);
+        Err(Error::host(ProcExit { code }))
+    });
+
+    externs.push(("proc_exit", proc_exit));
+    externs
+}
+
+// run a host function with the state and the exported memory, lending it the store's deterministic environment
+pub(crate) fn with_state<R>(
+    ctx: &WasiCtx,
+    fctx: &mut FuncContext<'_>,
+    func: impl FnOnce(&mut WasiState, Memory<'_, '_>) -> R,
+) -> tinywasm::Result<R> {
+    let mut state = ctx.0.borrow_mut();
+    state.deterministic = fctx.store_mut().deterministic_env().cloned();
+
+    let mut memory = fctx.exported_memory_mut("memory")?;
+    let result = func(&mut state, &mut memory);
+
+    if let (Some(env), Some(store_env)) = (state.deterministic.take(), fctx.store_mut().deterministic_env()) {
+        *store_env = env;
+    }
+    Ok(result)
+}
+
+pub(crate) fn arg32(args: &[WasmValue], idx: usize) -> u32 {
+    i32::try_from(args[idx]).unwrap_or_default() as u32
+}
+
+pub(crate) fn arg64(args: &[WasmValue], idx: usize) -> u64 {
+    i64::try_from(args[idx]).unwrap_or_default() as u64
+}
+
+fn read_str(memory: Memory<'_, '_>, ptr: u32, len: u32) -> Result<String, Errno> {
+    let bytes = memory.load_vec(ptr as usize, len as usize)?;
+    String::from_utf8(bytes).map_err(|_| Errno::ILSEQ)
+}
+
+pub(crate) fn write<T: WasmPod>(memory: Memory<'_, '_>, ptr: u32, value: T) -> Result<(), Errno> {
+    Ok(memory.write(ptr as usize, value)?)
+}
+
+pub(crate) fn store(memory: Memory<'_, '_>, ptr: u32, data: &[u8]) -> Result<(), Errno> {
+    Ok(memory.store(ptr as usize, data.len(), data)?)
+}
+
+// the guest pointer `offset` bytes after `ptr`, pointers past the end of a 4 GiB memory are a fault
+pub(crate) fn ptr_add(ptr: u32, offset: u32) -> Result<u32, Errno> {
+    ptr.checked_add(offset).ok_or(Errno::FAULT)
+}
+
+// a host buffer for a guest buffer, which is checked first so guests can't make the host allocate more than
+// the size of their memory
+fn buffer(memory: Memory<'_, '_>, ptr: u32, len: u32) -> Result<Vec<u8>, Errno> {
+    memory.load(ptr as usize, len as usize)?;
+    Ok(vec![0; len as usize])
+}
+
+fn iovecs(memory: Memory<'_, '_>, ptr: u32, len: u32) -> Result<Vec<Iovec>, Errno> {
+    Ok(WasmPtr::<Iovec>::new(ptr).slice(len).read_vec(&*memory)?)
+}
+
+// write null-terminated strings and a table of pointers to them
+fn write_strings(memory: Memory<'_, '_>, strings: &[String], ptrs: u32, buf: u32) -> Result<(), Errno> {
+    let mut offset = Some(buf);
+    for (i, string) in strings.iter().enumerate() {
+        let start = offset.ok_or(Errno::FAULT)?;
+        write(memory, ptr_add(ptrs, (i as u32).checked_mul(4).ok_or(Errno::FAULT)?)?, start)?;
+        store(memory, start, string.as_bytes())?;
+        let end = ptr_add(start, u32::try_from(string.len()).map_err(|_| Errno::FAULT)?)?;
+        write(memory, end, 0u8)?;
+        offset = end.checked_add(1);
+    }
+    Ok(())
+}
+
+fn write_sizes(memory: Memory<'_, '_>, strings: &[String], count: u32, size: u32) -> Result<(), Errno> {
+    write(memory, count, strings.len() as u32)?;
+    write(memory, size, strings.iter().map(|s| s.len() as u32 + 1).sum::<u32>())
+}
+
+fn environ(state: &WasiState) -> Vec<String> {
+    state.env.iter().map(|(key, value)| format!("{key}={value}")).collect()
+}
+
+fn args_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    write_strings(memory, &state.args, arg32(args, 0), arg32(args, 1))
+}
+
+fn args_sizes_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    write_sizes(memory, &state.args, arg32(args, 0), arg32(args, 1))
+}
+
+fn environ_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    write_strings(memory, &environ(state), arg32(args, 0), arg32(args, 1))
+}
+
+fn environ_sizes_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    write_sizes(memory, &environ(state), arg32(args, 0), arg32(args, 1))
+}
+
+// the virtual clock is used in deterministic mode
+pub(crate) fn now(state: &mut WasiState, clock: u32) -> Result<u64, Errno> {
+    match (clock, state.deterministic.as_mut()) {
+        (clockid::REALTIME, Some(env)) => Ok(env.wall_time()),
+        (clockid::MONOTONIC | clockid::PROCESS_CPUTIME | clockid::THREAD_CPUTIME, Some(env)) => {
+            Ok(env.monotonic_time())
+        }
+        _ => host_now(state, clock),
+    }
+}
+
+fn host_now(state: &WasiState, clock: u32) -> Result<u64, Errno> {
+    match clock {
+        clockid::REALTIME => state.clocks.wall_time(),
+        clockid::MONOTONIC | clockid::PROCESS_CPUTIME | clockid::THREAD_CPUTIME => state.clocks.monotonic_time(),
+        _ => Err(Errno::INVAL),
+    }
+}
+
+fn clock_res_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    // reading the virtual clock would advance it
+    let clock = arg32(args, 0);
+    let resolution = match &state.deterministic {
+        Some(env) if clock <= clockid::THREAD_CPUTIME => env.config().clock_step.max(1),
+        Some(_) => return Err(Errno::INVAL),
+        None => host_now(state, clock).map(|_| state.clocks.resolution())?,
+    };
+    write(memory, arg32(args, 1), resolution)
+}
+
+fn clock_time_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let time = now(state, arg32(args, 0))?;
+    write(memory, arg32(args, 2), time)
+}
+
+pub(crate) fn file(state: &mut WasiState, fd: u32) -> Result<&mut Box<dyn WasiFile>, Errno> {
+    match state.fd(fd)? {
+        Descriptor::File { file, .. } => Ok(file),
+        Descriptor::Dir { .. } => Err(Errno::ISDIR),
+    }
+}
+
+pub(crate) fn write_all(file: &mut dyn WasiFile, mut data: &[u8]) -> Result<(), Errno> {
+    while !data.is_empty() {
+        match file.write(data)? {
+            0 => return Err(Errno::IO),
+            written => data = &data[written..],
+        }
+    }
+    Ok(())
+}
+
+fn fd_advise(state: &mut WasiState, _: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    file(state, arg32(args, 0)).map(|_| ())
+}
+
+fn fd_allocate(state: &mut WasiState, _: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let file = file(state, arg32(args, 0))?;
+    let len = arg64(args, 1).checked_add(arg64(args, 2)).ok_or(Errno::FBIG)?;
+    if file.filestat()?.size < len {
+        file.set_size(len)?;
+    }
+    Ok(())
+}
+
+fn fd_close(state: &mut WasiState, _: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    state.fds.remove(&arg32(args, 0)).map(|_| ()).ok_or(Errno::BADF)
+}
+
+fn fd_datasync(state: &mut WasiState, _: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    match state.fd(arg32(args, 0))? {
+        Descriptor::File { file, .. } => file.sync(),
+        Descriptor::Dir { .. } => Ok(()),
+    }
+}
+
+fn fd_sync(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    fd_datasync(state, memory, args)
+}
+
+fn fd_fdstat_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (filetype, flags, rights_base, rights_inheriting) = match state.fd(arg32(args, 0))? {
+        Descriptor::File { file, rights, append } => {
+            (file.filestat()?.filetype, if *append { fdflags::APPEND } else { 0 }, *rights, 0)
+        }
+        Descriptor::Dir { .. } => (filetype::DIRECTORY, 0, rights::ALL, rights::ALL),
+    };
+    write(memory, arg32(args, 1), Fdstat { filetype, flags, rights_base, rights_inheriting })
+}
+
+fn fd_fdstat_set_flags(state: &mut WasiState, _: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let flags = arg32(args, 1) as u16;
+    match state.fd(arg32(args, 0))? {
+        Descriptor::File { append, .. } if flags & !fdflags::APPEND == 0 => {
+            *append = flags & fdflags::APPEND != 0;
+            Ok(())
+        }
+        _ if flags == 0 => Ok(()),
+        _ => Err(Errno::NOTSUP),
+    }
+}
+
+fn fd_fdstat_set_rights(state: &mut WasiState, _: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    state.fd(arg32(args, 0)).map(|_| ())
+}
+
+fn fd_filestat_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let stat = match state.fd(arg32(args, 0))? {
+        Descriptor::File { file, .. } => file.filestat()?,
+        Descriptor::Dir { fs, path, .. } => fs.stat(path, true)?,
+    };
+    write(memory, arg32(args, 1), stat)
+}
+
+fn fd_filestat_set_size(state: &mut WasiState, _: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    file(state, arg32(args, 0))?.set_size(arg64(args, 1))
+}
+
+type Times = (Option<u64>, Option<u64>);
+
+fn file_times(state: &mut WasiState, atim: u64, mtim: u64, flags: u16) -> Result<Times, Errno> {
+    let mut time = |value: u64, set: u16, now_flag: u16| match (flags & set != 0, flags & now_flag != 0) {
+        (true, true) => Err(Errno::INVAL),
+        (true, false) => Ok(Some(value)),
+        (false, true) => now(state, clockid::REALTIME).map(Some),
+        (false, false) => Ok(None),
+    };
+    Ok((time(atim, fstflags::ATIM, fstflags::ATIM_NOW)?, time(mtim, fstflags::MTIM, fstflags::MTIM_NOW)?))
+}
+
+fn fd_filestat_set_times(state: &mut WasiState, _: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (atim, mtim) = file_times(state, arg64(args, 1), arg64(args, 2), arg32(args, 3) as u16)?;
+    match state.fd(arg32(args, 0))? {
+        Descriptor::File { file, .. } => file.set_times(atim, mtim),
+        Descriptor::Dir { fs, path, .. } => fs.set_times(path, atim, mtim),
+    }
+}
+
+fn read_iovecs(reader: &mut dyn WasiFile, memory: Memory<'_, '_>, iovs: u32, iovs_len: u32) -> Result<u32, Errno> {
+    let mut total = 0;
+    for iovec in iovecs(memory, iovs, iovs_len)? {
+        let mut buf = buffer(memory, iovec.buf.offset(), iovec.len)?;
+        let read = reader.read(&mut buf)?;
+        store(memory, iovec.buf.offset(), &buf[..read])?;
+        total += read as u32;
+        if read < buf.len() {
+            break;
+        }
+    }
+    Ok(total)
+}
+
+fn gather_iovecs(memory: Memory<'_, '_>, iovs: u32, iovs_len: u32) -> Result<Vec<u8>, Errno> {
+    let mut data = Vec::new();
+    for iovec in iovecs(memory, iovs, iovs_len)? {
+        data.extend(iovec.buf.slice(iovec.len).read_vec(&*memory)?);
+    }
+    Ok(data)
+}
+
+fn fd_pread(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let file = file(state, arg32(args, 0))?;
+    let pos = file.seek(SeekFrom::Current(0))?;
+    file.seek(SeekFrom::Start(arg64(args, 3)))?;
+    let res = read_iovecs(file.as_mut(), memory, arg32(args, 1), arg32(args, 2));
+    file.seek(SeekFrom::Start(pos))?;
+    write(memory, arg32(args, 4), res?)
+}
+
+fn fd_pwrite(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let data = gather_iovecs(memory, arg32(args, 1), arg32(args, 2))?;
+    let file = file(state, arg32(args, 0))?;
+    let pos = file.seek(SeekFrom::Current(0))?;
+    file.seek(SeekFrom::Start(arg64(args, 3)))?;
+    let res = write_all(file.as_mut(), &data);
+    file.seek(SeekFrom::Start(pos))?;
+    res?;
+    write(memory, arg32(args, 4), data.len() as u32)
+}
+
+fn preopen(state: &mut WasiState, fd: u32) -> Result<&str, Errno> {
+    match state.fd(fd)? {
+        Descriptor::Dir { preopen: Some(name), .. } => Ok(name),
+        _ => Err(Errno::BADF),
+    }
+}
+
+fn fd_prestat_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let name_len = preopen(state, arg32(args, 0))?.len() as u32;
+    write(memory, arg32(args, 1), Prestat { tag: PREOPENTYPE_DIR, name_len })
+}
+
+fn fd_prestat_dir_name(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let name = preopen(state, arg32(args, 0))?;
+    if name.len() > arg32(args, 2) as usize {
+        return Err(Errno::NAMETOOLONG);
+    }
+    store(memory, arg32(args, 1), name.as_bytes())
+}
+
+fn fd_read(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let read = match state.fd(arg32(args, 0))? {
+        Descriptor::File { rights, .. } if *rights & rights::FD_READ == 0 => return Err(Errno::BADF),
+        Descriptor::File { file, .. } => read_iovecs(file.as_mut(), memory, arg32(args, 1), arg32(args, 2))?,
+        Descriptor::Dir { .. } => return Err(Errno::ISDIR),
+    };
+    write(memory, arg32(args, 3), read)
+}
+
+fn fd_write(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let data = gather_iovecs(memory, arg32(args, 1), arg32(args, 2))?;
+    match state.fd(arg32(args, 0))? {
+        Descriptor::File { rights, .. } if *rights & rights::FD_WRITE == 0 => return Err(Errno::BADF),
+        Descriptor::File { file, append, .. } => {
+            if *append {
+                file.seek(SeekFrom::End(0))?;
+            }
+            write_all(file.as_mut(), &data)?;
+        }
+        Descriptor::Dir { .. } => return Err(Errno::ISDIR),
+    };
+    write(memory, arg32(args, 3), data.len() as u32)
+}
+
+fn fd_readdir(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let Descriptor::Dir { fs, path, .. } = state.fd(arg32(args, 0))? else { return Err(Errno::NOTDIR) };
+    let (buf, buf_len, cookie) = (arg32(args, 1), arg32(args, 2), arg64(args, 3));
+
+    let mut entries = vec![(".".to_string(), filetype::DIRECTORY, 0), ("..".to_string(), filetype::DIRECTORY, 0)];
+    let mut children: Vec<_> = fs.read_dir(path)?.into_iter().map(|e| (e.name, e.filetype, e.ino)).collect();
+    children.sort();
+    entries.extend(children);
+
+    // entries that don't fit are truncated, the guest calls again with the cookie of the last full entry
+    let mut bytes = Vec::new();
+    for (i, (name, filetype, ino)) in entries.into_iter().enumerate().skip(cookie as usize) {
+        let mut dirent = [0; Dirent::SIZE];
+        Dirent { next: i as u64 + 1, ino, namlen: name.len() as u32, filetype }.write_to(&mut dirent);
+        bytes.extend(dirent);
+        bytes.extend(name.as_bytes());
+        if bytes.len() >= buf_len as usize {
+            break;
+        }
+    }
+
+    bytes.truncate(buf_len as usize);
+    store(memory, buf, &bytes)?;
+    write(memory, arg32(args, 4), bytes.len() as u32)
+}
+
+fn fd_renumber(state: &mut WasiState, _: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (from, to) = (arg32(args, 0), arg32(args, 1));
+    state.fd(to)?;
+    let descriptor = state.fds.remove(&from).ok_or(Errno::BADF)?;
+    state.fds.insert(to, descriptor);
+    Ok(())
+}
+
+fn fd_seek(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let offset = arg64(args, 1) as i64;
+    let pos = match arg32(args, 2) as u8 {
+        whence::SET => SeekFrom::Start(u64::try_from(offset).map_err(|_| Errno::INVAL)?),
+        whence::CUR => SeekFrom::Current(offset),
+        whence::END => SeekFrom::End(offset),
+        _ => return Err(Errno::INVAL),
+    };
+    let new_offset = file(state, arg32(args, 0))?.seek(pos)?;
+    write(memory, arg32(args, 3), new_offset)
+}
+
+fn fd_tell(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let offset = file(state, arg32(args, 0))?.seek(SeekFrom::Current(0))?;
+    write(memory, arg32(args, 1), offset)
+}
+
+// read a path argument and resolve it relative to a directory descriptor
+fn path_arg(
+    state: &mut WasiState,
+    memory: Memory<'_, '_>,
+    args: &[WasmValue],
+    fd: usize,
+    ptr: usize,
+) -> Result<(Rc<dyn FileSystem>, String), Errno> {
+    let path = read_str(memory, arg32(args, ptr), arg32(args, ptr + 1))?;
+    state.resolve(arg32(args, fd), &path)
+}
+
+// links and renames only work within a single filesystem
+pub(crate) fn same_fs(a: &Rc<dyn FileSystem>, b: &Rc<dyn FileSystem>) -> Result<(), Errno> {
+    match core::ptr::addr_eq(Rc::as_ptr(a), Rc::as_ptr(b)) {
+        true => Ok(()),
+        false => Err(Errno::XDEV),
+    }
+}
+
+fn path_create_directory(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (fs, path) = path_arg(state, memory, args, 0, 1)?;
+    fs.create_dir(&path)
+}
+
+// lookupflags::SYMLINK_FOLLOW
+fn follow_symlinks(lookup_flags: u32) -> bool {
+    lookup_flags & 1 != 0
+}
+
+fn path_filestat_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (fs, path) = path_arg(state, memory, args, 0, 2)?;
+    write(memory, arg32(args, 4), fs.stat(&path, follow_symlinks(arg32(args, 1)))?)
+}
+
+fn path_filestat_set_times(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (fs, path) = path_arg(state, memory, args, 0, 2)?;
+    let (atim, mtim) = file_times(state, arg64(args, 4), arg64(args, 5), arg32(args, 6) as u16)?;
+    fs.set_times(&path, atim, mtim)
+}
+
+fn path_link(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (fs, old_path) = path_arg(state, memory, args, 0, 2)?;
+    let (new_fs, new_path) = path_arg(state, memory, args, 4, 5)?;
+    same_fs(&fs, &new_fs)?;
+    fs.hard_link(&old_path, &new_path)
+}
+
+fn path_open(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (fs, path) = path_arg(state, memory, args, 0, 2)?;
+    let (rights, flags) = (arg64(args, 5), arg32(args, 7) as u16);
+    let (readable, writable) = (rights & rights::FD_READ != 0, rights & rights::FD_WRITE != 0);
+    let descriptor = open(fs, path, arg32(args, 4) as u16, readable, writable, flags & fdflags::APPEND != 0)?;
+    let fd = state.insert_fd(descriptor)?;
+    write(memory, arg32(args, 8), fd)
+}
+
+// open a file or directory, `oflags` are the preview 1 open flags
+pub(crate) fn open(
+    fs: Rc<dyn FileSystem>,
+    path: String,
+    oflags: u16,
+    read: bool,
+    write: bool,
+    append: bool,
+) -> Result<Descriptor, Errno> {
+    let stat = fs.stat(&path, true);
+    let is_dir = matches!(stat, Ok(ref stat) if stat.filetype == filetype::DIRECTORY);
+    if oflags & oflags::DIRECTORY != 0 || is_dir {
+        if !is_dir {
+            return Err(stat.err().unwrap_or(Errno::NOTDIR));
+        }
+        if oflags & (oflags::CREAT | oflags::TRUNC) != 0 {
+            return Err(Errno::ISDIR);
+        }
+        return Ok(Descriptor::Dir { fs, path, preopen: None });
+    }
+
+    let write = write || append || oflags & oflags::TRUNC != 0;
+    let file = fs.open(
+        &path,
+        OpenOptions {
+            read: read || !write,
+            write,
+            create: oflags & oflags::CREAT != 0,
+            create_new: oflags & oflags::CREAT != 0 && oflags & oflags::EXCL != 0,
+            truncate: oflags & oflags::TRUNC != 0,
+        },
+    )?;
+    Ok(Descriptor::File { file, rights: rights::ALL, append })
+}
+
+fn path_readlink(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (fs, path) = path_arg(state, memory, args, 0, 1)?;
+    let target = fs.read_link(&path)?;
+    let len = target.len().min(arg32(args, 4) as usize);
+    store(memory, arg32(args, 3), &target.as_bytes()[..len])?;
+    write(memory, arg32(args, 5), len as u32)
+}
+
+fn path_remove_directory(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (fs, path) = path_arg(state, memory, args, 0, 1)?;
+    fs.remove_dir(&path)
+}
+
+fn path_rename(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (fs, old_path) = path_arg(state, memory, args, 0, 1)?;
+    let (new_fs, new_path) = path_arg(state, memory, args, 3, 4)?;
+    same_fs(&fs, &new_fs)?;
+    fs.rename(&old_path, &new_path)
+}
+
+fn path_symlink(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let target = read_str(memory, arg32(args, 0), arg32(args, 1))?;
+    let (fs, link) = path_arg(state, memory, args, 2, 3)?;
+
+    // the target is stored as is, so it can't be allowed to point outside of the directory
+    crate::fs::join(crate::fs::split(&link).0, &target)?;
+    fs.symlink(&target, &link)
+}
+
+fn path_unlink_file(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (fs, path) = path_arg(state, memory, args, 0, 1)?;
+    fs.remove_file(&path)
+}
+
+fn poll_oneoff(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let (subs, events, count) = (arg32(args, 0), arg32(args, 1), arg32(args, 2));
+    if count == 0 {
+        return Err(Errno::INVAL);
+    }
+
+    let subs = WasmPtr::<Subscription>::new(subs).slice(count).read_vec(&*memory)?;
+    let event = |sub: &Subscription, error: Errno| Event {
+        userdata: sub.userdata,
+        error: error.raw(),
+        ty: sub.tag,
+        nbytes: 0,
+        flags: 0,
+    };
+
+    // file descriptors are always ready, so clocks are only waited on if there are none
+    let mut ready = Vec::new();
+    for sub in subs.iter().filter(|sub| sub.tag != eventtype::CLOCK) {
+        let error = match sub.tag {
+            eventtype::FD_READ | eventtype::FD_WRITE => state.fd(sub.u.id).err().unwrap_or(Errno::SUCCESS),
+            _ => Errno::INVAL,
+        };
+        ready.push(event(sub, error));
+    }
+
+    if ready.is_empty() {
+        let mut timeouts = Vec::new();
+        for sub in &subs {
+            let timeout = match sub.u.flags & SUBCLOCKFLAGS_ABSTIME != 0 {
+                true => sub.u.timeout.saturating_sub(now(state, sub.u.id)?),
+                false => sub.u.timeout,
+            };
+            timeouts.push((timeout, sub));
+        }
+
+        let min = timeouts.iter().map(|(timeout, _)| *timeout).min().unwrap_or_default();
+        sleep(state, min);
+        ready.extend(timeouts.iter().filter(|(timeout, _)| *timeout == min).map(|(_, sub)| event(sub, Errno::SUCCESS)));
+    }
+
+    let len = ready.len() as u32;
+    WasmPtr::<Event>::new(events).slice(len).write_slice(memory, &ready)?;
+    write(memory, arg32(args, 3), len)
+}
+
+fn proc_raise(_: &mut WasiState, _: Memory<'_, '_>, _: &[WasmValue]) -> Result<(), Errno> {
+    Err(Errno::NOTSUP)
+}
+
+fn sched_yield(_: &mut WasiState, _: Memory<'_, '_>, _: &[WasmValue]) -> Result<(), Errno> {
+    #[cfg(feature = "std")]
+    std::thread::yield_now();
+    Ok(())
+}
+
+// the virtual clock advances instead in deterministic mode
+pub(crate) fn sleep(state: &mut WasiState, nanos: u64) {
+    match state.deterministic.as_mut() {
+        Some(env) => env.sleep(nanos),
+        None => state.clocks.sleep(nanos),
+    }
+}
+
+pub(crate) fn fill_random(state: &mut WasiState, buf: &mut [u8]) -> Result<(), Errno> {
+    match state.deterministic.as_mut() {
+        Some(env) => {
+            env.fill_bytes(buf);
+            Ok(())
+        }
+        None => state.random.fill(buf),
+    }
+}
+
+fn random_get(state: &mut WasiState, memory: Memory<'_, '_>, args: &[WasmValue]) -> Result<(), Errno> {
+    let mut buf = buffer(memory, arg32(args, 0), arg32(args, 1))?;
+    fill_random(state, &mut buf)?;
+    store(memory, arg32(args, 0), &buf)
+}
+
+fn sock_accept(_: &mut WasiState, _: Memory<'_, '_>, _: &[WasmValue]) -> Result<(), Errno> {
+    Err(Errno::NOTSUP)
+}
+
+fn sock_recv(_: &mut WasiState, _: Memory<'_, '_>, _: &[WasmValue]) -> Result<(), Errno> {
+    Err(Errno::NOTSUP)
+}
+
+fn sock_send(_: &mut WasiState, _: Memory<'_, '_>, _: &[WasmValue]) -> Result<(), Errno> {
+    Err(Errno::NOTSUP)
+}
+
+fn sock_shutdown(_: &mut WasiState, _: Memory<'_, '_>, _: &[WasmValue]) -> Result<(), Errno> {
+    Err(Errno::NOTSUP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::{MemFs, Pipe};
+    use crate::MODULE;
+    use tinywasm::{Imports, Module, ModuleInstance, Store};
+
+    const FUNCS: &[(&str, &str)] = &[
+        ("args_get", "i32 i32"),
+        ("args_sizes_get", "i32 i32"),
+        ("environ_get", "i32 i32"),
+        ("environ_sizes_get", "i32 i32"),
+        ("fd_close", "i32"),
+        ("fd_filestat_set_size", "i32 i64"),
+        ("fd_read", "i32 i32 i32 i32"),
+        ("fd_readdir", "i32 i32 i32 i64 i32"),
+        ("fd_seek", "i32 i64 i32 i32"),
+        ("fd_write", "i32 i32 i32 i32"),
+        ("path_open", "i32 i32 i32 i32 i32 i64 i64 i32 i32"),
+        ("random_get", "i32 i32"),
+    ];
+
+    struct Guest {
+        store: Store,
+        instance: ModuleInstance,
+    }
+
+    impl Guest {
+        // a module re-exporting the WASI functions, so they can be called with any arguments
+        fn new(wasi: &WasiCtx) -> Self {
+            let funcs = FUNCS.iter().map(|(name, params)| {
+                format!(r#"(func (export "{name}") (import "{MODULE}" "{name}") (param {params}) (result i32))"#)
+            });
+            let wat = format!("(module {} (memory (export \"memory\") 1))", funcs.collect::<Vec<_>>().join(""));
+            let mut imports = Imports::new();
+            wasi.add_to_imports(&mut imports).unwrap();
+
+            let mut store = Store::default();
+            let module = Module::parse_bytes(&wat::parse_str(wat).unwrap()).unwrap();
+            let instance = module.instantiate(&mut store, Some(imports)).unwrap();
+            Self { store, instance }
+        }
+
+        fn call(&mut self, name: &str, args: &[WasmValue]) -> Errno {
+            let func = self.instance.exported_func_untyped(&self.store, name).unwrap();
+            let result = func.call(&mut self.store, args).unwrap();
+            Errno::from_raw(i32::try_from(result[0]).unwrap() as u16)
+        }
+
+        fn memory(&mut self) -> MemoryRefMut<'_> {
+            self.instance.exported_memory_mut(&mut self.store, "memory").unwrap()
+        }
+
+        fn load(&mut self, offset: usize, len: usize) -> Vec<u8> {
+            self.memory().load_vec(offset, len).unwrap()
+        }
+
+        fn read_u32(&mut self, offset: usize) -> u32 {
+            self.memory().read(offset).unwrap()
+        }
+
+        fn write(&mut self, offset: usize, data: &[u8]) {
+            self.memory().store(offset, data.len(), data).unwrap()
+        }
+
+        // write an iovec for `len` bytes at `buf` to `ptr`
+        fn iovec(&mut self, ptr: usize, buf: u32, len: u32) {
+            self.memory().write(ptr, Iovec { buf: WasmPtr::new(buf), len }).unwrap()
+        }
+    }
+
+    use WasmValue::{I32, I64};
+
+    #[test]
+    fn test_args_environ() {
+        let mut wasi = WasiCtx::new();
+        wasi.args(["prog", "x"]).env("A", "1");
+        let mut guest = Guest::new(&wasi);
+
+        assert_eq!(guest.call("args_sizes_get", &[I32(0), I32(4)]), Errno::SUCCESS);
+        assert_eq!((guest.read_u32(0), guest.read_u32(4)), (2, 7));
+        assert_eq!(guest.call("args_get", &[I32(16), I32(32)]), Errno::SUCCESS);
+        assert_eq!((guest.read_u32(16), guest.read_u32(20)), (32, 37));
+        assert_eq!(guest.load(32, 7), b"prog\0x\0");
+
+        assert_eq!(guest.call("environ_sizes_get", &[I32(0), I32(4)]), Errno::SUCCESS);
+        assert_eq!((guest.read_u32(0), guest.read_u32(4)), (1, 4));
+        assert_eq!(guest.call("environ_get", &[I32(16), I32(32)]), Errno::SUCCESS);
+        assert_eq!(guest.load(32, 4), b"A=1\0");
+    }
+
+    #[test]
+    fn test_fd_read_write() {
+        let (stdin, stdout) = (Pipe::from(&b"input"[..]), Pipe::new());
+        let mut wasi = WasiCtx::new();
+        wasi.stdin(stdin).stdout(stdout.clone());
+        let mut guest = Guest::new(&wasi);
+
+        // two iovecs, the first one is filled before the second
+        guest.iovec(0, 100, 2);
+        guest.iovec(8, 200, 10);
+        assert_eq!(guest.call("fd_read", &[I32(0), I32(0), I32(2), I32(16)]), Errno::SUCCESS);
+        assert_eq!(guest.read_u32(16), 5);
+        assert_eq!((guest.load(100, 2), guest.load(200, 3)), (b"in".to_vec(), b"put".to_vec()));
+
+        guest.write(300, b"hello ");
+        guest.write(400, b"world");
+        guest.iovec(0, 300, 6);
+        guest.iovec(8, 400, 5);
+        assert_eq!(guest.call("fd_write", &[I32(1), I32(0), I32(2), I32(16)]), Errno::SUCCESS);
+        assert_eq!(guest.read_u32(16), 11);
+        assert_eq!(stdout.take(), b"hello world");
+    }
+
+    #[test]
+    fn test_path_open_readdir() {
+        let fs = MemFs::new();
+        fs.write_file("a.txt", b"a").unwrap();
+        fs.create_dir_all("d").unwrap();
+        let mut wasi = WasiCtx::new();
+        wasi.preopen(fs.clone(), "/").unwrap();
+        let mut guest = Guest::new(&wasi);
+
+        guest.write(0, b"new.txt");
+        let rights = I64((rights::FD_READ | rights::FD_WRITE) as i64);
+        let args = [I32(3), I32(0), I32(0), I32(7), I32(oflags::CREAT as i32), rights, rights, I32(0), I32(16)];
+        assert_eq!(guest.call("path_open", &args), Errno::SUCCESS);
+        let fd = guest.read_u32(16) as i32;
+
+        guest.write(100, b"data");
+        guest.iovec(32, 100, 4);
+        assert_eq!(guest.call("fd_write", &[I32(fd), I32(32), I32(1), I32(40)]), Errno::SUCCESS);
+        assert_eq!(guest.call("fd_close", &[I32(fd)]), Errno::SUCCESS);
+        assert_eq!(fs.read_file("new.txt").as_deref(), Ok(&b"data"[..]));
+
+        assert_eq!(guest.call("fd_readdir", &[I32(3), I32(200), I32(1024), I64(0), I32(16)]), Errno::SUCCESS);
+        let len = guest.read_u32(16) as usize;
+        let mut entries = guest.load(200, len);
+        let mut names = Vec::new();
+        while !entries.is_empty() {
+            let namlen = u32::from_le_bytes(entries[16..20].try_into().unwrap()) as usize;
+            names.push(String::from_utf8(entries[Dirent::SIZE..Dirent::SIZE + namlen].to_vec()).unwrap());
+            entries.drain(..Dirent::SIZE + namlen);
+        }
+        assert_eq!(names, [".", "..", "a.txt", "d", "new.txt"]);
+    }
+
+    #[test]
+    fn test_errno() {
+        let fs = MemFs::new();
+        let mut wasi = WasiCtx::new();
+        wasi.preopen(fs.clone(), "/").unwrap();
+        let mut guest = Guest::new(&wasi);
+
+        assert_eq!(guest.call("fd_write", &[I32(99), I32(0), I32(0), I32(0)]), Errno::BADF);
+        assert_eq!(guest.call("fd_close", &[I32(99)]), Errno::BADF);
+        assert_eq!(guest.call("args_sizes_get", &[I32(65535), I32(0)]), Errno::FAULT);
+
+        guest.write(0, b"missing");
+        let args = [I32(3), I32(0), I32(0), I32(7), I32(0), I64(-1), I64(-1), I32(0), I32(16)];
+        assert_eq!(guest.call("path_open", &args), Errno::NOENT);
+        assert_eq!(guest.call("fd_readdir", &[I32(1), I32(0), I32(64), I64(0), I32(16)]), Errno::NOTDIR);
+
+        // out of bounds buffers fail before anything is allocated
+        assert_eq!(guest.call("random_get", &[I32(0), I32(-1)]), Errno::FAULT);
+        guest.iovec(0, 16, u32::MAX);
+        assert_eq!(guest.call("fd_read", &[I32(0), I32(0), I32(1), I32(16)]), Errno::FAULT);
+
+        // files can't grow beyond the maximum size of the filesystem
+        guest.write(0, b"file");
+        let args = [I32(3), I32(0), I32(0), I32(4), I32(oflags::CREAT as i32), I64(-1), I64(-1), I32(0), I32(16)];
+        assert_eq!(guest.call("path_open", &args), Errno::SUCCESS);
+        let fd = guest.read_u32(16) as i32;
+        assert_eq!(guest.call("fd_filestat_set_size", &[I32(fd), I64(1 << 44)]), Errno::FBIG);
+        assert_eq!(guest.call("fd_seek", &[I32(fd), I64(1 << 44), I32(0), I32(16)]), Errno::SUCCESS);
+        guest.iovec(32, 0, 1);
+        assert_eq!(guest.call("fd_write", &[I32(fd), I32(32), I32(1), I32(40)]), Errno::FBIG);
+    }
+}
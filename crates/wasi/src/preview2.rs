@@ -0,0 +1,967 @@
+//! The `wasi:cli/command` world of WASI 0.2 for components
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use alloc::{format, vec};
+
+use tinywasm::component::{ComponentImports, ResourceAny, Val};
+use tinywasm::{Error, FuncContext, Result};
+
+use crate::ctx::{Descriptor, WasiState};
+use crate::fs::{DirEntry, FileSystem, SeekFrom, WasiFile};
+use crate::preview1::{file, fill_random, now, open, same_fs, sleep, write_all};
+use crate::types::{clockid, filetype, oflags, rights, Errno, Filestat};
+use crate::WasiCtx;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+// the most a guest can read or write at once
+const MAX_BUFFER: u64 = 1 << 20;
+
+/// The resources the host created for the guest
+#[derive(Default)]
+pub(crate) struct Resources {
+    table: BTreeMap<u32, Resource>,
+    next: u32,
+}
+
+impl Resources {
+    fn insert(&mut self, resource: Resource) -> Val {
+        self.next += 1;
+        self.table.insert(self.next, resource);
+        // the resource type is taken from the function signature
+        Val::Resource(ResourceAny { resource: 0, rep: self.next, owned: true })
+    }
+
+    fn get(&mut self, rep: u32) -> Result<&mut Resource, Failure> {
+        self.table.get_mut(&rep).ok_or_else(|| trap(format!("unknown resource {rep}")))
+    }
+}
+
+enum Resource {
+    Stream { fd: u32, offset: Offset },
+    Error(Errno),
+    // always ready without a deadline on the monotonic clock
+    Pollable(Option<u64>),
+    Descriptor(u32),
+    DirEntries(VecDeque<DirEntry>),
+}
+
+// where a stream reads or writes its file
+#[derive(Clone, Copy)]
+enum Offset {
+    Current,
+    At(u64),
+    End,
+}
+
+// why a call failed, converted to the error type of the function
+enum Failure {
+    Errno(Errno),
+    Closed,
+    Trap(Error),
+}
+
+impl From<Errno> for Failure {
+    fn from(errno: Errno) -> Self {
+        Self::Errno(errno)
+    }
+}
+
+fn trap(message: String) -> Failure {
+    Failure::Trap(Error::Other(message))
+}
+
+#[derive(Clone, Copy)]
+enum Returns {
+    // a value or nothing, errors trap
+    Plain,
+    // `result<T, error-code>`
+    FsResult,
+    // `result<T, stream-error>`
+    StreamResult,
+}
+
+impl Returns {
+    fn wrap(self, state: &mut WasiState, result: Result<Option<Val>, Failure>) -> Result<Vec<Val>> {
+        let err = match (self, result) {
+            (_, Err(Failure::Trap(err))) => return Err(err),
+            (Returns::Plain, Ok(val)) => return Ok(val.into_iter().collect()),
+            (Returns::Plain, Err(Failure::Errno(errno))) => {
+                return Err(Error::Other(format!("WASI call failed: {}", error_code(errno))))
+            }
+            (Returns::Plain, Err(Failure::Closed)) => return Err(Error::Other("stream closed".to_string())),
+            (_, Ok(val)) => return Ok(vec![Val::Result(Ok(val.map(Box::new)))]),
+            (Returns::FsResult, Err(Failure::Errno(errno))) => Val::Enum(error_code(errno).to_string()),
+            (Returns::FsResult, Err(Failure::Closed)) => Val::Enum(error_code(Errno::PIPE).to_string()),
+            (Returns::StreamResult, Err(Failure::Errno(errno))) => {
+                let error = state.resources.insert(Resource::Error(errno));
+                Val::Variant("last-operation-failed".to_string(), Some(Box::new(error)))
+            }
+            (Returns::StreamResult, Err(Failure::Closed)) => Val::Variant("closed".to_string(), None),
+        };
+        Ok(vec![Val::Result(Err(Some(Box::new(err))))])
+    }
+}
+
+type HostFn = fn(&mut WasiState, &[Val]) -> Result<Option<Val>, Failure>;
+
+macro_rules! funcs {
+    ($($interface:literal => [$($name:literal: $func:ident -> $returns:ident),* $(,)?]),* $(,)?) => {
+        &[$($(($interface, $name, Returns::$returns, $func as HostFn)),*),*]
+    };
+}
+
+const FUNCS: &[(&str, &str, Returns, HostFn)] = funcs![
+    "wasi:io/error@0.2.0" => ["[method]error.to-debug-string": error_to_debug_string -> Plain],
+    "wasi:io/poll@0.2.0" => [
+        "[method]pollable.ready": pollable_ready -> Plain,
+        "[method]pollable.block": pollable_block -> Plain,
+        "poll": poll -> Plain,
+    ],
+    "wasi:io/streams@0.2.0" => [
+        "[method]input-stream.read": read -> StreamResult,
+        "[method]input-stream.blocking-read": read -> StreamResult,
+        "[method]input-stream.skip": skip -> StreamResult,
+        "[method]input-stream.blocking-skip": skip -> StreamResult,
+        "[method]input-stream.subscribe": subscribe -> Plain,
+        "[method]output-stream.check-write": check_write -> StreamResult,
+        "[method]output-stream.write": write -> StreamResult,
+        "[method]output-stream.blocking-write-and-flush": write -> StreamResult,
+        "[method]output-stream.flush": flush -> StreamResult,
+        "[method]output-stream.blocking-flush": flush -> StreamResult,
+        "[method]output-stream.subscribe": subscribe -> Plain,
+        "[method]output-stream.write-zeroes": write_zeroes -> StreamResult,
+        "[method]output-stream.blocking-write-zeroes-and-flush": write_zeroes -> StreamResult,
+        "[method]output-stream.splice": splice -> StreamResult,
+        "[method]output-stream.blocking-splice": splice -> StreamResult,
+    ],
+    "wasi:cli/environment@0.2.0" => [
+        "get-environment": get_environment -> Plain,
+        "get-arguments": get_arguments -> Plain,
+        "initial-cwd": initial_cwd -> Plain,
+    ],
+    "wasi:cli/exit@0.2.0" => ["exit": exit -> Plain],
+    "wasi:cli/stdin@0.2.0" => ["get-stdin": get_stdin -> Plain],
+    "wasi:cli/stdout@0.2.0" => ["get-stdout": get_stdout -> Plain],
+    "wasi:cli/stderr@0.2.0" => ["get-stderr": get_stderr -> Plain],
+    "wasi:cli/terminal-stdin@0.2.0" => ["get-terminal-stdin": no_terminal -> Plain],
+    "wasi:cli/terminal-stdout@0.2.0" => ["get-terminal-stdout": no_terminal -> Plain],
+    "wasi:cli/terminal-stderr@0.2.0" => ["get-terminal-stderr": no_terminal -> Plain],
+    "wasi:clocks/monotonic-clock@0.2.0" => [
+        "now": monotonic_now -> Plain,
+        "resolution": monotonic_resolution -> Plain,
+        "subscribe-instant": subscribe_instant -> Plain,
+        "subscribe-duration": subscribe_duration -> Plain,
+    ],
+    "wasi:clocks/wall-clock@0.2.0" => [
+        "now": wall_now -> Plain,
+        "resolution": wall_resolution -> Plain,
+    ],
+    "wasi:random/random@0.2.0" => [
+        "get-random-bytes": random_bytes -> Plain,
+        "get-random-u64": random_u64 -> Plain,
+    ],
+    "wasi:random/insecure@0.2.0" => [
+        "get-insecure-random-bytes": random_bytes -> Plain,
+        "get-insecure-random-u64": random_u64 -> Plain,
+    ],
+    "wasi:random/insecure-seed@0.2.0" => ["insecure-seed": insecure_seed -> Plain],
+    "wasi:filesystem/preopens@0.2.0" => ["get-directories": get_directories -> Plain],
+    "wasi:filesystem/types@0.2.0" => [
+        "[method]descriptor.read-via-stream": read_via_stream -> FsResult,
+        "[method]descriptor.write-via-stream": write_via_stream -> FsResult,
+        "[method]descriptor.append-via-stream": append_via_stream -> FsResult,
+        "[method]descriptor.advise": advise -> FsResult,
+        "[method]descriptor.sync-data": sync -> FsResult,
+        "[method]descriptor.get-flags": get_flags -> FsResult,
+        "[method]descriptor.get-type": get_type -> FsResult,
+        "[method]descriptor.set-size": set_size -> FsResult,
+        "[method]descriptor.set-times": set_times -> FsResult,
+        "[method]descriptor.read": descriptor_read -> FsResult,
+        "[method]descriptor.write": descriptor_write -> FsResult,
+        "[method]descriptor.read-directory": read_directory -> FsResult,
+        "[method]descriptor.sync": sync -> FsResult,
+        "[method]descriptor.create-directory-at": create_directory_at -> FsResult,
+        "[method]descriptor.stat": stat -> FsResult,
+        "[method]descriptor.stat-at": stat_at -> FsResult,
+        "[method]descriptor.set-times-at": set_times_at -> FsResult,
+        "[method]descriptor.link-at": link_at -> FsResult,
+        "[method]descriptor.open-at": open_at -> FsResult,
+        "[method]descriptor.readlink-at": readlink_at -> FsResult,
+        "[method]descriptor.remove-directory-at": remove_directory_at -> FsResult,
+        "[method]descriptor.rename-at": rename_at -> FsResult,
+        "[method]descriptor.symlink-at": symlink_at -> FsResult,
+        "[method]descriptor.unlink-file-at": unlink_file_at -> FsResult,
+        "[method]descriptor.is-same-object": is_same_object -> Plain,
+        "[method]descriptor.metadata-hash": metadata_hash -> FsResult,
+        "[method]descriptor.metadata-hash-at": metadata_hash_at -> FsResult,
+        "[method]directory-entry-stream.read-directory-entry": read_directory_entry -> FsResult,
+        "filesystem-error-code": filesystem_error_code -> Plain,
+    ],
+];
+
+const RESOURCES: [&str; 6] = [
+    "wasi:io/error@0.2.0#error",
+    "wasi:io/poll@0.2.0#pollable",
+    "wasi:io/streams@0.2.0#input-stream",
+    "wasi:io/streams@0.2.0#output-stream",
+    "wasi:filesystem/types@0.2.0#descriptor",
+    "wasi:filesystem/types@0.2.0#directory-entry-stream",
+];
+
+pub(crate) fn add_to_imports(ctx: &WasiCtx, imports: &mut ComponentImports) -> Result<()> {
+    for &(interface, name, returns, func) in FUNCS {
+        let ctx = ctx.clone();
+        imports.define_func(&format!("{interface}#{name}"), move |mut fctx, args| {
+            with_state(&ctx, &mut fctx, |state| {
+                let result = func(state, args);
+                returns.wrap(state, result)
+            })
+        })?;
+    }
+
+    for name in RESOURCES {
+        let ctx = ctx.clone();
+        imports.define_resource(name, move |_, rep| {
+            let mut state = ctx.0.borrow_mut();
+            if let Some(Resource::Descriptor(fd)) = state.resources.table.remove(&rep) {
+                // preopened directories are handed out again by `get-directories`
+                if !matches!(state.fds.get(&fd), Some(Descriptor::Dir { preopen: Some(_), .. })) {
+                    state.fds.remove(&fd);
+                }
+            }
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
+
+// run a host function with the state, lending it the store's deterministic environment
+fn with_state<R>(ctx: &WasiCtx, fctx: &mut FuncContext<'_>, func: impl FnOnce(&mut WasiState) -> R) -> R {
+    let mut state = ctx.0.borrow_mut();
+    state.deterministic = fctx.store_mut().deterministic_env().cloned();
+    let result = func(&mut state);
+    if let (Some(env), Some(store_env)) = (state.deterministic.take(), fctx.store_mut().deterministic_env()) {
+        *store_env = env;
+    }
+    result
+}
+
+fn invalid(idx: usize) -> Failure {
+    trap(format!("invalid argument {idx}"))
+}
+
+fn rep(args: &[Val], idx: usize) -> Result<u32, Failure> {
+    match args.get(idx) {
+        Some(Val::Resource(resource)) => Ok(resource.rep),
+        _ => Err(invalid(idx)),
+    }
+}
+
+fn u64_arg(args: &[Val], idx: usize) -> Result<u64, Failure> {
+    match args.get(idx) {
+        Some(Val::U64(value)) => Ok(*value),
+        _ => Err(invalid(idx)),
+    }
+}
+
+fn str_arg(args: &[Val], idx: usize) -> Result<&str, Failure> {
+    match args.get(idx) {
+        Some(Val::String(value)) => Ok(value),
+        _ => Err(invalid(idx)),
+    }
+}
+
+fn bytes_arg(args: &[Val], idx: usize) -> Result<Vec<u8>, Failure> {
+    match args.get(idx) {
+        Some(Val::List(values)) => {
+            values.iter().map(|value| if let Val::U8(byte) = value { Ok(*byte) } else { Err(invalid(idx)) }).collect()
+        }
+        _ => Err(invalid(idx)),
+    }
+}
+
+fn flags_arg(args: &[Val], idx: usize) -> Result<&[String], Failure> {
+    match args.get(idx) {
+        Some(Val::Flags(flags)) => Ok(flags),
+        _ => Err(invalid(idx)),
+    }
+}
+
+fn has_flag(flags: &[String], name: &str) -> bool {
+    flags.iter().any(|flag| flag == name)
+}
+
+fn bytes(data: &[u8]) -> Val {
+    Val::List(data.iter().copied().map(Val::U8).collect())
+}
+
+fn some(val: Val) -> Val {
+    Val::Option(Some(Box::new(val)))
+}
+
+fn datetime(nanos: u64) -> Val {
+    Val::Record(vec![
+        ("seconds".to_string(), Val::U64(nanos / NANOS_PER_SECOND)),
+        ("nanoseconds".to_string(), Val::U32((nanos % NANOS_PER_SECOND) as u32)),
+    ])
+}
+
+// the name of the `error-code` case of an errno
+fn error_code(errno: Errno) -> &'static str {
+    match errno {
+        Errno::ACCES => "access",
+        Errno::AGAIN => "would-block",
+        Errno::BADF => "bad-descriptor",
+        Errno::BUSY => "busy",
+        Errno::EXIST => "exist",
+        Errno::FBIG => "file-too-large",
+        Errno::ILSEQ => "illegal-byte-sequence",
+        Errno::INTR => "interrupted",
+        Errno::INVAL => "invalid",
+        Errno::ISDIR => "is-directory",
+        Errno::LOOP => "loop",
+        Errno::NAMETOOLONG => "name-too-long",
+        Errno::NOENT => "no-entry",
+        Errno::NOMEM => "insufficient-memory",
+        Errno::NOSPC => "insufficient-space",
+        Errno::NOTDIR => "not-directory",
+        Errno::NOTEMPTY => "not-empty",
+        Errno::NOTSUP | Errno::NOSYS => "unsupported",
+        Errno::NOTTY => "no-tty",
+        Errno::OVERFLOW => "overflow",
+        Errno::PERM | Errno::NOTCAPABLE => "not-permitted",
+        Errno::PIPE => "pipe",
+        Errno::ROFS => "read-only",
+        Errno::SPIPE => "invalid-seek",
+        Errno::XDEV => "cross-device",
+        _ => "io",
+    }
+}
+
+fn error_to_debug_string(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    match state.resources.get(rep(args, 0)?)? {
+        Resource::Error(errno) => Ok(Some(Val::String(error_code(*errno).to_string()))),
+        _ => Err(invalid(0)),
+    }
+}
+
+fn deadline(state: &mut WasiState, rep: u32) -> Result<Option<u64>, Failure> {
+    match state.resources.get(rep)? {
+        Resource::Pollable(deadline) => Ok(*deadline),
+        _ => Err(trap(format!("resource {rep} is not a pollable"))),
+    }
+}
+
+fn pollable_ready(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let ready = match deadline(state, rep(args, 0)?)? {
+        Some(deadline) => now(state, clockid::MONOTONIC)? >= deadline,
+        None => true,
+    };
+    Ok(Some(Val::Bool(ready)))
+}
+
+fn pollable_block(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    if let Some(deadline) = deadline(state, rep(args, 0)?)? {
+        let nanos = deadline.saturating_sub(now(state, clockid::MONOTONIC)?);
+        sleep(state, nanos);
+    }
+    Ok(None)
+}
+
+fn poll(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let Some(Val::List(pollables)) = args.first() else { return Err(invalid(0)) };
+    if pollables.is_empty() {
+        return Err(trap("poll called without pollables".to_string()));
+    }
+
+    let deadlines = (0..pollables.len()).map(|i| deadline(state, rep(pollables, i)?)).collect::<Result<Vec<_>, _>>()?;
+    let first = deadlines.iter().flatten().min().copied();
+    let until = match (deadlines.contains(&None), first) {
+        // only clocks can block, so wait for the first one if nothing else is ready
+        (false, Some(first)) => {
+            let now = now(state, clockid::MONOTONIC)?;
+            sleep(state, first.saturating_sub(now));
+            first.max(now)
+        }
+        (true, Some(_)) => now(state, clockid::MONOTONIC)?,
+        (_, None) => 0,
+    };
+
+    let ready = deadlines.iter().enumerate().filter(|(_, deadline)| deadline.map_or(true, |d| d <= until));
+    Ok(Some(Val::List(ready.map(|(i, _)| Val::U32(i as u32)).collect())))
+}
+
+// run an operation on the file of a stream at the position of the stream
+fn with_stream<R>(
+    state: &mut WasiState,
+    rep: u32,
+    op: impl FnOnce(&mut dyn WasiFile) -> Result<R, Errno>,
+) -> Result<R, Failure> {
+    let Resource::Stream { fd, offset } = *state.resources.get(rep)? else {
+        return Err(trap(format!("resource {rep} is not a stream")));
+    };
+
+    let file = file(state, fd)?;
+    match offset {
+        Offset::Current => {}
+        Offset::At(pos) => {
+            file.seek(SeekFrom::Start(pos))?;
+        }
+        Offset::End => {
+            file.seek(SeekFrom::End(0))?;
+        }
+    }
+    let result = op(file.as_mut())?;
+
+    if let Offset::At(_) = offset {
+        let pos = file.seek(SeekFrom::Current(0))?;
+        state.resources.table.insert(rep, Resource::Stream { fd, offset: Offset::At(pos) });
+    }
+    Ok(result)
+}
+
+fn read_stream(state: &mut WasiState, rep: u32, len: u64) -> Result<Vec<u8>, Failure> {
+    let mut buf = vec![0; len.min(MAX_BUFFER) as usize];
+    let read = with_stream(state, rep, |file| file.read(&mut buf))?;
+    if read == 0 && !buf.is_empty() {
+        return Err(Failure::Closed);
+    }
+    buf.truncate(read);
+    Ok(buf)
+}
+
+fn read(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(bytes(&read_stream(state, rep(args, 0)?, u64_arg(args, 1)?)?)))
+}
+
+fn skip(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(Val::U64(read_stream(state, rep(args, 0)?, u64_arg(args, 1)?)?.len() as u64)))
+}
+
+fn subscribe(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    // files and the standard streams never block
+    Ok(Some(state.resources.insert(Resource::Pollable(None))))
+}
+
+fn check_write(_: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(Val::U64(MAX_BUFFER)))
+}
+
+fn write_stream(state: &mut WasiState, rep: u32, data: &[u8]) -> Result<Option<Val>, Failure> {
+    with_stream(state, rep, |file| write_all(file, data))?;
+    Ok(None)
+}
+
+fn write(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    write_stream(state, rep(args, 0)?, &bytes_arg(args, 1)?)
+}
+
+fn write_zeroes(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    write_stream(state, rep(args, 0)?, &vec![0; u64_arg(args, 1)?.min(MAX_BUFFER) as usize])
+}
+
+fn flush(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    with_stream(state, rep(args, 0)?, |file| file.sync())?;
+    Ok(None)
+}
+
+fn splice(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let data = read_stream(state, rep(args, 1)?, u64_arg(args, 2)?)?;
+    write_stream(state, rep(args, 0)?, &data)?;
+    Ok(Some(Val::U64(data.len() as u64)))
+}
+
+fn get_environment(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    let env =
+        state.env.iter().map(|(key, value)| Val::Tuple(vec![Val::String(key.clone()), Val::String(value.clone())]));
+    Ok(Some(Val::List(env.collect())))
+}
+
+fn get_arguments(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(Val::List(state.args.iter().cloned().map(Val::String).collect())))
+}
+
+fn initial_cwd(_: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(Val::Option(None)))
+}
+
+fn exit(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let code = match args.first() {
+        Some(Val::Result(status)) => status.is_err() as i32,
+        _ => return Err(invalid(0)),
+    };
+    state.exit_code = Some(code);
+    Err(Failure::Trap(Error::host(crate::ProcExit { code })))
+}
+
+fn get_stdin(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(state.resources.insert(Resource::Stream { fd: 0, offset: Offset::Current })))
+}
+
+fn get_stdout(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(state.resources.insert(Resource::Stream { fd: 1, offset: Offset::Current })))
+}
+
+fn get_stderr(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(state.resources.insert(Resource::Stream { fd: 2, offset: Offset::Current })))
+}
+
+fn no_terminal(_: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(Val::Option(None)))
+}
+
+fn monotonic_now(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(Val::U64(now(state, clockid::MONOTONIC)?)))
+}
+
+// reading the virtual clock would advance it
+fn resolution(state: &WasiState) -> u64 {
+    match &state.deterministic {
+        Some(env) => env.config().clock_step.max(1),
+        None => state.clocks.resolution(),
+    }
+}
+
+fn monotonic_resolution(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(Val::U64(resolution(state))))
+}
+
+fn subscribe_instant(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let deadline = u64_arg(args, 0)?;
+    Ok(Some(state.resources.insert(Resource::Pollable(Some(deadline)))))
+}
+
+fn subscribe_duration(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let deadline = now(state, clockid::MONOTONIC)?.saturating_add(u64_arg(args, 0)?);
+    Ok(Some(state.resources.insert(Resource::Pollable(Some(deadline)))))
+}
+
+fn wall_now(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(datetime(now(state, clockid::REALTIME)?)))
+}
+
+fn wall_resolution(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    Ok(Some(datetime(resolution(state))))
+}
+
+fn random_bytes(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let mut buf = vec![0; u64_arg(args, 0)?.min(MAX_BUFFER) as usize];
+    fill_random(state, &mut buf)?;
+    Ok(Some(bytes(&buf)))
+}
+
+fn random_u64(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    let mut buf = [0; 8];
+    fill_random(state, &mut buf)?;
+    Ok(Some(Val::U64(u64::from_le_bytes(buf))))
+}
+
+fn insecure_seed(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    let mut buf = [0; 16];
+    fill_random(state, &mut buf)?;
+    let (low, high) = buf.split_at(8);
+    let seed = |half: &[u8]| Val::U64(u64::from_le_bytes(half.try_into().unwrap_or_default()));
+    Ok(Some(Val::Tuple(vec![seed(low), seed(high)])))
+}
+
+fn get_directories(state: &mut WasiState, _: &[Val]) -> Result<Option<Val>, Failure> {
+    let preopens: Vec<_> = state
+        .fds
+        .iter()
+        .filter_map(|(fd, descriptor)| match descriptor {
+            Descriptor::Dir { preopen: Some(name), .. } => Some((*fd, name.clone())),
+            _ => None,
+        })
+        .collect();
+
+    let dirs = preopens
+        .into_iter()
+        .map(|(fd, name)| Val::Tuple(vec![state.resources.insert(Resource::Descriptor(fd)), Val::String(name)]));
+    Ok(Some(Val::List(dirs.collect())))
+}
+
+// the file descriptor of a descriptor resource
+fn fd_arg(state: &mut WasiState, args: &[Val], idx: usize) -> Result<u32, Failure> {
+    match state.resources.get(rep(args, idx)?)? {
+        Resource::Descriptor(fd) => Ok(*fd),
+        _ => Err(invalid(idx)),
+    }
+}
+
+// a path argument resolved relative to the descriptor
+fn path_arg(state: &mut WasiState, args: &[Val], idx: usize) -> Result<(Rc<dyn FileSystem>, String), Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    Ok(state.resolve(fd, str_arg(args, idx)?)?)
+}
+
+fn descriptor_type(filetype: u8) -> Val {
+    let name = match filetype {
+        1 => "block-device",
+        filetype::CHARACTER_DEVICE => "character-device",
+        filetype::DIRECTORY => "directory",
+        filetype::REGULAR_FILE => "regular-file",
+        5 | 6 => "socket",
+        filetype::SYMBOLIC_LINK => "symbolic-link",
+        _ => "unknown",
+    };
+    Val::Enum(name.to_string())
+}
+
+fn descriptor_stat(stat: Filestat) -> Val {
+    Val::Record(vec![
+        ("type".to_string(), descriptor_type(stat.filetype)),
+        ("link-count".to_string(), Val::U64(stat.nlink)),
+        ("size".to_string(), Val::U64(stat.size)),
+        ("data-access-timestamp".to_string(), some(datetime(stat.atim))),
+        ("data-modification-timestamp".to_string(), some(datetime(stat.mtim))),
+        ("status-change-timestamp".to_string(), some(datetime(stat.ctim))),
+    ])
+}
+
+fn stream_at(state: &mut WasiState, args: &[Val], right: u64, offset: Offset) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    match state.fd(fd)? {
+        Descriptor::File { rights, .. } if *rights & right == 0 => Err(Errno::BADF.into()),
+        Descriptor::File { .. } => Ok(Some(state.resources.insert(Resource::Stream { fd, offset }))),
+        Descriptor::Dir { .. } => Err(Errno::ISDIR.into()),
+    }
+}
+
+fn read_via_stream(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    stream_at(state, args, rights::FD_READ, Offset::At(u64_arg(args, 1)?))
+}
+
+fn write_via_stream(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    stream_at(state, args, rights::FD_WRITE, Offset::At(u64_arg(args, 1)?))
+}
+
+fn append_via_stream(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    stream_at(state, args, rights::FD_WRITE, Offset::End)
+}
+
+fn advise(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    file(state, fd)?;
+    Ok(None)
+}
+
+fn sync(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    match state.fd(fd)? {
+        Descriptor::File { file, .. } => file.sync()?,
+        Descriptor::Dir { .. } => {}
+    }
+    Ok(None)
+}
+
+fn get_flags(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    let flags = match state.fd(fd)? {
+        Descriptor::File { rights, .. } => [(rights::FD_READ, "read"), (rights::FD_WRITE, "write")]
+            .into_iter()
+            .filter_map(|(right, flag)| (*rights & right != 0).then_some(flag))
+            .collect(),
+        Descriptor::Dir { .. } => vec!["read", "mutate-directory"],
+    };
+    Ok(Some(Val::Flags(flags.into_iter().map(ToString::to_string).collect())))
+}
+
+fn filestat(state: &mut WasiState, fd: u32) -> Result<Filestat, Errno> {
+    match state.fd(fd)? {
+        Descriptor::File { file, .. } => file.filestat(),
+        Descriptor::Dir { fs, path, .. } => fs.stat(path, true),
+    }
+}
+
+fn get_type(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    Ok(Some(descriptor_type(filestat(state, fd)?.filetype)))
+}
+
+fn set_size(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    file(state, fd)?.set_size(u64_arg(args, 1)?)?;
+    Ok(None)
+}
+
+// a `new-timestamp` in nanoseconds, `None` leaves the timestamp unchanged
+fn new_timestamp(state: &mut WasiState, args: &[Val], idx: usize) -> Result<Option<u64>, Failure> {
+    let Some(Val::Variant(case, payload)) = args.get(idx) else { return Err(invalid(idx)) };
+    match (case.as_str(), payload.as_deref()) {
+        ("no-change", _) => Ok(None),
+        ("now", _) => Ok(Some(now(state, clockid::REALTIME)?)),
+        ("timestamp", Some(Val::Record(fields))) => match fields.as_slice() {
+            [(_, Val::U64(seconds)), (_, Val::U32(nanos))] => {
+                Ok(Some(seconds.saturating_mul(NANOS_PER_SECOND).saturating_add(*nanos as u64)))
+            }
+            _ => Err(invalid(idx)),
+        },
+        _ => Err(invalid(idx)),
+    }
+}
+
+fn set_times(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    let (atim, mtim) = (new_timestamp(state, args, 1)?, new_timestamp(state, args, 2)?);
+    match state.fd(fd)? {
+        Descriptor::File { file, .. } => file.set_times(atim, mtim)?,
+        Descriptor::Dir { fs, path, .. } => fs.set_times(path, atim, mtim)?,
+    }
+    Ok(None)
+}
+
+fn descriptor_read(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    let mut buf = vec![0; u64_arg(args, 1)?.min(MAX_BUFFER) as usize];
+    let file = file(state, fd)?;
+    let pos = file.seek(SeekFrom::Current(0))?;
+    file.seek(SeekFrom::Start(u64_arg(args, 2)?))?;
+    let read = file.read(&mut buf);
+    file.seek(SeekFrom::Start(pos))?;
+    let read = read?;
+    Ok(Some(Val::Tuple(vec![bytes(&buf[..read]), Val::Bool(read == 0 && !buf.is_empty())])))
+}
+
+fn descriptor_write(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    let data = bytes_arg(args, 1)?;
+    let file = file(state, fd)?;
+    let pos = file.seek(SeekFrom::Current(0))?;
+    file.seek(SeekFrom::Start(u64_arg(args, 2)?))?;
+    let res = write_all(file.as_mut(), &data);
+    file.seek(SeekFrom::Start(pos))?;
+    res?;
+    Ok(Some(Val::U64(data.len() as u64)))
+}
+
+fn read_directory(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    let Descriptor::Dir { fs, path, .. } = state.fd(fd)? else { return Err(Errno::NOTDIR.into()) };
+    let mut entries = fs.read_dir(path)?;
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Some(state.resources.insert(Resource::DirEntries(entries.into()))))
+}
+
+fn read_directory_entry(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let Resource::DirEntries(entries) = state.resources.get(rep(args, 0)?)? else { return Err(invalid(0)) };
+    let entry = entries.pop_front().map(|entry| {
+        some(Val::Record(vec![
+            ("type".to_string(), descriptor_type(entry.filetype)),
+            ("name".to_string(), Val::String(entry.name)),
+        ]))
+    });
+    Ok(Some(entry.unwrap_or(Val::Option(None))))
+}
+
+fn create_directory_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let (fs, path) = path_arg(state, args, 1)?;
+    fs.create_dir(&path)?;
+    Ok(None)
+}
+
+fn stat(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    Ok(Some(descriptor_stat(filestat(state, fd)?)))
+}
+
+fn stat_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let follow = has_flag(flags_arg(args, 1)?, "symlink-follow");
+    let (fs, path) = path_arg(state, args, 2)?;
+    Ok(Some(descriptor_stat(fs.stat(&path, follow)?)))
+}
+
+fn set_times_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let (fs, path) = path_arg(state, args, 2)?;
+    let (atim, mtim) = (new_timestamp(state, args, 3)?, new_timestamp(state, args, 4)?);
+    fs.set_times(&path, atim, mtim)?;
+    Ok(None)
+}
+
+fn link_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let (fs, old_path) = path_arg(state, args, 2)?;
+    let new_fd = fd_arg(state, args, 3)?;
+    let (new_fs, new_path) = state.resolve(new_fd, str_arg(args, 4)?)?;
+    same_fs(&fs, &new_fs)?;
+    fs.hard_link(&old_path, &new_path)?;
+    Ok(None)
+}
+
+fn open_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let (fs, path) = path_arg(state, args, 2)?;
+    let open_flags = flags_arg(args, 3)?;
+    let oflags = [
+        ("create", oflags::CREAT),
+        ("directory", oflags::DIRECTORY),
+        ("exclusive", oflags::EXCL),
+        ("truncate", oflags::TRUNC),
+    ]
+    .into_iter()
+    .filter(|(name, _)| has_flag(open_flags, name))
+    .fold(0, |oflags, (_, flag)| oflags | flag);
+
+    let flags = flags_arg(args, 4)?;
+    let (read, write) = (has_flag(flags, "read"), has_flag(flags, "write"));
+    let mut descriptor = open(fs, path, oflags, read, write, false)?;
+    if let Descriptor::File { rights, .. } = &mut descriptor {
+        *rights = match (read, write) {
+            (_, false) => rights::FD_READ,
+            (false, true) => rights::FD_WRITE,
+            (true, true) => rights::FD_READ | rights::FD_WRITE,
+        };
+    }
+
+    let fd = state.insert_fd(descriptor)?;
+    Ok(Some(state.resources.insert(Resource::Descriptor(fd))))
+}
+
+fn readlink_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let (fs, path) = path_arg(state, args, 1)?;
+    Ok(Some(Val::String(fs.read_link(&path)?)))
+}
+
+fn remove_directory_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let (fs, path) = path_arg(state, args, 1)?;
+    fs.remove_dir(&path)?;
+    Ok(None)
+}
+
+fn rename_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let (fs, old_path) = path_arg(state, args, 1)?;
+    let new_fd = fd_arg(state, args, 2)?;
+    let (new_fs, new_path) = state.resolve(new_fd, str_arg(args, 3)?)?;
+    same_fs(&fs, &new_fs)?;
+    fs.rename(&old_path, &new_path)?;
+    Ok(None)
+}
+
+fn symlink_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let target = str_arg(args, 1)?;
+    let (fs, link) = path_arg(state, args, 2)?;
+
+    // the target is stored as is, so it can't be allowed to point outside of the directory
+    crate::fs::join(crate::fs::split(&link).0, target)?;
+    fs.symlink(target, &link)?;
+    Ok(None)
+}
+
+fn unlink_file_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let (fs, path) = path_arg(state, args, 1)?;
+    fs.remove_file(&path)?;
+    Ok(None)
+}
+
+fn is_same_object(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let (fd, other) = (fd_arg(state, args, 0)?, fd_arg(state, args, 1)?);
+    let same = match (state.fds.get(&fd), state.fds.get(&other)) {
+        (Some(Descriptor::Dir { fs, path, .. }), Some(Descriptor::Dir { fs: other_fs, path: other_path, .. })) => {
+            same_fs(fs, other_fs).is_ok() && path == other_path
+        }
+        _ => fd == other,
+    };
+    Ok(Some(Val::Bool(same)))
+}
+
+fn metadata_hash_value(stat: Filestat) -> Val {
+    Val::Record(vec![("lower".to_string(), Val::U64(stat.ino)), ("upper".to_string(), Val::U64(stat.dev))])
+}
+
+fn metadata_hash(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let fd = fd_arg(state, args, 0)?;
+    Ok(Some(metadata_hash_value(filestat(state, fd)?)))
+}
+
+fn metadata_hash_at(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let follow = has_flag(flags_arg(args, 1)?, "symlink-follow");
+    let (fs, path) = path_arg(state, args, 2)?;
+    Ok(Some(metadata_hash_value(fs.stat(&path, follow)?)))
+}
+
+fn filesystem_error_code(state: &mut WasiState, args: &[Val]) -> Result<Option<Val>, Failure> {
+    let code = match state.resources.get(rep(args, 0)?)? {
+        Resource::Error(errno) => some(Val::Enum(error_code(*errno).to_string())),
+        _ => Val::Option(None),
+    };
+    Ok(Some(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::Pipe;
+    use tinywasm::component::Component;
+    use tinywasm::Store;
+
+    const COMMAND: &str = r#"(component $C
+        (import "wasi:io/error@0.2.0" (instance $error (export "error" (type (sub resource)))))
+        (alias export $error "error" (type $error-res))
+        (import "wasi:io/streams@0.2.0" (instance $streams
+            (alias outer $C $error-res (type $error'))
+            (export "error" (type $error (eq $error')))
+            (type $stream-error' (variant (case "last-operation-failed" (own $error)) (case "closed")))
+            (export "stream-error" (type $stream-error (eq $stream-error')))
+            (export "output-stream" (type $os (sub resource)))
+            (export "[method]output-stream.blocking-write-and-flush"
+                (func (param "self" (borrow $os)) (param "contents" (list u8)) (result (result (error $stream-error)))))))
+        (alias export $streams "output-stream" (type $os))
+        (import "wasi:cli/stdout@0.2.0" (instance $stdout
+            (alias outer $C $os (type $os'))
+            (export "output-stream" (type $os (eq $os')))
+            (export "get-stdout" (func (result (own $os))))))
+        (import "wasi:cli/exit@0.2.0" (instance $exit (export "exit" (func (param "status" (result))))))
+
+        (core module $libc (memory (export "memory") 1))
+        (core instance $libc (instantiate $libc))
+        (alias export $stdout "get-stdout" (func $get-stdout))
+        (alias export $streams "[method]output-stream.blocking-write-and-flush" (func $write))
+        (alias export $exit "exit" (func $exit))
+        (core func $get-stdout (canon lower (func $get-stdout)))
+        (core func $write (canon lower (func $write) (memory $libc "memory")))
+        (core func $exit (canon lower (func $exit)))
+        (core func $drop (canon resource.drop $os))
+
+        (core module $m
+            (import "libc" "memory" (memory 1))
+            (import "wasi" "get-stdout" (func $get-stdout (result i32)))
+            (import "wasi" "write" (func $write (param i32 i32 i32 i32)))
+            (import "wasi" "exit" (func $exit (param i32)))
+            (import "wasi" "drop" (func $drop (param i32)))
+            (data (i32.const 8) "hello")
+            (func (export "run") (result i32)
+                (local $stdout i32)
+                (local.set $stdout (call $get-stdout))
+                (call $write (local.get $stdout) (i32.const 8) (i32.const 5) (i32.const 16))
+                (call $drop (local.get $stdout))
+                (if (i32.load8_u (i32.const 16)) (then (return (i32.const 1))))
+                (call $exit (i32.const 1))
+                (i32.const 0)))
+        (core instance $i (instantiate $m
+            (with "libc" (instance $libc))
+            (with "wasi" (instance
+                (export "get-stdout" (func $get-stdout))
+                (export "write" (func $write))
+                (export "exit" (func $exit))
+                (export "drop" (func $drop))))))
+
+        (func $run (result (result)) (canon lift (core func $i "run")))
+        (instance $run (export "run" (func $run)))
+        (export "wasi:cli/run@0.2.0" (instance $run)))"#;
+
+    #[test]
+    fn test_run_component() {
+        let component = Component::parse_bytes(&wat::parse_str(COMMAND).unwrap()).unwrap();
+        let stdout = Pipe::new();
+        let mut wasi = WasiCtx::new();
+        wasi.stdout(stdout.clone());
+
+        let mut store = Store::default();
+        assert_eq!(wasi.run_component(&mut store, &component, None).unwrap(), 1);
+        assert_eq!(stdout.take(), b"hello");
+        assert_eq!(wasi.exit_code(), Some(1));
+        assert!(wasi.0.borrow().resources.table.is_empty());
+    }
+}
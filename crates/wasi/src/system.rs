@@ -0,0 +1,96 @@
+use crate::types::Errno;
+
+/// The clocks of the host, used by both WASI preview 1 and preview 2
+///
+/// Set with [`WasiCtx::clocks`](crate::WasiCtx::clocks). In [deterministic mode](tinywasm::deterministic), the
+/// virtual clock of the store is used instead.
+pub trait WasiClocks {
+    /// The current time in nanoseconds since the Unix epoch
+    fn wall_time(&self) -> Result<u64, Errno>;
+
+    /// The time in nanoseconds since an arbitrary point, which never decreases
+    fn monotonic_time(&self) -> Result<u64, Errno>;
+
+    /// The resolution of both clocks in nanoseconds
+    fn resolution(&self) -> u64 {
+        1
+    }
+
+    /// Block the guest for the given number of nanoseconds
+    fn sleep(&self, nanos: u64) {
+        let _ = nanos;
+    }
+}
+
+/// A source of random bytes, used by both WASI preview 1 and preview 2
+///
+/// Set with [`WasiCtx::random`](crate::WasiCtx::random). In [deterministic mode](tinywasm::deterministic), the
+/// seeded generator of the store is used instead.
+pub trait WasiRandom {
+    /// Fill `buf` with random bytes
+    fn fill(&mut self, buf: &mut [u8]) -> Result<(), Errno>;
+}
+
+/// The clocks of the host system
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClocks {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl Default for SystemClocks {
+    fn default() -> Self {
+        Self { start: std::time::Instant::now() }
+    }
+}
+
+#[cfg(feature = "std")]
+impl WasiClocks for SystemClocks {
+    fn wall_time(&self) -> Result<u64, Errno> {
+        use std::time::{SystemTime, UNIX_EPOCH};
+        Ok(SystemTime::now().duration_since(UNIX_EPOCH).map_err(|_| Errno::IO)?.as_nanos() as u64)
+    }
+
+    fn monotonic_time(&self) -> Result<u64, Errno> {
+        Ok(self.start.elapsed().as_nanos() as u64)
+    }
+
+    fn sleep(&self, nanos: u64) {
+        std::thread::sleep(std::time::Duration::from_nanos(nanos));
+    }
+}
+
+/// The random number generator of the host system
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRandom;
+
+#[cfg(feature = "std")]
+impl WasiRandom for SystemRandom {
+    fn fill(&mut self, buf: &mut [u8]) -> Result<(), Errno> {
+        getrandom::getrandom(buf).map_err(|_| Errno::IO)
+    }
+}
+
+// there are no clocks or randomness without the standard library
+#[cfg(not(feature = "std"))]
+pub(crate) struct Unsupported;
+
+#[cfg(not(feature = "std"))]
+impl WasiClocks for Unsupported {
+    fn wall_time(&self) -> Result<u64, Errno> {
+        Err(Errno::NOTSUP)
+    }
+
+    fn monotonic_time(&self) -> Result<u64, Errno> {
+        Err(Errno::NOTSUP)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl WasiRandom for Unsupported {
+    fn fill(&mut self, _: &mut [u8]) -> Result<(), Errno> {
+        Err(Errno::NOSYS)
+    }
+}
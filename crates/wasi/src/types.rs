@@ -0,0 +1,229 @@
+//! Types of the `wasi_snapshot_preview1` ABI
+//!
+//! See <https://github.com/WebAssembly/WASI/blob/main/legacy/preview1/witx/typenames.witx>
+
+use tinywasm::{WasmPod, WasmPtr};
+
+/// An error code returned by WASI functions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Errno(u16);
+
+#[allow(missing_docs)]
+impl Errno {
+    pub const SUCCESS: Self = Self(0);
+    pub const TOOBIG: Self = Self(1);
+    pub const ACCES: Self = Self(2);
+    pub const AGAIN: Self = Self(6);
+    pub const BADF: Self = Self(8);
+    pub const BUSY: Self = Self(10);
+    pub const EXIST: Self = Self(20);
+    pub const FAULT: Self = Self(21);
+    pub const FBIG: Self = Self(22);
+    pub const ILSEQ: Self = Self(25);
+    pub const INTR: Self = Self(27);
+    pub const INVAL: Self = Self(28);
+    pub const IO: Self = Self(29);
+    pub const ISDIR: Self = Self(31);
+    pub const LOOP: Self = Self(32);
+    pub const MFILE: Self = Self(33);
+    pub const NAMETOOLONG: Self = Self(37);
+    pub const NOENT: Self = Self(44);
+    pub const NOMEM: Self = Self(48);
+    pub const NOSPC: Self = Self(51);
+    pub const NOSYS: Self = Self(52);
+    pub const NOTDIR: Self = Self(54);
+    pub const NOTEMPTY: Self = Self(55);
+    pub const NOTSUP: Self = Self(58);
+    pub const NOTTY: Self = Self(59);
+    pub const OVERFLOW: Self = Self(61);
+    pub const PERM: Self = Self(63);
+    pub const PIPE: Self = Self(64);
+    pub const RANGE: Self = Self(68);
+    pub const ROFS: Self = Self(69);
+    pub const SPIPE: Self = Self(70);
+    pub const TIMEDOUT: Self = Self(73);
+    pub const XDEV: Self = Self(75);
+    pub const NOTCAPABLE: Self = Self(76);
+}
+
+impl Errno {
+    /// Create an error code from its raw value
+    pub const fn from_raw(raw: u16) -> Self {
+        Self(raw)
+    }
+
+    /// Get the raw value of the error code
+    pub const fn raw(self) -> u16 {
+        self.0
+    }
+}
+
+// memory accesses outside of the guest memory are reported to the guest instead of trapping
+impl From<tinywasm::Error> for Errno {
+    fn from(_: tinywasm::Error) -> Self {
+        Self::FAULT
+    }
+}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for Errno {
+    fn from(err: std::io::Error) -> Self {
+        use std::io::ErrorKind;
+        match err.kind() {
+            ErrorKind::NotFound => Self::NOENT,
+            ErrorKind::PermissionDenied => Self::ACCES,
+            ErrorKind::AlreadyExists => Self::EXIST,
+            ErrorKind::InvalidInput => Self::INVAL,
+            ErrorKind::InvalidData => Self::ILSEQ,
+            ErrorKind::TimedOut => Self::TIMEDOUT,
+            ErrorKind::WouldBlock => Self::AGAIN,
+            ErrorKind::Interrupted => Self::INTR,
+            ErrorKind::BrokenPipe => Self::PIPE,
+            ErrorKind::Unsupported => Self::NOTSUP,
+            ErrorKind::OutOfMemory => Self::NOMEM,
+            _ => Self::IO,
+        }
+    }
+}
+
+/// The type of a file descriptor or file
+#[allow(missing_docs)]
+pub mod filetype {
+    pub const UNKNOWN: u8 = 0;
+    pub const CHARACTER_DEVICE: u8 = 2;
+    pub const DIRECTORY: u8 = 3;
+    pub const REGULAR_FILE: u8 = 4;
+    pub const SYMBOLIC_LINK: u8 = 7;
+}
+
+pub(crate) mod clockid {
+    pub(crate) const REALTIME: u32 = 0;
+    pub(crate) const MONOTONIC: u32 = 1;
+    pub(crate) const PROCESS_CPUTIME: u32 = 2;
+    pub(crate) const THREAD_CPUTIME: u32 = 3;
+}
+
+pub(crate) mod whence {
+    pub(crate) const SET: u8 = 0;
+    pub(crate) const CUR: u8 = 1;
+    pub(crate) const END: u8 = 2;
+}
+
+pub(crate) mod oflags {
+    pub(crate) const CREAT: u16 = 1 << 0;
+    pub(crate) const DIRECTORY: u16 = 1 << 1;
+    pub(crate) const EXCL: u16 = 1 << 2;
+    pub(crate) const TRUNC: u16 = 1 << 3;
+}
+
+pub(crate) mod fdflags {
+    pub(crate) const APPEND: u16 = 1 << 0;
+}
+
+pub(crate) mod fstflags {
+    pub(crate) const ATIM: u16 = 1 << 0;
+    pub(crate) const ATIM_NOW: u16 = 1 << 1;
+    pub(crate) const MTIM: u16 = 1 << 2;
+    pub(crate) const MTIM_NOW: u16 = 1 << 3;
+}
+
+pub(crate) mod rights {
+    pub(crate) const FD_READ: u64 = 1 << 1;
+    pub(crate) const FD_WRITE: u64 = 1 << 6;
+    // every right defined by preview1
+    pub(crate) const ALL: u64 = (1 << 30) - 1;
+}
+
+pub(crate) mod eventtype {
+    pub(crate) const CLOCK: u8 = 0;
+    pub(crate) const FD_READ: u8 = 1;
+    pub(crate) const FD_WRITE: u8 = 2;
+}
+
+pub(crate) const SUBCLOCKFLAGS_ABSTIME: u16 = 1 << 0;
+pub(crate) const PREOPENTYPE_DIR: u8 = 0;
+
+#[derive(Debug, WasmPod)]
+pub(crate) struct Iovec {
+    pub(crate) buf: WasmPtr<u8>,
+    pub(crate) len: u32,
+}
+
+#[derive(Debug, WasmPod)]
+pub(crate) struct Fdstat {
+    pub(crate) filetype: u8,
+    pub(crate) flags: u16,
+    pub(crate) rights_base: u64,
+    pub(crate) rights_inheriting: u64,
+}
+
+/// The attributes of a file, timestamps are in nanoseconds since the Unix epoch
+#[derive(Debug, Clone, Default, PartialEq, Eq, WasmPod)]
+pub struct Filestat {
+    /// The device containing the file
+    pub dev: u64,
+    /// The inode number of the file
+    pub ino: u64,
+    /// The type of the file, see [`filetype`]
+    pub filetype: u8,
+    /// The number of hard links to the file
+    pub nlink: u64,
+    /// The size of the file in bytes
+    pub size: u64,
+    /// The last access time
+    pub atim: u64,
+    /// The last modification time
+    pub mtim: u64,
+    /// The last status change time
+    pub ctim: u64,
+}
+
+#[derive(Debug, WasmPod)]
+pub(crate) struct Prestat {
+    pub(crate) tag: u8,
+    pub(crate) name_len: u32,
+}
+
+#[derive(Debug, WasmPod)]
+pub(crate) struct Dirent {
+    pub(crate) next: u64,
+    pub(crate) ino: u64,
+    pub(crate) namlen: u32,
+    pub(crate) filetype: u8,
+}
+
+// the clock and fd variants share the same memory, `id` is the file descriptor for fd subscriptions
+#[derive(Debug, WasmPod)]
+pub(crate) struct SubscriptionClock {
+    pub(crate) id: u32,
+    pub(crate) timeout: u64,
+    pub(crate) precision: u64,
+    pub(crate) flags: u16,
+}
+
+#[derive(Debug, WasmPod)]
+pub(crate) struct Subscription {
+    pub(crate) userdata: u64,
+    pub(crate) tag: u8,
+    pub(crate) u: SubscriptionClock,
+}
+
+#[derive(Debug, WasmPod)]
+pub(crate) struct Event {
+    pub(crate) userdata: u64,
+    pub(crate) error: u16,
+    pub(crate) ty: u8,
+    pub(crate) nbytes: u64,
+    pub(crate) flags: u16,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        assert_eq!((Iovec::SIZE, Fdstat::SIZE, Filestat::SIZE, Prestat::SIZE), (8, 24, 64, 8));
+        assert_eq!((Dirent::SIZE, Subscription::SIZE, Event::SIZE), (24, 48, 32));
+    }
+}